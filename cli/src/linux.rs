@@ -0,0 +1,4828 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use common::{build_limit, format_bytes, Config, Error, MemoryLimit, OomPolicy, Result};
+use rlm_core::{CgroupManager, Controller};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{picker, top};
+
+fn resolve_pids(pid: Option<u32>, name: Option<&str>, cmdline: Option<&str>) -> Result<Vec<u32>> {
+    match (pid, name, cmdline) {
+        (Some(pid), None, None) => Ok(vec![pid]),
+        (None, Some(name), None) => rlm_core::process::find_by_name(name),
+        (None, None, Some(cmdline)) => rlm_core::process::find_by_cmdline(cmdline),
+        (None, None, None) => Err(Error::InvalidArgs(
+            "specify --pid, --name, or --cmdline".into(),
+        )),
+        _ => unreachable!("clap prevents this"),
+    }
+}
+
+/// `pids` but for those matching one of the `--exclude` `patterns`.
+fn without_excluded(pids: Vec<u32>, patterns: &[String]) -> Result<Vec<u32>> {
+    if patterns.is_empty() {
+        return Ok(pids);
+    }
+    let kept: Vec<u32> = pids
+        .into_iter()
+        .filter(|&pid| !rlm_core::process::matches_any(pid, patterns))
+        .collect();
+    if kept.is_empty() {
+        return Err(Error::InvalidArgs(
+            "--exclude leaves out every matching process".into(),
+        ));
+    }
+    Ok(kept)
+}
+
+fn resolve_application_pids(application: &str) -> Result<Vec<u32>> {
+    let processes = rlm_core::process::find_all_by_executable(application)?;
+    Ok(processes.iter().map(|p| p.pid).collect())
+}
+
+/// The profile called `name`, built-in or from the config.
+fn load_profile(name: &str) -> Result<common::Profile> {
+    Config::load()?
+        .get_profile(name)
+        .ok_or_else(|| Error::Config(format!("profile '{name}' not found")))
+}
+
+/// The limits for `profile` and the limit flags: the profile's, with each
+/// one a flag gives replacing its value, or else the flags' alone.
+fn resolve_limit(profile: Option<String>, limits: &LimitArgs) -> Result<common::Limit> {
+    let profile = profile.as_deref().map(load_profile).transpose()?;
+    let limit = limits.with_profile(profile.as_ref())?;
+    if profile.is_none() && limit.is_empty() {
+        return Err(LimitArgs::none_given());
+    }
+    Ok(limit)
+}
+
+/// A rule snapshot for `supervise`: the profile's limit strings, with each
+/// one a flag gives replacing its value, or else the flags' alone.
+fn app_rule_from(
+    name: &str,
+    profile: Option<String>,
+    limits: &LimitArgs,
+) -> Result<common::AppRule> {
+    let profile = profile.as_deref().map(load_profile).transpose()?;
+    // Parsed here too, so a bad value is caught before it is saved
+    if limits.with_profile(profile.as_ref())?.is_empty() {
+        return Err(LimitArgs::none_given());
+    }
+    Ok(limits.rule_with_profile(name, profile.as_ref()))
+}
+
+fn list_supervised() -> Result<ExitCode> {
+    let config = Config::load()?;
+    if config.supervise.is_empty() {
+        println!("no supervised applications");
+        println!("  add one with: rlm supervise --name <exe> --profile <profile>");
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!(
+        "{:<20} {:>10} {:>8} {:>10} {:>10} {:>8}",
+        "APP", "MEMORY", "CPU", "IO_READ", "IO_WRITE", "ON_OOM"
+    );
+    println!("{}", "-".repeat(71));
+    let mut names: Vec<_> = config.supervise.keys().collect();
+    names.sort();
+    for name in names {
+        let s = &config.supervise[name];
+        println!(
+            "{:<20} {:>10} {:>8} {:>10} {:>10} {:>8}",
+            name,
+            s.rule.memory.as_deref().unwrap_or("-"),
+            s.rule.cpu.as_deref().unwrap_or("-"),
+            s.rule.io_read.as_deref().unwrap_or("-"),
+            s.rule.io_write.as_deref().unwrap_or("-"),
+            s.on_oom.to_string(),
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn parse_pid_list(pids_str: &str) -> Result<Vec<u32>> {
+    pids_str
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<u32>()
+                .map_err(|_| Error::InvalidArgs(format!("invalid PID: {}", s.trim())))
+        })
+        .collect()
+}
+
+/// Prompt user for confirmation when affecting multiple processes, unless
+/// `yes` (`--yes`) answers for them
+fn confirm_batch(pids: &[u32], action: &str, yes: bool) -> Result<bool> {
+    if pids.len() <= 1 || yes {
+        return Ok(true);
+    }
+
+    println!("Found {} processes:", pids.len());
+    for pid in pids.iter().take(10) {
+        let name = std::fs::read_to_string(format!("/proc/{pid}/comm"))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| "?".to_string());
+        println!("  {pid}: {name}");
+    }
+    if pids.len() > 10 {
+        println!("  ... and {} more", pids.len() - 10);
+    }
+
+    ask(&format!("{} all {} processes?", action, pids.len()))
+}
+
+/// The processes of `pids` (from --name or --cmdline) to `action`: when
+/// there are several, the ones the user picks, or all of them with `yes`.
+/// Empty if they cancel.
+fn pick_batch(pids: Vec<u32>, action: &str, yes: bool) -> Result<Vec<u32>> {
+    use std::io::IsTerminal;
+    if pids.len() <= 1 || yes {
+        return Ok(pids);
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        // No screen for the picker: all or nothing
+        return Ok(if confirm_batch(&pids, action, yes)? {
+            pids
+        } else {
+            Vec::new()
+        });
+    }
+    picker::pick(action, &pids)
+}
+
+/// Ask a yes/no `question` on the terminal; anything but yes is no. Without
+/// a terminal to answer on (cron, a pipe) it fails rather than wait for
+/// input that never comes, or take end of input as a no.
+fn ask(question: &str) -> Result<bool> {
+    use std::io::IsTerminal;
+    if !io::stdin().is_terminal() {
+        return Err(Error::InvalidArgs(format!(
+            "can't ask \"{question}\": stdin is not a terminal (pass --yes to confirm)"
+        )));
+    }
+    print!("{question} [y/N] ");
+    io::stdout().flush().ok();
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input).is_err() {
+        return Ok(false);
+    }
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[derive(Parser)]
+#[command(name = "rlm", bin_name = "rlm")]
+#[command(about = "Resource Limit Manager - control process resource usage via cgroups v2")]
+#[command(version)]
+struct Cli {
+    /// Create cgroups in this cgroup instead of the one rlm finds itself
+    /// (also RLM_CGROUP_ROOT, or cgroup_root in the config)
+    #[arg(long, global = true, value_name = "PATH")]
+    cgroup_root: Option<std::path::PathBuf>,
+
+    /// Answer yes to every confirmation, for scripts and cron jobs. Without
+    /// it, a command that would ask fails when stdin is not a terminal.
+    #[arg(long, short, global = true, alias = "no-confirm")]
+    yes: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+// Parsed once per invocation, so `Run` being much the largest costs nothing
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Commands {
+    /// Apply resource limits to a running process
+    Limit {
+        /// Process ID to limit
+        #[arg(long, conflicts_with_all = ["name", "cmdline", "application", "all_pids"])]
+        pid: Option<u32>,
+
+        /// Process name to limit (limits all matching processes individually)
+        #[arg(long, conflicts_with_all = ["pid", "cmdline", "application", "all_pids"])]
+        name: Option<String>,
+
+        /// Limit the processes whose command line contains this text, e.g.
+        /// 'manage.py runserver' (each individually, as --name)
+        #[arg(long, value_name = "TEXT", conflicts_with_all = ["pid", "name", "application", "all_pids"])]
+        cmdline: Option<String>,
+
+        /// Leave out processes matching this: a PID, or text in the process
+        /// name or command line (repeatable)
+        #[arg(long, value_name = "PATTERN", conflicts_with_all = ["unit", "session", "user", "persistent"])]
+        exclude: Vec<String>,
+
+        /// Application name to limit (all processes share the same limit pool)
+        /// Use this for applications with multiple processes (e.g., firefox, chrome)
+        /// All processes will share the specified limits (combined, not per-process)
+        #[arg(long, conflicts_with_all = ["pid", "name", "cmdline", "all_pids"])]
+        application: Option<String>,
+
+        /// Comma-separated list of PIDs to limit together (share the same limit pool)
+        #[arg(long, conflicts_with_all = ["pid", "name", "cmdline", "application"])]
+        all_pids: Option<String>,
+
+        /// With --pid: limit the process together with everything it has
+        /// started (its children, theirs, and so on), all sharing the limits
+        #[arg(long, short, requires = "pid")]
+        recursive: bool,
+
+        /// With --pid, its children: current limits the ones it has now along
+        /// with it (as --recursive does); follow does too, then stays running
+        /// to move the ones started later into its cgroup, until it exits
+        #[arg(
+            long,
+            value_enum,
+            value_name = "MODE",
+            requires = "pid",
+            conflicts_with = "recursive"
+        )]
+        children: Option<ChildrenMode>,
+
+        /// systemd unit to limit (e.g. nextcloud.service); the limits become
+        /// the unit's resource properties and survive restarts and reboots
+        #[arg(long, conflicts_with_all = ["pid", "name", "cmdline", "application", "all_pids", "persistent"])]
+        unit: Option<String>,
+
+        /// With --unit: drop the limits at the next reboot
+        #[arg(long, requires = "unit")]
+        runtime: bool,
+
+        /// Limit a whole login session (default: this one), e.g. another
+        /// user's desktop; the limits last until the session ends
+        #[arg(long, value_name = "SESSION_ID", conflicts_with_all = ["pid", "name", "cmdline", "application", "all_pids", "unit", "persistent"])]
+        session: Option<Option<String>>,
+
+        /// Limit every process of a user (name or UID), all sharing the limits:
+        /// their user slice while they are logged in, else their processes
+        #[arg(long, value_name = "USER", conflicts_with_all = ["pid", "name", "cmdline", "application", "all_pids", "unit", "session", "persistent"])]
+        user: Option<String>,
+
+        /// Use limits from a named profile; limit flags given as well replace
+        /// its values for those resources
+        #[arg(long, short)]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// Scheduler hints for the processes (not with --unit, --session, or --user)
+        #[command(flatten)]
+        sched: SchedArgs,
+
+        /// Show what would be done without applying limits
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Keep the limit: save it as a rule (with --application or --name) that
+        /// rlm-guard and `rlm daemon` re-apply to restarted instances and after
+        /// reboots. With --name, instances share one limit, like --application.
+        #[arg(long, alias = "save")]
+        persistent: bool,
+    },
+
+    /// Remove resource limits from a process
+    Unlimit {
+        /// Process ID to unlimit
+        #[arg(long, conflicts_with_all = ["name", "cmdline", "application", "cgroup"])]
+        pid: Option<u32>,
+
+        /// Process name to unlimit (all matching processes)
+        #[arg(long, conflicts_with_all = ["pid", "cmdline", "application", "cgroup"])]
+        name: Option<String>,
+
+        /// Unlimit the processes whose command line contains this text
+        #[arg(long, value_name = "TEXT", conflicts_with_all = ["pid", "name", "application", "cgroup"])]
+        cmdline: Option<String>,
+
+        /// With --name or --cmdline: leave out processes matching this, a PID
+        /// or text in the process name or command line (repeatable)
+        #[arg(long, value_name = "PATTERN", conflicts_with_all = ["application", "cgroup", "unit", "session", "user", "all"])]
+        exclude: Vec<String>,
+
+        /// Application name to unlimit (removes shared cgroup)
+        #[arg(long, conflicts_with_all = ["pid", "name", "cmdline", "cgroup"])]
+        application: Option<String>,
+
+        /// Cgroup name to remove (for shared application cgroups)
+        #[arg(long, conflicts_with_all = ["pid", "name", "cmdline", "application"])]
+        cgroup: Option<String>,
+
+        /// systemd unit limited with `rlm limit --unit`; resets its properties
+        #[arg(long, conflicts_with_all = ["pid", "name", "cmdline", "application", "cgroup"])]
+        unit: Option<String>,
+
+        /// Login session limited with `rlm limit --session` (default: this one)
+        #[arg(long, value_name = "SESSION_ID", conflicts_with_all = ["pid", "name", "cmdline", "application", "cgroup", "unit"])]
+        session: Option<Option<String>>,
+
+        /// User limited with `rlm limit --user`
+        #[arg(long, value_name = "USER", conflicts_with_all = ["pid", "name", "cmdline", "application", "cgroup", "unit", "session"])]
+        user: Option<String>,
+
+        /// Remove every cgroup rlm holds limits in (rlm status lists what
+        /// they hold), after asking (--yes to skip)
+        #[arg(long, conflicts_with_all = ["pid", "name", "cmdline", "application", "cgroup", "unit", "session", "user", "forget"])]
+        all: bool,
+
+        /// Lift only the memory limits, keeping the others and the cgroup
+        #[arg(long, conflicts_with_all = ["unit", "session", "user", "all", "forget"])]
+        memory: bool,
+
+        /// Lift only the CPU limits, keeping the others and the cgroup
+        #[arg(long, conflicts_with_all = ["unit", "session", "user", "all", "forget"])]
+        cpu: bool,
+
+        /// Lift only the I/O limits, keeping the others and the cgroup
+        #[arg(long, conflicts_with_all = ["unit", "session", "user", "all", "forget"])]
+        io: bool,
+
+        /// Also delete the persistent rule (with --application). Without this,
+        /// unlimit drops the live limit but keeps the saved rule.
+        #[arg(long)]
+        forget: bool,
+    },
+
+    /// Guarantee memory headroom for an application
+    ///
+    /// Protects its processes with memory.low and caps every other managed
+    /// cgroup with a memory.high ceiling so they can't eat into the reservation
+    Reserve {
+        /// Application name (all matching processes share the reservation)
+        #[arg(long)]
+        name: String,
+
+        /// Memory to reserve (K=1024, M=1024K, G=1024M, T=1024G)
+        #[arg(long, value_name = "SIZE")]
+        memory: String,
+
+        /// Also set memory.min: never reclaimed, even under global pressure
+        #[arg(long)]
+        hard: bool,
+    },
+
+    /// Release a reservation made with `rlm reserve` and lift the ceilings
+    Unreserve {
+        /// Application name used when reserving
+        #[arg(long)]
+        name: String,
+    },
+
+    /// Keep the desktop responsive: put all session apps under one parent
+    /// cgroup with conservative memory.high/cpu.weight, exempting the
+    /// compositor and shell. Without an argument, shows whether it is on.
+    /// With --pid and --memory-reserve, reserves memory for one process instead
+    Protect {
+        #[arg(value_enum, conflicts_with = "pid")]
+        state: Option<Toggle>,
+
+        /// Process whose working set to keep (e.g. the compositor or an IDE)
+        #[arg(long, requires = "memory_reserve")]
+        pid: Option<u32>,
+
+        /// Memory to reserve for --pid (memory.low; K/M/G/T units)
+        #[arg(long, value_name = "SIZE", requires = "pid")]
+        memory_reserve: Option<String>,
+
+        /// Also set memory.min: never reclaimed, even under global pressure
+        #[arg(long, requires = "pid")]
+        hard: bool,
+    },
+
+    /// Reserve memory headroom for the desktop session: memory.low on the
+    /// compositor and shell's cgroup, and a memory.high ceiling on every
+    /// rlm-managed cgroup to leave it free. Without an argument, shows
+    /// whether it is on
+    ProtectSession {
+        #[arg(value_enum)]
+        state: Option<Toggle>,
+
+        /// Headroom to reserve (K=1024, M=1024K, G=1024M, T=1024G)
+        #[arg(long, value_name = "SIZE", default_value = "1G")]
+        memory: String,
+    },
+
+    /// Get the machine back when it already stutters: throttle the processes
+    /// using the most memory and CPU right now in one `rescue` cgroup (a
+    /// quarter core, lowest weights, memory.high), optionally freezing them
+    Rescue {
+        /// How many of the worst memory and of the worst CPU users to take
+        #[arg(long, default_value_t = 3, value_name = "N")]
+        count: usize,
+
+        /// Freeze them as well, until undone
+        #[arg(long)]
+        freeze: bool,
+
+        /// Thaw the rescued processes and put them back where they were
+        #[arg(long, conflicts_with_all = ["count", "freeze"])]
+        undo: bool,
+    },
+
+    /// Show OOM kills and memory.high/max hits of managed cgroups: totals so
+    /// far, or with --follow, each one as it happens
+    Events {
+        /// Keep running and print events as they happen
+        #[arg(long, short)]
+        follow: bool,
+
+        /// One JSON object per line
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show what rlm did: limits applied, updated, and removed, newest last
+    History {
+        /// Only actions on this process
+        #[arg(long)]
+        pid: Option<u32>,
+
+        /// Only actions whose process or cgroup name contains this
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Show at most this many (most recent) entries
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+
+        /// One JSON object per line
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Sample a process's cgroup usage over time into a CSV or JSON lines
+    /// file, to pick limits from real data. Stops on Ctrl+C or when the
+    /// process exits
+    Record {
+        /// Process to record (its rlm cgroup if managed, otherwise its own)
+        #[arg(long)]
+        pid: u32,
+
+        /// Time between samples (e.g. 1s, 500ms)
+        #[arg(long, value_name = "DURATION", default_value = "1s", value_parser = parse_duration)]
+        interval: Duration,
+
+        /// File to write; "-" for stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: std::path::PathBuf,
+
+        /// Output format [default: json for .json/.jsonl files, else csv]
+        #[arg(long, value_enum)]
+        format: Option<RecordFormat>,
+
+        /// Stop after this long (e.g. 10m)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        duration: Option<Duration>,
+    },
+
+    /// Reclaim memory from an idle process's cgroup now (memory.reclaim),
+    /// pushing it out to swap or disk without restarting the process
+    Reclaim {
+        /// Process whose cgroup to reclaim from (its rlm cgroup, if managed)
+        #[arg(long)]
+        pid: u32,
+
+        /// How much to reclaim (K=1024, M=1024K, G=1024M, T=1024G)
+        #[arg(long, value_name = "SIZE")]
+        amount: String,
+    },
+
+    /// Watch an application's usage without limiting it, and notify, freeze,
+    /// or kill when it crosses a threshold (Ctrl+C to stop)
+    Watchdog {
+        /// Application (executable) name; all its processes count together
+        #[arg(long)]
+        name: String,
+
+        /// Memory threshold over all its processes (K/M/G/T units)
+        #[arg(long, value_name = "SIZE", required_unless_present = "cpu")]
+        memory: Option<String>,
+
+        /// CPU threshold as percentage (100% = one full core)
+        #[arg(long, value_name = "PERCENT")]
+        cpu: Option<String>,
+
+        /// What to do: notify, freeze (until the watchdog stops), or kill
+        /// (SIGTERM)
+        #[arg(long, value_name = "ACTION", default_value_t = rlm_core::watchdog::Action::Notify)]
+        action: rlm_core::watchdog::Action,
+
+        /// Only act once a threshold has been exceeded this long (e.g. 30s)
+        #[arg(long = "for", value_name = "DURATION", default_value = "0s", value_parser = parse_duration)]
+        sustain: Duration,
+
+        /// Time between checks (e.g. 2s, 500ms)
+        #[arg(long, value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+        interval: Duration,
+    },
+
+    /// Supervise an application: rlm-guard keeps it limited and restarts or
+    /// re-limits it when it is OOM-killed or keeps exceeding its soft limit.
+    /// Without --name, lists supervised applications
+    Supervise {
+        /// Application (executable) name to supervise
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Use limits from a named profile; limit flags given as well replace
+        /// its values for those resources
+        #[arg(long, short, requires = "name")]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// What to do on an OOM kill or repeated limit breaches:
+        /// restart (same limits), tighten (lower limits, then restart), or log
+        #[arg(long, value_name = "POLICY", default_value_t = OomPolicy::Restart)]
+        on_oom: OomPolicy,
+    },
+
+    /// Stop supervising an application (its current limit stays until unlimited)
+    Unsupervise {
+        /// Application name used with `rlm supervise`
+        #[arg(long)]
+        name: String,
+    },
+
+    /// Manage persistent application rules (enforced by rlm-guard)
+    Rule {
+        #[command(subcommand)]
+        action: RuleAction,
+    },
+
+    /// Run a command with resource limits
+    #[command(group = clap::ArgGroup::new("restarting").args(["restart_on_oom", "restart"]))]
+    Run {
+        /// Use limits from a named profile; limit flags given as well replace
+        /// its values for those resources
+        #[arg(long, short)]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        #[command(flatten)]
+        launch: LaunchArgs,
+
+        /// Restart the command in a fresh cgroup if it is OOM-killed
+        #[arg(long)]
+        restart_on_oom: bool,
+
+        /// Restart the command in the same cgroup when it exits non-zero or
+        /// crashes: on-failure (with no limit), or on-failure:max=N
+        #[arg(long, value_name = "POLICY", value_parser = parse_restart, conflicts_with = "restart_on_oom")]
+        restart: Option<OnFailure>,
+
+        /// Maximum restarts with --restart-on-oom
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 3,
+            requires = "restart_on_oom"
+        )]
+        max_restarts: u32,
+
+        /// Wait before the first restart, doubling after each up to 5m (e.g.
+        /// 10s, 500ms, 2m)
+        #[arg(long, value_name = "DURATION", default_value = "10s", value_parser = parse_duration, requires = "restarting")]
+        backoff: Duration,
+
+        /// Where no cgroup controllers are writable, run anyway under
+        /// setrlimit limits, which are much weaker (see the warning it prints)
+        #[arg(long, conflicts_with = "deny_device")]
+        fallback_rlimit: bool,
+
+        /// With --fallback-rlimit: CPU time each process may use in total
+        /// (RLIMIT_CPU, e.g. 30m)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "fallback_rlimit")]
+        cpu_time: Option<Duration>,
+
+        /// Stop the command once it has run this long (e.g. 30m): SIGTERM to
+        /// everything in its cgroup, then SIGKILL. rlm then exits with 124
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Once the command is told to stop (by --timeout, or SIGINT, SIGTERM,
+        /// or SIGHUP passed on to it), SIGKILL everything left in its cgroup
+        /// after this long. Default with --timeout: 10s
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        kill_after: Option<Duration>,
+
+        /// Start the command in the background, detached from the terminal,
+        /// and return once it is running. Its cgroup shows in `rlm status`
+        /// and goes away when it exits
+        #[arg(long, conflicts_with_all = ["restart_on_oom", "restart", "timeout"])]
+        detach: bool,
+
+        /// When the command exits, print what it used (wall and CPU time,
+        /// peak memory, I/O, throttling, OOM kills) to stderr, as text or json
+        #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "text", conflicts_with = "detach")]
+        summary: Option<SummaryFormat>,
+
+        /// Run in a cgroup shared by every `rlm run --join NAME`, so they draw
+        /// on one budget. The first creates it with the limits given; later
+        /// ones join it as it is, or replace its limits if they give any. It
+        /// goes away when the last one exits
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["restart_on_oom", "timeout", "kill_after", "detach", "fallback_rlimit"])]
+        join: Option<String>,
+
+        /// Command to run
+        #[arg(trailing_var_arg = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Start an installed desktop application under limits, by its name in
+    /// the application menu (e.g. "Firefox"). Without a name, lists them
+    Launch {
+        /// Application name, or enough of it to pick out one
+        app: Option<String>,
+
+        /// Use limits from a named profile; limit flags given as well replace
+        /// its values for those resources
+        #[arg(long, short, requires = "app")]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// Start it in the background and return once it is running, as with
+        /// `rlm run --detach`
+        #[arg(long, requires = "app")]
+        detach: bool,
+
+        /// Files or URLs for the application to open
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    /// Limit everything launched from this terminal
+    ///
+    /// Starts a subshell inside a limited cgroup; every command run from it
+    /// shares the budget. With --attach, the current shell itself is moved
+    Shell {
+        /// Use limits from a named profile; limit flags given as well replace
+        /// its values for those resources
+        #[arg(long, short)]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// Move the calling shell into the cgroup instead of starting a subshell
+        #[arg(long)]
+        attach: bool,
+    },
+
+    /// Print shell functions that run the commands profiles match (match_exe)
+    /// under `rlm run`, to eval from your shell's rc file
+    ShellInit {
+        #[arg(value_enum)]
+        shell: InitShell,
+    },
+
+    /// List available profiles from config
+    Profiles,
+
+    /// Export profiles to a file, or one profile as systemd resource directives
+    Export {
+        /// Output file path (default: standard output)
+        #[arg(value_name = "FILE")]
+        file: Option<String>,
+
+        /// yaml: all your profiles, for `rlm import`; systemd: a drop-in for a
+        /// service; systemd-slice: a slice unit services can share
+        #[arg(long, value_enum, default_value = "yaml")]
+        format: ExportFormat,
+
+        /// Profile to export, for the systemd formats
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// Import profiles from a file
+    Import {
+        /// Input file path (YAML format)
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        /// Overwrite existing profiles with same name
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Show status of managed processes
+    Status {
+        /// Keep redrawing the table, highlighting what changed (Ctrl+C to stop)
+        #[arg(long, short)]
+        watch: bool,
+
+        /// Time between redraws with --watch (e.g. 2s, 500ms)
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "2s",
+            requires = "watch",
+            value_parser = parse_duration
+        )]
+        interval: Duration,
+
+        /// Output format; json is the full state for scripts
+        #[arg(long, value_enum, default_value = "text", conflicts_with = "watch")]
+        output: StatusOutput,
+
+        #[command(flatten)]
+        filter: StatusFilter,
+    },
+
+    /// Interactive view of managed processes with live usage; sort, unlimit,
+    /// freeze, or kill from the keyboard
+    Top,
+
+    /// Show everything about one process's cgroup: limits, usage, cpu.stat,
+    /// io.stat per device, and pressure
+    Stats {
+        /// PID (its rlm cgroup if managed, otherwise its own) or managed
+        /// cgroup name (e.g. pid-1234, reserve-build)
+        target: String,
+    },
+
+    /// Show CPU, memory, and I/O pressure (PSI) system-wide and for each
+    /// managed cgroup, to see which limited workload is starving
+    Pressure,
+
+    /// Write a snapshot report (system, pressure, managed cgroups, memory
+    /// events, doctor) to attach to bug reports or share
+    Report {
+        /// Output format
+        #[arg(long, value_enum, default_value = "md")]
+        format: ReportFormat,
+
+        /// Write to this file instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+    },
+
+    /// Check system requirements and diagnose issues
+    Doctor,
+
+    /// Wait for an application to start and limit it as soon as it does
+    /// (an instance already running is limited right away)
+    Watch {
+        /// Application (executable) name; its instances share one limit
+        #[arg(long)]
+        name: String,
+
+        /// Use limits from a named profile; limit flags given as well replace
+        /// its values for those resources
+        #[arg(long, short)]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// Stop after limiting the first instance (the default)
+        #[arg(long, conflicts_with = "forever")]
+        once: bool,
+
+        /// Keep limiting new instances until Ctrl+C
+        #[arg(long)]
+        forever: bool,
+
+        /// Time between scans for new processes when not running as root
+        #[arg(long, value_name = "DURATION", default_value = "100ms", value_parser = parse_duration)]
+        interval: Duration,
+    },
+
+    /// Limit running processes matching a profile's match_exe, once; see
+    /// `rlm daemon` to keep doing it for new ones
+    Apply {
+        /// Only this profile (built-in presets included)
+        #[arg(long, short, required_unless_present = "all", conflicts_with = "all")]
+        profile: Option<String>,
+
+        /// Every profile in your config that has match_exe, and persistent limits
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Apply profiles to matching processes as they start: profiles with
+    /// `match_exe` and persistent limits move each new instance into their
+    /// shared cgroup (runs until Ctrl+C)
+    Daemon {
+        /// Time between scans for new processes when not running as root
+        /// (e.g. 100ms); as root, exec events arrive from the kernel
+        #[arg(long, value_name = "DURATION", default_value = "100ms")]
+        interval: String,
+
+        /// Away time after which profiles' `when_idle` limits apply (e.g. 10m)
+        #[arg(long, value_name = "DURATION", default_value = "10m", value_parser = parse_duration)]
+        idle_after: Duration,
+
+        /// Raise cpu.weight of the managed app with the focused window and
+        /// lower the other managed apps' (Hyprland, Sway, or X11)
+        #[arg(long)]
+        boost_focus: bool,
+
+        /// Reload the config as soon as config.yaml or profiles.d/ change,
+        /// not only on SIGHUP
+        #[arg(long)]
+        watch_config: bool,
+
+        /// Under sustained memory pressure (PSI), freeze or kill the cgroup
+        /// of the most `expendable` profile; thresholds under `responder:`
+        #[arg(long)]
+        respond_to_pressure: bool,
+    },
+
+    /// Call the control API of a running `rlm daemon` (version, list, apply,
+    /// remove) and print the JSON result
+    Ctl {
+        /// Method to call
+        method: String,
+
+        /// Its parameters as JSON, e.g. '{"pid": 4242, "limits": {"memory": "2G"}}'
+        #[arg(value_name = "JSON")]
+        params: Option<String>,
+    },
+
+    /// Manage the freeze-guard daemon (rlm-guard)
+    Guard {
+        #[command(subcommand)]
+        action: GuardAction,
+    },
+
+    /// Clean up cgroups left behind by killed rlm sessions
+    Gc {
+        /// Only show what would be cleaned up
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Measure the system and suggest limits
+    Calibrate {
+        #[command(subcommand)]
+        target: CalibrateTarget,
+    },
+}
+
+/// Limit flags shared by `limit`, `supervise`, `run`, `shell`, and `watch`
+#[derive(Args, Default)]
+struct LimitArgs {
+    /// Memory limit (K=1024, M=1024K, G=1024M, T=1024G)
+    #[arg(long, value_name = "SIZE")]
+    memory: Option<String>,
+
+    /// Swap the process may use (0 disables swap; --memory alone implies 0)
+    #[arg(long, value_name = "SIZE")]
+    swap: Option<String>,
+
+    /// On OOM, kill all processes in the cgroup together, not just one
+    #[arg(long)]
+    oom_group: bool,
+
+    /// CPU limit as percentage (50%=half core, 100%=1 core, 200%=2 cores)
+    #[arg(long, value_name = "PERCENT")]
+    cpu: Option<String>,
+
+    /// Proportional CPU share, 1-10000 (default 100); only throttles under contention
+    #[arg(long, value_name = "WEIGHT")]
+    cpu_weight: Option<u32>,
+
+    /// Run only when the CPU would otherwise be idle (good for batch jobs)
+    #[arg(long)]
+    idle: bool,
+
+    /// I/O read bandwidth limit per second (K/M/G/T units)
+    #[arg(long, value_name = "SIZE")]
+    io_read: Option<String>,
+
+    /// I/O write bandwidth limit per second (K/M/G/T units)
+    #[arg(long, value_name = "SIZE")]
+    io_write: Option<String>,
+
+    /// Apply --io-read/--io-write only to the disk holding this path (e.g. /home)
+    #[arg(long, value_name = "PATH")]
+    io_path: Option<std::path::PathBuf>,
+
+    /// Proportional I/O share, 1-10000 (default 100); only throttles under contention
+    #[arg(long, value_name = "WEIGHT")]
+    io_weight: Option<u32>,
+
+    /// Maximum number of processes and threads (stops fork bombs)
+    #[arg(long, value_name = "N")]
+    max_pids: Option<u64>,
+
+    /// NUMA memory nodes to allocate from (e.g. 0, 0-1, 0,2)
+    #[arg(long, value_name = "NODES")]
+    mems: Option<String>,
+}
+
+impl LimitArgs {
+    /// The error for a command given neither `--profile` nor any of these
+    /// flags, naming them.
+    fn none_given() -> Error {
+        let flags: Vec<String> = Self::augment_args(clap::Command::new("limits"))
+            .get_arguments()
+            .filter_map(|arg| arg.get_long())
+            // Only narrows --io-read/--io-write down, so not a limit itself
+            .filter(|&long| long != "io-path")
+            .map(|long| format!("--{long}"))
+            .collect();
+        Error::InvalidArgs(format!(
+            "specify --profile or at least one limit ({})",
+            flags.join(", ")
+        ))
+    }
+
+    /// The limits of `profile`, if any, with each one these flags give
+    /// replacing its value.
+    fn with_profile(&self, profile: Option<&common::Profile>) -> Result<common::Limit> {
+        let flags = self.to_limit()?;
+        let limit = match profile {
+            Some(profile) => profile.to_limit()?.merge(&flags),
+            None => flags,
+        };
+        // Checked on the merged limits: the profile may hold the I/O limits
+        // the flag's path is for
+        if limit.io_path.is_some() && limit.io.is_none() {
+            return Err(Error::InvalidArgs(
+                "--io-path needs --io-read or --io-write".into(),
+            ));
+        }
+        Ok(limit)
+    }
+
+    /// A rule for the executable `name` with the limits of `profile`, if
+    /// any, each one these flags give replacing its value. Kept as the
+    /// strings given, like a profile's.
+    fn rule_with_profile(&self, name: &str, profile: Option<&common::Profile>) -> common::AppRule {
+        let flags = self.to_profile();
+        let merged = match profile {
+            Some(profile) => profile.with_overrides(&flags),
+            None => flags,
+        };
+        common::AppRule::from_profile(name, &merged)
+    }
+
+    /// These flags as a profile's limits
+    fn to_profile(&self) -> common::Profile {
+        common::Profile {
+            memory: self.memory.clone(),
+            swap: self.swap.clone(),
+            oom_group: self.oom_group,
+            cpu: self.cpu.clone(),
+            cpu_weight: self.cpu_weight,
+            idle: self.idle,
+            io_read: self.io_read.clone(),
+            io_write: self.io_write.clone(),
+            io_path: self.io_path.as_ref().map(|p| p.display().to_string()),
+            io_weight: self.io_weight,
+            pids: self.max_pids,
+            mems: self.mems.clone(),
+            ..common::Profile::default()
+        }
+    }
+
+    fn to_limit(&self) -> Result<common::Limit> {
+        let mut limit = build_limit(
+            self.memory.as_deref(),
+            self.cpu.as_deref(),
+            self.io_read.as_deref(),
+            self.io_write.as_deref(),
+        )?;
+        limit.swap = self
+            .swap
+            .as_deref()
+            .map(common::SwapLimit::parse)
+            .transpose()?;
+        limit.oom_group = self.oom_group;
+        limit.cpu_weight = self.cpu_weight.map(common::CpuWeight::new).transpose()?;
+        limit.idle = self.idle;
+        limit.io_path = self.io_path.clone();
+        limit.io_weight = self.io_weight.map(common::IoWeight::new).transpose()?;
+        limit.pids = self.max_pids.map(common::PidsLimit::new).transpose()?;
+        limit.mems = self
+            .mems
+            .as_deref()
+            .map(common::MemNodes::parse)
+            .transpose()?;
+        Ok(limit)
+    }
+}
+
+/// Scheduler hints for `rlm run` and `rlm limit`, set alongside the limits.
+#[derive(Args, Default)]
+struct SchedArgs {
+    /// Niceness, -20 (most favored) to 19
+    #[arg(long, value_name = "N", allow_hyphen_values = true)]
+    nice: Option<i32>,
+
+    /// I/O priority class: realtime, best-effort, or idle (like ionice -c)
+    #[arg(long, value_name = "CLASS")]
+    ionice_class: Option<rlm_core::sched::IoClass>,
+
+    /// CPU scheduling policy: batch (CPU-bound, can wait) or idle (only
+    /// otherwise idle CPU)
+    #[arg(long, value_name = "POLICY")]
+    sched: Option<rlm_core::sched::Policy>,
+}
+
+impl SchedArgs {
+    fn to_hints(&self) -> Result<rlm_core::sched::Hints> {
+        rlm_core::sched::Hints::new(self.nice, self.ionice_class, self.sched)
+    }
+}
+
+/// How `rlm run` starts its command, besides the limits.
+#[derive(Args, Default)]
+struct LaunchArgs {
+    /// Directory to run the command in
+    #[arg(long, value_name = "DIR")]
+    workdir: Option<std::path::PathBuf>,
+
+    /// Set an environment variable for the command (repeatable)
+    #[arg(long, value_name = "KEY=VAL", value_parser = parse_env)]
+    env: Vec<(String, String)>,
+
+    /// Start from an empty environment, with only what --env sets (add PATH
+    /// if the command needs it)
+    #[arg(long)]
+    clear_env: bool,
+
+    /// Run the command as this user (name or UID), with their groups, HOME,
+    /// USER, and LOGNAME; needs root
+    #[arg(long)]
+    user: Option<String>,
+
+    /// Tell Go, Java, and Node about the memory limit (GOMEMLIMIT,
+    /// JAVA_TOOL_OPTIONS -Xmx, NODE_OPTIONS --max-old-space-size), so they
+    /// collect garbage before the limit instead of being OOM-killed at it
+    #[arg(long)]
+    export_env: bool,
+
+    /// Give the command a cgroup namespace of its own, so it sees its cgroup
+    /// as the root of the hierarchy and container-aware runtimes read their
+    /// limits from it (a user namespace too, when not run as root)
+    #[arg(long)]
+    cgroupns: bool,
+
+    /// Keep the command (and what it starts) from gaining privileges through
+    /// setuid programs like sudo, or file capabilities
+    #[arg(long)]
+    no_new_privs: bool,
+
+    /// Give the command an empty /tmp of its own, gone when it exits
+    #[arg(long)]
+    private_tmp: bool,
+
+    /// Let the command read its user's home directory but not change it
+    #[arg(long)]
+    read_only_home: bool,
+
+    /// Keep the command from opening this device node, e.g. /dev/video0 for
+    /// a webcam or /dev/dri/renderD128 for a GPU (repeatable)
+    #[arg(long, value_name = "PATH", value_parser = parse_device)]
+    deny_device: Vec<rlm_core::devices::Device>,
+
+    /// The variables --export-env sets, once the limit is known
+    #[arg(skip)]
+    runtime_env: Vec<(String, String)>,
+
+    #[command(flatten)]
+    rlimits: RlimitArgs,
+
+    #[command(flatten)]
+    sched: SchedArgs,
+}
+
+/// setrlimit limits for `rlm run`, for what cgroups can't limit. Each
+/// process gets its own, inherited by the ones it starts.
+#[derive(Args, Default)]
+struct RlimitArgs {
+    /// Open files each process may have (RLIMIT_NOFILE)
+    #[arg(long, value_name = "N", alias = "max-files")]
+    max_open_files: Option<u64>,
+
+    /// Largest core dump each process may write, 0 for none (K/M/G units;
+    /// RLIMIT_CORE)
+    #[arg(long, value_name = "SIZE")]
+    max_core: Option<String>,
+
+    /// Processes and threads the user may have in all, counting ones
+    /// outside the command (RLIMIT_NPROC)
+    #[arg(long, value_name = "N")]
+    max_nproc: Option<u64>,
+
+    /// The profile's, for those the flags leave unset
+    #[arg(skip)]
+    from_profile: rlm_core::rlimit::Rlimits,
+}
+
+impl RlimitArgs {
+    /// Take the limits the flags leave unset from profile `name`, if any.
+    fn add_profile(&mut self, name: Option<&str>) -> Result<()> {
+        let Some(name) = name else {
+            return Ok(());
+        };
+        if let Some(profile) = Config::load()?.get_profile(name) {
+            self.from_profile = rlm_core::rlimit::Rlimits::from_profile(&profile)?;
+        }
+        Ok(())
+    }
+
+    fn to_rlimits(&self) -> Result<rlm_core::rlimit::Rlimits> {
+        Ok(self.from_profile.or(rlm_core::rlimit::Rlimits {
+            open_files: self.max_open_files,
+            core_size: self
+                .max_core
+                .as_deref()
+                .map(rlm_core::rlimit::parse_core)
+                .transpose()?,
+            processes: self.max_nproc,
+            ..Default::default()
+        }))
+    }
+}
+
+impl LaunchArgs {
+    /// With --export-env, work out the runtimes' variables from `memory`,
+    /// the command's memory limit.
+    fn export_memory(&mut self, memory: Option<MemoryLimit>) -> Result<()> {
+        if !self.export_env {
+            return Ok(());
+        }
+        let Some(memory) = memory else {
+            return Err(Error::InvalidArgs(
+                "--export-env needs a memory limit (--memory, or a profile with one)".into(),
+            ));
+        };
+        let clear_env = self.clear_env;
+        self.runtime_env = rlm_core::runtime_env::for_memory(memory.bytes(), |key| {
+            std::env::var(key).ok().filter(|_| !clear_env)
+        });
+        Ok(())
+    }
+
+    /// Set up `command` accordingly. Call after the cgroup placement hook is
+    /// in, so that still runs with rlm's privileges.
+    fn apply_to(&self, command: &mut std::process::Command) -> Result<()> {
+        if self.clear_env {
+            command.env_clear();
+        }
+        // Before --user drops the privileges raising priority and limits needs
+        self.sched.to_hints()?.apply_to(command);
+        let rlimits = self.rlimits.to_rlimits()?;
+        if !rlimits.is_empty() {
+            rlimits.apply_to(command);
+        }
+        let account = match &self.user {
+            Some(user) => {
+                // SAFETY: geteuid has no preconditions.
+                if unsafe { libc::geteuid() } != 0 {
+                    return Err(Error::InvalidArgs("--user needs root".into()));
+                }
+                Some(rlm_core::account::Account::of(rlm_core::process::user_id(
+                    user,
+                )?)?)
+            }
+            None => None,
+        };
+        let read_only_home = match (&account, self.read_only_home) {
+            (Some(account), true) => Some(account.home.clone()),
+            // SAFETY: geteuid has no preconditions.
+            (None, true) => Some(rlm_core::account::Account::of(unsafe { libc::geteuid() })?.home),
+            (_, false) => None,
+        };
+        // After the hints and rlimits: in the user namespace a non-root rlm
+        // enters with it, raising them is no longer allowed
+        rlm_core::sandbox::Sandbox {
+            cgroup_ns: self.cgroupns,
+            private_tmp: self.private_tmp,
+            read_only: read_only_home.into_iter().collect(),
+            no_new_privs: self.no_new_privs,
+        }
+        .apply_to(command)?;
+        if let Some(account) = account {
+            command
+                .env("HOME", &account.home)
+                .env("USER", account.user())
+                .env("LOGNAME", account.user());
+            account.run_as(command);
+        }
+        // Before --env, so a variable given there wins
+        command.envs(self.runtime_env.iter().map(|(k, v)| (k, v)));
+        command.envs(self.env.iter().map(|(k, v)| (k, v)));
+        if let Some(dir) = &self.workdir {
+            command.current_dir(dir);
+        }
+        Ok(())
+    }
+}
+
+/// Parse a device node path for `--deny-device`.
+fn parse_device(s: &str) -> Result<rlm_core::devices::Device> {
+    rlm_core::devices::Device::of(std::path::Path::new(s))
+}
+
+/// Parse `KEY=VAL` for `--env`.
+fn parse_env(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(Error::InvalidArgs(format!(
+            "invalid --env '{s}' (expected KEY=VAL)"
+        ))),
+    }
+}
+
+#[derive(Subcommand)]
+enum CalibrateTarget {
+    /// Probe disk throughput and suggest I/O limits for each tier
+    Io {
+        /// Block device to probe read-only (e.g. /dev/nvme0n1; usually needs root)
+        #[arg(long, value_name = "DEVICE", conflicts_with = "dir")]
+        device: Option<std::path::PathBuf>,
+
+        /// Directory whose filesystem to probe with a temporary file [default: cache dir]
+        #[arg(long, value_name = "DIR")]
+        dir: Option<std::path::PathBuf>,
+
+        /// Amount of data for the sequential passes (K/M/G units)
+        #[arg(long, value_name = "SIZE", default_value = "256M")]
+        size: String,
+
+        /// Save the suggestions as profiles io-balanced, io-background, io-strict
+        #[arg(long)]
+        save: bool,
+    },
+}
+
+/// Which managed processes `rlm status` shows, and how.
+#[derive(Args)]
+struct StatusFilter {
+    /// List every process in shared cgroups under their row
+    #[arg(long)]
+    members: bool,
+
+    /// Only processes whose name contains this (case-insensitive)
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Only cgroups whose name starts with this (e.g. app-, run-, reserve-)
+    #[arg(long, value_name = "PREFIX")]
+    cgroup_prefix: Option<String>,
+
+    /// Sort by memory or CPU use (highest first) or by PID
+    #[arg(long, value_enum)]
+    sort: Option<StatusSort>,
+
+    /// Show at most this many processes
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ChildrenMode {
+    Current,
+    Follow,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SummaryFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StatusOutput {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatusSort {
+    Memory,
+    Cpu,
+    Pid,
+}
+
+impl StatusFilter {
+    fn apply(
+        &self,
+        mut processes: Vec<rlm_core::status::ProcessStatus>,
+    ) -> Vec<rlm_core::status::ProcessStatus> {
+        if let Some(name) = &self.name {
+            let name = name.to_lowercase();
+            processes.retain(|p| p.name.to_lowercase().contains(&name));
+        }
+        if let Some(prefix) = &self.cgroup_prefix {
+            processes.retain(|p| p.cgroup_name.starts_with(prefix.as_str()));
+        }
+        match self.sort {
+            Some(StatusSort::Memory) => {
+                processes.sort_by_key(|p| std::cmp::Reverse(p.usage.memory_current))
+            }
+            Some(StatusSort::Cpu) => {
+                let cpu = |p: &rlm_core::status::ProcessStatus| {
+                    p.rates.and_then(|r| r.cpu_percent).unwrap_or(0.0)
+                };
+                processes.sort_by(|a, b| cpu(b).total_cmp(&cpu(a)))
+            }
+            Some(StatusSort::Pid) => processes.sort_by_key(|p| p.pid),
+            None => {}
+        }
+        if let Some(limit) = self.limit {
+            processes.truncate(limit);
+        }
+        processes
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Md,
+    Html,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExportFormat {
+    Yaml,
+    Systemd,
+    SystemdSlice,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InitShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RecordFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Subcommand)]
+enum GuardAction {
+    /// Show current memory pressure and active guard interventions
+    Status,
+    /// Enable and start the guard user service
+    Enable,
+    /// Disable and stop the guard user service
+    Disable,
+    /// Dry-run: print what the guard would do right now, without acting
+    Test,
+}
+
+#[derive(Subcommand)]
+enum RuleAction {
+    /// List saved persistent application rules
+    List,
+    /// Remove a saved rule by name
+    Remove {
+        /// Rule name (the executable name used when saving)
+        name: String,
+    },
+}
+
+pub fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive(tracing::Level::INFO.into()),
+        )
+        .init();
+
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<ExitCode> {
+    let mut cli = Cli::parse();
+    let yes = cli.yes;
+    if let Some(ref root) = cli.cgroup_root {
+        // Not through the environment, or every program rlm runs would
+        // inherit it
+        CgroupManager::set_root(root.clone());
+    }
+    let manager = match CgroupManager::new() {
+        Ok(manager) if manager.has_delegation() => manager,
+        result => match cli.command {
+            Commands::Run {
+                fallback_rlimit: true,
+                ref profile,
+                ref limits,
+                cpu_time,
+                ref mut launch,
+                ref command,
+                ..
+            } => {
+                launch.rlimits.add_profile(profile.as_deref())?;
+                return run_with_rlimits(profile.clone(), limits, cpu_time, launch, command);
+            }
+            _ => result?,
+        },
+    };
+
+    // Clean up after sessions that were killed before they could
+    if !matches!(cli.command, Commands::Gc { .. }) {
+        if let Err(e) = rlm_core::state::gc(&manager, false) {
+            tracing::debug!("cgroup gc failed: {e}");
+        }
+    }
+
+    match cli.command {
+        Commands::Limit {
+            pid,
+            name,
+            cmdline,
+            exclude,
+            application,
+            all_pids,
+            recursive,
+            children,
+            unit,
+            runtime,
+            session,
+            user,
+            profile,
+            limits,
+            sched,
+            dry_run,
+            persistent,
+        } => {
+            let profile = profile.as_deref().map(load_profile).transpose()?;
+            let limit = limits.with_profile(profile.as_ref())?;
+            if limit.is_empty() {
+                return Err(LimitArgs::none_given());
+            }
+
+            let hints = sched.to_hints()?;
+            if !hints.is_empty() && (unit.is_some() || session.is_some() || user.is_some()) {
+                return Err(Error::InvalidArgs(
+                    "--nice, --ionice-class, and --sched need --pid, --name, --application, or --all-pids".into(),
+                ));
+            }
+
+            if let Some(unit) = unit {
+                return limit_unit(&unit, &limit, runtime, dry_run);
+            }
+            if let Some(session) = session {
+                let scope = rlm_core::units::session_scope(session.as_deref())?;
+                return limit_unit(&scope, &limit, true, dry_run);
+            }
+            if let Some(user) = user {
+                return limit_user(&manager, &user, &limit, dry_run, yes);
+            }
+
+            // A persistent limit is a rule, which keeps every instance in one
+            // shared cgroup; limit the running ones the same way.
+            let (name, application) = match (persistent, name, application) {
+                (true, Some(name), None) => (None, Some(name)),
+                (_, name, application) => (name, application),
+            };
+            if persistent && application.is_none() {
+                return Err(Error::InvalidArgs(
+                    "--persistent needs --application or --name".into(),
+                ));
+            }
+            // Remember the application name for persisting a rule after apply.
+            let save_app = if persistent {
+                application.clone()
+            } else {
+                None
+            };
+
+            // Determine which mode we're in
+            let (pids, cgroup_name, is_shared) = if let Some(app_name) = application {
+                // Application mode: all processes share limits
+                let pids = resolve_application_pids(&app_name)?;
+                if pids.is_empty() {
+                    return Err(Error::ProcessNameNotFound(app_name));
+                }
+                let cgroup_name = format!("app-{}", app_name.replace(['/', ' '], "_"));
+                println!(
+                    "Found {} process(es) for application '{}'",
+                    pids.len(),
+                    app_name
+                );
+                (pids, cgroup_name, true)
+            } else if let Some(pids_str) = all_pids {
+                // Multiple PIDs mode: all share limits
+                let pids = parse_pid_list(&pids_str)?;
+                if pids.is_empty() {
+                    return Err(Error::InvalidArgs("no valid PIDs specified".into()));
+                }
+                let cgroup_name = format!("multi-{}", pids[0]);
+                (pids, cgroup_name, true)
+            } else if let Some(root) = pid.filter(|_| recursive || children.is_some()) {
+                // Process tree mode: the tree shares the root's own cgroup,
+                // so `rlm unlimit --pid` on the root undoes it
+                if rlm_core::process::info(root).is_none() {
+                    return Err(Error::ProcessNotFound(root));
+                }
+                let pids = rlm_core::process::find_process_tree(root)?;
+                println!("Found {} process(es) in the tree of {root}", pids.len());
+                (pids, format!("pid-{root}"), true)
+            } else {
+                // Individual mode: each process gets its own limits
+                let pids = resolve_pids(pid, name.as_deref(), cmdline.as_deref())?;
+                (pids, String::new(), false)
+            };
+            let pids = without_excluded(pids, &exclude)?;
+
+            if dry_run {
+                println!(
+                    "Dry run - would apply limits to {} process(es):",
+                    pids.len()
+                );
+                for pid in &pids {
+                    let name = std::fs::read_to_string(format!("/proc/{pid}/comm"))
+                        .map(|s| s.trim().to_string())
+                        .unwrap_or_else(|_| "?".to_string());
+                    println!("  {pid}: {name}");
+                }
+                if is_shared {
+                    println!("\n⚠️  All processes will SHARE these limits (combined pool):");
+                } else {
+                    println!("\nLimits (per process):");
+                }
+                if let Some(ref mem) = limit.memory {
+                    println!("  Memory: {}", format_bytes(mem.bytes()));
+                }
+                match limit.swap.map(|s| s.bytes()) {
+                    Some(0) => println!("  Swap: disabled"),
+                    Some(bytes) => println!("  Swap: {}", format_bytes(bytes)),
+                    None => {}
+                }
+                if limit.oom_group {
+                    println!("  OOM: kill the whole group");
+                }
+                if let Some(ref cpu) = limit.cpu {
+                    println!("  CPU: {}%", cpu.percent());
+                }
+                if let Some(weight) = limit.cpu_weight {
+                    println!("  CPU weight: {}", weight.weight());
+                }
+                if limit.idle {
+                    println!("  CPU: idle scheduling");
+                }
+                if let Some(ref io) = limit.io {
+                    if let Some(r) = io.read_bps {
+                        println!("  I/O Read: {}/s", format_bytes(r));
+                    }
+                    if let Some(w) = io.write_bps {
+                        println!("  I/O Write: {}/s", format_bytes(w));
+                    }
+                    if let Some(ref path) = limit.io_path {
+                        println!("  I/O device: the one holding {}", path.display());
+                    }
+                }
+                if let Some(weight) = limit.io_weight {
+                    println!("  I/O weight: {}", weight.weight());
+                }
+                if let Some(pids) = limit.pids {
+                    println!("  Max PIDs: {}", pids.count());
+                }
+                if let Some(ref mems) = limit.mems {
+                    println!("  NUMA nodes: {}", mems.as_str());
+                }
+                if let Some(nice) = hints.nice {
+                    println!("  Nice: {nice}");
+                }
+                if let Some(class) = hints.io_class {
+                    println!("  I/O class: {class}");
+                }
+                if let Some(policy) = hints.policy {
+                    println!("  Scheduling policy: {policy}");
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            // Each process on its own: the user may pick some of them
+            let pids = if is_shared {
+                if confirm_batch(&pids, "Limit", yes)? {
+                    pids
+                } else {
+                    Vec::new()
+                }
+            } else {
+                pick_batch(pids, "Limit", yes)?
+            };
+            if pids.is_empty() {
+                println!("cancelled");
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            if is_shared {
+                // Apply shared limits to all processes
+                manager.apply_limit_to_multiple(&pids, &limit, &cgroup_name)?;
+                println!(
+                    "Applied shared limits to {} process(es) in cgroup '{}'",
+                    pids.len(),
+                    cgroup_name
+                );
+                println!("⚠️  Note: All processes share these limits (combined pool)");
+
+                // Persist as a rule so it survives reboot and applies to future
+                // instances (enforced by rlm-guard).
+                if let Some(app) = save_app {
+                    let mut config = Config::load()?;
+                    config.add_rule(&app, limits.rule_with_profile(&app, profile.as_ref()));
+                    config.save()?;
+                    println!(
+                        "Saved persistent rule '{app}' (rlm-guard and rlm daemon re-apply it to restarted and future instances)"
+                    );
+                    if !is_guard_active() {
+                        println!(
+                            "  hint: to re-apply it after restarts and reboots, run: rlm guard enable"
+                        );
+                    }
+                }
+            } else {
+                // Apply individual limits to each process
+                for pid in &pids {
+                    manager.apply_limit(*pid, &limit)?;
+                    println!("applied limits to pid {pid}");
+                }
+            }
+            if !hints.is_empty() {
+                for pid in &pids {
+                    hints.apply_to_pid(*pid)?;
+                }
+                println!("set scheduler hints on {} process(es)", pids.len());
+            }
+            if let (Some(root), Some(ChildrenMode::Follow)) = (pid, children) {
+                follow_children(&manager, root, &cgroup_name, &pids)?;
+            }
+        }
+
+        Commands::Unlimit {
+            pid,
+            name,
+            cmdline,
+            exclude,
+            application,
+            cgroup,
+            unit,
+            session,
+            user,
+            all,
+            memory,
+            cpu,
+            io,
+            forget,
+        } => {
+            if all {
+                return unlimit_all(&manager, yes);
+            }
+            let controllers: Vec<Controller> = [
+                (memory, Controller::Memory),
+                (cpu, Controller::Cpu),
+                (io, Controller::Io),
+            ]
+            .into_iter()
+            .filter_map(|(given, controller)| given.then_some(controller))
+            .collect();
+            if !controllers.is_empty() {
+                let cgroups = match (cgroup, application) {
+                    (Some(cgroup_name), _) => vec![cgroup_name],
+                    (None, Some(app_name)) => {
+                        vec![format!("app-{}", app_name.replace(['/', ' '], "_"))]
+                    }
+                    (None, None) => {
+                        let pids = resolve_pids(pid, name.as_deref(), cmdline.as_deref())?;
+                        let pids = without_excluded(pids, &exclude)?;
+                        let mut cgroups = Vec::new();
+                        for pid in pids {
+                            let cgroup = manager.find_cgroup_for_pid(pid).ok_or_else(|| {
+                                Error::InvalidArgs(format!("pid {pid} has no limits rlm set"))
+                            })?;
+                            if !cgroups.contains(&cgroup) {
+                                cgroups.push(cgroup);
+                            }
+                        }
+                        cgroups
+                    }
+                };
+                let names: Vec<&str> = controllers.iter().map(|c| c.name()).collect();
+                for cgroup_name in cgroups {
+                    manager.reset_controllers(&cgroup_name, &controllers)?;
+                    println!(
+                        "removed {} limits from cgroup '{cgroup_name}'; its other limits stay",
+                        names.join(" and ")
+                    );
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            let unit = match session {
+                Some(session) => Some(rlm_core::units::session_scope(session.as_deref())?),
+                None => unit,
+            };
+            if let Some(user) = user {
+                let uid = rlm_core::process::user_id(&user)?;
+                if rlm_core::units::remove(&rlm_core::units::user_slice(uid))? {
+                    println!("reset the limits rlm set on user {user}'s slice");
+                } else {
+                    manager.remove_application_limit(&format!("user-{uid}"))?;
+                    println!("removed limits from user {user}");
+                }
+            } else if let Some(unit) = unit {
+                if !rlm_core::units::remove(&unit)? {
+                    return Err(Error::InvalidArgs(format!(
+                        "rlm set no limits on unit '{unit}'"
+                    )));
+                }
+                println!("reset the limits rlm set on {unit}");
+            } else if let Some(cgroup_name) = cgroup {
+                // Remove by cgroup name
+                manager.remove_application_limit(&cgroup_name)?;
+                println!("removed limits from cgroup '{}'", cgroup_name);
+            } else if let Some(app_name) = application {
+                // Remove application cgroup
+                let cgroup_name = format!("app-{}", app_name.replace(['/', ' '], "_"));
+                manager.remove_application_limit(&cgroup_name)?;
+                println!("removed limits from application '{}'", app_name);
+
+                // The saved rule persists unless --forget is given. Otherwise the
+                // daemon would simply re-apply it on the next reconcile.
+                if forget {
+                    let mut config = Config::load()?;
+                    if config.remove_rule(&app_name) {
+                        config.save()?;
+                        println!("forgot persistent rule '{}'", app_name);
+                    }
+                } else {
+                    let config = Config::load()?;
+                    if config.rules.contains_key(&app_name) {
+                        println!(
+                            "  note: persistent rule '{}' still saved (rlm-guard will re-apply it); use --forget to delete it",
+                            app_name
+                        );
+                    }
+                }
+            } else {
+                // Remove individual processes
+                let pids = resolve_pids(pid, name.as_deref(), cmdline.as_deref())?;
+                let pids = without_excluded(pids, &exclude)?;
+
+                let pids = pick_batch(pids, "Unlimit", yes)?;
+                if pids.is_empty() {
+                    println!("cancelled");
+                    return Ok(ExitCode::SUCCESS);
+                }
+
+                for pid in &pids {
+                    manager.remove_limit(*pid)?;
+                    println!("removed limits from pid {pid}");
+                }
+            }
+        }
+
+        Commands::Reserve { name, memory, hard } => {
+            let bytes = MemoryLimit::parse(&memory)?.bytes();
+            let pids = resolve_application_pids(&name)?;
+            if pids.is_empty() {
+                return Err(Error::ProcessNameNotFound(name));
+            }
+            let ceiling =
+                manager.reserve_memory(&pids, &name.replace(['/', ' '], "_"), bytes, hard)?;
+            println!(
+                "Reserved {} for '{}' ({} process(es))",
+                format_bytes(bytes),
+                name,
+                pids.len()
+            );
+            println!(
+                "Other managed cgroups are capped at {} (memory.high)",
+                format_bytes(ceiling)
+            );
+            println!("Undo with: rlm unreserve --name {name}");
+        }
+
+        Commands::Unreserve { name } => {
+            manager.release_reservation(&name.replace(['/', ' '], "_"))?;
+            println!("released memory reservation for '{}'", name);
+        }
+
+        Commands::Protect {
+            pid: Some(pid),
+            memory_reserve: Some(memory),
+            hard,
+            ..
+        } => {
+            let bytes = MemoryLimit::parse(&memory)?.bytes();
+            let name = format!("pid-{pid}");
+            let ceiling = manager.reserve_memory(&[pid], &name, bytes, hard)?;
+            let kind = if hard { "memory.min" } else { "memory.low" };
+            println!("Reserved {} for PID {pid} ({kind})", format_bytes(bytes));
+            println!(
+                "Other managed cgroups are capped at {} (memory.high)",
+                format_bytes(ceiling)
+            );
+            println!("Undo with: rlm unreserve --name {name}");
+        }
+
+        Commands::Protect { state, .. } => match state {
+            Some(Toggle::On) => {
+                let moved = rlm_core::protect::enable(&manager)?;
+                println!("desktop protection on ({moved} process(es) moved)");
+                if is_guard_active() {
+                    println!("  rlm-guard will move newly launched apps in as well");
+                } else {
+                    println!(
+                        "  hint: apps launched from now on are only covered with rlm-guard running: rlm guard enable"
+                    );
+                }
+            }
+            Some(Toggle::Off) => {
+                rlm_core::protect::disable(&manager)?;
+                println!("desktop protection off");
+            }
+            None => {
+                let state = if manager.protect_enabled() {
+                    "on"
+                } else {
+                    "off"
+                };
+                println!("desktop protection is {state}");
+            }
+        },
+
+        Commands::ProtectSession { state, memory } => match state {
+            Some(Toggle::On) => {
+                let bytes = MemoryLimit::parse(&memory)?.bytes();
+                let p = rlm_core::session::enable(&manager, bytes)?;
+                println!(
+                    "session protection on: {} reserved for {}",
+                    format_bytes(p.bytes),
+                    p.cgroup.display()
+                );
+                println!("  managed cgroups are capped to leave it free (memory.high)");
+                if let Some(dir) = &p.bounded_by {
+                    println!(
+                        "  note: {} has a lower memory.low and caps the protection; run as root to raise it",
+                        dir.display()
+                    );
+                }
+            }
+            Some(Toggle::Off) => {
+                if rlm_core::session::disable(&manager)? {
+                    println!("session protection off");
+                } else {
+                    println!("session protection was not on");
+                }
+            }
+            None => match rlm_core::session::current() {
+                Some(p) => println!(
+                    "session protection is on: {} reserved for {}",
+                    format_bytes(p.bytes),
+                    p.cgroup.display()
+                ),
+                None => println!("session protection is off"),
+            },
+        },
+
+        Commands::Rescue { undo: true, .. } => match rlm_core::rescue::undo(&manager)? {
+            Some(held) => {
+                println!("released {} rescued process(es)", held.offenders.len());
+                for o in &held.offenders {
+                    println!("  {} ({}) -> {}", o.pid, o.name, o.from);
+                }
+            }
+            None => println!("nothing is rescued"),
+        },
+
+        Commands::Rescue { count, freeze, .. } => {
+            let moved = rlm_core::rescue::rescue(&manager, count, freeze)?;
+            if moved.is_empty() {
+                println!("no process is using enough memory or CPU to rescue from");
+            } else {
+                let what = if freeze {
+                    "froze and throttled"
+                } else {
+                    "throttled"
+                };
+                println!("{what} {} process(es):", moved.len());
+                for o in &moved {
+                    println!(
+                        "  {:>7}  {:<16} {:>9}  {:>5.0}% CPU",
+                        o.pid,
+                        o.name,
+                        format_bytes(o.memory),
+                        o.cpu_percent
+                    );
+                }
+                println!("Undo with: rlm rescue --undo");
+            }
+        }
+
+        Commands::Events { follow, json } => {
+            run_events(&manager, follow, json)?;
+        }
+
+        Commands::History {
+            pid,
+            name,
+            limit,
+            json,
+        } => {
+            print_history(pid, name.as_deref(), limit, json);
+        }
+
+        Commands::Record {
+            pid,
+            interval,
+            output,
+            format,
+            duration,
+        } => {
+            run_record(&manager, pid, interval, &output, format, duration)?;
+        }
+
+        Commands::Watchdog {
+            name,
+            memory,
+            cpu,
+            action,
+            sustain,
+            interval,
+        } => {
+            let watchdog = rlm_core::watchdog::Watchdog::new(
+                memory
+                    .map(|m| MemoryLimit::parse(&m).map(|m| m.bytes()))
+                    .transpose()?,
+                cpu.map(|c| common::CpuLimit::parse(&c).map(|c| c.percent()))
+                    .transpose()?,
+                sustain,
+            );
+            run_watchdog(&manager, &name, watchdog, action, interval)?;
+        }
+
+        Commands::Reclaim { pid, amount } => {
+            let bytes = MemoryLimit::parse(&amount)?.bytes();
+            let (cgroup_path, freed) = manager.reclaim_pid(pid, bytes)?;
+            println!(
+                "reclaimed {} of {} requested from {}",
+                format_bytes(freed),
+                format_bytes(bytes),
+                cgroup_path.display()
+            );
+            if freed < bytes {
+                println!("  the rest is in active use (or there is no swap to move it to)");
+            }
+        }
+
+        Commands::Supervise {
+            name,
+            profile,
+            limits,
+            on_oom,
+        } => {
+            let Some(name) = name else {
+                if !limits.to_limit()?.is_empty() || limits.io_path.is_some() {
+                    return Err(Error::InvalidArgs("limit flags need --name".into()));
+                }
+                return list_supervised();
+            };
+
+            let rule = app_rule_from(&name, profile, &limits)?;
+            let limit = rule.to_limit()?;
+
+            // Apply now to running instances; rlm-guard takes over from here.
+            let cgroup_name = rlm_core::supervise::cgroup_name_for(&name);
+            let pids = resolve_application_pids(&name)?;
+            if !pids.is_empty() {
+                manager.apply_limit_to_multiple(&pids, &limit, &cgroup_name)?;
+                println!(
+                    "limited {} running process(es) in cgroup '{}'",
+                    pids.len(),
+                    cgroup_name
+                );
+            }
+
+            let mut config = Config::load()?;
+            config.add_supervised(&name, common::SuperviseRule { rule, on_oom });
+            config.save()?;
+            println!(
+                "supervising '{name}' (on OOM: {on_oom}); interventions are logged by rlm-guard"
+            );
+            println!("  view them with: journalctl --user -u rlm-guard");
+            if !is_guard_active() {
+                println!(
+                    "  hint: enable the daemon for supervision to take effect: rlm guard enable"
+                );
+            }
+        }
+
+        Commands::Unsupervise { name } => {
+            let mut config = Config::load()?;
+            if !config.remove_supervised(&name) {
+                return Err(Error::InvalidArgs(format!("'{name}' is not supervised")));
+            }
+            config.save()?;
+            println!("stopped supervising '{name}'");
+            println!(
+                "  note: its current limit stays; use `rlm unlimit --cgroup {}` to drop it",
+                rlm_core::supervise::cgroup_name_for(&name)
+            );
+        }
+
+        Commands::Run {
+            profile,
+            limits,
+            restart_on_oom,
+            restart,
+            max_restarts,
+            backoff,
+            timeout,
+            kill_after,
+            detach,
+            summary,
+            join,
+            mut launch,
+            command,
+            ..
+        } => {
+            launch.rlimits.add_profile(profile.as_deref())?;
+            // Joining without limits takes the shared cgroup as it is
+            let limit = match &join {
+                Some(_) if profile.is_none() && limits.to_limit()?.is_empty() => None,
+                _ => Some(resolve_limit(profile, &limits)?),
+            };
+            launch.export_memory(limit.as_ref().and_then(|l| l.memory))?;
+            if let Some(limit) = limit.as_ref().filter(|_| detach) {
+                return run_detached(&manager, limit, &launch, &command);
+            }
+            let cgroup = match (join, &limit) {
+                (None, Some(limit)) => RunCgroup::Own(limit),
+                (None, None) => unreachable!("only --join runs without limits"),
+                (Some(name), _) => {
+                    let name = format!("run-join-{name}");
+                    if limit.is_none() && !manager.cgroup_exists(&name) {
+                        return Err(Error::InvalidArgs(format!(
+                            "no {name} cgroup to join yet; the first command to join it needs --profile or a limit"
+                        )));
+                    }
+                    RunCgroup::Joined {
+                        name,
+                        limit: limit.as_ref(),
+                    }
+                }
+            };
+            let restart = match restart {
+                Some(on_failure) => Some(RestartPolicy {
+                    on: Restart::OnFailure,
+                    max_restarts: on_failure.max,
+                    backoff,
+                }),
+                None => restart_on_oom.then_some(RestartPolicy {
+                    on: Restart::OnOom,
+                    max_restarts: Some(max_restarts),
+                    backoff,
+                }),
+            };
+            let stopping = Stopping {
+                timeout,
+                kill_after: kill_after.or(timeout.map(|_| Duration::from_secs(10))),
+            };
+            return run_with_limits(
+                &manager,
+                cgroup,
+                &command,
+                restart.as_ref(),
+                &stopping,
+                summary,
+                &launch,
+            );
+        }
+
+        Commands::Launch {
+            app,
+            profile,
+            limits,
+            detach,
+            args,
+        } => {
+            let Some(app) = app else {
+                for app in rlm_core::desktop::list_applications()? {
+                    println!("{:<32} {}", app.name, app.exec);
+                }
+                return Ok(ExitCode::SUCCESS);
+            };
+            let app = rlm_core::desktop::find_application(&app)?;
+            let limit = resolve_limit(profile, &limits)?;
+            let mut command = app.command();
+            command.extend(args);
+            let launch = LaunchArgs::default();
+            if detach {
+                return run_detached(&manager, &limit, &launch, &command);
+            }
+            eprintln!("launching {}: {}", app.name, command.join(" "));
+            return run_with_limits(
+                &manager,
+                RunCgroup::Own(&limit),
+                &command,
+                None,
+                &Stopping::default(),
+                None,
+                &launch,
+            );
+        }
+
+        Commands::Shell {
+            profile,
+            limits,
+            attach,
+        } => {
+            let label = profile.clone().unwrap_or_else(|| "limited".into());
+            let limit = resolve_limit(profile, &limits)?;
+
+            if attach {
+                let ppid = std::os::unix::process::parent_id();
+                let cgroup_name = format!("shell-{ppid}");
+                manager.apply_limit_to_multiple(&[ppid], &limit, &cgroup_name)?;
+                println!("limited this shell (pid {ppid}) in cgroup '{cgroup_name}'");
+                println!("  commands started from now on share the budget; jobs already running are not moved");
+                println!("  undo with: rlm unlimit --cgroup {cgroup_name}");
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
+            if let Ok(outer) = std::env::var("RLM_SHELL") {
+                eprintln!("note: already in a limited shell ({outer}); the new one gets its own budget instead");
+            }
+            let summary = rlm_core::history::summary(&limit);
+            println!("starting limited shell ({shell}: {summary}); exit it to leave the budget");
+            println!("  $RLM_SHELL is set inside; add ${{RLM_SHELL:+[rlm:$RLM_SHELL] }} to PS1 to see it in your prompt");
+            // So the shell, its prompt, and scripts can tell they are limited
+            let launch = LaunchArgs {
+                env: vec![("RLM_SHELL".into(), label), ("RLM_LIMITS".into(), summary)],
+                ..LaunchArgs::default()
+            };
+            return run_with_limits(
+                &manager,
+                RunCgroup::Own(&limit),
+                &[shell],
+                None,
+                &Stopping::default(),
+                None,
+                &launch,
+            );
+        }
+
+        Commands::ShellInit { shell } => {
+            let shell = match shell {
+                InitShell::Bash => rlm_core::shell_init::Shell::Bash,
+                InitShell::Zsh => rlm_core::shell_init::Shell::Zsh,
+                InitShell::Fish => rlm_core::shell_init::Shell::Fish,
+            };
+            let profiles = Config::load()?.all_profiles();
+            print!("{}", rlm_core::shell_init::script(shell, &profiles));
+        }
+        Commands::Profiles => {
+            let config = Config::load()?;
+            let all_profiles = config.all_profiles();
+
+            println!(
+                "{:<15} {:>10} {:>10} {:>10} {:>10} {:>6}",
+                "NAME", "MEMORY", "CPU", "IO_READ", "IO_WRITE", "PIDS"
+            );
+            println!("{}", "-".repeat(67));
+
+            // Sort profiles by name
+            let mut names: Vec<_> = all_profiles.keys().collect();
+            names.sort();
+
+            for name in names {
+                let profile = &all_profiles[name];
+                let mem = profile.memory.as_deref().unwrap_or("-");
+                let cpu = profile.cpu.as_deref().unwrap_or("-");
+                let ior = profile.io_read.as_deref().unwrap_or("-");
+                let iow = profile.io_write.as_deref().unwrap_or("-");
+                let pids = profile.pids.map_or("-".into(), |n| n.to_string());
+                println!(
+                    "{:<15} {:>10} {:>10} {:>10} {:>10} {:>6}",
+                    name, mem, cpu, ior, iow, pids
+                );
+            }
+
+            if config.profiles.is_empty() {
+                println!("\n(showing built-in presets; add custom profiles to ~/.config/rlm/config.yaml)");
+            }
+        }
+
+        Commands::Export {
+            file,
+            format,
+            profile,
+        } => {
+            let config = Config::load()?;
+            if format != ExportFormat::Yaml {
+                let Some(name) = profile else {
+                    return Err(Error::InvalidArgs(
+                        "--format systemd needs --profile".into(),
+                    ));
+                };
+                let Some(p) = config.get_profile(&name) else {
+                    return Err(Error::Config(format!("profile '{name}' not found")));
+                };
+                let unit = rlm_core::units::unit_file(
+                    &name,
+                    &p.to_limit()?,
+                    format == ExportFormat::SystemdSlice,
+                )?;
+                match file {
+                    Some(file) => {
+                        std::fs::write(&file, unit)?;
+                        println!("exported profile '{name}' to {file}");
+                    }
+                    None => print!("{unit}"),
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            // Export only user-defined profiles. Built-in presets are always
+            // available, so including them would re-import as user profiles and
+            // permanently pollute the user's config on a round-trip.
+            let profiles = config.profiles.clone();
+
+            if profiles.is_empty() {
+                println!(
+                    "no user-defined profiles to export (built-in presets are always available)"
+                );
+            } else {
+                // Create export structure
+                let export = serde_yaml_ng::to_string(&profiles)
+                    .map_err(|e| Error::Config(format!("Failed to serialize profiles: {e}")))?;
+
+                match file {
+                    Some(file) => {
+                        std::fs::write(&file, export)?;
+                        println!("exported {} profiles to {}", profiles.len(), file);
+                    }
+                    None => print!("{export}"),
+                }
+            }
+        }
+
+        Commands::Import { file, overwrite } => {
+            // 1MB limit (same as config loading)
+            let metadata = std::fs::metadata(&file)?;
+            if metadata.len() > 1024 * 1024 {
+                return Err(Error::Config("import file too large (max 1MB)".into()));
+            }
+            let content = std::fs::read_to_string(&file)?;
+            let imported: std::collections::HashMap<String, common::Profile> =
+                serde_yaml_ng::from_str(&content)
+                    .map_err(|e| Error::Config(format!("Failed to parse profiles: {e}")))?;
+
+            if imported.is_empty() {
+                println!("no profiles in file");
+            } else {
+                let mut config = Config::load()?;
+                let mut added = 0;
+                let mut skipped = 0;
+
+                for (name, profile) in imported {
+                    if config.profiles.contains_key(&name) && !overwrite {
+                        println!("skipped '{}' (already exists, use --overwrite)", name);
+                        skipped += 1;
+                    } else {
+                        config.profiles.insert(name.clone(), profile);
+                        println!("imported '{}'", name);
+                        added += 1;
+                    }
+                }
+
+                config.save()?;
+                println!("\nimported {} profiles ({} skipped)", added, skipped);
+            }
+        }
+
+        Commands::Status {
+            watch,
+            interval,
+            output,
+            filter,
+        } => {
+            if watch {
+                watch_status(&manager, interval, &filter)?;
+            } else {
+                // Long enough for a meaningful CPU% and I/O rate
+                let processes = rlm_core::status::get_managed_processes_sampled(
+                    &manager,
+                    std::time::Duration::from_millis(500),
+                )?;
+                let processes = filter.apply(processes);
+                if output == StatusOutput::Json {
+                    let state = rlm_core::snapshot::ManagedState::from_processes(&processes);
+                    println!("{}", state.to_json());
+                } else {
+                    print_status(&processes, None, filter.members);
+                    print_unit_status(&rlm_core::units::status());
+                }
+            }
+        }
+
+        Commands::Top => {
+            top::run(&manager)?;
+        }
+
+        Commands::Stats { target } => {
+            let path = rlm_core::stats::resolve(&manager, &target)?;
+            print_stats(&rlm_core::stats::read(&path)?);
+        }
+
+        Commands::Pressure => {
+            print_pressure(&manager);
+        }
+
+        Commands::Report { format, output } => {
+            let report = rlm_core::report::collect(&manager, Duration::from_millis(500));
+            let text = match format {
+                ReportFormat::Md => report.to_markdown(),
+                ReportFormat::Html => report.to_html(),
+            };
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, text)?;
+                    println!("wrote report to {}", path.display());
+                }
+                None => print!("{text}"),
+            }
+        }
+
+        Commands::Doctor => {
+            run_doctor();
+        }
+
+        Commands::Watch {
+            name,
+            profile,
+            limits,
+            once: _,
+            forever,
+            interval,
+        } => {
+            let limit = resolve_limit(profile, &limits)?;
+            let matcher =
+                rlm_core::daemon::ProfileDaemon::for_application(&Config::load()?, &name, limit);
+            watch_and_limit(&manager, &matcher, &name, forever, interval)?;
+        }
+
+        Commands::Apply { profile, all: _ } => {
+            let config = Config::load()?;
+            let matcher = match profile {
+                Some(name) => rlm_core::daemon::ProfileDaemon::for_profile(&config, &name)?,
+                None => rlm_core::daemon::ProfileDaemon::new(&config),
+            };
+            if matcher.profile_names().is_empty() {
+                return Err(Error::Config(
+                    "no profile has match_exe and no limit is persistent; add executables to a profile first".into(),
+                ));
+            }
+            apply_profiles(&manager, &matcher, yes)?;
+        }
+
+        Commands::Daemon {
+            interval,
+            idle_after,
+            boost_focus,
+            watch_config,
+            respond_to_pressure,
+        } => {
+            run_daemon(
+                &manager,
+                parse_duration(&interval)?,
+                idle_after,
+                boost_focus,
+                watch_config,
+                respond_to_pressure,
+            )?;
+        }
+
+        Commands::Ctl { method, params } => {
+            let params = match params {
+                Some(json) => serde_json::from_str(&json)
+                    .map_err(|e| Error::InvalidArgs(format!("params are not JSON: {e}")))?,
+                None => serde_json::Value::Null,
+            };
+            let result = rlm_core::control::call(&method, params)?;
+            if !result.is_null() {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&result).unwrap_or_default()
+                );
+            }
+        }
+
+        Commands::Guard { action } => {
+            return run_guard(&manager, action);
+        }
+
+        Commands::Rule { action } => {
+            return run_rule(action);
+        }
+
+        Commands::Gc { dry_run } => {
+            let report = rlm_core::state::gc(&manager, dry_run)?;
+            if report.is_empty() {
+                println!("nothing to clean up");
+            }
+            let verb = if dry_run { "would remove" } else { "removed" };
+            for name in &report.removed {
+                println!("{verb} leftover cgroup {name}");
+            }
+            let verb = if dry_run { "would adopt" } else { "adopted" };
+            for name in &report.adopted {
+                println!("{verb} {name} (its processes keep their limits; removed once they exit)");
+            }
+            if report.forgotten > 0 {
+                let verb = if dry_run { "would drop" } else { "dropped" };
+                println!("{verb} {} stale state record(s)", report.forgotten);
+            }
+        }
+
+        Commands::Calibrate { target } => {
+            return run_calibrate(target);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_calibrate(target: CalibrateTarget) -> Result<ExitCode> {
+    use rlm_core::calibrate;
+
+    let CalibrateTarget::Io {
+        device,
+        dir,
+        size,
+        save,
+    } = target;
+    let size = MemoryLimit::parse(&size)?.bytes();
+    if size == 0 {
+        return Err(Error::InvalidArgs("--size must be greater than 0".into()));
+    }
+
+    let measurement = match device {
+        Some(device) => {
+            println!("probing {} (read-only)...", device.display());
+            calibrate::probe_device(&device, size)?
+        }
+        None => {
+            let dir = dir.unwrap_or_else(calibrate::default_dir);
+            println!(
+                "probing {} with a {} temporary file...",
+                dir.display(),
+                format_bytes(size)
+            );
+            calibrate::probe_dir(&dir, size)?
+        }
+    };
+
+    println!();
+    println!(
+        "sequential read:  {}/s",
+        format_bytes(measurement.seq_read_bps)
+    );
+    match measurement.seq_write_bps {
+        Some(w) => println!("sequential write: {}/s", format_bytes(w)),
+        None => println!("sequential write: not measured (devices are never written to)"),
+    }
+    println!(
+        "random 4K read:   {}/s ({} IOPS)",
+        format_bytes(measurement.rand_read_bps),
+        measurement.rand_read_iops
+    );
+
+    let suggestions = calibrate::suggest(&measurement);
+    println!();
+    println!("{:<12} {:>10} {:>10}", "TIER", "IO_READ", "IO_WRITE");
+    println!("{}", "-".repeat(34));
+    for s in &suggestions {
+        println!(
+            "{:<12} {:>10} {:>10}",
+            s.tier.name(),
+            s.io_read,
+            s.io_write.as_deref().unwrap_or("-")
+        );
+    }
+
+    if save {
+        let mut config = Config::load()?;
+        for s in &suggestions {
+            // Keep any memory/CPU limits already in the profile
+            let profile = config
+                .profiles
+                .entry(format!("io-{}", s.tier.name()))
+                .or_default();
+            profile.io_read = Some(s.io_read.clone());
+            profile.io_write = s.io_write.clone();
+        }
+        config.save()?;
+        println!("\nsaved as profiles io-balanced, io-background, io-strict");
+        println!("  use with: rlm run --profile io-background -- <command>");
+    } else {
+        println!("\nsave these as profiles with --save");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `rlm limit --unit`: set the limits as the unit's resource properties.
+fn limit_unit(unit: &str, limit: &common::Limit, runtime: bool, dry_run: bool) -> Result<ExitCode> {
+    if dry_run {
+        println!("Dry run - would set on {unit}:");
+        for property in rlm_core::units::properties(limit)? {
+            println!("  {property}");
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let entry = rlm_core::units::apply(unit, limit, runtime)?;
+    println!("set {} on {unit}", entry.properties.join(" "));
+    if runtime {
+        println!("  (until the next reboot)");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// `rlm limit --user`: limit the user's slice if systemd has one loaded,
+/// else put all their processes in one shared cgroup.
+fn limit_user(
+    manager: &CgroupManager,
+    user: &str,
+    limit: &common::Limit,
+    dry_run: bool,
+    yes: bool,
+) -> Result<ExitCode> {
+    let uid = rlm_core::process::user_id(user)?;
+    let pids = rlm_core::process::owned_by(uid)?;
+    let slice = rlm_core::units::user_slice(uid);
+    let has_slice = rlm_core::units::is_loaded(&slice);
+    if !has_slice && pids.is_empty() {
+        return Err(Error::InvalidArgs(format!(
+            "user {user} has no running processes"
+        )));
+    }
+
+    if dry_run {
+        if has_slice {
+            return limit_unit(&slice, limit, false, true);
+        }
+        println!(
+            "Dry run - would put {} process(es) of user {user} in cgroup 'user-{uid}', sharing the limits",
+            pids.len()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if !confirm_batch(&pids, "Limit", yes)? {
+        println!("cancelled");
+        return Ok(ExitCode::SUCCESS);
+    }
+    if has_slice {
+        limit_unit(&slice, limit, false, false)
+    } else {
+        let cgroup = format!("user-{uid}");
+        manager.apply_limit_to_multiple(&pids, limit, &cgroup)?;
+        println!(
+            "Applied shared limits to {} process(es) of user {user} in cgroup '{cgroup}'",
+            pids.len()
+        );
+        println!("  note: processes they start later are not limited");
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn print_unit_status(units: &[rlm_core::units::UnitStatus]) {
+    if units.is_empty() {
+        return;
+    }
+    let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".into());
+    println!();
+    println!(
+        "{:<32} {:>10} {:>10} {:>10} {:>6} {:>12}",
+        "UNIT", "STATE", "MEMORY", "MAX", "CPU", "TASKS"
+    );
+    for u in units {
+        let name = if u.runtime {
+            format!("{} (runtime)", u.unit)
+        } else {
+            u.unit.clone()
+        };
+        let tasks = match (u.tasks_current, u.tasks_max) {
+            (Some(n), Some(max)) => format!("{n}/{max}"),
+            (Some(n), None) => n.to_string(),
+            _ => "-".into(),
+        };
+        println!(
+            "{:<32} {:>10} {:>10} {:>10} {:>6} {:>12}",
+            name,
+            u.active,
+            or_dash(u.memory_current.map(format_bytes)),
+            or_dash(u.memory_max.map(format_bytes)),
+            or_dash(u.cpu_quota.map(|p| format!("{p}%"))),
+            tasks
+        );
+    }
+}
+
+/// Whether the rlm-guard user service is active (best-effort, for hints).
+fn is_guard_active() -> bool {
+    std::process::Command::new("systemctl")
+        .args(["--user", "is-active", "--quiet", "rlm-guard"])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+fn run_rule(action: RuleAction) -> Result<ExitCode> {
+    match action {
+        RuleAction::List => {
+            let config = Config::load()?;
+            if config.rules.is_empty() {
+                println!("no persistent rules configured");
+                println!("  create one with: rlm limit --name <exe> --memory <size> --persistent");
+                return Ok(ExitCode::SUCCESS);
+            }
+            println!(
+                "{:<20} {:>10} {:>8} {:>10} {:>10}",
+                "RULE", "MEMORY", "CPU", "IO_READ", "IO_WRITE"
+            );
+            println!("{}", "-".repeat(62));
+            let mut names: Vec<_> = config.rules.keys().collect();
+            names.sort();
+            for name in names {
+                let r = &config.rules[name];
+                println!(
+                    "{:<20} {:>10} {:>8} {:>10} {:>10}",
+                    name,
+                    r.memory.as_deref().unwrap_or("-"),
+                    r.cpu.as_deref().unwrap_or("-"),
+                    r.io_read.as_deref().unwrap_or("-"),
+                    r.io_write.as_deref().unwrap_or("-"),
+                );
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        RuleAction::Remove { name } => {
+            let mut config = Config::load()?;
+            if config.remove_rule(&name) {
+                config.save()?;
+                println!("removed rule '{name}'");
+                println!("  note: this does not drop a currently-applied limit; use `rlm unlimit --application {name}` for that");
+                Ok(ExitCode::SUCCESS)
+            } else {
+                Err(Error::InvalidArgs(format!("no rule named '{name}'")))
+            }
+        }
+    }
+}
+
+fn run_guard(manager: &CgroupManager, action: GuardAction) -> Result<ExitCode> {
+    match action {
+        GuardAction::Enable => systemctl(&["enable", "--now", "rlm-guard"]),
+        GuardAction::Disable => systemctl(&["disable", "--now", "rlm-guard"]),
+        GuardAction::Status => {
+            guard_status(manager);
+            Ok(ExitCode::SUCCESS)
+        }
+        GuardAction::Test => {
+            guard_test();
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<ExitCode> {
+    let status = std::process::Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .map_err(|e| Error::InvalidArgs(format!("failed to run systemctl: {e}")))?;
+    Ok(if status.success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Current real UID from the kernel.
+fn current_uid() -> u32 {
+    // SAFETY: getuid() is always safe; it only reads our real UID.
+    unsafe { libc::getuid() }
+}
+
+fn guard_status(manager: &CgroupManager) {
+    let cfg = Config::load().unwrap_or_default();
+    let sampler = rlm_core::guard::Sampler::new(cfg.guard, std::process::id(), current_uid());
+
+    match sampler.sample() {
+        Some(s) => println!(
+            "Memory pressure: some(avg10)={:.1}%  full(avg10)={:.1}%  available={} MB",
+            s.some_avg10, s.full_avg10, s.mem_available_mb
+        ),
+        None => println!("Memory pressure: PSI unavailable (/proc/pressure/memory)"),
+    }
+
+    let base = manager.base_path();
+    let pids = manager.list_guard_pids();
+    if pids.is_empty() {
+        println!("\nNo active guard interventions.");
+        return;
+    }
+
+    println!(
+        "\n{:<8} {:<20} {:<8} {:<14}",
+        "PID", "NAME", "STATE", "MEM.HIGH"
+    );
+    println!("{}", "-".repeat(52));
+    for pid in pids {
+        let gpath = base.join(format!("guard-{pid}"));
+        let frozen = std::fs::read_to_string(gpath.join("cgroup.freeze"))
+            .map(|s| s.trim() == "1")
+            .unwrap_or(false);
+        let high = std::fs::read_to_string(gpath.join("memory.high"))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        let name = std::fs::read_to_string(format!("/proc/{pid}/comm"))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| "?".to_string());
+        let state = if frozen {
+            "frozen"
+        } else if !high.is_empty() && high != "max" {
+            "capped"
+        } else {
+            "active"
+        };
+        println!("{:<8} {:<20} {:<8} {:<14}", pid, name, state, high);
+    }
+}
+
+fn guard_test() {
+    // Single-shot preview: ticks a FRESH engine once at now_ms=0, so it shows
+    // what the guard's *first* action would be right now (the escalation gate is
+    // open and no prior interventions exist). It does not simulate recovery or
+    // cooldown behavior, and applies nothing.
+    let cfg = Config::load().unwrap_or_default();
+    let sampler =
+        rlm_core::guard::Sampler::new(cfg.guard.clone(), std::process::id(), current_uid());
+    let mut engine = rlm_core::guard::PolicyEngine::new(cfg.guard);
+
+    let Some(sample) = sampler.sample() else {
+        println!("PSI unavailable; cannot evaluate guard actions.");
+        return;
+    };
+    let procs = sampler.eligible();
+    println!(
+        "Pressure: some={:.1}%  full={:.1}%  available={} MB  |  {} eligible process(es)",
+        sample.some_avg10,
+        sample.full_avg10,
+        sample.mem_available_mb,
+        procs.len()
+    );
+
+    let actions = engine.tick(0, sample, &procs);
+    if actions.is_empty() {
+        println!("No action would be taken right now.");
+    } else {
+        println!("Would take {} action(s):", actions.len());
+        for a in &actions {
+            println!("  {a:?}");
+        }
+    }
+}
+
+fn run_doctor() {
+    println!("rlm doctor - checking system requirements\n");
+
+    let checks = rlm_core::doctor::run_checks();
+    for check in &checks {
+        print_check(&check.name, check.ok);
+        if !check.ok {
+            let mut hints = check.hints.iter();
+            if let Some(first) = hints.next() {
+                println!("  -> {first}");
+            }
+            for line in hints {
+                println!("  {line}");
+            }
+        }
+    }
+
+    println!();
+    if rlm_core::doctor::all_ok(&checks) {
+        println!("all checks passed - rlm is ready to use");
+    } else {
+        println!("some checks failed - see hints above");
+    }
+}
+
+fn print_check(name: &str, ok: bool) {
+    let status = if ok { "[ok]" } else { "[FAIL]" };
+    println!("{:>8} {}", status, name);
+}
+
+/// What `rlm run` restarts the command after.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Restart {
+    /// --restart-on-oom: an OOM kill
+    OnOom,
+    /// --restart on-failure: a non-zero exit or a crash
+    OnFailure,
+}
+
+/// `rlm run --restart on-failure[:max=N]`.
+#[derive(Clone, Copy)]
+struct OnFailure {
+    max: Option<u32>,
+}
+
+fn parse_restart(s: &str) -> Result<OnFailure> {
+    let invalid =
+        || Error::InvalidArgs(format!("invalid restart policy '{s}' (on-failure[:max=N])"));
+    match s.split_once(':') {
+        None if s == "on-failure" => Ok(OnFailure { max: None }),
+        Some(("on-failure", max)) => {
+            let max = max.strip_prefix("max=").ok_or_else(invalid)?;
+            Ok(OnFailure {
+                max: Some(max.parse().map_err(|_| invalid())?),
+            })
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// `rlm run --restart-on-oom` and `--restart` settings.
+struct RestartPolicy {
+    on: Restart,
+    /// `None` to keep restarting
+    max_restarts: Option<u32>,
+    /// Delay before the first restart; doubles after each one, up to
+    /// [`MAX_BACKOFF`] (or itself, if longer).
+    backoff: Duration,
+}
+
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// `rlm run --timeout` and `--kill-after` settings.
+#[derive(Default)]
+struct Stopping {
+    timeout: Option<Duration>,
+    /// Between SIGTERM and SIGKILL of what is left in the cgroup
+    kill_after: Option<Duration>,
+}
+
+/// What `rlm run` exits with when `--timeout` stopped the command, as
+/// timeout(1) does.
+const TIMED_OUT: u8 = 124;
+
+/// Parse a duration like `10s`, `500ms`, `2m`, or `1h`. A bare number is seconds.
+fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let invalid = || Error::InvalidArgs(format!("invalid duration '{s}' (e.g. 10s, 500ms, 2m)"));
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: u64 = num.parse().map_err(|_| invalid())?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(num)),
+        "" | "s" => Ok(Duration::from_secs(num)),
+        "m" => Ok(Duration::from_secs(num.saturating_mul(60))),
+        "h" => Ok(Duration::from_secs(num.saturating_mul(3600))),
+        _ => Err(invalid()),
+    }
+}
+
+/// The cgroup `rlm run` runs the command in.
+enum RunCgroup<'a> {
+    /// One of its own, with these limits
+    Own(&'a common::Limit),
+    /// One shared by `rlm run --join` commands: created (or its limits
+    /// replaced) with these limits if given, or else joined as it is
+    Joined {
+        name: String,
+        limit: Option<&'a common::Limit>,
+    },
+}
+
+fn run_with_limits(
+    manager: &CgroupManager,
+    cgroup: RunCgroup,
+    command: &[String],
+    restart: Option<&RestartPolicy>,
+    stopping: &Stopping,
+    summary: Option<SummaryFormat>,
+    launch: &LaunchArgs,
+) -> Result<ExitCode> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| common::Error::InvalidArgs("command is required".into()))?;
+
+    let joined = matches!(cgroup, RunCgroup::Joined { .. });
+    let (cgroup_name, limit) = match cgroup {
+        RunCgroup::Own(limit) => {
+            // Generate a collision-resistant cgroup name. Using only the PID
+            // risks reusing a stale leaked `run-<pid>` cgroup after PID reuse;
+            // the timestamp suffix makes that effectively impossible.
+            let uniq = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            (format!("run-{}-{}", std::process::id(), uniq), Some(limit))
+        }
+        RunCgroup::Joined { name, limit } => (name, limit),
+    };
+    // Create cgroup and set limits BEFORE spawning the process
+    let prepare = || {
+        let path = match limit {
+            Some(limit) => manager.prepare_cgroup(&cgroup_name, limit),
+            None => manager.managed_cgroup_path(&cgroup_name),
+        }?;
+        if let Err(e) = rlm_core::devices::deny(&path, &launch.deny_device) {
+            if !joined {
+                cleanup_run_cgroup(manager, &cgroup_name);
+            }
+            return Err(e);
+        }
+        Ok(path)
+    };
+
+    // Termination signals are passed on to the command (see run_attempt) and
+    // stop any further restarts
+    let terminated = Arc::new(AtomicBool::new(false));
+    for signal in FORWARDED {
+        signal_hook::flag::register(signal, Arc::clone(&terminated))?;
+    }
+    let events = Events::new()?;
+
+    let mut restarts = 0;
+    let mut backoff = restart.map(|r| r.backoff).unwrap_or_default();
+    // --restart-on-oom gives each attempt a fresh cgroup; --restart keeps
+    // them in one, along with anything a crashed attempt left behind
+    let fresh = !joined && restart.is_none_or(|r| r.on == Restart::OnOom);
+    let run_started = std::time::Instant::now();
+    let mut usage = None;
+    let mut oom_kills = 0;
+    let mut cgroup_path = prepare()?;
+    let (status, timed_out) = loop {
+        let attempt = Attempt {
+            cgroup_name: &cgroup_name,
+            cgroup_path: &cgroup_path,
+            stopping,
+            launch,
+        };
+        let started = std::time::Instant::now();
+        let (status, timed_out) = run_attempt(manager, &attempt, program, args, &events)?;
+        if summary.is_some() {
+            let wall = if fresh { started } else { run_started }.elapsed();
+            usage = Some(rlm_core::stats::RunSummary::read(&cgroup_path, wall));
+        }
+
+        // Read the OOM count before the cgroup (and its counters) goes away.
+        // It counts for the cgroup's whole life, which can span attempts.
+        let oom_total = std::fs::read_to_string(cgroup_path.join("memory.events"))
+            .map(|c| rlm_core::supervise::parse_memory_events(&c).oom_kill)
+            .unwrap_or(0);
+        let oom_killed = oom_total > oom_kills;
+        oom_kills = oom_total;
+        let failure = match restart.map(|r| r.on) {
+            Some(Restart::OnOom) if oom_killed => Some("OOM-killed".to_string()),
+            Some(Restart::OnFailure) if !status.success() => Some(match status.code() {
+                Some(code) => format!("exited with {code}"),
+                None => format!("killed by signal {}", status.signal().unwrap_or(0)),
+            }),
+            _ => None,
+        };
+        // Not a failure to retry: rlm or the command was told to stop
+        let stopped = timed_out
+            || terminated.load(Ordering::SeqCst)
+            || status.signal().is_some_and(|s| FORWARDED.contains(&s));
+
+        let (Some(policy), Some(failure)) = (restart, failure.filter(|_| !stopped)) else {
+            break (status, timed_out);
+        };
+        if policy.max_restarts.is_some_and(|max| restarts >= max) {
+            eprintln!("{failure}; giving up after {restarts} restart(s)");
+            break (status, timed_out);
+        }
+        if fresh {
+            cleanup_run_cgroup(manager, &cgroup_name);
+        }
+        restarts += 1;
+        let of = policy
+            .max_restarts
+            .map_or(String::new(), |max| format!("/{max}"));
+        eprintln!(
+            "{failure}; restarting in {}s (restart {restarts}{of})",
+            backoff.as_secs_f32()
+        );
+        if !sleep_unless_terminated(backoff, &terminated) {
+            break (status, timed_out);
+        }
+        backoff = backoff
+            .saturating_mul(2)
+            .min(MAX_BACKOFF.max(policy.backoff));
+        if fresh {
+            cgroup_path = prepare()?;
+        }
+    };
+    if !joined {
+        cleanup_run_cgroup(manager, &cgroup_name);
+    } else if let Err(e) = manager.remove_if_empty(&cgroup_name) {
+        // Left for `rlm gc`; others may still be in it anyway
+        eprintln!("warning: failed to remove cgroup: {e}");
+    }
+
+    if let (Some(format), Some(usage)) = (summary, &usage) {
+        print_run_summary(usage, format);
+    }
+    if timed_out {
+        return Ok(ExitCode::from(TIMED_OUT));
+    }
+    Ok(ExitCode::from(exit_code(status)))
+}
+
+/// Clean up `rlm run`'s ephemeral cgroup. Don't propagate a cleanup error here:
+/// cgroup v2 can briefly return EBUSY on rmdir right after the last process
+/// exits, and we must not let that mask the child program's real exit code.
+fn cleanup_run_cgroup(manager: &CgroupManager, cgroup_name: &str) {
+    if let Err(e) = manager.cleanup_cgroup(cgroup_name) {
+        eprintln!("warning: failed to remove cgroup: {e}");
+    }
+}
+
+/// `rlm run --summary`, on stderr so it stays out of the command's output.
+/// With --restart-on-oom it covers the last attempt; with --restart, all of
+/// them, as they share a cgroup.
+fn print_run_summary(usage: &rlm_core::stats::RunSummary, format: SummaryFormat) {
+    if format == SummaryFormat::Json {
+        eprintln!("{}", serde_json::to_string(usage).unwrap_or_default());
+        return;
+    }
+    eprintln!("rlm: resource usage");
+    eprintln!("  {:<14} {:.2}s", "wall time", usage.wall_secs);
+    eprintln!(
+        "  {:<14} {:.2}s user, {:.2}s system",
+        "cpu time", usage.user_secs, usage.system_secs
+    );
+    eprintln!("  {:<14} {:.2}s", "cpu throttled", usage.throttled_secs);
+    eprintln!(
+        "  {:<14} {}",
+        "peak memory",
+        usage.peak_memory.map_or("-".into(), format_bytes)
+    );
+    eprintln!(
+        "  {:<14} {} read, {} written",
+        "io",
+        format_bytes(usage.read_bytes),
+        format_bytes(usage.write_bytes)
+    );
+    eprintln!(
+        "  {:<14} {} over memory.high, {} oom kill(s)",
+        "memory events", usage.memory_high_events, usage.oom_kills
+    );
+}
+
+/// `rlm run --detach`: start `command` limited, in the background, and return
+/// once it is running.
+///
+/// A monitor process does the work, in a session of its own so closing the
+/// terminal doesn't hang it up: it creates the cgroup, so the state file names
+/// it as the owner (and `rlm gc` adopts the cgroup if the monitor is killed),
+/// starts the command in it, and removes the cgroup when the command exits.
+/// It reports the command's PID, or why it couldn't start it, over a socket.
+fn run_detached(
+    manager: &CgroupManager,
+    limit: &common::Limit,
+    launch: &LaunchArgs,
+    command: &[String],
+) -> Result<ExitCode> {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Error::InvalidArgs("command is required".into()))?;
+    let (parent, child) = UnixStream::pair()?;
+
+    // SAFETY: rlm has started no threads, so the child may run any code.
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error().into()),
+        0 => {
+            drop(parent);
+            let code = monitor_detached(manager, limit, launch, program, args, child);
+            // SAFETY: _exit has no preconditions; skipping atexit handlers and
+            // stdio flushing is intended in a forked child.
+            unsafe { libc::_exit(code) }
+        }
+        _ => drop(child),
+    }
+
+    let mut reply = String::new();
+    BufReader::new(parent).read_line(&mut reply)?;
+    let reply = reply.trim();
+    if let Some(message) = reply.strip_prefix("error: ") {
+        return Err(Error::Cgroup(message.to_string()));
+    }
+    let Some((pid, cgroup)) = reply.split_once(' ') else {
+        return Err(Error::Cgroup(
+            "detached monitor exited without starting the command".into(),
+        ));
+    };
+    println!("started {program} (PID {pid}) in cgroup '{cgroup}'");
+    println!("  see it with: rlm status");
+    println!("  drop its limits with: rlm unlimit --cgroup {cgroup}");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The monitor side of [`run_detached`]; returns its exit code.
+fn monitor_detached(
+    manager: &CgroupManager,
+    limit: &common::Limit,
+    launch: &LaunchArgs,
+    program: &str,
+    args: &[String],
+    mut reply: std::os::unix::net::UnixStream,
+) -> i32 {
+    use std::process::Stdio;
+
+    // SAFETY: setsid has no preconditions; a fork child is never a group leader.
+    unsafe { libc::setsid() };
+    let cgroup_name = format!("run-{}-detached", std::process::id());
+    let started = manager
+        .prepare_cgroup(&cgroup_name, limit)
+        .and_then(|path| {
+            let spawned = rlm_core::devices::deny(&path, &launch.deny_device).and_then(|()| {
+                let mut cmd = manager.placement_command(&path, program)?;
+                launch.apply_to(&mut cmd)?;
+                cmd.args(args)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null());
+                cmd.spawn()
+                    .map_err(|e| Error::InvalidArgs(format!("failed to run {program}: {e}")))
+            });
+            if spawned.is_err() {
+                let _ = manager.cleanup_cgroup(&cgroup_name);
+            }
+            spawned
+        });
+    let mut child = match started {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = writeln!(reply, "error: {e}");
+            return 1;
+        }
+    };
+    let _ = writeln!(reply, "{} {cgroup_name}", child.id());
+    drop(reply);
+
+    // Let go of the terminal
+    if let Ok(null) = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+    {
+        use std::os::unix::io::AsRawFd;
+        for fd in 0..=2 {
+            // SAFETY: both descriptors are valid; dup2 just replaces fd.
+            unsafe { libc::dup2(null.as_raw_fd(), fd) };
+        }
+    }
+    let _ = child.wait();
+    if let Err(e) = manager.cleanup_cgroup(&cgroup_name) {
+        tracing::warn!(cgroup = %cgroup_name, error = %e, "failed to remove cgroup");
+    }
+    0
+}
+
+/// `rlm run --fallback-rlimit` where cgroups can't be used: run `command`
+/// under setrlimit limits instead, after saying how much weaker they are.
+fn run_with_rlimits(
+    profile: Option<String>,
+    limits: &LimitArgs,
+    cpu_time: Option<Duration>,
+    launch: &mut LaunchArgs,
+    command: &[String],
+) -> Result<ExitCode> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| Error::InvalidArgs("command is required".into()))?;
+    let limit = match profile {
+        Some(_) => resolve_limit(profile, limits)?,
+        None => limits.with_profile(None)?,
+    };
+    launch.export_memory(limit.memory)?;
+    let rlimits = rlm_core::rlimit::Rlimits {
+        address_space: limit.memory.map(|m| m.bytes()),
+        cpu_seconds: cpu_time.map(|t| t.as_secs().max(1)),
+        ..Default::default()
+    };
+    if rlimits.is_empty() && launch.rlimits.to_rlimits()?.is_empty() {
+        return Err(Error::InvalidArgs(
+            "nothing setrlimit can enforce: give --memory, --cpu-time, or --max-open-files".into(),
+        ));
+    }
+
+    eprintln!(
+        "warning: no writable cgroup controllers; falling back to setrlimit, which is weaker:"
+    );
+    eprintln!("  - each process gets its own limits, not a budget shared with what it starts");
+    if rlimits.address_space.is_some() {
+        eprintln!(
+            "  - --memory caps address space, not RAM; programs that reserve a lot of it (JVMs, browsers) may fail to start"
+        );
+    }
+    if rlimits.cpu_seconds.is_some() {
+        eprintln!("  - --cpu-time kills a process once it has used that much CPU; it does not slow it down");
+    }
+    let ignored = rlm_core::rlimit::unenforced(&limit);
+    if !ignored.is_empty() {
+        eprintln!("  - not enforced: {}", ignored.join(", "));
+    }
+
+    let mut cmd = std::process::Command::new(program);
+    cmd.args(args);
+    launch.apply_to(&mut cmd)?;
+    rlimits.apply_to(&mut cmd);
+    let status = cmd
+        .status()
+        .map_err(|e| Error::InvalidArgs(format!("failed to run {program}: {e}")))?;
+    Ok(ExitCode::from(exit_code(status)))
+}
+
+/// The exit code a shell reports for `status`: the command's own, or
+/// 128+N when signal N killed it.
+fn exit_code(status: std::process::ExitStatus) -> u8 {
+    use std::os::unix::process::ExitStatusExt;
+
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code as u8,
+        (None, Some(signal)) => 128u8.wrapping_add(signal as u8),
+        _ => 1,
+    }
+}
+
+/// `rlm stats`: one cgroup in depth, section by section.
+fn print_stats(stats: &rlm_core::stats::Stats) {
+    use rlm_core::pressure::Resource;
+
+    let bytes = |v: Option<u64>| v.map_or("-".into(), format_bytes);
+    println!("cgroup: {}", stats.path.display());
+    let procs: Vec<String> = stats.procs.iter().map(|p| p.to_string()).collect();
+    println!(
+        "processes: {}",
+        if procs.is_empty() {
+            "none".to_string()
+        } else {
+            procs.join(" ")
+        }
+    );
+
+    println!("\nlimits:");
+    for (file, value) in &stats.limits {
+        let mut lines = value.lines();
+        println!("  {:<18} {}", file, lines.next().unwrap_or("-"));
+        for line in lines {
+            println!("  {:<18} {}", "", line);
+        }
+    }
+    if let Some((max, from)) = &stats.effective_memory_max {
+        let note = if *from == stats.path {
+            String::new()
+        } else {
+            format!(" (set on {})", from.display())
+        };
+        println!(
+            "  {:<18} {}{}",
+            "effective memory",
+            format_bytes(*max),
+            note
+        );
+    }
+
+    println!("\nusage:");
+    let memory = stats.memory.as_ref();
+    println!(
+        "  {:<18} {}",
+        "memory.current",
+        bytes(memory.map(|m| m.current))
+    );
+    if let Some(m) = memory {
+        println!("  {:<18} {}", "  anon", format_bytes(m.anon));
+        println!("  {:<18} {}", "  file", format_bytes(m.file));
+        println!("  {:<18} {}", "  kernel", format_bytes(m.kernel));
+    }
+    println!(
+        "  {:<18} {}",
+        "memory.peak",
+        bytes(memory.and_then(|m| m.peak))
+    );
+    println!(
+        "  {:<18} {}",
+        "memory.swap",
+        bytes(memory.and_then(|m| m.swap_current))
+    );
+    if let Some(n) = stats.pids_current {
+        println!("  {:<18} {}", "pids.current", n);
+    }
+
+    if let Some(cpu) = &stats.cpu {
+        println!("\ncpu.stat:");
+        for (key, value) in &cpu.entries {
+            if key.ends_with("_usec") {
+                println!("  {:<18} {:.2}s", key, *value as f64 / 1e6);
+            } else {
+                println!("  {:<18} {}", key, value);
+            }
+        }
+    }
+
+    if !stats.io.devices.is_empty() {
+        println!("\nio.stat:");
+        println!(
+            "  {:<18} {:>10} {:>10} {:>10} {:>10}",
+            "DEVICE", "READ", "WRITTEN", "RIOS", "WIOS"
+        );
+        for dev in &stats.io.devices {
+            let name = match &dev.name {
+                Some(name) => format!("{name} ({})", dev.device),
+                None => dev.device.clone(),
+            };
+            println!(
+                "  {:<18} {:>10} {:>10} {:>10} {:>10}",
+                name,
+                format_bytes(dev.rbytes),
+                format_bytes(dev.wbytes),
+                dev.rios,
+                dev.wios
+            );
+        }
+    }
+
+    println!("\npressure:");
+    let pct = |v: Option<f64>| v.map_or("-".into(), |v| format!("{v:.2}%"));
+    println!(
+        "  {:<18} {:>8} {:>8} {:>8} {:>8}",
+        "RESOURCE", "SOME10", "SOME60", "FULL10", "FULL60"
+    );
+    for resource in Resource::ALL {
+        let Some(psi) = stats.pressure.get(resource) else {
+            continue;
+        };
+        println!(
+            "  {:<18} {:>8} {:>8} {:>8} {:>8}",
+            resource.name(),
+            pct(Some(psi.some_avg10)),
+            pct(Some(psi.some_avg60)),
+            pct(psi.full_avg10),
+            pct(psi.full_avg60)
+        );
+    }
+}
+
+/// `rlm pressure`: one row per target and resource, most starved cgroup first.
+fn print_pressure(manager: &CgroupManager) {
+    use rlm_core::pressure::{self, Resource};
+
+    let system = pressure::system();
+    if system.memory.is_none() {
+        println!("PSI unavailable (kernel built without CONFIG_PSI, or psi=0)");
+        return;
+    }
+
+    println!(
+        "{:<25} {:<7} {:>8} {:>8} {:>8} {:>8}",
+        "TARGET", "RES", "SOME10", "SOME60", "FULL10", "FULL60"
+    );
+    println!("{}", "-".repeat(69));
+    let pct = |v: Option<f64>| v.map_or("-".into(), |v| format!("{v:.2}%"));
+    for target in std::iter::once(system).chain(pressure::cgroups(manager)) {
+        for resource in Resource::ALL {
+            let Some(psi) = target.get(resource) else {
+                continue;
+            };
+            println!(
+                "{:<25} {:<7} {:>8} {:>8} {:>8} {:>8}",
+                target.target,
+                resource.name(),
+                pct(Some(psi.some_avg10)),
+                pct(Some(psi.some_avg60)),
+                pct(psi.full_avg10),
+                pct(psi.full_avg60)
+            );
+        }
+    }
+    println!(
+        "
+some = share of time at least one task stalled; full = all tasks stalled"
+    );
+}
+
+/// `rlm events`: totals per cgroup, or a live stream with `follow`.
+fn run_events(manager: &CgroupManager, follow: bool, json: bool) -> Result<()> {
+    use rlm_core::events::{self, EventKind, EventWatcher};
+
+    if !follow {
+        let all = events::snapshot(manager);
+        if json {
+            for (name, counters) in &all {
+                println!("{}", counters.to_json(name));
+            }
+            return Ok(());
+        }
+        if all.is_empty() {
+            println!("no processes currently managed");
+            return Ok(());
+        }
+        println!(
+            "{:<25} {:>8} {:>8} {:>8} {:>9} {:>15}",
+            "CGROUP", "HIGH", "MAX", "OOM", "OOM_KILL", "OOM_GROUP_KILL"
+        );
+        for (name, c) in &all {
+            println!(
+                "{:<25} {:>8} {:>8} {:>8} {:>9} {:>15}",
+                name,
+                c.get(EventKind::High),
+                c.get(EventKind::Max),
+                c.get(EventKind::Oom),
+                c.get(EventKind::OomKill),
+                c.get(EventKind::OomGroupKill)
+            );
+        }
+        return Ok(());
+    }
+
+    let mut watcher = EventWatcher::new(manager)?;
+    if !json {
+        println!("watching memory events of managed cgroups (Ctrl+C to stop)");
+    }
+    loop {
+        for event in watcher.wait()? {
+            if json {
+                println!("{}", event.to_json());
+            } else {
+                println!(
+                    "{}  {:<25} {:<15} +{} (total {})",
+                    clock(event.time),
+                    event.cgroup,
+                    event.kind.key(),
+                    event.count,
+                    event.total
+                );
+            }
+        }
+        io::stdout().flush().ok();
+    }
+}
+
+/// Local wall-clock time (HH:MM:SS) of a Unix timestamp.
+fn clock(secs: u64) -> String {
+    let Some(tm) = rlm_core::schedule::local_time(secs as i64) else {
+        return secs.to_string();
+    };
+    format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+/// Cells of one `rlm status` row, in column order.
+type StatusRow = [String; 9];
+
+/// Column widths of `rlm status`; NAME (left-aligned) is the only one cut
+/// to fit, so columns stay put between `--watch` redraws.
+const STATUS_WIDTHS: [usize; 9] = [8, 25, 20, 10, 15, 14, 6, 9, 15];
+
+fn status_row(p: &rlm_core::status::ProcessStatus) -> StatusRow {
+    use rlm_core::events::EventKind;
+
+    let limit = match (p.memory_max, p.memory_low) {
+        (Some(max), _) => format_bytes(max),
+        (None, Some(low)) => format!("{} rsv", format_bytes(low)),
+        (None, None) => "-".into(),
+    };
+    let mem = match p.usage.memory_current {
+        Some(cur) => format!("{}/{}", format_bytes(cur), limit),
+        None => limit,
+    };
+    let cpu = match (p.cpu_quota, p.cpu_weight) {
+        (Some(q), _) => format!("{}%", q),
+        (None, Some(w)) => format!("weight {w}"),
+        (None, None) => "-".into(),
+    };
+    let cpu = match p.rates.and_then(|r| r.cpu_percent) {
+        Some(used) => format!("{used:.0}%/{cpu}"),
+        None => cpu,
+    };
+    let cpu = if p.cpu_idle {
+        if cpu == "-" {
+            "idle".to_string()
+        } else {
+            format!("{cpu} idle")
+        }
+    } else {
+        cpu
+    };
+    let limited = p.io_read_bps.is_some() || p.io_write_bps.is_some();
+    let io = match (p.rates, limited) {
+        (Some(r), _) => format!(
+            "{}/{}{}",
+            format_bytes(r.io_read_bps),
+            format_bytes(r.io_write_bps),
+            if limited { "*" } else { "" }
+        ),
+        (None, true) => "limited".to_string(),
+        (None, false) => "-".to_string(),
+    };
+    let type_info = if p.is_shared {
+        if let Some(count) = p.process_count {
+            format!("shared ({} procs)", count)
+        } else {
+            "shared".to_string()
+        }
+    } else {
+        "individual".to_string()
+    };
+    let pids = p
+        .pids_max
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| "-".into());
+    let peak = p.memory_peak.map_or("-".into(), format_bytes);
+    let oom_kills = p.memory_events.get(EventKind::OomKill).to_string();
+    [
+        p.pid.to_string(),
+        p.name.chars().take(STATUS_WIDTHS[1]).collect(),
+        mem,
+        peak,
+        cpu,
+        io,
+        pids,
+        oom_kills,
+        type_info,
+    ]
+}
+
+/// Print the `rlm status` table. With `previous` (the last `--watch` frame,
+/// by cgroup name), cells that changed since are shown in reverse video.
+/// Returns this frame's rows for the next comparison.
+fn print_status(
+    processes: &[rlm_core::status::ProcessStatus],
+    previous: Option<&HashMap<String, StatusRow>>,
+    members: bool,
+) -> HashMap<String, StatusRow> {
+    let mut rows = HashMap::new();
+    if processes.is_empty() {
+        println!("no processes currently managed");
+        return rows;
+    }
+
+    let line = |cells: &StatusRow, changed: &dyn Fn(usize) -> bool| {
+        let out: Vec<String> = cells
+            .iter()
+            .zip(STATUS_WIDTHS)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                let padded = if i == 1 {
+                    format!("{cell:<width$}")
+                } else {
+                    format!("{cell:>width$}")
+                };
+                if changed(i) {
+                    format!("\x1b[7m{padded}\x1b[0m")
+                } else {
+                    padded
+                }
+            })
+            .collect();
+        out.join(" ")
+    };
+
+    let header = [
+        "PID",
+        "NAME",
+        "MEMORY",
+        "PEAK",
+        "CPU",
+        "I/O R/W",
+        "PIDS",
+        "OOM KILLS",
+        "TYPE",
+    ]
+    .map(String::from);
+    println!("{}", line(&header, &|_| false));
+    println!("{}", "-".repeat(130));
+
+    for p in processes {
+        let cells = status_row(p);
+        let before = previous.map(|prev| prev.get(&p.cgroup_name));
+        let changed = |i: usize| match before {
+            // A new row is all change
+            Some(None) => true,
+            Some(Some(old)) => old[i] != cells[i],
+            None => false,
+        };
+        println!("{}", line(&cells, &changed));
+        rows.insert(p.cgroup_name.clone(), cells);
+        if members && p.is_shared {
+            for &pid in &p.members {
+                let name = rlm_core::status::process_name(pid).unwrap_or_else(|| "?".into());
+                println!("{:>8} └ {name}", pid);
+            }
+        }
+    }
+
+    let t = rlm_core::status::totals(processes);
+    println!("{}", "-".repeat(130));
+    println!(
+        "total: {} processes in {} cgroups | memory {} used, {} limited{} | CPU quota {}% of {}%",
+        t.processes,
+        t.cgroups,
+        format_bytes(t.memory_used),
+        format_bytes(t.memory_limit),
+        t.machine_memory
+            .map_or(String::new(), |m| format!(" of {}", format_bytes(m))),
+        t.cpu_quota,
+        t.machine_cpus * 100
+    );
+
+    println!("\nNote: 'shared' means multiple processes share the same limit pool");
+    if !members && processes.iter().any(|p| p.is_shared) {
+        println!("      use --members to list every process in shared cgroups");
+    }
+    println!("      usage is shown as used/limit; I/O is per second");
+    if processes.iter().any(|p| p.memory_low.is_some()) {
+        println!("      'rsv' is memory reserved with `rlm reserve`");
+    }
+    if processes
+        .iter()
+        .any(|p| p.io_read_bps.is_some() || p.io_write_bps.is_some())
+    {
+        println!("      '*' marks I/O with a bandwidth limit");
+    }
+    rows
+}
+
+/// `rlm status --watch`: redraw every `interval` until Ctrl+C.
+fn watch_status(manager: &CgroupManager, interval: Duration, filter: &StatusFilter) -> Result<()> {
+    use rlm_core::status::{get_managed_processes, UsageSampler};
+    use std::io::IsTerminal;
+
+    let terminated = Arc::new(AtomicBool::new(false));
+    let terminated_clone = Arc::clone(&terminated);
+    ctrlc::set_handler(move || {
+        terminated_clone.store(true, Ordering::SeqCst);
+    })
+    .ok();
+
+    // Highlighting only makes sense on a terminal, not when piped
+    let tty = io::stdout().is_terminal();
+    let interval = interval.max(Duration::from_millis(200));
+    let mut sampler = UsageSampler::new();
+    sampler.update(&mut get_managed_processes(manager)?);
+    let mut previous = None;
+    // The first frame comes sooner, with rates over a short window
+    let mut wait = Duration::from_millis(500);
+    while sleep_unless_terminated(wait, &terminated) {
+        let mut processes = get_managed_processes(manager)?;
+        sampler.update(&mut processes);
+        let processes = filter.apply(processes);
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if tty {
+            print!("\x1b[H\x1b[2J");
+        }
+        println!(
+            "every {}: rlm status    {}\n",
+            format_interval(interval),
+            clock(now)
+        );
+        let rows = print_status(
+            &processes,
+            previous.as_ref().filter(|_| tty),
+            filter.members,
+        );
+        io::stdout().flush()?;
+        previous = Some(rows);
+        wait = interval;
+    }
+    Ok(())
+}
+
+fn format_interval(d: Duration) -> String {
+    if d.subsec_millis() == 0 {
+        format!("{}s", d.as_secs())
+    } else {
+        format!("{}ms", d.as_millis())
+    }
+}
+
+/// `rlm record`: write a sample every `interval` until Ctrl+C, the process
+/// exits, or `duration` elapses.
+fn run_record(
+    manager: &CgroupManager,
+    pid: u32,
+    interval: Duration,
+    output: &std::path::Path,
+    format: Option<RecordFormat>,
+    duration: Option<Duration>,
+) -> Result<()> {
+    use rlm_core::record::{Recorder, CSV_HEADER};
+
+    let format = format.unwrap_or_else(|| match output.extension().and_then(|e| e.to_str()) {
+        Some("json" | "jsonl") => RecordFormat::Json,
+        _ => RecordFormat::Csv,
+    });
+    let cgroup_path = manager.cgroup_path_for_pid(pid)?;
+    let mut recorder = Recorder::new(&cgroup_path);
+
+    let to_stdout = output.as_os_str() == "-";
+    let mut out: Box<dyn Write> = if to_stdout {
+        Box::new(io::stdout())
+    } else {
+        Box::new(io::BufWriter::new(std::fs::File::create(output)?))
+    };
+    if format == RecordFormat::Csv {
+        writeln!(out, "{CSV_HEADER}")?;
+    }
+
+    let terminated = Arc::new(AtomicBool::new(false));
+    let terminated_clone = Arc::clone(&terminated);
+    ctrlc::set_handler(move || {
+        terminated_clone.store(true, Ordering::SeqCst);
+    })
+    .ok();
+
+    if !to_stdout {
+        eprintln!(
+            "recording {} every {} to {} (Ctrl+C to stop)",
+            cgroup_path.display(),
+            format_interval(interval),
+            output.display()
+        );
+    }
+    let start = std::time::Instant::now();
+    let mut samples = 0u64;
+    loop {
+        let Some(sample) = recorder.sample() else {
+            if !to_stdout {
+                eprintln!("process {pid} exited");
+            }
+            break;
+        };
+        match format {
+            RecordFormat::Csv => writeln!(out, "{}", sample.to_csv())?,
+            RecordFormat::Json => writeln!(out, "{}", sample.to_json())?,
+        }
+        // Keep the file useful if we're killed rather than interrupted
+        out.flush()?;
+        samples += 1;
+
+        if duration.is_some_and(|d| start.elapsed() >= d)
+            || !sleep_unless_terminated(interval, &terminated)
+        {
+            break;
+        }
+    }
+    if !to_stdout {
+        eprintln!("wrote {samples} samples to {}", output.display());
+    }
+    Ok(())
+}
+
+/// `rlm apply`: list the running processes `matcher` would place, confirm,
+/// and place them.
+fn apply_profiles(
+    manager: &CgroupManager,
+    matcher: &rlm_core::daemon::ProfileDaemon,
+    yes: bool,
+) -> Result<()> {
+    let matching = matcher.matching(manager);
+    if matching.is_empty() {
+        println!("no unmanaged running process matches");
+        return Ok(());
+    }
+    println!("{:>8}  {:<20} PROFILE", "PID", "PROCESS");
+    for (p, profile) in &matching {
+        println!("{:>8}  {:<20} {}", p.pid, p.name, profile);
+    }
+    if !yes && !ask(&format!("Limit these {} processes?", matching.len()))? {
+        println!("cancelled");
+        return Ok(());
+    }
+
+    let mut placed: HashMap<String, usize> = HashMap::new();
+    for (p, _) in matching {
+        let pid = p.pid;
+        match matcher.place(manager, p) {
+            Some(placement) => *placed.entry(placement.cgroup).or_default() += 1,
+            None => eprintln!("  {pid}: not limited (exited or failed; see logs)"),
+        }
+    }
+    let mut cgroups: Vec<_> = placed.into_iter().collect();
+    cgroups.sort();
+    for (cgroup, count) in cgroups {
+        println!("limited {count} process(es) in cgroup '{cgroup}'");
+    }
+    Ok(())
+}
+
+/// `rlm watch`: limit `name` now if it runs, else when it starts; with
+/// `forever`, every new instance until Ctrl+C.
+fn watch_and_limit(
+    manager: &CgroupManager,
+    matcher: &rlm_core::daemon::ProfileDaemon,
+    name: &str,
+    forever: bool,
+    interval: Duration,
+) -> Result<()> {
+    let terminated = Arc::new(AtomicBool::new(false));
+    let terminated_clone = Arc::clone(&terminated);
+    ctrlc::set_handler(move || {
+        terminated_clone.store(true, Ordering::SeqCst);
+    })
+    .ok();
+
+    // Subscribe first so an instance starting meanwhile is not missed
+    let mut events = rlm_core::proc_events::ProcessEvents::new(interval);
+    let report = |p: &rlm_core::daemon::Placement| {
+        println!("limited {} {} in cgroup '{}'", p.pid, p.name, p.cgroup);
+    };
+    let running = matcher.place_running(manager);
+    running.iter().for_each(report);
+    if !running.is_empty() && !forever {
+        return Ok(());
+    }
+    eprintln!(
+        "waiting for {name} to start{} (Ctrl+C to stop)",
+        if forever {
+            ", limiting every instance"
+        } else {
+            ""
+        }
+    );
+
+    while !terminated.load(Ordering::SeqCst) {
+        for event in events.wait(Some(Duration::from_millis(250)))? {
+            if let Some(p) = matcher.handle(manager, &event) {
+                report(&p);
+                if !forever {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `rlm unlimit --all`: remove every cgroup holding limits, asking first
+/// unless `yes`.
+fn unlimit_all(manager: &CgroupManager, yes: bool) -> Result<ExitCode> {
+    let cgroups = manager.limit_cgroups();
+    if cgroups.is_empty() {
+        println!("no limited cgroups");
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !yes {
+        println!("Found {} limited cgroups:", cgroups.len());
+        for name in &cgroups {
+            println!(
+                "  {name} ({} processes)",
+                manager.pids_in_cgroup(name).len()
+            );
+        }
+        if !ask(&format!("Unlimit all {} cgroups?", cgroups.len()))? {
+            println!("cancelled");
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+
+    let mut failed = 0;
+    for name in &cgroups {
+        match manager.remove_application_limit(name) {
+            Ok(()) => println!("removed limits from cgroup '{name}'"),
+            Err(e) => {
+                eprintln!("failed to remove cgroup '{name}': {e}");
+                failed += 1;
+            }
+        }
+    }
+    let rules = Config::load()?.rules.len();
+    if rules > 0 {
+        println!("  note: {rules} persistent rule(s) still saved (rlm-guard will re-apply them); remove them with `rlm rule remove`");
+    }
+    Ok(if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// `rlm limit --children follow`: move what the tree of `root` (now
+/// `members`, in `cgroup_name`) starts from here on into its cgroup, until
+/// `root` exits or Ctrl+C.
+fn follow_children(
+    manager: &CgroupManager,
+    root: u32,
+    cgroup_name: &str,
+    members: &[u32],
+) -> Result<()> {
+    let terminated = Arc::new(AtomicBool::new(false));
+    let terminated_clone = Arc::clone(&terminated);
+    ctrlc::set_handler(move || {
+        terminated_clone.store(true, Ordering::SeqCst);
+    })
+    .ok();
+
+    let mut events = rlm_core::proc_events::ProcessEvents::new(Duration::from_millis(500));
+    let mut follower = rlm_core::follow::TreeFollower::new(
+        root,
+        cgroup_name,
+        manager.managed_cgroup_path(cgroup_name)?,
+        members.iter().copied(),
+    );
+    let report = |pid: u32| {
+        let name = rlm_core::process::info(pid).map_or_else(|| "?".into(), |p| p.name);
+        println!("moved {pid} {name} into cgroup '{cgroup_name}'");
+    };
+    // Children started between the first look at the tree and subscribing
+    for pid in rlm_core::process::find_process_tree(root)? {
+        let event = rlm_core::proc_events::ProcessEvent::Exec { pid };
+        follower
+            .handle(manager, &event)
+            .into_iter()
+            .for_each(report);
+    }
+    eprintln!("following the children of {root} until it exits (Ctrl+C to stop)");
+
+    while !terminated.load(Ordering::SeqCst) && !follower.root_exited() {
+        for event in events.wait(Some(Duration::from_millis(250)))? {
+            follower
+                .handle(manager, &event)
+                .into_iter()
+                .for_each(report);
+        }
+    }
+    Ok(())
+}
+
+/// `rlm daemon`: place new processes by profile every `interval` until
+/// Ctrl+C.
+/// How often `rlm daemon` re-checks schedules, the power source, and idleness.
+const CONDITIONS_CHECK: Duration = Duration::from_secs(5);
+
+/// How often `rlm daemon --boost-focus` asks the compositor for the focus.
+const FOCUS_CHECK: Duration = Duration::from_millis(500);
+
+/// How often `rlm daemon --respond-to-pressure` reads memory pressure.
+const PRESSURE_CHECK: Duration = Duration::from_secs(1);
+
+/// How often `rlm daemon` looks for processes that escaped their cgroup.
+const ESCAPE_CHECK: Duration = Duration::from_secs(2);
+
+fn run_daemon(
+    manager: &CgroupManager,
+    interval: Duration,
+    idle_after: Duration,
+    boost_focus: bool,
+    watch_config: bool,
+    respond_to_pressure: bool,
+) -> Result<()> {
+    let focus = if boost_focus {
+        Some(rlm_core::focus::Backend::detect().ok_or_else(|| {
+            Error::InvalidArgs(
+                "--boost-focus needs Hyprland, Sway, or an X11 session (GNOME and KDE on Wayland have no focus API)".into(),
+            )
+        })?)
+    } else {
+        None
+    };
+    let cfg = Config::load()?;
+    let mut responder = if respond_to_pressure {
+        if rlm_core::pressure::system().memory.is_none() {
+            return Err(Error::InvalidArgs(
+                "--respond-to-pressure needs memory PSI (/proc/pressure/memory)".into(),
+            ));
+        }
+        let responder = rlm_core::responder::PressureResponder::new(&cfg);
+        if responder.is_empty() {
+            return Err(Error::Config(
+                "--respond-to-pressure: no profile is marked expendable; set `expendable:` on one first".into(),
+            ));
+        }
+        Some(responder)
+    } else {
+        None
+    };
+    let mut daemon = rlm_core::daemon::ProfileDaemon::new(&cfg);
+    let describe = |daemon: &rlm_core::daemon::ProfileDaemon| -> Vec<String> {
+        daemon
+            .profile_names()
+            .into_iter()
+            .map(|name| {
+                let mut conditions = Vec::new();
+                if let Some(schedule) = daemon.schedule(name) {
+                    conditions.push(schedule.to_string());
+                }
+                if daemon.has_battery_limits(name) {
+                    conditions.push("on battery".to_string());
+                }
+                if daemon.has_idle_limits(name) {
+                    conditions.push(format!("idle {}", format_interval(idle_after)));
+                }
+                if conditions.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{name} ({})", conditions.join(", "))
+                }
+            })
+            .collect()
+    };
+    let names = describe(&daemon);
+    if names.is_empty() && focus.is_none() && responder.is_none() {
+        return Err(Error::Config(
+            "no profile has match_exe and no limit is persistent; add executables to a profile first".into(),
+        ));
+    }
+    if !names.is_empty() {
+        eprintln!("applying profiles: {} (Ctrl+C to stop)", names.join(", "));
+    }
+
+    let terminated = Arc::new(AtomicBool::new(false));
+    let terminated_clone = Arc::clone(&terminated);
+    ctrlc::set_handler(move || {
+        terminated_clone.store(true, Ordering::SeqCst);
+    })
+    .ok();
+    // After the Ctrl+C handler, so SIGHUP reloads instead of stopping
+    let mut reload = rlm_core::reload::ConfigReload::on_hangup();
+    if watch_config {
+        reload = reload.watch_files()?;
+    }
+
+    // Subscribe before placing what runs now, so nothing starts in between
+    let mut events = rlm_core::proc_events::ProcessEvents::new(interval);
+    if !events.is_netlink() {
+        eprintln!(
+            "polling /proc every {} (run as root for instant exec events)",
+            format_interval(interval)
+        );
+    }
+    let now = || {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    };
+    let report = |p: rlm_core::daemon::Placement| {
+        let from = p
+            .from
+            .map(|from| format!(", re-matched from {from}"))
+            .unwrap_or_default();
+        eprintln!(
+            "{} {} {} -> {} ({}{from})",
+            clock(now()),
+            p.pid,
+            p.name,
+            p.profile,
+            p.cgroup
+        );
+    };
+    // Switch the limits of scheduled, power-aware and idle-aware profiles
+    let update_conditions = |daemon: &mut rlm_core::daemon::ProfileDaemon| {
+        let Some(minute) = rlm_core::schedule::local_minute(std::time::SystemTime::now()) else {
+            return;
+        };
+        let conditions = rlm_core::daemon::Conditions {
+            minute,
+            on_battery: rlm_core::power::on_battery(),
+            idle: daemon.uses_idle() && rlm_core::idle::idle_for().is_some_and(|d| d >= idle_after),
+        };
+        for switch in daemon.update_conditions(manager, conditions) {
+            let state = match (switch.window_open, switch.on_battery, switch.idle) {
+                (false, _, _) => "window closed, limits lifted",
+                (true, true, true) => "on-battery and idle limits applied",
+                (true, true, false) => "on-battery limits applied",
+                (true, false, true) => "idle limits applied",
+                (true, false, false) => "limits applied",
+            };
+            eprintln!("{} {}: {state}", clock(now()), switch.profile);
+        }
+    };
+    update_conditions(&mut daemon);
+    let mut checked = std::time::Instant::now();
+    daemon.place_running(manager).into_iter().for_each(report);
+
+    let mut booster = rlm_core::focus::FocusBooster::new();
+    let mut focus_checked = None::<std::time::Instant>;
+    if let Some(backend) = focus {
+        eprintln!(
+            "boosting the focused app's CPU weight to {} (others {}) via {}",
+            rlm_core::focus::FOCUS_WEIGHT,
+            rlm_core::focus::BACKGROUND_WEIGHT,
+            backend.name()
+        );
+    }
+    if let Some(responder) = &responder {
+        let cfg = &cfg.responder;
+        eprintln!(
+            "responding to memory pressure: freeze at {}%, kill at {}% (full avg10) in order: {}",
+            cfg.freeze_full,
+            cfg.kill_full,
+            responder.profile_names().join(", ")
+        );
+    }
+    let respond = |interventions: Vec<rlm_core::responder::Intervention>| {
+        for i in interventions {
+            eprintln!(
+                "{} memory pressure {:.1}%: {} {} ({})",
+                clock(now()),
+                i.full_avg10,
+                i.response.name(),
+                i.profile,
+                i.cgroup
+            );
+        }
+    };
+    let mut pressure_checked = std::time::Instant::now();
+    let mut escapes = rlm_core::escape::EscapeWatcher::new();
+    escapes.tick(manager);
+    let mut escapes_checked = std::time::Instant::now();
+    // Scripts manage limits through it while the daemon runs
+    let _control = match rlm_core::control::ControlServer::start() {
+        Ok(server) => {
+            eprintln!("control socket: {}", server.path().display());
+            Some(server)
+        }
+        Err(e) => {
+            eprintln!("control socket not available: {e}");
+            None
+        }
+    };
+    rlm_core::sd_notify::ready();
+    let mut watchdog = rlm_core::sd_notify::Watchdog::from_env();
+
+    while !terminated.load(Ordering::SeqCst) {
+        watchdog.tick();
+        if reload.requested() {
+            rlm_core::sd_notify::reloading();
+            match Config::load() {
+                Ok(cfg) => {
+                    let (changed, placed) = daemon.reload(manager, &cfg);
+                    if let Some(responder) = &mut responder {
+                        responder.reload(&cfg);
+                    }
+                    let names = describe(&daemon);
+                    eprintln!(
+                        "{} config reloaded, applying profiles: {}",
+                        clock(now()),
+                        if names.is_empty() {
+                            "none".to_string()
+                        } else {
+                            names.join(", ")
+                        }
+                    );
+                    for name in changed {
+                        eprintln!("  {name}: new limits applied");
+                    }
+                    update_conditions(&mut daemon);
+                    checked = std::time::Instant::now();
+                    placed.into_iter().for_each(report);
+                }
+                Err(e) => eprintln!(
+                    "{} config not reloaded, keeping the old one: {e}",
+                    clock(now())
+                ),
+            }
+            rlm_core::sd_notify::ready();
+        }
+        if let Some(backend) = focus {
+            if focus_checked.is_none_or(|t| t.elapsed() >= FOCUS_CHECK) {
+                if booster.update(manager, backend.focused_pid()) {
+                    let name = booster.focused().unwrap_or("(unmanaged app)");
+                    eprintln!("{} focus: {name}", clock(now()));
+                }
+                focus_checked = Some(std::time::Instant::now());
+            }
+        }
+        if let Some(responder) = &mut responder {
+            if pressure_checked.elapsed() >= PRESSURE_CHECK {
+                respond(responder.tick(manager));
+                pressure_checked = std::time::Instant::now();
+            }
+        }
+        if escapes_checked.elapsed() >= ESCAPE_CHECK {
+            for e in escapes.tick(manager) {
+                let outcome = if e.reattached {
+                    "moved back"
+                } else {
+                    "could not move it back"
+                };
+                eprintln!(
+                    "{} {} {} left {} for {}: {outcome}",
+                    clock(now()),
+                    e.pid,
+                    e.name,
+                    e.cgroup,
+                    e.found_in
+                );
+            }
+            escapes_checked = std::time::Instant::now();
+        }
+        // Idleness takes a loginctl call: look now and then, not every wakeup
+        if checked.elapsed() >= CONDITIONS_CHECK {
+            update_conditions(&mut daemon);
+            checked = std::time::Instant::now();
+        }
+        // Wake up now and then to notice Ctrl+C
+        let happened = match events.wait(Some(Duration::from_millis(250))) {
+            Ok(happened) => happened,
+            Err(e) => {
+                booster.restore(manager);
+                if let Some(responder) = &mut responder {
+                    responder.release(manager);
+                }
+                return Err(e);
+            }
+        };
+        for event in happened {
+            if let Some(p) = daemon.handle(manager, &event) {
+                report(p);
+            }
+        }
+    }
+    rlm_core::sd_notify::stopping();
+    // Leave the weights as they were found, and nothing frozen
+    booster.restore(manager);
+    if let Some(responder) = &mut responder {
+        respond(responder.release(manager));
+    }
+    Ok(())
+}
+
+/// `rlm watchdog`: check `name` every `interval` until Ctrl+C. Processes it
+/// froze are thawed on the way out.
+fn run_watchdog(
+    manager: &CgroupManager,
+    name: &str,
+    mut watchdog: rlm_core::watchdog::Watchdog,
+    action: rlm_core::watchdog::Action,
+    interval: Duration,
+) -> Result<()> {
+    use rlm_core::watchdog::{self, Sampler};
+
+    let terminated = Arc::new(AtomicBool::new(false));
+    let terminated_clone = Arc::clone(&terminated);
+    ctrlc::set_handler(move || {
+        terminated_clone.store(true, Ordering::SeqCst);
+    })
+    .ok();
+
+    let mut thresholds = Vec::new();
+    if let Some(m) = watchdog.memory {
+        thresholds.push(format!("memory over {}", format_bytes(m)));
+    }
+    if let Some(c) = watchdog.cpu {
+        thresholds.push(format!("CPU over {c}%"));
+    }
+    eprintln!(
+        "watching {name}: {} -> {action} (Ctrl+C to stop)",
+        thresholds.join(", ")
+    );
+
+    let mut sampler = Sampler::new();
+    let mut frozen: Vec<u32> = Vec::new();
+    loop {
+        let observed = sampler.sample(name);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        for breach in watchdog.check(&observed, std::time::Instant::now()) {
+            eprintln!(
+                "{} {name} ({} processes): {breach}",
+                clock(now),
+                observed.pids.len()
+            );
+            match watchdog::act(manager, action, name, &observed.pids, &breach) {
+                Ok(()) if action == watchdog::Action::Freeze => {
+                    frozen.extend(&observed.pids);
+                    frozen.sort_unstable();
+                    frozen.dedup();
+                    eprintln!(
+                        "  froze {} process(es); Ctrl+C thaws them",
+                        observed.pids.len()
+                    );
+                }
+                Ok(()) if action == watchdog::Action::Kill => {
+                    eprintln!("  sent SIGTERM to {} process(es)", observed.pids.len());
+                }
+                Ok(()) => {}
+                Err(e) => eprintln!("  {action} failed: {e}"),
+            }
+        }
+        if !sleep_unless_terminated(interval, &terminated) {
+            break;
+        }
+    }
+    if !frozen.is_empty() {
+        watchdog::release(manager, &frozen);
+        eprintln!("thawed {} process(es)", frozen.len());
+    }
+    Ok(())
+}
+
+/// `rlm history`: the most recent `limit` matching entries, oldest first.
+fn print_history(pid: Option<u32>, name: Option<&str>, limit: usize, json: bool) {
+    use rlm_core::history;
+
+    let name = name.map(str::to_lowercase);
+    let matching: Vec<_> = history::entries()
+        .into_iter()
+        .filter(|e| pid.is_none_or(|pid| e.pids.contains(&pid) || e.cgroup == format!("pid-{pid}")))
+        .filter(|e| {
+            name.as_ref().is_none_or(|n| {
+                e.cgroup.to_lowercase().contains(n)
+                    || e.name
+                        .as_ref()
+                        .is_some_and(|p| p.to_lowercase().contains(n))
+            })
+        })
+        .collect();
+    let shown = &matching[matching.len().saturating_sub(limit)..];
+
+    if json {
+        for e in shown {
+            println!("{}", e.to_json());
+        }
+        return;
+    }
+    if shown.is_empty() {
+        println!("no history recorded");
+        return;
+    }
+    println!(
+        "{:<19} {:<7} {:<25} {:<20} LIMITS / RESULT",
+        "TIME", "ACTION", "CGROUP", "PROCESS"
+    );
+    for e in shown {
+        let process = match (e.pids.first(), &e.name) {
+            (Some(pid), Some(name)) => format!("{pid} {name}"),
+            (Some(pid), None) => pid.to_string(),
+            (None, _) => "-".into(),
+        };
+        let limits = e.limit.as_ref().map(history::summary).unwrap_or_default();
+        let result = match &e.error {
+            Some(err) => format!("FAILED: {err}"),
+            None => limits,
+        };
+        println!(
+            "{:<19} {:<7} {:<25} {:<20} {}",
+            date_time(e.time),
+            e.action.name(),
+            e.cgroup,
+            process,
+            result
+        );
+    }
+}
+
+/// Local date and time of a Unix timestamp, e.g. "2024-05-01 14:03:09".
+fn date_time(secs: u64) -> String {
+    let Some(tm) = rlm_core::schedule::local_time(secs as i64) else {
+        return secs.to_string();
+    };
+    format!(
+        "{:04}-{:02}-{:02} {}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        clock(secs)
+    )
+}
+
+/// Sleep for `total`, returning false early if Ctrl+C arrives.
+fn sleep_unless_terminated(total: Duration, terminated: &AtomicBool) -> bool {
+    let step = Duration::from_millis(100);
+    let mut slept = Duration::ZERO;
+    while slept < total {
+        if terminated.load(Ordering::SeqCst) {
+            return false;
+        }
+        let chunk = step.min(total - slept);
+        std::thread::sleep(chunk);
+        slept += chunk;
+    }
+    !terminated.load(Ordering::SeqCst)
+}
+
+/// Signals `rlm run` passes on to the command, so it sees what it would have
+/// seen had it been run directly.
+const FORWARDED: [libc::c_int; 4] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT];
+
+/// Job control signals `rlm run` passes on too: `kill -TSTP` on rlm stops the
+/// command (and rlm with it), and `kill -CONT` continues it.
+const JOB_CONTROL: [libc::c_int; 2] = [libc::SIGTSTP, libc::SIGCONT];
+
+/// What [`run_attempt`] waits for.
+enum Event {
+    /// A signal to pass on
+    Signal(libc::c_int),
+    /// The command stopped (Ctrl+Z)
+    Stopped,
+    /// The command exited, with its raw wait status
+    Exited(libc::c_int),
+    /// waitpid failed
+    Lost(io::Error),
+}
+
+/// The [`Event`]s of an `rlm run`. Signals arrive through a thread of their
+/// own for the whole run, and each attempt's waitpid through another, so the
+/// wait blocks until something happens rather than polling.
+struct Events {
+    tx: std::sync::mpsc::Sender<Event>,
+    rx: std::sync::mpsc::Receiver<Event>,
+}
+
+impl Events {
+    fn new() -> Result<Self> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut signals =
+            signal_hook::iterator::Signals::new(FORWARDED.iter().chain(&JOB_CONTROL))?;
+        let forward = tx.clone();
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                if forward.send(Event::Signal(signal)).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { tx, rx })
+    }
+
+    /// Report `pid`'s stops and its exit, until it exits.
+    fn wait_for(&self, pid: libc::pid_t) {
+        let tx = self.tx.clone();
+        std::thread::spawn(move || loop {
+            let mut raw = 0;
+            // SAFETY: `raw` is a valid out-pointer; pid is our unreaped child.
+            let event = match unsafe { libc::waitpid(pid, &mut raw, libc::WUNTRACED) } {
+                -1 => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    Event::Lost(e)
+                }
+                _ if libc::WIFSTOPPED(raw) => Event::Stopped,
+                _ => Event::Exited(raw),
+            };
+            let stopped = matches!(event, Event::Stopped);
+            if tx.send(event).is_err() || !stopped {
+                break;
+            }
+        });
+    }
+
+    /// The next event, or `None` once `deadline` (since `start`) has passed.
+    fn next(&self, start: std::time::Instant, deadline: Option<Duration>) -> Option<Event> {
+        match deadline {
+            Some(deadline) => self
+                .rx
+                .recv_timeout(deadline.saturating_sub(start.elapsed()))
+                .ok(),
+            // The sender in `self` keeps the channel open
+            None => self.rx.recv().ok(),
+        }
+    }
+}
+
+/// Where and for how long [`run_attempt`] runs the command.
+struct Attempt<'a> {
+    cgroup_name: &'a str,
+    cgroup_path: &'a std::path::Path,
+    stopping: &'a Stopping,
+    launch: &'a LaunchArgs,
+}
+
+/// Spawn `program` inside the attempt's cgroup and wait for it, passing
+/// signals from `events` on to its process group. Also returns whether it ran
+/// out of time and was stopped.
+///
+/// With `--kill-after`, a command told to stop gets that long to do so before
+/// its cgroup is SIGKILLed, and so do processes it leaves behind. A command
+/// killed by Ctrl+C counts as told, though the terminal signals it directly.
+///
+/// Like a shell job, the command gets a process group of its own (so a signal
+/// reaches everything it started, not just the first process) and, when rlm
+/// holds the terminal, the terminal too: Ctrl+C and Ctrl+Z then go straight to
+/// the command, and rlm stops and continues along with it.
+fn run_attempt(
+    manager: &CgroupManager,
+    attempt: &Attempt,
+    program: &str,
+    args: &[String],
+    events: &Events,
+) -> Result<(std::process::ExitStatus, bool)> {
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+    // Place the child into the cgroup BEFORE it execs, so it is constrained from
+    // its first instruction (see CgroupManager::placement_command).
+    let mut cmd = manager.placement_command(attempt.cgroup_path, program)?;
+    cmd.args(args).process_group(0);
+    attempt.launch.apply_to(&mut cmd)?;
+    let foreground = holds_terminal();
+    if foreground {
+        // SAFETY: hand_terminal_to only makes async-signal-safe calls.
+        unsafe {
+            cmd.pre_exec(|| {
+                hand_terminal_to(libc::getpid());
+                Ok(())
+            });
+        }
+    }
+    let pid = cmd.spawn()?.id() as libc::pid_t;
+    events.wait_for(pid);
+    let started = std::time::Instant::now();
+    let grace = attempt.stopping.kill_after;
+    let mut timed_out = false;
+    // When the cgroup gets SIGKILL, once the command has been told to stop
+    let mut kill_at = None;
+    let mut killed = false;
+
+    let status = loop {
+        let timeout = attempt.stopping.timeout.filter(|_| !timed_out);
+        let deadline = match (timeout, kill_at.filter(|_| !killed)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        match events.next(started, deadline) {
+            Some(Event::Signal(signal)) => {
+                // SAFETY: kill has no memory-safety preconditions.
+                unsafe { libc::kill(-pid, signal) };
+                if FORWARDED.contains(&signal) {
+                    kill_at = kill_at.or(grace.map(|g| started.elapsed() + g));
+                }
+            }
+            Some(Event::Stopped) => {
+                // Stopped (Ctrl+Z): stop too, so the shell sees the job stop,
+                // and take the command along when continued
+                if foreground {
+                    // SAFETY: getpgrp has no preconditions.
+                    hand_terminal_to(unsafe { libc::getpgrp() });
+                }
+                // SAFETY: raise and kill have no memory-safety preconditions.
+                unsafe { libc::raise(libc::SIGSTOP) };
+                if foreground && holds_terminal() {
+                    hand_terminal_to(pid);
+                }
+                unsafe { libc::kill(-pid, libc::SIGCONT) };
+            }
+            Some(Event::Exited(raw)) => break std::process::ExitStatus::from_raw(raw),
+            Some(Event::Lost(e)) => return Err(e.into()),
+            None => {
+                let elapsed = started.elapsed();
+                // The whole cgroup, so children that left the process group
+                // (daemons, setsid) go too
+                if let Some(after) = timeout.filter(|&after| elapsed >= after) {
+                    eprintln!(
+                        "rlm: timed out after {}s; sending SIGTERM",
+                        after.as_secs_f32()
+                    );
+                    manager.signal_cgroup(attempt.cgroup_name, libc::SIGTERM)?;
+                    timed_out = true;
+                    kill_at = kill_at.or(grace.map(|g| elapsed + g));
+                }
+                if !killed && kill_at.is_some_and(|at| elapsed >= at) {
+                    kill_stragglers(manager, attempt.cgroup_name)?;
+                    killed = true;
+                }
+            }
+        }
+    };
+    if foreground {
+        // SAFETY: getpgrp has no preconditions.
+        hand_terminal_to(unsafe { libc::getpgrp() });
+    }
+
+    if status.signal().is_some_and(|s| FORWARDED.contains(&s)) {
+        kill_at = kill_at.or(grace.map(|g| started.elapsed() + g));
+    }
+    if let Some(at) = kill_at.filter(|_| !killed) {
+        while !manager.pids_in_cgroup(attempt.cgroup_name).is_empty() {
+            if started.elapsed() >= at {
+                kill_stragglers(manager, attempt.cgroup_name)?;
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+    Ok((status, timed_out))
+}
+
+fn kill_stragglers(manager: &CgroupManager, cgroup_name: &str) -> Result<()> {
+    eprintln!("rlm: still running after SIGTERM; sending SIGKILL");
+    manager.kill_cgroup(cgroup_name)
+}
+
+/// Whether rlm's process group is the foreground one on the terminal on stdin.
+fn holds_terminal() -> bool {
+    // SAFETY: isatty, tcgetpgrp, and getpgrp have no preconditions.
+    unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+    }
+}
+
+/// Make process group `pgid` the foreground one on the terminal on stdin.
+/// Callers may be in the background, where that raises SIGTTOU, so it is
+/// blocked meanwhile. Only async-signal-safe calls, for use before exec.
+fn hand_terminal_to(pgid: libc::pid_t) {
+    // SAFETY: the sigsets are initialized by sigemptyset/pthread_sigmask
+    // before they are read, and the old mask is restored.
+    unsafe {
+        let mut ttou: libc::sigset_t = std::mem::zeroed();
+        let mut old: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut ttou);
+        libc::sigaddset(&mut ttou, libc::SIGTTOU);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &ttou, &mut old);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &old, std::ptr::null_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pid_list_basic() {
+        assert_eq!(parse_pid_list("1,2,3").unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn parse_pid_list_trims_whitespace() {
+        assert_eq!(parse_pid_list(" 10 , 20 ,30 ").unwrap(), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn parse_pid_list_single() {
+        assert_eq!(parse_pid_list("42").unwrap(), vec![42]);
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("10s").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+
+    #[test]
+    fn parse_restart_policies() {
+        assert_eq!(parse_restart("on-failure").unwrap().max, None);
+        assert_eq!(parse_restart("on-failure:max=5").unwrap().max, Some(5));
+        assert!(parse_restart("always").is_err());
+        assert!(parse_restart("on-failure:5").is_err());
+        assert!(parse_restart("on-failure:max=-1").is_err());
+    }
+
+    #[test]
+    fn io_path_flag_applies_to_a_profiles_io_limits() {
+        let flags = LimitArgs {
+            io_path: Some("/home".into()),
+            ..LimitArgs::default()
+        };
+        assert!(flags.with_profile(None).is_err());
+
+        let profile = common::Profile {
+            io_write: Some("20M".into()),
+            ..common::Profile::default()
+        };
+        let limit = flags.with_profile(Some(&profile)).unwrap();
+        assert_eq!(limit.io_path, Some("/home".into()));
+        assert_eq!(limit.io.and_then(|io| io.write_bps), Some(20 << 20));
+    }
+
+    #[test]
+    fn persistent_rule_takes_flags_over_the_profile() {
+        let flags = LimitArgs {
+            memory: Some("1G".into()),
+            io_path: Some("/home".into()),
+            ..LimitArgs::default()
+        };
+        let profile = common::Profile {
+            memory: Some("4G".into()),
+            cpu: Some("50%".into()),
+            ..common::Profile::default()
+        };
+        let rule = flags.rule_with_profile("code", Some(&profile));
+        assert_eq!(rule.match_exe, ["code"]);
+        assert_eq!(rule.memory.as_deref(), Some("1G"));
+        assert_eq!(rule.cpu.as_deref(), Some("50%"));
+        assert_eq!(rule.io_path.as_deref(), Some("/home"));
+    }
+
+    #[test]
+    fn missing_limit_error_names_every_limit_flag() {
+        let message = LimitArgs::none_given().to_string();
+        assert!(message.contains("(--memory, --swap, --oom-group, --cpu,"));
+        assert!(message.contains("--max-pids, --mems)"));
+        assert!(!message.contains("--io-path"));
+    }
+
+    #[test]
+    fn parse_env_splits_at_first_equals() {
+        assert_eq!(
+            parse_env("OPTS=a=b").unwrap(),
+            ("OPTS".to_string(), "a=b".to_string())
+        );
+        assert_eq!(parse_env("EMPTY=").unwrap().1, "");
+        assert!(parse_env("NOVALUE").is_err());
+        assert!(parse_env("=x").is_err());
+    }
+
+    #[test]
+    fn exit_code_reports_signals_like_a_shell() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        assert_eq!(exit_code(ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(exit_code(ExitStatus::from_raw(libc::SIGKILL)), 137);
+        assert_eq!(exit_code(ExitStatus::from_raw(libc::SIGINT)), 130);
+    }
+
+    #[test]
+    fn run_attempt_passes_signals_on_and_reports_the_exit() {
+        use rlm_core::backend::FakeBackend;
+        use std::os::unix::process::ExitStatusExt;
+
+        // placement_command only writes to cgroup.procs
+        let dir = std::env::temp_dir().join(format!("rlm-run-attempt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cgroup.procs"), "").unwrap();
+        let manager =
+            CgroupManager::with_backend("/cg".into(), Arc::new(FakeBackend::new("/cg", "")));
+        let stopping = Stopping::default();
+        let launch = LaunchArgs::default();
+        let attempt = Attempt {
+            cgroup_name: "run-test",
+            cgroup_path: &dir,
+            stopping: &stopping,
+            launch: &launch,
+        };
+        let events = Events::new().unwrap();
+        let run = |script: &str| {
+            let args = ["-c".to_string(), script.to_string()];
+            run_attempt(&manager, &attempt, "sh", &args, &events).unwrap()
+        };
+
+        let (status, timed_out) = run("exit 3");
+        assert_eq!(status.code(), Some(3));
+        assert!(!timed_out);
+
+        events.tx.send(Event::Signal(libc::SIGTERM)).unwrap();
+        let (status, _) = run("sleep 10");
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_pid_list_rejects_invalid() {
+        assert!(parse_pid_list("1,abc,3").is_err());
+        assert!(parse_pid_list("1,,3").is_err()); // empty element
+        assert!(parse_pid_list("-1").is_err()); // negative
+    }
+}
@@ -0,0 +1,276 @@
+//! The picker `rlm limit --name` and `rlm unlimit --name` (and `--cmdline`)
+//! show when several processes match: type to filter, space to pick, and
+//! enter to act on the picked ones.
+
+use common::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashSet;
+
+const HELP: &str =
+    "type to filter  ↑/↓ move  space pick  tab pick all shown  enter confirm  esc cancel";
+
+struct Candidate {
+    pid: u32,
+    name: String,
+    cmdline: String,
+}
+
+impl Candidate {
+    fn of(pid: u32) -> Self {
+        Self {
+            pid,
+            name: rlm_core::process::info(pid).map_or_else(|| "?".into(), |p| p.name),
+            cmdline: rlm_core::process::cmdline(pid).unwrap_or_default(),
+        }
+    }
+
+    fn matches(&self, filter: &str) -> bool {
+        fuzzy_match(
+            filter,
+            &format!("{} {} {}", self.pid, self.name, self.cmdline),
+        )
+    }
+}
+
+/// Whether `query`'s characters appear in `text` in order, ignoring case:
+/// "mpyrun" finds "manage.py runserver".
+fn fuzzy_match(query: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| text.any(|t| t == q))
+}
+
+struct Picker {
+    action: String,
+    candidates: Vec<Candidate>,
+    chosen: HashSet<u32>,
+    filter: String,
+    table: TableState,
+}
+
+/// Let the user pick which of `pids` to `action`; empty if they cancel. The
+/// terminal is restored however it ends.
+pub fn pick(action: &str, pids: &[u32]) -> Result<Vec<u32>> {
+    let mut picker = Picker {
+        action: action.to_string(),
+        candidates: pids.iter().map(|&pid| Candidate::of(pid)).collect(),
+        chosen: HashSet::new(),
+        filter: String::new(),
+        table: TableState::default().with_selected(0),
+    };
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut picker);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, picker: &mut Picker) -> Result<Vec<u32>> {
+    loop {
+        terminal.draw(|frame| picker.draw(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        // Raw mode turns Ctrl+C into a key like any other
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Ok(Vec::new());
+        }
+        if let Some(picked) = picker.handle_key(key.code) {
+            return Ok(picked);
+        }
+    }
+}
+
+impl Picker {
+    fn visible(&self) -> Vec<&Candidate> {
+        self.candidates
+            .iter()
+            .filter(|c| c.matches(&self.filter))
+            .collect()
+    }
+
+    fn highlighted(&self) -> Option<u32> {
+        let index = self.table.selected()?;
+        self.visible().get(index).map(|c| c.pid)
+    }
+
+    /// Handle one key press; the picked PIDs once the user is done.
+    fn handle_key(&mut self, code: KeyCode) -> Option<Vec<u32>> {
+        match code {
+            KeyCode::Esc => return Some(Vec::new()),
+            KeyCode::Enter => {
+                // Nothing picked: the highlighted one
+                if self.chosen.is_empty() {
+                    return Some(self.highlighted().into_iter().collect());
+                }
+                return Some(
+                    self.candidates
+                        .iter()
+                        .map(|c| c.pid)
+                        .filter(|pid| self.chosen.contains(pid))
+                        .collect(),
+                );
+            }
+            KeyCode::Down => self.table.select_next(),
+            KeyCode::Up => self.table.select_previous(),
+            KeyCode::Char(' ') => {
+                if let Some(pid) = self.highlighted() {
+                    if !self.chosen.remove(&pid) {
+                        self.chosen.insert(pid);
+                    }
+                }
+            }
+            KeyCode::Tab => {
+                let shown: Vec<u32> = self.visible().iter().map(|c| c.pid).collect();
+                if shown.iter().all(|pid| self.chosen.contains(pid)) {
+                    shown.iter().for_each(|pid| {
+                        self.chosen.remove(pid);
+                    });
+                } else {
+                    self.chosen.extend(shown);
+                }
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.table.select(Some(0));
+            }
+            KeyCode::Char(c) => {
+                self.filter.push(c);
+                self.table.select(Some(0));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [filter_area, table_area, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(format!("filter: {}", self.filter)),
+            filter_area,
+        );
+
+        let visible = self.visible();
+        let shown = visible.len();
+        let rows = visible.into_iter().map(|c| {
+            let mark = if self.chosen.contains(&c.pid) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            Row::new([
+                mark.to_string(),
+                c.pid.to_string(),
+                c.name.clone(),
+                c.cmdline.clone(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(3),
+                Constraint::Length(8),
+                Constraint::Length(16),
+                Constraint::Min(20),
+            ],
+        )
+        .header(
+            Row::new(["", "PID", "NAME", "COMMAND"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::bordered().title(format!(
+            " {} which processes? {} picked, {} of {} shown ",
+            self.action,
+            self.chosen.len(),
+            shown,
+            self.candidates.len()
+        )))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        if self.table.selected().is_some_and(|i| i >= shown) {
+            self.table.select(shown.checked_sub(1));
+        }
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+        frame.render_widget(Paragraph::new(HELP), footer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Candidates from (name, command line) pairs, with PIDs from 100
+    fn picker(processes: &[(&str, &str)]) -> Picker {
+        Picker {
+            action: "Limit".into(),
+            candidates: processes
+                .iter()
+                .enumerate()
+                .map(|(i, (name, cmdline))| Candidate {
+                    pid: 100 + i as u32,
+                    name: name.to_string(),
+                    cmdline: cmdline.to_string(),
+                })
+                .collect(),
+            chosen: HashSet::new(),
+            filter: String::new(),
+            table: TableState::default().with_selected(0),
+        }
+    }
+
+    #[test]
+    fn fuzzy_match_in_order_ignoring_case() {
+        assert!(fuzzy_match("mpyrun", "python3 manage.py runserver"));
+        assert!(fuzzy_match("MANAGE", "manage.py"));
+        assert!(fuzzy_match("", "anything"));
+        assert!(!fuzzy_match("nurpy", "manage.py runserver"));
+    }
+
+    #[test]
+    fn picks_the_chosen_or_else_the_highlighted() {
+        let mut p = picker(&[
+            ("python3", "python3 manage.py runserver"),
+            ("python3", "python3 worker.py"),
+            ("python3", "python3 manage.py shell"),
+        ]);
+        for c in "manage".chars() {
+            assert_eq!(p.handle_key(KeyCode::Char(c)), None);
+        }
+        assert_eq!(p.visible().len(), 2);
+        p.handle_key(KeyCode::Tab);
+        p.handle_key(KeyCode::Char(' '));
+        assert_eq!(p.handle_key(KeyCode::Enter), Some(vec![102]));
+
+        let mut p = picker(&[("python3", "python3 a.py"), ("python3", "python3 b.py")]);
+        p.handle_key(KeyCode::Down);
+        assert_eq!(p.handle_key(KeyCode::Enter), Some(vec![101]));
+        assert_eq!(p.handle_key(KeyCode::Esc), Some(vec![]));
+    }
+
+    #[test]
+    fn filter_matches_the_name_as_well_as_the_command_line() {
+        // A process that renamed itself: only its name says what it is
+        let mut p = picker(&[
+            ("celeryd", "python3 -m celery worker"),
+            ("python3", "python3 app.py"),
+        ]);
+        for c in "celeryd".chars() {
+            p.handle_key(KeyCode::Char(c));
+        }
+        assert_eq!(p.visible().len(), 1);
+        assert_eq!(p.handle_key(KeyCode::Enter), Some(vec![100]));
+    }
+}
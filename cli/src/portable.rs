@@ -0,0 +1,214 @@
+//! The CLI where there are no cgroups v2: `limit`, `unlimit`, `run`, and
+//! `status`, with the limits the platform's own backend can enforce
+//! (`rlm_core::job` on Windows, `rlm_core::rctl` on FreeBSD).
+
+use clap::{Args, Parser, Subcommand};
+use common::{build_limit, format_bytes, Config, Error, Result};
+#[cfg(windows)]
+use rlm_core::job as platform;
+#[cfg(target_os = "freebsd")]
+use rlm_core::rctl as platform;
+use std::process::{Command, ExitCode};
+
+#[derive(Parser)]
+#[command(name = "rlm", bin_name = "rlm")]
+#[command(about = "Resource Limit Manager - control process resource usage")]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Apply resource limits to a running process and what it starts
+    Limit {
+        /// Process ID to limit
+        #[arg(long)]
+        pid: Option<u32>,
+
+        /// Jail (name or ID) to limit, all its processes sharing the limits
+        #[cfg(target_os = "freebsd")]
+        #[arg(long, conflicts_with = "pid")]
+        jail: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+
+    /// Remove resource limits from a process
+    Unlimit {
+        /// Process ID to unlimit
+        #[arg(long)]
+        pid: Option<u32>,
+
+        /// Jail (name or ID) to unlimit
+        #[cfg(target_os = "freebsd")]
+        #[arg(long, conflicts_with = "pid")]
+        jail: Option<String>,
+    },
+
+    /// Run a command with resource limits
+    Run {
+        /// Use limits from a named profile
+        #[arg(long, short)]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        /// Command to run
+        #[arg(trailing_var_arg = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Show limited processes
+    Status,
+}
+
+#[derive(Args)]
+struct LimitArgs {
+    /// Memory limit (K=1024, M=1024K, G=1024M, T=1024G)
+    #[arg(long, value_name = "SIZE")]
+    memory: Option<String>,
+
+    /// CPU limit as percentage (50%=half core, 100%=1 core, 200%=2 cores)
+    #[arg(long, value_name = "PERCENT")]
+    cpu: Option<String>,
+
+    /// I/O read bandwidth limit per second (K/M/G/T units; FreeBSD only)
+    #[arg(long, value_name = "SIZE")]
+    io_read: Option<String>,
+
+    /// I/O write bandwidth limit per second (K/M/G/T units; FreeBSD only)
+    #[arg(long, value_name = "SIZE")]
+    io_write: Option<String>,
+
+    /// Maximum number of processes (on FreeBSD, threads of a process)
+    #[arg(long, value_name = "N")]
+    max_pids: Option<u64>,
+}
+
+impl LimitArgs {
+    fn to_limit(&self) -> Result<common::Limit> {
+        let mut limit = build_limit(
+            self.memory.as_deref(),
+            self.cpu.as_deref(),
+            self.io_read.as_deref(),
+            self.io_write.as_deref(),
+        )?;
+        limit.pids = self.max_pids.map(common::PidsLimit::new).transpose()?;
+        Ok(limit)
+    }
+}
+
+pub fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive(tracing::Level::INFO.into()),
+        )
+        .init();
+
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<ExitCode> {
+    match Cli::parse().command {
+        Commands::Limit {
+            pid,
+            #[cfg(target_os = "freebsd")]
+            jail,
+            limits,
+        } => {
+            let limit = limits.to_limit()?;
+            if limit.is_empty() {
+                return Err(Error::InvalidArgs("specify at least one limit".into()));
+            }
+            #[cfg(target_os = "freebsd")]
+            if let Some(jail) = jail {
+                platform::limit_jail(&jail, &limit)?;
+                println!("Applied limits to jail {jail}");
+                return Ok(ExitCode::SUCCESS);
+            }
+            let pid = pid.ok_or_else(|| Error::InvalidArgs("specify --pid".into()))?;
+            platform::limit(pid, &limit)?;
+            println!("Applied limits to PID {pid}");
+        }
+        Commands::Unlimit {
+            pid,
+            #[cfg(target_os = "freebsd")]
+            jail,
+        } => {
+            #[cfg(target_os = "freebsd")]
+            if let Some(jail) = jail {
+                platform::unlimit_jail(&jail)?;
+                println!("Removed limits from jail {jail}");
+                return Ok(ExitCode::SUCCESS);
+            }
+            let pid = pid.ok_or_else(|| Error::InvalidArgs("specify --pid".into()))?;
+            platform::unlimit(pid)?;
+            println!("Removed limits from PID {pid}");
+        }
+        Commands::Run {
+            profile,
+            limits,
+            command,
+        } => {
+            let limit = match profile {
+                Some(name) => Config::load()?
+                    .get_profile(&name)
+                    .ok_or_else(|| Error::Config(format!("profile '{name}' not found")))?
+                    .to_limit()?,
+                None => limits.to_limit()?,
+            };
+            if limit.is_empty() {
+                return Err(Error::InvalidArgs(
+                    "specify --profile or at least one limit".into(),
+                ));
+            }
+            let (program, args) = command
+                .split_first()
+                .ok_or_else(|| Error::InvalidArgs("command is required".into()))?;
+            let status = platform::spawn(Command::new(program).args(args), &limit)?.wait()?;
+            return Ok(status
+                .code()
+                .map(|c| ExitCode::from(c as u8))
+                .unwrap_or(ExitCode::FAILURE));
+        }
+        Commands::Status => print_status(&platform::status()?),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_status(limited: &[platform::Limited]) {
+    if limited.is_empty() {
+        println!("no processes currently managed");
+        return;
+    }
+    println!(
+        "{:>8} {:<20} {:>10} {:>10} {:>6} {:>10}",
+        "PID", "NAME", "MEMORY", "USED", "CPU", "PROCESSES"
+    );
+    for p in limited {
+        let procs = match p.processes_max {
+            Some(max) => format!("{}/{max}", p.processes),
+            None => p.processes.to_string(),
+        };
+        println!(
+            "{:>8} {:<20} {:>10} {:>10} {:>6} {:>10}",
+            p.pid.map_or("jail".into(), |pid| pid.to_string()),
+            p.name,
+            p.memory_max.map_or("-".into(), format_bytes),
+            format_bytes(p.memory_used),
+            p.cpu_percent.map_or("-".into(), |c| format!("{c}%")),
+            procs
+        );
+    }
+}
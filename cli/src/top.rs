@@ -0,0 +1,291 @@
+//! `rlm top`: managed cgroups with live usage, refreshed every second, with
+//! keys to sort and to unlimit, freeze, or kill the selected one.
+
+use common::{format_bytes, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use rlm_core::status::{ProcessStatus, UsageSampler};
+use rlm_core::CgroupManager;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+const REFRESH: Duration = Duration::from_secs(1);
+
+const HELP: &str =
+    "q quit  ↑/↓ select  p/n/m/c/i sort (again to reverse)  u unlimit  f freeze/thaw  x kill";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Pid,
+    Name,
+    Memory,
+    Cpu,
+    Io,
+}
+
+impl SortKey {
+    fn from_key(c: char) -> Option<Self> {
+        match c {
+            'p' => Some(SortKey::Pid),
+            'n' => Some(SortKey::Name),
+            'm' => Some(SortKey::Memory),
+            'c' => Some(SortKey::Cpu),
+            'i' => Some(SortKey::Io),
+            _ => None,
+        }
+    }
+
+    /// Usage columns sort biggest first, the others ascending.
+    fn descending(self) -> bool {
+        matches!(self, SortKey::Memory | SortKey::Cpu | SortKey::Io)
+    }
+}
+
+struct App {
+    rows: Vec<ProcessStatus>,
+    frozen: HashSet<String>,
+    sampler: UsageSampler,
+    sort: SortKey,
+    reverse: bool,
+    table: TableState,
+    /// Last action's outcome, shown instead of the help line
+    message: Option<String>,
+    /// Cgroup waiting for a `y` to be killed
+    pending_kill: Option<String>,
+}
+
+/// Run until the user quits. The terminal is restored however it ends.
+pub fn run(manager: &CgroupManager) -> Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, manager);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, manager: &CgroupManager) -> Result<()> {
+    let mut app = App {
+        rows: Vec::new(),
+        frozen: HashSet::new(),
+        sampler: UsageSampler::new(),
+        sort: SortKey::Memory,
+        reverse: false,
+        table: TableState::default().with_selected(0),
+        message: None,
+        pending_kill: None,
+    };
+    let mut last = None::<Instant>;
+    loop {
+        if last.is_none_or(|t| t.elapsed() >= REFRESH) {
+            app.refresh(manager);
+            last = Some(Instant::now());
+        }
+        terminal.draw(|frame| app.draw(frame))?;
+
+        let wait = REFRESH.saturating_sub(last.map_or(REFRESH, |t| t.elapsed()));
+        if !event::poll(wait)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if !app.handle_key(key.code, manager) {
+            return Ok(());
+        }
+        if matches!(key.code, KeyCode::Char('u' | 'f' | 'y')) {
+            // Show the effect of an action right away
+            last = None;
+        }
+    }
+}
+
+impl App {
+    fn refresh(&mut self, manager: &CgroupManager) {
+        let selected = self.selected().map(|p| p.cgroup_name.clone());
+        match rlm_core::status::get_managed_processes(manager) {
+            Ok(mut rows) => {
+                self.sampler.update(&mut rows);
+                self.rows = rows;
+            }
+            Err(e) => self.message = Some(format!("error: {e}")),
+        }
+        self.frozen = self
+            .rows
+            .iter()
+            .filter(|p| manager.is_frozen(&p.cgroup_name))
+            .map(|p| p.cgroup_name.clone())
+            .collect();
+        self.sort_rows();
+
+        // Keep the same cgroup selected as rows move around
+        let index = selected
+            .and_then(|name| self.rows.iter().position(|p| p.cgroup_name == name))
+            .unwrap_or(0);
+        self.table
+            .select((!self.rows.is_empty()).then_some(index.min(self.rows.len() - 1)));
+    }
+
+    fn sort_rows(&mut self) {
+        let key = self.sort;
+        let io = |p: &ProcessStatus| p.rates.map_or(0, |r| r.io_read_bps + r.io_write_bps);
+        let cpu = |p: &ProcessStatus| p.rates.and_then(|r| r.cpu_percent).unwrap_or(0.0);
+        self.rows.sort_by(|a, b| {
+            let order = match key {
+                SortKey::Pid => a.pid.cmp(&b.pid),
+                SortKey::Name => a.name.cmp(&b.name),
+                SortKey::Memory => a.usage.memory_current.cmp(&b.usage.memory_current),
+                SortKey::Cpu => cpu(a).total_cmp(&cpu(b)),
+                SortKey::Io => io(a).cmp(&io(b)),
+            };
+            if key.descending() {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        if self.reverse {
+            self.rows.reverse();
+        }
+    }
+
+    fn selected(&self) -> Option<&ProcessStatus> {
+        self.table.selected().and_then(|i| self.rows.get(i))
+    }
+
+    /// Handle one key press; false to quit.
+    fn handle_key(&mut self, code: KeyCode, manager: &CgroupManager) -> bool {
+        if let Some(name) = self.pending_kill.take() {
+            self.message = Some(if code == KeyCode::Char('y') {
+                match manager.kill_cgroup(&name) {
+                    Ok(()) => format!("killed {name}"),
+                    Err(e) => format!("error: {e}"),
+                }
+            } else {
+                "kill cancelled".into()
+            });
+            return true;
+        }
+        self.message = None;
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::Char(c) if SortKey::from_key(c).is_some() => {
+                let key = SortKey::from_key(c).unwrap_or(self.sort);
+                self.reverse = key == self.sort && !self.reverse;
+                self.sort = key;
+                self.sort_rows();
+            }
+            KeyCode::Char('u') => {
+                if let Some(p) = self.selected() {
+                    let (name, process) = (p.cgroup_name.clone(), p.name.clone());
+                    self.message = Some(match manager.remove_application_limit(&name) {
+                        Ok(()) => format!("removed limits from {process} ({name})"),
+                        Err(e) => format!("error: {e}"),
+                    });
+                }
+            }
+            KeyCode::Char('f') => {
+                if let Some(p) = self.selected() {
+                    let name = p.cgroup_name.clone();
+                    let freeze = !self.frozen.contains(&name);
+                    self.message = Some(match manager.set_frozen(&name, freeze) {
+                        Ok(()) if freeze => format!("froze {name}"),
+                        Ok(()) => format!("thawed {name}"),
+                        Err(e) => format!("error: {e}"),
+                    });
+                }
+            }
+            KeyCode::Char('x') => {
+                if let Some(p) = self.selected() {
+                    let (name, process) = (p.cgroup_name.clone(), p.name.clone());
+                    self.message = Some(format!("kill every process in {name} ({process})? y/N"));
+                    self.pending_kill = Some(name);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [table_area, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+
+        let arrow = |key: SortKey| match (key == self.sort, key.descending() != self.reverse) {
+            (false, _) => "",
+            (true, true) => "▼",
+            (true, false) => "▲",
+        };
+        let header = Row::new([
+            format!("PID{}", arrow(SortKey::Pid)),
+            format!("NAME{}", arrow(SortKey::Name)),
+            "CGROUP".to_string(),
+            format!("MEMORY{}", arrow(SortKey::Memory)),
+            format!("CPU{}", arrow(SortKey::Cpu)),
+            format!("I/O R/W{}", arrow(SortKey::Io)),
+            "STATE".to_string(),
+        ])
+        .style(Style::new().add_modifier(Modifier::BOLD));
+
+        let rows = self.rows.iter().map(|p| {
+            let limit = p.memory_max.map_or("-".into(), format_bytes);
+            let mem = match p.usage.memory_current {
+                Some(cur) => format!("{}/{}", format_bytes(cur), limit),
+                None => limit,
+            };
+            let quota = p.cpu_quota.map_or("-".into(), |q| format!("{q}%"));
+            let cpu = match p.rates.and_then(|r| r.cpu_percent) {
+                Some(used) => format!("{used:.0}%/{quota}"),
+                None => quota,
+            };
+            let io = p.rates.map_or("-".into(), |r| {
+                format!(
+                    "{}/{}",
+                    format_bytes(r.io_read_bps),
+                    format_bytes(r.io_write_bps)
+                )
+            });
+            let state = if self.frozen.contains(&p.cgroup_name) {
+                "frozen"
+            } else {
+                ""
+            };
+            Row::new([
+                p.pid.to_string(),
+                p.name.clone(),
+                p.cgroup_name.clone(),
+                mem,
+                cpu,
+                io,
+                state.to_string(),
+            ])
+        });
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(8),
+                Constraint::Min(15),
+                Constraint::Min(15),
+                Constraint::Length(20),
+                Constraint::Length(12),
+                Constraint::Length(16),
+                Constraint::Length(7),
+            ],
+        )
+        .header(header)
+        .block(Block::bordered().title(format!(" rlm top: {} managed ", self.rows.len())))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let footer_text = self.message.as_deref().unwrap_or(HELP);
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+}
@@ -17,30 +17,160 @@ pub struct Config {
     #[serde(default, skip_serializing_if = "GuardConfig::is_default")]
     pub guard: GuardConfig,
 
-    /// Persistent application limit rules, enforced continuously by rlm-guard.
+    /// Persistent application limit rules, enforced continuously by rlm-guard
+    /// and re-applied by `rlm daemon` as matching processes start.
     /// Keyed by rule name (defaults to the executable basename). Omitted from
     /// serialized output when empty.
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub rules: HashMap<String, AppRule>,
+
+    /// Supervised applications, watched by rlm-guard for OOM kills and
+    /// repeated limit breaches. Keyed like `rules`; omitted when empty.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub supervise: HashMap<String, SuperviseRule>,
+
+    /// Audit logging of limit changes; omitted when off.
+    #[serde(default, skip_serializing_if = "AuditConfig::is_default")]
+    pub audit: AuditConfig,
+
+    /// User scripts run on limit events; omitted when none are set.
+    #[serde(default, skip_serializing_if = "HooksConfig::is_default")]
+    pub hooks: HooksConfig,
+
+    /// Thresholds of `rlm daemon --respond-to-pressure`; omitted at defaults.
+    #[serde(default, skip_serializing_if = "ResponderConfig::is_default")]
+    pub responder: ResponderConfig,
+
+    /// Cgroup rlm creates its cgroups in, instead of the one it finds
+    /// itself (e.g. a subtree an admin delegated). `RLM_CGROUP_ROOT` and
+    /// `rlm --cgroup-root` take precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup_root: Option<PathBuf>,
+}
+
+/// Where limit changes are audited, besides `rlm history`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Write a structured systemd journal entry for every limit applied,
+    /// updated, removed, or failed, with the invoking user and target
+    pub journald: bool,
+}
+
+impl AuditConfig {
+    pub fn is_default(&self) -> bool {
+        *self == AuditConfig::default()
+    }
+}
+
+/// Shell commands run on limit events, with `RLM_*` environment variables
+/// describing the event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Limits were set on a cgroup, new or already managed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_limit_applied: Option<String>,
+    /// Limits were removed from a cgroup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_limit_removed: Option<String>,
+    /// A process in a managed cgroup was OOM-killed (run by rlm-guard)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_oom_kill: Option<String>,
+    /// A process in a managed cgroup exited (run by rlm-guard)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_process_exit: Option<String>,
+}
+
+impl HooksConfig {
+    pub fn is_default(&self) -> bool {
+        *self == HooksConfig::default()
+    }
+}
+
+/// What a rule or profile matches besides `match_exe`. Every condition set
+/// must hold; without `match_exe`, the conditions alone pick processes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchConditions {
+    /// Regex searched for in the command line, arguments joined by spaces.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_cmdline: Option<String>,
+
+    /// Owning user, by name or UID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_user: Option<String>,
+
+    /// Parent process, by name or executable basename.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_parent: Option<String>,
+
+    /// systemd unit (service, scope or slice) the process runs in; a trailing
+    /// `*` matches any suffix (e.g., "app-gnome-code-*.scope").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_unit: Option<String>,
+
+    /// Working directory, or a directory above it (e.g., "~/work/api").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_cwd: Option<String>,
+
+    /// When several rules match a process, the highest priority wins; ties
+    /// go by name.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+}
+
+fn is_zero(n: &i32) -> bool {
+    *n == 0
+}
+
+impl MatchConditions {
+    /// Whether no condition is set.
+    pub fn is_empty(&self) -> bool {
+        self.match_cmdline.is_none()
+            && self.match_user.is_none()
+            && self.match_parent.is_none()
+            && self.match_unit.is_none()
+            && self.match_cwd.is_none()
+    }
 }
 
 /// A persistent application limit rule. Instances whose executable basename is
-/// in `match_exe` are placed into a shared `app-<name>` cgroup with these limits.
-/// Limits are stored inline (a snapshot), not as a reference to a profile.
+/// in `match_exe` (and that meet its `conditions`) are placed into a shared
+/// `app-<name>` cgroup with these limits. Limits are stored inline (a
+/// snapshot), not as a reference to a profile.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct AppRule {
     /// Executable basenames this rule matches.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub match_exe: Vec<String>,
 
+    #[serde(flatten)]
+    pub conditions: MatchConditions,
+
     /// Memory limit (e.g., "4G").
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memory: Option<String>,
 
+    /// Swap limit (e.g., "1G"); "0" disables swap.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap: Option<String>,
+
+    /// On OOM, kill all of the application's processes together.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub oom_group: bool,
+
     /// CPU limit (e.g., "75%").
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpu: Option<String>,
 
+    /// Proportional CPU weight, 1-10000 (default 100).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<u32>,
+
+    /// Run only when the CPU would otherwise be idle.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub idle: bool,
+
     /// I/O read bandwidth limit (e.g., "100M").
     #[serde(skip_serializing_if = "Option::is_none")]
     pub io_read: Option<String>,
@@ -48,11 +178,50 @@ pub struct AppRule {
     /// I/O write bandwidth limit (e.g., "50M").
     #[serde(skip_serializing_if = "Option::is_none")]
     pub io_write: Option<String>,
+
+    /// Only limit I/O on the disk holding this path (e.g., "/home").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_path: Option<String>,
+
+    /// Proportional I/O weight, 1-10000 (default 100).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_weight: Option<u32>,
+
+    /// Maximum number of processes and threads (e.g., 512).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pids: Option<u64>,
+
+    /// NUMA memory nodes to allocate from (e.g., "0" or "0-1").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mems: Option<String>,
 }
 
 impl AppRule {
+    /// A rule matching the executable `name`, with a snapshot of `profile`'s
+    /// cgroup limits (not its rlimits, schedule, or overrides).
+    pub fn from_profile(name: &str, profile: &Profile) -> AppRule {
+        AppRule {
+            match_exe: vec![name.to_string()],
+            conditions: MatchConditions::default(),
+            memory: profile.memory.clone(),
+            swap: profile.swap.clone(),
+            oom_group: profile.oom_group,
+            cpu: profile.cpu.clone(),
+            cpu_weight: profile.cpu_weight,
+            idle: profile.idle,
+            io_read: profile.io_read.clone(),
+            io_write: profile.io_write.clone(),
+            io_path: profile.io_path.clone(),
+            io_weight: profile.io_weight,
+            pids: profile.pids,
+            mems: profile.mems.clone(),
+        }
+    }
+
     pub fn to_limit(&self) -> Result<Limit> {
-        use crate::{CpuLimit, IoLimit, MemoryLimit};
+        use crate::{
+            CpuLimit, CpuWeight, IoLimit, IoWeight, MemNodes, MemoryLimit, PidsLimit, SwapLimit,
+        };
 
         let read_bps = self
             .io_read
@@ -79,12 +248,103 @@ impl AppRule {
                 .as_ref()
                 .map(|s| MemoryLimit::parse(s))
                 .transpose()?,
+            swap: self.swap.as_deref().map(SwapLimit::parse).transpose()?,
+            oom_group: self.oom_group,
             cpu: self.cpu.as_ref().map(|s| CpuLimit::parse(s)).transpose()?,
+            cpu_weight: self.cpu_weight.map(CpuWeight::new).transpose()?,
+            idle: self.idle,
             io,
+            io_path: self.io_path.as_deref().map(std::path::PathBuf::from),
+            io_weight: self.io_weight.map(IoWeight::new).transpose()?,
+            pids: self.pids.map(PidsLimit::new).transpose()?,
+            mems: self.mems.as_deref().map(MemNodes::parse).transpose()?,
+        })
+    }
+}
+
+/// A supervised application: an [`AppRule`] (placed in a `sup-<name>` cgroup)
+/// plus what to do when it is OOM-killed or keeps exceeding its soft limit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SuperviseRule {
+    #[serde(flatten)]
+    pub rule: AppRule,
+
+    #[serde(default)]
+    pub on_oom: OomPolicy,
+}
+
+/// Intervention policy for a supervised application.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OomPolicy {
+    /// Restart it with the same limits after an OOM kill.
+    #[default]
+    Restart,
+    /// Lower its memory and CPU limits on every intervention, restarting it
+    /// with the tighter limits after an OOM kill.
+    Tighten,
+    /// Only log interventions.
+    Log,
+}
+
+impl std::str::FromStr for OomPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "restart" => Ok(Self::Restart),
+            "tighten" => Ok(Self::Tighten),
+            "log" => Ok(Self::Log),
+            _ => Err(Error::InvalidArgs(format!(
+                "unknown OOM policy '{s}' (expected restart, tighten, or log)"
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for OomPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Restart => "restart",
+            Self::Tighten => "tighten",
+            Self::Log => "log",
         })
     }
 }
 
+/// When `rlm daemon --respond-to-pressure` acts on the most expendable
+/// profile's cgroup, by system-wide memory PSI `full` avg10 (%).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponderConfig {
+    /// Freeze it at this much pressure.
+    pub freeze_full: f64,
+    /// Kill it at this much pressure.
+    pub kill_full: f64,
+    /// How long pressure must stay there before acting, and between actions.
+    pub sustain_secs: u64,
+    /// How long pressure must stay below half of `freeze_full` before the
+    /// frozen cgroups are thawed.
+    pub thaw_after_secs: u64,
+}
+
+impl Default for ResponderConfig {
+    fn default() -> Self {
+        Self {
+            freeze_full: 10.0,
+            kill_full: 40.0,
+            sustain_secs: 5,
+            thaw_after_secs: 30,
+        }
+    }
+}
+
+impl ResponderConfig {
+    pub fn is_default(&self) -> bool {
+        *self == ResponderConfig::default()
+    }
+}
+
 /// Configuration for the `rlm-guard` freeze-guard daemon. Every field defaults,
 /// so a missing `guard:` section (or any missing key) yields a working setup.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -212,14 +472,34 @@ pub struct Profile {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub match_exe: Vec<String>,
 
+    /// Further conditions on the processes it matches
+    #[serde(flatten)]
+    pub conditions: MatchConditions,
+
     /// Memory limit (e.g., "2G")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub memory: Option<String>,
 
+    /// Swap limit (e.g., "1G"); "0" disables swap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap: Option<String>,
+
+    /// On OOM, kill all of the application's processes together
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub oom_group: bool,
+
     /// CPU limit (e.g., "50%")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpu: Option<String>,
 
+    /// Proportional CPU weight, 1-10000 (default 100)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<u32>,
+
+    /// Run only when the CPU would otherwise be idle
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub idle: bool,
+
     /// I/O read bandwidth limit (e.g., "100M")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub io_read: Option<String>,
@@ -227,11 +507,93 @@ pub struct Profile {
     /// I/O write bandwidth limit (e.g., "50M")
     #[serde(skip_serializing_if = "Option::is_none")]
     pub io_write: Option<String>,
+
+    /// Only limit I/O on the disk holding this path (e.g., "/home")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_path: Option<String>,
+
+    /// Proportional I/O weight, 1-10000 (default 100)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_weight: Option<u32>,
+
+    /// Maximum number of processes and threads (e.g., 512)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pids: Option<u64>,
+
+    /// NUMA memory nodes to allocate from (e.g., "0" or "0-1")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mems: Option<String>,
+
+    /// Open files each process of `rlm run` may have (RLIMIT_NOFILE)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_open_files: Option<u64>,
+
+    /// Largest core dump each process of `rlm run` may write (e.g., "0" for
+    /// none, "512M"; RLIMIT_CORE)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_core: Option<String>,
+
+    /// Processes the user of `rlm run` may have in all (RLIMIT_NPROC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_nproc: Option<u64>,
+
+    /// Local time window the limits apply in (e.g., "22:00-07:00"); outside
+    /// it `rlm daemon` lifts them. Always applied when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+
+    /// How expendable its cgroup is under memory pressure: with
+    /// `rlm daemon --respond-to-pressure`, the highest is frozen or killed
+    /// first. Never touched when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expendable: Option<u32>,
+
+    /// Limits that replace these while on battery (e.g., `cpu: 25%`);
+    /// `rlm daemon` switches between them as the power source changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_battery: Option<Box<Profile>>,
+
+    /// Limits that replace these while the user is away (see
+    /// `rlm daemon --idle-after`), e.g. looser ones for batch jobs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when_idle: Option<Box<Profile>>,
 }
 
 impl Profile {
+    /// This profile with the limits `overrides` sets replaced (e.g. its
+    /// `on_battery` ones); the rest are kept. The result has no overrides
+    /// of its own.
+    pub fn with_overrides(&self, overrides: &Profile) -> Profile {
+        let b = overrides;
+        Profile {
+            match_exe: self.match_exe.clone(),
+            conditions: self.conditions.clone(),
+            memory: b.memory.clone().or_else(|| self.memory.clone()),
+            swap: b.swap.clone().or_else(|| self.swap.clone()),
+            oom_group: b.oom_group || self.oom_group,
+            cpu: b.cpu.clone().or_else(|| self.cpu.clone()),
+            cpu_weight: b.cpu_weight.or(self.cpu_weight),
+            idle: b.idle || self.idle,
+            io_read: b.io_read.clone().or_else(|| self.io_read.clone()),
+            io_write: b.io_write.clone().or_else(|| self.io_write.clone()),
+            io_path: b.io_path.clone().or_else(|| self.io_path.clone()),
+            io_weight: b.io_weight.or(self.io_weight),
+            pids: b.pids.or(self.pids),
+            mems: b.mems.clone().or_else(|| self.mems.clone()),
+            max_open_files: b.max_open_files.or(self.max_open_files),
+            max_core: b.max_core.clone().or_else(|| self.max_core.clone()),
+            max_nproc: b.max_nproc.or(self.max_nproc),
+            schedule: self.schedule.clone(),
+            expendable: self.expendable,
+            on_battery: None,
+            when_idle: None,
+        }
+    }
+
     pub fn to_limit(&self) -> Result<Limit> {
-        use crate::{CpuLimit, IoLimit, MemoryLimit};
+        use crate::{
+            CpuLimit, CpuWeight, IoLimit, IoWeight, MemNodes, MemoryLimit, PidsLimit, SwapLimit,
+        };
 
         let read_bps = self
             .io_read
@@ -258,8 +620,16 @@ impl Profile {
                 .as_ref()
                 .map(|s| MemoryLimit::parse(s))
                 .transpose()?,
+            swap: self.swap.as_deref().map(SwapLimit::parse).transpose()?,
+            oom_group: self.oom_group,
             cpu: self.cpu.as_ref().map(|s| CpuLimit::parse(s)).transpose()?,
+            cpu_weight: self.cpu_weight.map(CpuWeight::new).transpose()?,
+            idle: self.idle,
             io,
+            io_path: self.io_path.as_deref().map(std::path::PathBuf::from),
+            io_weight: self.io_weight.map(IoWeight::new).transpose()?,
+            pids: self.pids.map(PidsLimit::new).transpose()?,
+            mems: self.mems.as_deref().map(MemNodes::parse).transpose()?,
         })
     }
 }
@@ -272,10 +642,26 @@ pub fn builtin_presets() -> HashMap<String, Profile> {
         "Light".to_string(),
         Profile {
             match_exe: Vec::new(),
+            conditions: MatchConditions::default(),
             memory: Some("512M".to_string()),
+            swap: None,
+            oom_group: false,
             cpu: Some("25%".to_string()),
+            cpu_weight: None,
+            idle: false,
             io_read: None,
             io_write: None,
+            io_path: None,
+            io_weight: None,
+            pids: None,
+            mems: None,
+            max_open_files: None,
+            max_core: None,
+            max_nproc: None,
+            schedule: None,
+            expendable: None,
+            on_battery: None,
+            when_idle: None,
         },
     );
 
@@ -283,10 +669,26 @@ pub fn builtin_presets() -> HashMap<String, Profile> {
         "Medium".to_string(),
         Profile {
             match_exe: Vec::new(),
+            conditions: MatchConditions::default(),
             memory: Some("2G".to_string()),
+            swap: None,
+            oom_group: false,
             cpu: Some("50%".to_string()),
+            cpu_weight: None,
+            idle: false,
             io_read: Some("50M".to_string()),
             io_write: Some("25M".to_string()),
+            io_path: None,
+            io_weight: None,
+            pids: None,
+            mems: None,
+            max_open_files: None,
+            max_core: None,
+            max_nproc: None,
+            schedule: None,
+            expendable: None,
+            on_battery: None,
+            when_idle: None,
         },
     );
 
@@ -294,10 +696,26 @@ pub fn builtin_presets() -> HashMap<String, Profile> {
         "Heavy".to_string(),
         Profile {
             match_exe: Vec::new(),
+            conditions: MatchConditions::default(),
             memory: Some("4G".to_string()),
+            swap: None,
+            oom_group: false,
             cpu: Some("100%".to_string()),
+            cpu_weight: None,
+            idle: false,
             io_read: Some("100M".to_string()),
             io_write: Some("50M".to_string()),
+            io_path: None,
+            io_weight: None,
+            pids: None,
+            mems: None,
+            max_open_files: None,
+            max_core: None,
+            max_nproc: None,
+            schedule: None,
+            expendable: None,
+            on_battery: None,
+            when_idle: None,
         },
     );
 
@@ -309,10 +727,26 @@ pub fn builtin_presets() -> HashMap<String, Profile> {
                 "chrome".to_string(),
                 "chromium".to_string(),
             ],
+            conditions: MatchConditions::default(),
             memory: Some("4G".to_string()),
+            swap: None,
+            oom_group: false,
             cpu: Some("75%".to_string()),
+            cpu_weight: None,
+            idle: false,
             io_read: None,
             io_write: None,
+            io_path: None,
+            io_weight: None,
+            pids: None,
+            mems: None,
+            max_open_files: None,
+            max_core: None,
+            max_nproc: None,
+            schedule: None,
+            expendable: None,
+            on_battery: None,
+            when_idle: None,
         },
     );
 
@@ -369,10 +803,20 @@ impl Config {
         let other = Self::load_from(path)?;
         self.profiles.extend(other.profiles);
         self.rules.extend(other.rules);
+        self.supervise.extend(other.supervise);
         // A non-default guard block in a loaded file takes effect.
         if !other.guard.is_default() {
             self.guard = other.guard;
         }
+        // Auditing switched on system-wide can't be switched off per user
+        self.audit.journald |= other.audit.journald;
+        // A file that sets any hook replaces them all
+        if !other.hooks.is_default() {
+            self.hooks = other.hooks;
+        }
+        if !other.responder.is_default() {
+            self.responder = other.responder;
+        }
         Ok(())
     }
 
@@ -391,6 +835,18 @@ impl Config {
         dirs::config_dir().map(|d| d.join("rlm").join("config.yaml"))
     }
 
+    /// Directories config is loaded from: /etc/rlm, the user's config
+    /// directory and its profiles.d, for daemons that reload on changes.
+    pub fn source_dirs() -> Vec<PathBuf> {
+        let mut dirs = vec![PathBuf::from("/etc/rlm")];
+        if let Some(dir) = Self::user_config_path().and_then(|p| p.parent().map(Path::to_path_buf))
+        {
+            dirs.push(dir.join("profiles.d"));
+            dirs.push(dir);
+        }
+        dirs
+    }
+
     /// Find a profile by name (includes built-in presets)
     pub fn get_profile(&self, name: &str) -> Option<Profile> {
         // User profiles override built-in presets
@@ -420,6 +876,16 @@ impl Config {
         self.rules.remove(name).is_some()
     }
 
+    /// Add or replace a supervised application.
+    pub fn add_supervised(&mut self, name: impl Into<String>, rule: SuperviseRule) {
+        self.supervise.insert(name.into(), rule);
+    }
+
+    /// Stop supervising an application. Returns true if it was supervised.
+    pub fn remove_supervised(&mut self, name: &str) -> bool {
+        self.supervise.remove(name).is_some()
+    }
+
     /// Save config to user config path (atomic write)
     pub fn save(&self) -> Result<()> {
         let path = Self::user_config_path()
@@ -448,15 +914,25 @@ mod tests {
     fn app_rule_to_limit_parses_fields() {
         let rule = AppRule {
             match_exe: vec!["firefox".into()],
+            conditions: MatchConditions::default(),
             memory: Some("4G".into()),
+            swap: None,
+            oom_group: false,
             cpu: Some("75%".into()),
+            cpu_weight: None,
+            idle: false,
             io_read: None,
             io_write: None,
+            io_path: None,
+            io_weight: None,
+            pids: Some(256),
+            mems: None,
         };
         let limit = rule.to_limit().unwrap();
         assert_eq!(limit.memory.unwrap().bytes(), 4 * 1024 * 1024 * 1024);
         assert_eq!(limit.cpu.unwrap().percent(), 75);
         assert!(limit.io.is_none());
+        assert_eq!(limit.pids.unwrap().count(), 256);
     }
 
     #[test]
@@ -486,10 +962,19 @@ mod tests {
             "firefox",
             AppRule {
                 match_exe: vec!["firefox".into()],
+                conditions: MatchConditions::default(),
                 memory: Some("4G".into()),
+                swap: None,
+                oom_group: false,
                 cpu: Some("75%".into()),
+                cpu_weight: None,
+                idle: false,
                 io_read: None,
                 io_write: None,
+                io_path: None,
+                io_weight: None,
+                pids: None,
+                mems: None,
             },
         );
         let yaml = serde_yaml_ng::to_string(&cfg).unwrap();
@@ -500,6 +985,75 @@ mod tests {
         assert_eq!(r.memory.as_deref(), Some("4G"));
     }
 
+    #[test]
+    fn supervise_rule_flattens_and_round_trips() {
+        let yaml =
+            "supervise:\n  miner:\n    match_exe: [miner]\n    memory: 2G\n    on_oom: tighten\n";
+        let cfg: Config = serde_yaml_ng::from_str(yaml).unwrap();
+        let s = cfg.supervise.get("miner").expect("supervised app present");
+        assert_eq!(s.rule.match_exe, vec!["miner".to_string()]);
+        assert_eq!(s.rule.memory.as_deref(), Some("2G"));
+        assert_eq!(s.on_oom, OomPolicy::Tighten);
+
+        let back: Config =
+            serde_yaml_ng::from_str(&serde_yaml_ng::to_string(&cfg).unwrap()).unwrap();
+        assert_eq!(back.supervise.get("miner"), Some(s));
+    }
+
+    #[test]
+    fn match_conditions_flatten_into_rules() {
+        let yaml = "rules:\n  api-node:\n    match_exe: [node]\n    match_cwd: /srv/api\n    priority: 10\n    memory: 2G\n";
+        let cfg: Config = serde_yaml_ng::from_str(yaml).unwrap();
+        let rule = &cfg.rules["api-node"];
+        assert_eq!(rule.conditions.match_cwd.as_deref(), Some("/srv/api"));
+        assert_eq!(rule.conditions.priority, 10);
+        assert!(!rule.conditions.is_empty());
+
+        let out = serde_yaml_ng::to_string(&cfg).unwrap();
+        assert!(out.contains("match_cwd: /srv/api"), "{out}");
+        assert!(
+            !out.contains("match_user"),
+            "unset conditions are omitted: {out}"
+        );
+    }
+
+    #[test]
+    fn oom_policy_defaults_to_restart_and_parses() {
+        let yaml = "supervise:\n  x:\n    match_exe: [x]\n";
+        let cfg: Config = serde_yaml_ng::from_str(yaml).unwrap();
+        assert_eq!(cfg.supervise["x"].on_oom, OomPolicy::Restart);
+        assert_eq!("log".parse::<OomPolicy>().unwrap(), OomPolicy::Log);
+        assert!("kill".parse::<OomPolicy>().is_err());
+    }
+
+    #[test]
+    fn on_battery_overrides_only_what_it_sets() {
+        let yaml = "profiles:\n  build:\n    match_exe: [cc1]\n    memory: 8G\n    cpu: 100%\n    on_battery:\n      cpu: 25%\n";
+        let cfg: Config = serde_yaml_ng::from_str(yaml).unwrap();
+        let build = &cfg.profiles["build"];
+        let battery = build.with_overrides(build.on_battery.as_deref().expect("has on_battery"));
+        assert_eq!(battery.cpu.as_deref(), Some("25%"));
+        assert_eq!(battery.memory.as_deref(), Some("8G"));
+        assert!(battery.on_battery.is_none());
+    }
+
+    #[test]
+    fn rule_from_profile_snapshots_its_limits() {
+        let profile = Profile {
+            match_exe: vec!["cc1".into()],
+            memory: Some("8G".into()),
+            oom_group: true,
+            pids: Some(512),
+            schedule: Some("22:00-07:00".into()),
+            ..Profile::default()
+        };
+        let rule = AppRule::from_profile("make", &profile);
+        assert_eq!(rule.match_exe, ["make"]);
+        assert_eq!(rule.memory.as_deref(), Some("8G"));
+        assert!(rule.oom_group);
+        assert_eq!(rule.pids, Some(512));
+    }
+
     #[test]
     fn add_and_remove_rule() {
         let mut cfg = Config::default();
@@ -24,7 +24,7 @@ pub enum Error {
     #[error("invalid arguments: {0}")]
     InvalidArgs(String),
 
-    #[error("permission denied: {path}\n  hint: run as root, or enable cgroup delegation:\n  sudo mkdir -p /etc/systemd/system/user@.service.d\n  echo '[Service]\\nDelegate=cpu memory io' | sudo tee /etc/systemd/system/user@.service.d/delegate.conf\n  sudo systemctl daemon-reload && logout")]
+    #[error("permission denied: {path}\n  hint: run as root, or enable cgroup delegation:\n  sudo mkdir -p /etc/systemd/system/user@.service.d\n  echo '[Service]\\nDelegate=cpu memory io pids cpuset' | sudo tee /etc/systemd/system/user@.service.d/delegate.conf\n  sudo systemctl daemon-reload && logout")]
     PermissionDenied { path: PathBuf },
 
     #[error("cgroups v2 not available at {0}\n  hint: ensure your kernel supports cgroups v2 (Linux 4.5+) and it's mounted")]
@@ -4,9 +4,12 @@ mod limit;
 mod util;
 
 pub use config::{
-    builtin_presets, AppRule, Config, GuardConfig, GuardSelection, GuardTiming, GuardTrigger,
-    Profile, BUILTIN_PROTECT,
+    builtin_presets, AppRule, AuditConfig, Config, GuardConfig, GuardSelection, GuardTiming,
+    GuardTrigger, HooksConfig, MatchConditions, OomPolicy, Profile, ResponderConfig, SuperviseRule,
+    BUILTIN_PROTECT,
 };
 pub use error::{Error, Result};
-pub use limit::{CpuLimit, IoLimit, Limit, MemoryLimit};
+pub use limit::{
+    CpuLimit, CpuWeight, IoLimit, IoWeight, Limit, MemNodes, MemoryLimit, PidsLimit, SwapLimit,
+};
 pub use util::{build_limit, format_bytes};
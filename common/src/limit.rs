@@ -2,15 +2,69 @@ use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 
 /// Resource limits to apply to a process
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Limit {
     pub memory: Option<MemoryLimit>,
+    pub swap: Option<SwapLimit>,
+    /// On OOM, kill the whole cgroup rather than one process (`memory.oom.group`)
+    pub oom_group: bool,
     pub cpu: Option<CpuLimit>,
+    pub cpu_weight: Option<CpuWeight>,
+    /// Run only when the CPU would otherwise be idle (`cpu.idle`)
+    pub idle: bool,
     pub io: Option<IoLimit>,
+    /// Restrict `io` to the disk holding this path instead of every disk
+    pub io_path: Option<std::path::PathBuf>,
+    pub io_weight: Option<IoWeight>,
+    pub pids: Option<PidsLimit>,
+    pub mems: Option<MemNodes>,
+}
+
+impl Limit {
+    /// Whether no limit is set at all
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_none()
+            && self.swap.is_none()
+            && !self.oom_group
+            && self.cpu.is_none()
+            && self.cpu_weight.is_none()
+            && !self.idle
+            && self.io.is_none_or(|io| io.is_empty())
+            && self.io_weight.is_none()
+            && self.pids.is_none()
+            && self.mems.is_none()
+    }
+
+    /// These limits with each one `overrides` sets in its place: a profile
+    /// as the base, say, with the flags given on top. Switches (`oom_group`,
+    /// `idle`) are on if either turns them on, and the read and write halves
+    /// of `io` are taken separately.
+    pub fn merge(&self, overrides: &Limit) -> Limit {
+        let io = match (self.io, overrides.io) {
+            (Some(base), Some(over)) => Some(IoLimit {
+                read_bps: over.read_bps.or(base.read_bps),
+                write_bps: over.write_bps.or(base.write_bps),
+            }),
+            (base, over) => over.or(base),
+        };
+        Limit {
+            memory: overrides.memory.or(self.memory),
+            swap: overrides.swap.or(self.swap),
+            oom_group: self.oom_group || overrides.oom_group,
+            cpu: overrides.cpu.or(self.cpu),
+            cpu_weight: overrides.cpu_weight.or(self.cpu_weight),
+            idle: self.idle || overrides.idle,
+            io,
+            io_path: overrides.io_path.clone().or_else(|| self.io_path.clone()),
+            io_weight: overrides.io_weight.or(self.io_weight),
+            pids: overrides.pids.or(self.pids),
+            mems: overrides.mems.clone().or_else(|| self.mems.clone()),
+        }
+    }
 }
 
 /// I/O bandwidth limit in bytes per second
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct IoLimit {
     /// Read bandwidth limit (bytes/sec)
     pub read_bps: Option<u64>,
@@ -29,8 +83,28 @@ impl IoLimit {
     }
 }
 
+/// Proportional I/O share (`io.weight`, 1-10000, default 100). Unlike an
+/// [`IoLimit`] cap it only throttles when other cgroups want the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IoWeight(u32);
+
+impl IoWeight {
+    pub fn weight(self) -> u32 {
+        self.0
+    }
+
+    pub fn new(weight: u32) -> Result<Self> {
+        if !(1..=10000).contains(&weight) {
+            return Err(Error::InvalidArgs(format!(
+                "I/O weight {weight} out of range (1-10000, default 100)"
+            )));
+        }
+        Ok(Self(weight))
+    }
+}
+
 /// Memory limit in bytes
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MemoryLimit(u64);
 
 impl MemoryLimit {
@@ -70,8 +144,27 @@ impl MemoryLimit {
     }
 }
 
+/// Swap a cgroup may use (`memory.swap.max`), in bytes. Unlike a
+/// [`MemoryLimit`], zero is valid: it disables swapping entirely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapLimit(u64);
+
+impl SwapLimit {
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+
+    /// Parse "0" or a memory size (e.g., "1G", "512M")
+    pub fn parse(s: &str) -> Result<Self> {
+        if s.trim() == "0" {
+            return Ok(Self(0));
+        }
+        MemoryLimit::parse(s).map(|m| Self(m.bytes()))
+    }
+}
+
 /// CPU limit as percentage (0-100 per core, can exceed 100 for multiple cores)
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CpuLimit(u32);
 
 impl CpuLimit {
@@ -96,10 +189,112 @@ impl CpuLimit {
     }
 }
 
+/// Proportional CPU share (`cpu.weight`, 1-10000, default 100). Unlike a
+/// [`CpuLimit`] quota it only throttles when other cgroups want the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuWeight(u32);
+
+impl CpuWeight {
+    pub fn weight(self) -> u32 {
+        self.0
+    }
+
+    pub fn new(weight: u32) -> Result<Self> {
+        if !(1..=10000).contains(&weight) {
+            return Err(Error::InvalidCpu(format!(
+                "weight {weight} out of range (1-10000, default 100)"
+            )));
+        }
+        Ok(Self(weight))
+    }
+}
+
+/// Maximum number of processes and threads in a cgroup (`pids.max`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PidsLimit(u64);
+
+impl PidsLimit {
+    pub fn count(self) -> u64 {
+        self.0
+    }
+
+    pub fn new(count: u64) -> Result<Self> {
+        if count == 0 {
+            return Err(Error::InvalidArgs(
+                "max pids cannot be zero (nothing could run)".into(),
+            ));
+        }
+        Ok(Self(count))
+    }
+}
+
+/// NUMA memory nodes a cgroup may allocate from (`cpuset.mems`), in the
+/// kernel's list format, e.g. "0", "0-1", or "0,2-3"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemNodes(String);
+
+impl MemNodes {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let invalid =
+            || Error::InvalidArgs(format!("invalid NUMA node list '{s}' (e.g. 0, 0-1, 0,2)"));
+        if s.is_empty() {
+            return Err(invalid());
+        }
+        for part in s.split(',') {
+            let (lo, hi) = part.split_once('-').unwrap_or((part, part));
+            let lo: u32 = lo.parse().map_err(|_| invalid())?;
+            let hi: u32 = hi.parse().map_err(|_| invalid())?;
+            if lo > hi {
+                return Err(invalid());
+            }
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn merge_takes_each_override_in_place_of_the_base() {
+        let base = Limit {
+            memory: Some(MemoryLimit::parse("4G").unwrap()),
+            cpu: Some(CpuLimit::parse("200%").unwrap()),
+            io: Some(IoLimit {
+                read_bps: Some(100),
+                write_bps: Some(50),
+            }),
+            idle: true,
+            ..Limit::default()
+        };
+        let overrides = Limit {
+            memory: Some(MemoryLimit::parse("1G").unwrap()),
+            io: Some(IoLimit {
+                read_bps: None,
+                write_bps: Some(10),
+            }),
+            ..Limit::default()
+        };
+        let merged = base.merge(&overrides);
+        assert_eq!(merged.memory, overrides.memory);
+        assert_eq!(merged.cpu, base.cpu);
+        assert_eq!(
+            merged.io,
+            Some(IoLimit {
+                read_bps: Some(100),
+                write_bps: Some(10),
+            })
+        );
+        assert!(merged.idle);
+        assert_eq!(base.merge(&Limit::default()), base);
+    }
+
     #[test]
     fn parse_memory_units() {
         assert_eq!(MemoryLimit::parse("1024").unwrap().bytes(), 1024);
@@ -140,6 +335,14 @@ mod tests {
         assert!(MemoryLimit::parse("999999999999999999T").is_err());
     }
 
+    #[test]
+    fn parse_swap_allows_zero() {
+        assert_eq!(SwapLimit::parse("0").unwrap().bytes(), 0);
+        assert_eq!(SwapLimit::parse("1G").unwrap().bytes(), 1024 * 1024 * 1024);
+        assert!(SwapLimit::parse("").is_err());
+        assert!(SwapLimit::parse("lots").is_err());
+    }
+
     #[test]
     fn parse_cpu_percent() {
         assert_eq!(CpuLimit::parse("50%").unwrap().percent(), 50);
@@ -153,6 +356,47 @@ mod tests {
         assert!(CpuLimit::parse("-50%").is_err());
     }
 
+    #[test]
+    fn pids_limit_rejects_zero() {
+        assert_eq!(PidsLimit::new(512).unwrap().count(), 512);
+        assert!(PidsLimit::new(0).is_err());
+    }
+
+    #[test]
+    fn cpu_weight_range() {
+        assert_eq!(CpuWeight::new(1).unwrap().weight(), 1);
+        assert_eq!(CpuWeight::new(10000).unwrap().weight(), 10000);
+        assert!(CpuWeight::new(0).is_err());
+        assert!(CpuWeight::new(10001).is_err());
+    }
+
+    #[test]
+    fn io_weight_range() {
+        assert_eq!(IoWeight::new(100).unwrap().weight(), 100);
+        assert!(IoWeight::new(0).is_err());
+        assert!(IoWeight::new(10001).is_err());
+    }
+
+    #[test]
+    fn parse_mem_nodes() {
+        assert_eq!(MemNodes::parse("0").unwrap().as_str(), "0");
+        assert_eq!(MemNodes::parse(" 0,2-3 ").unwrap().as_str(), "0,2-3");
+        assert!(MemNodes::parse("").is_err());
+        assert!(MemNodes::parse("1-0").is_err());
+        assert!(MemNodes::parse("0,,1").is_err());
+        assert!(MemNodes::parse("node0").is_err());
+    }
+
+    #[test]
+    fn limit_is_empty() {
+        assert!(Limit::default().is_empty());
+        let pids_only = Limit {
+            pids: Some(PidsLimit::new(64).unwrap()),
+            ..Limit::default()
+        };
+        assert!(!pids_only.is_empty());
+    }
+
     #[test]
     fn io_limit_is_empty() {
         let empty = IoLimit::default();
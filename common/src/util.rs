@@ -38,7 +38,19 @@ pub fn build_limit(
 
     // Note: Zero validation happens at parse time in MemoryLimit/CpuLimit/IoLimit
 
-    Ok(Limit { memory, cpu, io })
+    Ok(Limit {
+        memory,
+        swap: None,
+        oom_group: false,
+        cpu,
+        cpu_weight: None,
+        idle: false,
+        io,
+        io_path: None,
+        io_weight: None,
+        pids: None,
+        mems: None,
+    })
 }
 
 /// Format bytes as human-readable string
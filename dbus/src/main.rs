@@ -0,0 +1,51 @@
+//! `rlm-dbus` — the D-Bus service.
+//!
+//! Runs as root on the system bus under [`BUS_NAME`], so the GTK GUI and
+//! third-party tools can apply and remove limits without cgroup delegation
+//! of their own. Every call that changes something is checked with polkit
+//! against the actions in `io.github.rlm.policy`; see [`manager`] for the
+//! interface.
+
+mod manager;
+mod polkit;
+
+use manager::Manager;
+
+/// Well-known name the service owns.
+pub const BUS_NAME: &str = "io.github.rlm.Manager";
+/// Object the interface is served at.
+pub const OBJECT_PATH: &str = "/io/github/rlm/Manager";
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive(tracing::Level::INFO.into()),
+        )
+        .init();
+
+    if let Err(e) = run() {
+        tracing::error!("rlm-dbus exiting: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let manager = rlm_core::CgroupManager::new()?;
+    // Cgroups of `RunLimited` commands whose service instance died
+    if let Err(e) = rlm_core::state::gc(&manager, false) {
+        tracing::warn!("startup cgroup gc failed: {e}");
+    }
+
+    let _connection = zbus::blocking::connection::Builder::system()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Manager::new(manager))?
+        .build()?;
+    tracing::info!(name = BUS_NAME, "rlm-dbus started");
+    rlm_core::sd_notify::ready();
+
+    // Requests are served on zbus's own threads
+    loop {
+        std::thread::park();
+    }
+}
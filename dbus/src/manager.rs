@@ -0,0 +1,300 @@
+//! The `io.github.rlm.Manager1` interface.
+//!
+//! Limits are passed as a dictionary with the keys of a config profile
+//! (`memory`, `swap`, `cpu`, `cpu_weight`, `idle`, `io_read`, `io_write`,
+//! `io_path`, `io_weight`, `pids`, `mems`, `oom_group`), every value a string.
+//! Changing the caller's own processes needs `io.github.rlm.manage`; anyone
+//! else's needs `io.github.rlm.manage-any`. Listing needs nothing.
+//!
+//! `LimitsChanged(cgroup, change)` is emitted with `applied`, `removed`,
+//! `started`, or `exited` whenever a managed cgroup changes through the
+//! service.
+
+use crate::polkit;
+use common::Profile;
+use rlm_core::account::Account;
+use rlm_core::CgroupManager;
+use std::collections::HashMap;
+use std::os::unix::fs::MetadataExt;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
+use zbus::{fdo, Connection};
+
+pub struct Manager {
+    mgr: Arc<CgroupManager>,
+    /// Numbers the cgroups of `RunLimited` commands
+    runs: AtomicU64,
+}
+
+impl Manager {
+    pub fn new(mgr: CgroupManager) -> Self {
+        Self {
+            mgr: Arc::new(mgr),
+            runs: AtomicU64::new(0),
+        }
+    }
+
+    /// Check with polkit that the sender may change processes owned by
+    /// `owners`: the own-process action when they are all its own (or there
+    /// are none), the any-process one otherwise. Returns the sender's uid.
+    async fn authorize(
+        &self,
+        conn: &Connection,
+        header: &Header<'_>,
+        owners: &[u32],
+    ) -> fdo::Result<u32> {
+        let sender = header
+            .sender()
+            .ok_or_else(|| fdo::Error::Failed("call has no sender".into()))?
+            .to_string();
+        let uid = polkit::caller_uid(conn, &sender).await?;
+        let action = if owners.iter().all(|&o| o == uid) {
+            polkit::MANAGE_OWN
+        } else {
+            polkit::MANAGE_ANY
+        };
+        if !polkit::check(conn, &sender, action).await? {
+            return Err(fdo::Error::AccessDenied(format!(
+                "not authorized for {action}"
+            )));
+        }
+        Ok(uid)
+    }
+}
+
+#[zbus::interface(name = "io.github.rlm.Manager1")]
+impl Manager {
+    /// Limit process `pid` in a cgroup of its own, or update the limits it
+    /// already has. Returns the cgroup.
+    async fn apply_limit(
+        &self,
+        pid: u32,
+        limits: HashMap<String, String>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<String> {
+        let limit = limit_from(&limits).map_err(failed)?;
+        let owner = owner_of(pid).ok_or_else(|| failed(common::Error::ProcessNotFound(pid)))?;
+        let uid = self.authorize(conn, &header, &[owner]).await?;
+
+        self.mgr.apply_limit(pid, &limit).map_err(failed)?;
+        let cgroup = format!("pid-{pid}");
+        tracing::info!(pid, uid, cgroup, "limit applied over D-Bus");
+        Self::limits_changed(&emitter, &cgroup, "applied").await?;
+        Ok(cgroup)
+    }
+
+    /// Remove the limits of the cgroup process `pid` is in, releasing every
+    /// process in it.
+    async fn remove_limit(
+        &self,
+        pid: u32,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let cgroup = self
+            .mgr
+            .find_cgroup_for_pid(pid)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("process {pid} is not managed")))?;
+        let owners: Vec<u32> = self
+            .mgr
+            .pids_in_cgroup(&cgroup)
+            .into_iter()
+            .filter_map(owner_of)
+            .collect();
+        let uid = self.authorize(conn, &header, &owners).await?;
+
+        if cgroup == format!("pid-{pid}") {
+            self.mgr.remove_limit(pid)
+        } else {
+            self.mgr.remove_application_limit(&cgroup)
+        }
+        .map_err(failed)?;
+        tracing::info!(pid, uid, cgroup, "limit removed over D-Bus");
+        Self::limits_changed(&emitter, &cgroup, "removed").await?;
+        Ok(())
+    }
+
+    /// Every managed cgroup: its first process, the process name, the
+    /// cgroup, memory in use, the memory limit, and the CPU limit in percent
+    /// of a core (0 when unlimited).
+    async fn list_managed(&self) -> fdo::Result<Vec<(u32, String, String, u64, u64, u32)>> {
+        let processes = rlm_core::status::get_managed_processes(&self.mgr).map_err(failed)?;
+        Ok(processes
+            .into_iter()
+            .map(|p| {
+                (
+                    p.pid,
+                    p.name,
+                    p.cgroup_name,
+                    p.usage.memory_current.unwrap_or(0),
+                    p.memory_max.unwrap_or(0),
+                    p.cpu_quota.unwrap_or(0),
+                )
+            })
+            .collect())
+    }
+
+    /// Start `argv` as the caller, limited from its first instruction, with
+    /// `env` as its environment and the caller's home as its working
+    /// directory. The cgroup is removed when it exits. Returns its pid.
+    async fn run_limited(
+        &self,
+        argv: Vec<String>,
+        env: HashMap<String, String>,
+        limits: HashMap<String, String>,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<u32> {
+        let (program, args) = argv
+            .split_first()
+            .ok_or_else(|| fdo::Error::InvalidArgs("command is required".into()))?;
+        let limit = limit_from(&limits).map_err(failed)?;
+        let uid = self.authorize(conn, &header, &[]).await?;
+        let account = Account::of(uid).map_err(|e| failed(e.into()))?;
+
+        let n = self.runs.fetch_add(1, Ordering::SeqCst);
+        let cgroup = format!("run-{}-{n}", std::process::id());
+        let path = self.mgr.prepare_cgroup(&cgroup, &limit).map_err(failed)?;
+
+        // Placed in the cgroup (by the first pre_exec hook) before dropping
+        // to the caller's user
+        let mut cmd = self.mgr.placement_command(&path, program).map_err(failed)?;
+        cmd.args(args)
+            .env_clear()
+            .env("PATH", "/usr/local/bin:/usr/bin:/bin")
+            .envs(&env)
+            .env("HOME", &account.home)
+            .env("USER", account.user())
+            .env("LOGNAME", account.user())
+            .current_dir(&account.home)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        account.run_as(&mut cmd);
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = self.mgr.cleanup_cgroup(&cgroup);
+                return Err(fdo::Error::SpawnFailed(format!("{program}: {e}")));
+            }
+        };
+        let pid = child.id();
+        tracing::info!(pid, uid, cgroup, program, "started over D-Bus");
+        Self::limits_changed(&emitter, &cgroup, "started").await?;
+
+        let mgr = Arc::clone(&self.mgr);
+        let emitter = emitter.into_owned();
+        std::thread::spawn(move || {
+            let _ = child.wait();
+            if let Err(e) = mgr.cleanup_cgroup(&cgroup) {
+                tracing::warn!(cgroup, "failed to remove cgroup: {e}");
+            }
+            let _ = zbus::block_on(Self::limits_changed(&emitter, &cgroup, "exited"));
+        });
+        Ok(pid)
+    }
+
+    /// A managed cgroup was changed through the service.
+    #[zbus(signal)]
+    async fn limits_changed(
+        emitter: &SignalEmitter<'_>,
+        cgroup: &str,
+        change: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// The limit a D-Bus limits dictionary describes.
+fn limit_from(limits: &HashMap<String, String>) -> common::Result<common::Limit> {
+    let mut profile = Profile::default();
+    for (key, value) in limits {
+        let flag = || match value.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(common::Error::InvalidArgs(format!(
+                "{key}: expected true or false, got {value}"
+            ))),
+        };
+        match key.as_str() {
+            "memory" => profile.memory = Some(value.clone()),
+            "swap" => profile.swap = Some(value.clone()),
+            "cpu" => profile.cpu = Some(value.clone()),
+            "cpu_weight" => profile.cpu_weight = Some(number(key, value)?),
+            "idle" => profile.idle = flag()?,
+            "io_read" => profile.io_read = Some(value.clone()),
+            "io_write" => profile.io_write = Some(value.clone()),
+            "io_path" => profile.io_path = Some(value.clone()),
+            "io_weight" => profile.io_weight = Some(number(key, value)?),
+            "pids" => profile.pids = Some(number(key, value)?),
+            "mems" => profile.mems = Some(value.clone()),
+            "oom_group" => profile.oom_group = flag()?,
+            _ => return Err(common::Error::InvalidArgs(format!("unknown limit: {key}"))),
+        }
+    }
+    let limit = profile.to_limit()?;
+    if limit.is_empty() {
+        return Err(common::Error::InvalidArgs("no limits given".into()));
+    }
+    Ok(limit)
+}
+
+fn number<T: std::str::FromStr>(key: &str, value: &str) -> common::Result<T> {
+    value
+        .parse()
+        .map_err(|_| common::Error::InvalidArgs(format!("{key}: not a number: {value}")))
+}
+
+fn owner_of(pid: u32) -> Option<u32> {
+    std::fs::metadata(format!("/proc/{pid}"))
+        .ok()
+        .map(|m| m.uid())
+}
+
+fn failed(e: common::Error) -> fdo::Error {
+    match e {
+        common::Error::PermissionDenied { .. } => fdo::Error::AccessDenied(e.to_string()),
+        common::Error::Cgroup(_)
+        | common::Error::CgroupsV2NotAvailable(_)
+        | common::Error::Config(_)
+        | common::Error::Io(_) => fdo::Error::Failed(e.to_string()),
+        _ => fdo::Error::InvalidArgs(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn limits_are_read_like_profile_keys() {
+        let limit = limit_from(&limits(&[
+            ("memory", "2G"),
+            ("cpu", "50%"),
+            ("pids", "512"),
+            ("idle", "true"),
+        ]))
+        .unwrap();
+        assert_eq!(limit.memory.map(|m| m.bytes()), Some(2 << 30));
+        assert_eq!(limit.cpu.map(|c| c.percent()), Some(50));
+        assert_eq!(limit.pids.map(|p| p.count()), Some(512));
+        assert!(limit.idle);
+
+        assert!(limit_from(&limits(&[])).is_err());
+        assert!(limit_from(&limits(&[("memroy", "2G")])).is_err());
+        assert!(limit_from(&limits(&[("pids", "many")])).is_err());
+        assert!(limit_from(&limits(&[("idle", "yes")])).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Authorization through polkit's `CheckAuthorization`, with the caller's bus
+//! name as the subject so polkit can tie it to a session and ask for a
+//! password when the action calls for one.
+
+use std::collections::HashMap;
+use zbus::zvariant::Value;
+use zbus::Connection;
+
+/// Limit, remove, and run the caller's own processes.
+pub const MANAGE_OWN: &str = "io.github.rlm.manage";
+/// Limit and remove any user's processes.
+pub const MANAGE_ANY: &str = "io.github.rlm.manage-any";
+
+/// `CheckAuthorizationFlags.AllowUserInteraction`
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+/// Whether the sender of a call may perform `action`. May wait for the user
+/// to authenticate.
+pub async fn check(conn: &Connection, sender: &str, action: &str) -> zbus::Result<bool> {
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender))]),
+    );
+    let details: HashMap<&str, &str> = HashMap::new();
+    let reply = conn
+        .call_method(
+            Some("org.freedesktop.PolicyKit1"),
+            "/org/freedesktop/PolicyKit1/Authority",
+            Some("org.freedesktop.PolicyKit1.Authority"),
+            "CheckAuthorization",
+            &(subject, action, details, ALLOW_USER_INTERACTION, ""),
+        )
+        .await?;
+    let (authorized, _challenge, _details): (bool, bool, HashMap<String, String>) =
+        reply.body().deserialize()?;
+    Ok(authorized)
+}
+
+/// Unix user behind the sender of a call.
+pub async fn caller_uid(conn: &Connection, sender: &str) -> zbus::Result<u32> {
+    let dbus = zbus::fdo::DBusProxy::new(conn).await?;
+    Ok(dbus.get_connection_unix_user(sender.try_into()?).await?)
+}
@@ -0,0 +1,26 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Embed the short git commit hash as `RLM_GIT_COMMIT` for the About window.
+/// Builds from a source tarball simply leave it unset.
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+    if !commit.is_empty() {
+        println!("cargo:rustc-env=RLM_GIT_COMMIT={commit}");
+    }
+
+    // Re-run when HEAD moves, but only watch files that exist: a missing path
+    // would force a rerun on every build.
+    for path in ["../.git/HEAD", "../.git/refs/heads", "../.git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+}
@@ -1,4 +1,6 @@
 mod pages;
+mod privileged;
+mod shortcuts;
 mod widgets;
 mod window;
 
@@ -21,7 +23,22 @@ fn main() -> gtk::glib::ExitCode {
 fn build_ui(app: &adw::Application) {
     // Initialize cgroup manager
     let (manager, error) = match CgroupManager::new() {
-        Ok(m) => (Some(Arc::new(m)), None),
+        // Without delegation, work in the system-wide tree the privileged
+        // helper writes for us
+        Ok(m) if !m.has_delegation() && privileged::helper_available() => {
+            tracing::info!("no cgroup delegation; using the privileged helper");
+            match CgroupManager::system() {
+                Ok(system) => (Some(Arc::new(system)), None),
+                Err(e) => (None, Some(e.to_string())),
+            }
+        }
+        Ok(m) => {
+            // Clean up after GUI or CLI sessions that were killed
+            if let Err(e) = rlm_core::state::gc(&m, false) {
+                tracing::warn!("cgroup gc failed: {e}");
+            }
+            (Some(Arc::new(m)), None)
+        }
         Err(e) => {
             tracing::error!("Failed to initialize cgroup manager: {e}");
             (None, Some(e.to_string()))
@@ -1,6 +1,7 @@
 use crate::widgets::{
-    create_unit_dropdown, get_unit_suffix, parse_cpu_value, set_value_with_unit,
-    setup_number_validation,
+    create_icon_button, create_status_label, create_unit_dropdown, find_widget_by_name,
+    get_unit_suffix, parse_cpu_value, set_accessible_label, set_value_with_unit,
+    setup_number_validation, show_toast, PAGE_APPLY, PAGE_REFRESH, PAGE_SEARCH,
 };
 use adw::prelude::*;
 use gtk::glib;
@@ -52,10 +53,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     page.add(&header_group);
 
     // Status label for feedback
-    let status_label = gtk::Label::new(None);
-    status_label.set_margin_top(12);
-    status_label.set_margin_bottom(12);
-    status_label.set_wrap(true);
+    let status_label = create_status_label();
 
     // Limit mode selection
     let mode_group = adw::PreferencesGroup::new();
@@ -91,9 +89,8 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     search_group.set_title("Find Process");
 
     // Refresh button in header
-    let refresh_btn = gtk::Button::from_icon_name("view-refresh-symbolic");
-    refresh_btn.add_css_class("flat");
-    refresh_btn.set_tooltip_text(Some("Refresh process list"));
+    let refresh_btn = create_icon_button("view-refresh-symbolic", "Refresh process list");
+    refresh_btn.set_widget_name(PAGE_REFRESH);
     search_group.set_header_suffix(Some(&refresh_btn));
 
     // Mode info label
@@ -105,12 +102,15 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
 
     let search_entry = gtk::SearchEntry::new();
     search_entry.set_placeholder_text(Some("Type to search by name or PID..."));
+    set_accessible_label(&search_entry, "Search processes by name or PID");
+    search_entry.set_widget_name(PAGE_SEARCH);
     search_entry.set_margin_bottom(12);
     search_group.add(&search_entry);
 
     let process_list = gtk::ListBox::new();
     process_list.set_selection_mode(gtk::SelectionMode::Multiple); // Allow multi-select
     process_list.add_css_class("boxed-list");
+    set_accessible_label(&process_list, "Running processes");
 
     let scroll = gtk::ScrolledWindow::new();
     scroll.set_child(Some(&process_list));
@@ -131,6 +131,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     let profile_dropdown = gtk::DropDown::new(Some(profile_list), gtk::Expression::NONE);
     profile_dropdown.set_selected(0);
     profile_dropdown.set_valign(gtk::Align::Center);
+    set_accessible_label(&profile_dropdown, "Profile");
     profile_dropdown.set_widget_name("limit-profile-dropdown");
 
     let profile_row = adw::ActionRow::new();
@@ -150,7 +151,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     memory_entry.set_title("Memory");
     memory_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&memory_entry);
-    let memory_unit = create_unit_dropdown();
+    let memory_unit = create_unit_dropdown("Memory unit");
     memory_unit.set_selected(1); // Default to MB
     memory_entry.add_suffix(&memory_unit);
     limits_group.add(&memory_entry);
@@ -171,7 +172,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     io_read_entry.set_title("I/O Read");
     io_read_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_read_entry);
-    let io_read_unit = create_unit_dropdown();
+    let io_read_unit = create_unit_dropdown("I/O read unit");
     io_read_unit.set_selected(1); // Default to MB
     io_read_entry.add_suffix(&io_read_unit);
     limits_group.add(&io_read_entry);
@@ -181,7 +182,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     io_write_entry.set_title("I/O Write");
     io_write_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_write_entry);
-    let io_write_unit = create_unit_dropdown();
+    let io_write_unit = create_unit_dropdown("I/O write unit");
     io_write_unit.set_selected(1); // Default to MB
     io_write_entry.add_suffix(&io_write_unit);
     limits_group.add(&io_write_entry);
@@ -196,6 +197,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
 
     // Apply button
     let apply_btn = gtk::Button::with_label("Apply Limits");
+    apply_btn.set_widget_name(PAGE_APPLY);
     apply_btn.add_css_class("suggested-action");
     apply_btn.add_css_class("pill");
     apply_btn.set_halign(gtk::Align::Center);
@@ -565,10 +567,19 @@ fn save_app_rule(
         app_name,
         common::AppRule {
             match_exe: vec![app_name.to_string()],
+            conditions: Default::default(),
             memory,
+            swap: None,
+            oom_group: false,
             cpu,
+            cpu_weight: None,
+            idle: false,
             io_read,
             io_write,
+            io_path: None,
+            io_weight: None,
+            pids: None,
+            mems: None,
         },
     );
     config.save()
@@ -696,9 +707,8 @@ fn apply_limits(state: &Rc<RefCell<LimitState>>) {
                 format!("app-{}", app_name.replace(['/', ' '], "_"))
             };
 
-            match manager.apply_limit_to_multiple(&pids, &limit, &cgroup_name) {
+            match crate::privileged::apply_limit_to_multiple(manager, &pids, &limit, &cgroup_name) {
                 Ok(()) => {
-                    state.status_label.set_text("");
                     let mut msg = if pids.len() == 1 {
                         format!("Limits applied to PID {}", pids[0])
                     } else {
@@ -726,9 +736,7 @@ fn apply_limits(state: &Rc<RefCell<LimitState>>) {
                         }
                     }
 
-                    let toast = adw::Toast::new(&msg);
-                    toast.set_timeout(3);
-                    state.toast_overlay.add_toast(toast);
+                    show_toast(&state.toast_overlay, &state.status_label, &msg, 3);
                 }
                 Err(e) => show_status(&state.status_label, &format!("{e}"), true),
             }
@@ -752,13 +760,13 @@ fn apply_limits(state: &Rc<RefCell<LimitState>>) {
                 }
             };
 
-            match manager.apply_limit(pid, &limit) {
-                Ok(()) => {
-                    state.status_label.set_text("");
-                    let toast = adw::Toast::new(&format!("Limits applied to PID {pid}"));
-                    toast.set_timeout(3);
-                    state.toast_overlay.add_toast(toast);
-                }
+            match crate::privileged::apply_limit(manager, pid, &limit) {
+                Ok(()) => show_toast(
+                    &state.toast_overlay,
+                    &state.status_label,
+                    &format!("Limits applied to PID {pid}"),
+                    3,
+                ),
                 Err(e) => show_status(&state.status_label, &format!("{e}"), true),
             }
         }
@@ -788,17 +796,3 @@ pub fn refresh_profiles(widget: &gtk::Widget) {
         }
     }
 }
-
-fn find_widget_by_name(widget: &gtk::Widget, name: &str) -> Option<gtk::Widget> {
-    if widget.widget_name() == name {
-        return Some(widget.clone());
-    }
-    let mut child = widget.first_child();
-    while let Some(c) = child {
-        if let Some(found) = find_widget_by_name(&c, name) {
-            return Some(found);
-        }
-        child = c.next_sibling();
-    }
-    None
-}
@@ -1,4 +1,6 @@
-use crate::widgets::{create_unit_dropdown, get_unit_suffix, setup_number_validation};
+use crate::widgets::{
+    create_icon_button, create_unit_dropdown, get_unit_suffix, setup_number_validation,
+};
 use adw::prelude::*;
 use common::{Config, Profile};
 use std::cell::RefCell;
@@ -17,9 +19,7 @@ pub fn create() -> gtk::Widget {
     page.set_icon_name(Some("document-properties-symbolic"));
 
     // Add button header
-    let add_btn = gtk::Button::from_icon_name("list-add-symbolic");
-    add_btn.add_css_class("flat");
-    add_btn.set_tooltip_text(Some("Create new profile"));
+    let add_btn = create_icon_button("list-add-symbolic", "Create new profile");
 
     // Profiles group
     let profiles_group = adw::PreferencesGroup::new();
@@ -95,15 +95,36 @@ fn create_profile_row(name: &str, profile: &Profile) -> adw::ExpanderRow {
     if let Some(ref mem) = profile.memory {
         limits.push(format!("Mem: {mem}"));
     }
+    if let Some(ref swap) = profile.swap {
+        limits.push(format!("Swap: {swap}"));
+    }
+    if profile.oom_group {
+        limits.push("OOM group".to_string());
+    }
     if let Some(ref cpu) = profile.cpu {
         limits.push(format!("CPU: {cpu}"));
     }
+    if let Some(weight) = profile.cpu_weight {
+        limits.push(format!("CPU weight: {weight}"));
+    }
+    if profile.idle {
+        limits.push("Idle CPU only".to_string());
+    }
     if let Some(ref ior) = profile.io_read {
         limits.push(format!("IO↓: {ior}"));
     }
     if let Some(ref iow) = profile.io_write {
         limits.push(format!("IO↑: {iow}"));
     }
+    if let Some(weight) = profile.io_weight {
+        limits.push(format!("I/O weight: {weight}"));
+    }
+    if let Some(pids) = profile.pids {
+        limits.push(format!("PIDs: {pids}"));
+    }
+    if let Some(ref mems) = profile.mems {
+        limits.push(format!("NUMA: {mems}"));
+    }
     if limits.is_empty() {
         row.set_subtitle("No limits set");
     } else {
@@ -118,6 +139,20 @@ fn create_profile_row(name: &str, profile: &Profile) -> adw::ExpanderRow {
         row.add_row(&detail);
     }
 
+    if let Some(ref swap) = profile.swap {
+        let detail = adw::ActionRow::new();
+        detail.set_title("Swap Limit");
+        detail.set_subtitle(swap);
+        row.add_row(&detail);
+    }
+
+    if profile.oom_group {
+        let detail = adw::ActionRow::new();
+        detail.set_title("Out of Memory");
+        detail.set_subtitle("Kill all processes together");
+        row.add_row(&detail);
+    }
+
     if let Some(ref cpu) = profile.cpu {
         let detail = adw::ActionRow::new();
         detail.set_title("CPU Limit");
@@ -125,6 +160,20 @@ fn create_profile_row(name: &str, profile: &Profile) -> adw::ExpanderRow {
         row.add_row(&detail);
     }
 
+    if let Some(weight) = profile.cpu_weight {
+        let detail = adw::ActionRow::new();
+        detail.set_title("CPU Weight");
+        detail.set_subtitle(&weight.to_string());
+        row.add_row(&detail);
+    }
+
+    if profile.idle {
+        let detail = adw::ActionRow::new();
+        detail.set_title("CPU Scheduling");
+        detail.set_subtitle("Only when otherwise idle");
+        row.add_row(&detail);
+    }
+
     if let Some(ref ior) = profile.io_read {
         let detail = adw::ActionRow::new();
         detail.set_title("I/O Read Limit");
@@ -139,14 +188,40 @@ fn create_profile_row(name: &str, profile: &Profile) -> adw::ExpanderRow {
         row.add_row(&detail);
     }
 
+    if let Some(ref path) = profile.io_path {
+        let detail = adw::ActionRow::new();
+        detail.set_title("I/O Limited On");
+        detail.set_subtitle(&format!("Disk holding {path}"));
+        row.add_row(&detail);
+    }
+
+    if let Some(weight) = profile.io_weight {
+        let detail = adw::ActionRow::new();
+        detail.set_title("I/O Weight");
+        detail.set_subtitle(&weight.to_string());
+        row.add_row(&detail);
+    }
+
+    if let Some(pids) = profile.pids {
+        let detail = adw::ActionRow::new();
+        detail.set_title("Max Processes");
+        detail.set_subtitle(&pids.to_string());
+        row.add_row(&detail);
+    }
+
+    if let Some(ref mems) = profile.mems {
+        let detail = adw::ActionRow::new();
+        detail.set_title("NUMA Memory Nodes");
+        detail.set_subtitle(mems);
+        row.add_row(&detail);
+    }
+
     // Button box for edit and delete
     let btn_box = gtk::Box::new(gtk::Orientation::Horizontal, 4);
     btn_box.set_valign(gtk::Align::Center);
 
     // Edit button
-    let edit_btn = gtk::Button::from_icon_name("document-edit-symbolic");
-    edit_btn.add_css_class("flat");
-    edit_btn.set_tooltip_text(Some("Edit profile"));
+    let edit_btn = create_icon_button("document-edit-symbolic", &format!("Edit profile {name}"));
 
     let name_for_edit = name.to_string();
     let profile_for_edit = profile.clone();
@@ -161,10 +236,8 @@ fn create_profile_row(name: &str, profile: &Profile) -> adw::ExpanderRow {
     btn_box.append(&edit_btn);
 
     // Delete button
-    let delete_btn = gtk::Button::from_icon_name("user-trash-symbolic");
-    delete_btn.add_css_class("flat");
+    let delete_btn = create_icon_button("user-trash-symbolic", &format!("Delete profile {name}"));
     delete_btn.add_css_class("error");
-    delete_btn.set_tooltip_text(Some("Delete profile"));
 
     let name_clone = name.to_string();
     delete_btn.connect_clicked(move |btn| {
@@ -241,7 +314,7 @@ fn show_profile_dialog(parent: &adw::PreferencesPage, state: &Rc<RefCell<Profile
     memory_entry.set_title("Memory");
     memory_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&memory_entry);
-    let memory_unit = create_unit_dropdown();
+    let memory_unit = create_unit_dropdown("Memory unit");
     memory_unit.set_selected(1); // Default to MB
     memory_entry.add_suffix(&memory_unit);
     limits_group.add(&memory_entry);
@@ -262,7 +335,7 @@ fn show_profile_dialog(parent: &adw::PreferencesPage, state: &Rc<RefCell<Profile
     io_read_entry.set_title("I/O Read");
     io_read_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_read_entry);
-    let io_read_unit = create_unit_dropdown();
+    let io_read_unit = create_unit_dropdown("I/O read unit");
     io_read_unit.set_selected(1); // Default to MB
     io_read_entry.add_suffix(&io_read_unit);
     limits_group.add(&io_read_entry);
@@ -272,7 +345,7 @@ fn show_profile_dialog(parent: &adw::PreferencesPage, state: &Rc<RefCell<Profile
     io_write_entry.set_title("I/O Write");
     io_write_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_write_entry);
-    let io_write_unit = create_unit_dropdown();
+    let io_write_unit = create_unit_dropdown("I/O write unit");
     io_write_unit.set_selected(1); // Default to MB
     io_write_entry.add_suffix(&io_write_unit);
     limits_group.add(&io_write_entry);
@@ -353,6 +426,7 @@ fn show_profile_dialog(parent: &adw::PreferencesPage, state: &Rc<RefCell<Profile
             cpu,
             io_read,
             io_write,
+            ..Default::default()
         };
 
         // Check if profile exists and warn about overwrite
@@ -397,6 +471,8 @@ fn show_profile_dialog(parent: &adw::PreferencesPage, state: &Rc<RefCell<Profile
     });
 
     dialog.present();
+    // Start on the form rather than the header's Cancel button
+    name_entry.grab_focus();
 }
 
 fn delete_profile(name: &str, btn: &gtk::Button) {
@@ -533,7 +609,7 @@ fn show_edit_profile_dialog(parent: &adw::PreferencesPage, name: &str, profile:
     memory_entry.set_title("Memory");
     memory_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&memory_entry);
-    let memory_unit = create_unit_dropdown();
+    let memory_unit = create_unit_dropdown("Memory unit");
     let (mem_val, mem_unit_idx) = parse_limit(profile.memory.as_ref());
     memory_entry.set_text(&mem_val);
     memory_unit.set_selected(mem_unit_idx);
@@ -558,7 +634,7 @@ fn show_edit_profile_dialog(parent: &adw::PreferencesPage, name: &str, profile:
     io_read_entry.set_title("I/O Read");
     io_read_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_read_entry);
-    let io_read_unit = create_unit_dropdown();
+    let io_read_unit = create_unit_dropdown("I/O read unit");
     let (ior_val, ior_unit_idx) = parse_limit(profile.io_read.as_ref());
     io_read_entry.set_text(&ior_val);
     io_read_unit.set_selected(ior_unit_idx);
@@ -570,7 +646,7 @@ fn show_edit_profile_dialog(parent: &adw::PreferencesPage, name: &str, profile:
     io_write_entry.set_title("I/O Write");
     io_write_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_write_entry);
-    let io_write_unit = create_unit_dropdown();
+    let io_write_unit = create_unit_dropdown("I/O write unit");
     let (iow_val, iow_unit_idx) = parse_limit(profile.io_write.as_ref());
     io_write_entry.set_text(&iow_val);
     io_write_unit.set_selected(iow_unit_idx);
@@ -595,9 +671,11 @@ fn show_edit_profile_dialog(parent: &adw::PreferencesPage, name: &str, profile:
     let dialog_clone = dialog.clone();
     let name_clone = name.to_string();
     let parent_clone = parent.clone();
+    let original = profile.clone();
+    let memory_entry_clone = memory_entry.clone();
     save_btn.connect_clicked(move |_| {
         // Build limit values with units
-        let memory_val = memory_entry.text();
+        let memory_val = memory_entry_clone.text();
         let cpu_val = cpu_entry.text();
         let io_read_val = io_read_entry.text();
         let io_write_val = io_write_entry.text();
@@ -627,12 +705,13 @@ fn show_edit_profile_dialog(parent: &adw::PreferencesPage, name: &str, profile:
             ))
         };
 
+        // Keep settings this dialog doesn't edit (e.g. swap, CPU weight, pids)
         let profile = Profile {
-            match_exe: Vec::new(),
             memory,
             cpu,
             io_read,
             io_write,
+            ..original.clone()
         };
 
         // Save directly (no overwrite warning - we're editing existing)
@@ -670,6 +749,8 @@ fn show_edit_profile_dialog(parent: &adw::PreferencesPage, name: &str, profile:
     });
 
     dialog.present();
+    // The name is fixed, so start on the first limit
+    memory_entry.grab_focus();
 }
 
 fn save_profile_to_config(name: &str, profile: Profile, state: &Rc<RefCell<ProfilesState>>) {
@@ -1,6 +1,7 @@
 use crate::widgets::{
-    create_unit_dropdown, get_unit_suffix, parse_cpu_value, set_value_with_unit,
-    setup_number_validation,
+    create_icon_button, create_status_label, create_unit_dropdown, find_widget_by_name,
+    get_unit_suffix, parse_cpu_value, set_accessible_label, set_value_with_unit,
+    setup_number_validation, show_toast, PAGE_APPLY, PAGE_REFRESH, PAGE_SEARCH,
 };
 use adw::prelude::*;
 use gtk::glib;
@@ -48,10 +49,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     page.add(&header_group);
 
     // Status label
-    let status_label = gtk::Label::new(None);
-    status_label.set_margin_top(12);
-    status_label.set_margin_bottom(12);
-    status_label.set_wrap(true);
+    let status_label = create_status_label();
 
     // Command group
     let command_group = adw::PreferencesGroup::new();
@@ -69,14 +67,15 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     apps_group.set_title("Applications");
 
     // Refresh button in header
-    let refresh_btn = gtk::Button::from_icon_name("view-refresh-symbolic");
-    refresh_btn.add_css_class("flat");
-    refresh_btn.set_tooltip_text(Some("Refresh application list"));
+    let refresh_btn = create_icon_button("view-refresh-symbolic", "Refresh application list");
+    refresh_btn.set_widget_name(PAGE_REFRESH);
     apps_group.set_header_suffix(Some(&refresh_btn));
 
     // Search entry
     let search_entry = gtk::SearchEntry::new();
     search_entry.set_placeholder_text(Some("Type to search applications..."));
+    set_accessible_label(&search_entry, "Search applications");
+    search_entry.set_widget_name(PAGE_SEARCH);
     search_entry.set_margin_bottom(12);
     apps_group.add(&search_entry);
 
@@ -84,6 +83,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     let app_list = gtk::ListBox::new();
     app_list.set_selection_mode(gtk::SelectionMode::None);
     app_list.add_css_class("boxed-list");
+    set_accessible_label(&app_list, "Applications");
 
     let scroll = gtk::ScrolledWindow::new();
     scroll.set_child(Some(&app_list));
@@ -104,6 +104,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     let profile_dropdown = gtk::DropDown::new(Some(profile_list), gtk::Expression::NONE);
     profile_dropdown.set_selected(0);
     profile_dropdown.set_valign(gtk::Align::Center);
+    set_accessible_label(&profile_dropdown, "Profile");
     profile_dropdown.set_widget_name("run-profile-dropdown");
 
     let profile_row = adw::ActionRow::new();
@@ -123,7 +124,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     memory_entry.set_title("Memory Limit");
     memory_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&memory_entry);
-    let memory_unit = create_unit_dropdown();
+    let memory_unit = create_unit_dropdown("Memory unit");
     memory_unit.set_selected(1); // Default to MB
     memory_entry.add_suffix(&memory_unit);
     limits_group.add(&memory_entry);
@@ -144,7 +145,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     io_read_entry.set_title("I/O Read Limit");
     io_read_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_read_entry);
-    let io_read_unit = create_unit_dropdown();
+    let io_read_unit = create_unit_dropdown("I/O read unit");
     io_read_unit.set_selected(1); // Default to MB
     io_read_entry.add_suffix(&io_read_unit);
     limits_group.add(&io_read_entry);
@@ -154,7 +155,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     io_write_entry.set_title("I/O Write Limit");
     io_write_entry.set_input_purpose(gtk::InputPurpose::Digits);
     setup_number_validation(&io_write_entry);
-    let io_write_unit = create_unit_dropdown();
+    let io_write_unit = create_unit_dropdown("I/O write unit");
     io_write_unit.set_selected(1); // Default to MB
     io_write_entry.add_suffix(&io_write_unit);
     limits_group.add(&io_write_entry);
@@ -163,6 +164,7 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
 
     // Run button
     let run_btn = gtk::Button::with_label("Run Command");
+    run_btn.set_widget_name(PAGE_APPLY);
     run_btn.add_css_class("suggested-action");
     run_btn.add_css_class("pill");
     run_btn.set_halign(gtk::Align::Center);
@@ -432,7 +434,7 @@ fn run_command(state: &Rc<RefCell<RunState>>) {
     let count = RUN_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
     let cgroup_name = format!("gtk-{}-{}", std::process::id(), count);
 
-    let cgroup_path = match manager.prepare_cgroup(&cgroup_name, &limit) {
+    let cgroup_path = match crate::privileged::prepare_cgroup(manager, &cgroup_name, &limit) {
         Ok(p) => p,
         Err(e) => {
             show_status(
@@ -445,14 +447,18 @@ fn run_command(state: &Rc<RefCell<RunState>>) {
     };
 
     // Place the child into the cgroup before it execs, so limits apply from its
-    // first instruction (see CgroupManager::placement_command). add_to_cgroup
-    // below remains as a fallback.
-    let mut cmd = manager.placement_command(&cgroup_path, program);
+    // first instruction (see CgroupManager::placement_command). When the cgroup
+    // is root's and only the privileged helper can write it, the child is
+    // moved in by the helper right after it starts instead.
+    let (mut cmd, placed) = match manager.placement_command(&cgroup_path, program) {
+        Ok(cmd) => (cmd, true),
+        Err(_) => (std::process::Command::new(program), false),
+    };
     cmd.args(args);
     let child = match cmd.spawn() {
         Ok(c) => c,
         Err(e) => {
-            let _ = manager.cleanup_cgroup(&cgroup_name);
+            let _ = crate::privileged::cleanup_cgroup(manager, &cgroup_name);
             show_status(&state.status_label, &format!("Error spawning: {e}"), true);
             return;
         }
@@ -460,8 +466,13 @@ fn run_command(state: &Rc<RefCell<RunState>>) {
 
     let pid = child.id();
 
-    if let Err(e) = manager.add_to_cgroup(&cgroup_path, pid) {
-        let _ = manager.cleanup_cgroup(&cgroup_name);
+    let added = if placed {
+        Ok(())
+    } else {
+        crate::privileged::add_to_cgroup(manager, &cgroup_name, pid)
+    };
+    if let Err(e) = added {
+        let _ = crate::privileged::cleanup_cgroup(manager, &cgroup_name);
         show_status(
             &state.status_label,
             &format!("Error adding to cgroup: {e}"),
@@ -474,21 +485,27 @@ fn run_command(state: &Rc<RefCell<RunState>>) {
     *state.cgroup_name.borrow_mut() = Some(cgroup_name.clone());
 
     // Show success toast
-    state.status_label.set_text("");
-    let toast = adw::Toast::new(&format!("Started {} (PID {})", program, pid));
-    toast.set_timeout(3);
-    state.toast_overlay.add_toast(toast);
+    show_toast(
+        &state.toast_overlay,
+        &state.status_label,
+        &format!("Started {} (PID {})", program, pid),
+        3,
+    );
 
     // Monitor process exit
     let manager_clone = manager.clone();
     let toast_overlay = state.toast_overlay.clone();
+    let status_label = state.status_label.clone();
     glib::timeout_add_local(std::time::Duration::from_millis(500), move || {
         let proc_path = format!("/proc/{pid}");
         if !std::path::Path::new(&proc_path).exists() {
-            let _ = manager_clone.cleanup_cgroup(&cgroup_name);
-            let toast = adw::Toast::new(&format!("Process {} exited", pid));
-            toast.set_timeout(2);
-            toast_overlay.add_toast(toast);
+            let _ = crate::privileged::cleanup_cgroup(&manager_clone, &cgroup_name);
+            show_toast(
+                &toast_overlay,
+                &status_label,
+                &format!("Process {} exited", pid),
+                2,
+            );
             return glib::ControlFlow::Break;
         }
         glib::ControlFlow::Continue
@@ -533,17 +550,3 @@ pub fn refresh_profiles(widget: &gtk::Widget) {
         }
     }
 }
-
-fn find_widget_by_name(widget: &gtk::Widget, name: &str) -> Option<gtk::Widget> {
-    if widget.widget_name() == name {
-        return Some(widget.clone());
-    }
-    let mut child = widget.first_child();
-    while let Some(c) = child {
-        if let Some(found) = find_widget_by_name(&c, name) {
-            return Some(found);
-        }
-        child = c.next_sibling();
-    }
-    None
-}
@@ -1,29 +1,45 @@
+use crate::widgets::{create_icon_button, find_widget_by_name, set_accessible_label, PAGE_REFRESH};
 use adw::prelude::*;
 use common::format_bytes;
 use gtk::glib;
+use rlm_core::status::UsageSampler;
 use rlm_core::CgroupManager;
+use std::cell::RefCell;
 use std::sync::Arc;
 
+thread_local! {
+    // Previous usage readings, so refreshes can show CPU and I/O rates
+    static SAMPLER: RefCell<UsageSampler> = RefCell::new(UsageSampler::new());
+}
+
 pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     let page = adw::PreferencesPage::new();
     page.set_title("Status");
     page.set_icon_name(Some("view-list-symbolic"));
 
+    // Totals over all managed cgroups, filled in by each refresh
+    let totals_group = adw::PreferencesGroup::new();
+    let totals_row = adw::ActionRow::new();
+    totals_row.set_widget_name("status-totals-row");
+    totals_row.set_title("No managed processes");
+    totals_group.add(&totals_row);
+    page.add(&totals_group);
+
     // Process list group
     let group = adw::PreferencesGroup::new();
     group.set_title("Managed Processes");
     group.set_description(Some("Processes with active resource limits"));
 
     // Refresh button in header
-    let refresh_btn = gtk::Button::from_icon_name("view-refresh-symbolic");
-    refresh_btn.add_css_class("flat");
-    refresh_btn.set_tooltip_text(Some("Refresh process list"));
+    let refresh_btn = create_icon_button("view-refresh-symbolic", "Refresh process list");
+    refresh_btn.set_widget_name(PAGE_REFRESH);
     group.set_header_suffix(Some(&refresh_btn));
 
     let list_box = gtk::ListBox::new();
     list_box.set_selection_mode(gtk::SelectionMode::None);
     list_box.add_css_class("boxed-list");
     list_box.set_widget_name("status-list-box");
+    set_accessible_label(&list_box, "Managed processes");
 
     // Empty state
     let empty_row = adw::ActionRow::new();
@@ -34,6 +50,76 @@ pub fn create(manager: Option<Arc<CgroupManager>>) -> gtk::Widget {
     group.add(&list_box);
     page.add(&group);
 
+    // Desktop protection toggle (same as `rlm protect on|off`)
+    let protect_group = adw::PreferencesGroup::new();
+    protect_group.set_title("Desktop Protection");
+    let protect_row = adw::SwitchRow::builder()
+        .title("Keep Desktop Responsive")
+        .subtitle(
+            "Run apps under conservative memory and CPU limits, except the compositor and shell",
+        )
+        .build();
+    protect_group.add(&protect_row);
+    // Session headroom (same as `rlm protect-session on|off`)
+    let session_row = adw::SwitchRow::builder()
+        .title("Reserve Memory for the Session")
+        .subtitle(
+            "Keep 1 GiB free for the compositor and shell; managed apps are capped to leave it",
+        )
+        .build();
+    protect_group.add(&session_row);
+    page.add(&protect_group);
+
+    match manager {
+        Some(ref mgr) => {
+            protect_row.set_active(mgr.protect_enabled());
+            let mgr_clone = mgr.clone();
+            protect_row.connect_active_notify(move |row| {
+                let enable = row.is_active();
+                // Also stops the revert below from re-triggering the toggle
+                if enable == mgr_clone.protect_enabled() {
+                    return;
+                }
+                let result = if enable {
+                    rlm_core::protect::enable(&mgr_clone).map(|_| ())
+                } else {
+                    rlm_core::protect::disable(&mgr_clone)
+                };
+                if let Err(e) = result {
+                    tracing::error!("Failed to toggle desktop protection: {e}");
+                    row.set_subtitle(&format!("Failed: {e}"));
+                    row.set_active(!enable);
+                }
+            });
+        }
+        None => protect_row.set_sensitive(false),
+    }
+
+    match manager {
+        Some(ref mgr) => {
+            session_row.set_active(rlm_core::session::current().is_some());
+            let mgr_clone = mgr.clone();
+            session_row.connect_active_notify(move |row| {
+                let enable = row.is_active();
+                if enable == rlm_core::session::current().is_some() {
+                    return;
+                }
+                let result = if enable {
+                    rlm_core::session::enable(&mgr_clone, rlm_core::session::DEFAULT_HEADROOM)
+                        .map(|_| ())
+                } else {
+                    rlm_core::session::disable(&mgr_clone).map(|_| ())
+                };
+                if let Err(e) = result {
+                    tracing::error!("Failed to toggle session protection: {e}");
+                    row.set_subtitle(&format!("Failed: {e}"));
+                    row.set_active(!enable);
+                }
+            });
+        }
+        None => session_row.set_sensitive(false),
+    }
+
     // Initial refresh
     if let Some(ref mgr) = manager {
         do_refresh(&list_box, mgr.clone());
@@ -58,20 +144,6 @@ pub fn refresh(widget: &gtk::Widget, manager: Arc<CgroupManager>) {
     }
 }
 
-fn find_widget_by_name(widget: &gtk::Widget, name: &str) -> Option<gtk::Widget> {
-    if widget.widget_name() == name {
-        return Some(widget.clone());
-    }
-    let mut child = widget.first_child();
-    while let Some(c) = child {
-        if let Some(found) = find_widget_by_name(&c, name) {
-            return Some(found);
-        }
-        child = c.next_sibling();
-    }
-    None
-}
-
 fn do_refresh(list_box: &gtk::ListBox, manager: Arc<CgroupManager>) {
     // Clear existing rows
     while let Some(child) = list_box.first_child() {
@@ -80,7 +152,9 @@ fn do_refresh(list_box: &gtk::ListBox, manager: Arc<CgroupManager>) {
 
     // Get managed processes
     match rlm_core::status::get_managed_processes(&manager) {
-        Ok(processes) => {
+        Ok(mut processes) => {
+            SAMPLER.with(|s| s.borrow_mut().update(&mut processes));
+            update_totals(list_box, &processes);
             if processes.is_empty() {
                 let empty_row = adw::ActionRow::new();
                 empty_row.set_title("No managed processes");
@@ -103,11 +177,47 @@ fn do_refresh(list_box: &gtk::ListBox, manager: Arc<CgroupManager>) {
     }
 }
 
+/// Fill in the totals row above the list.
+fn update_totals(list_box: &gtk::ListBox, processes: &[rlm_core::status::ProcessStatus]) {
+    let Some(page) = list_box.ancestor(adw::PreferencesPage::static_type()) else {
+        return;
+    };
+    let Some(row) = find_widget_by_name(&page, "status-totals-row")
+        .and_then(|w| w.downcast::<adw::ActionRow>().ok())
+    else {
+        return;
+    };
+
+    let t = rlm_core::status::totals(processes);
+    if t.cgroups == 0 {
+        row.set_title("No managed processes");
+        row.set_subtitle("");
+        return;
+    }
+    row.set_title(&format!(
+        "{} processes in {} cgroups",
+        t.processes, t.cgroups
+    ));
+    let machine = t
+        .machine_memory
+        .map_or(String::new(), |m| format!(" of {}", format_bytes(m)));
+    row.set_subtitle(&format!(
+        "Memory: {} used, {} limited{} | CPU quota: {}% of {}%",
+        format_bytes(t.memory_used),
+        format_bytes(t.memory_limit),
+        machine,
+        t.cpu_quota,
+        t.machine_cpus * 100
+    ));
+}
+
 fn create_process_row(
     proc: &rlm_core::status::ProcessStatus,
     manager: Arc<CgroupManager>,
     list_box: &gtk::ListBox,
-) -> adw::ActionRow {
+) -> gtk::Widget {
+    // Shared cgroups expand to list every process in them
+    let expander = (proc.is_shared && proc.members.len() > 1).then(adw::ExpanderRow::new);
     let row = adw::ActionRow::new();
 
     // Build title with process count for shared cgroups
@@ -137,11 +247,46 @@ fn create_process_row(
 
     // Build subtitle with limits
     let mut limits = Vec::new();
-    if let Some(mem) = proc.memory_max {
-        limits.push(format!("Memory: {}", format_bytes(mem)));
+    match (proc.usage.memory_current, proc.memory_max) {
+        (Some(cur), Some(max)) => limits.push(format!(
+            "Memory: {} of {}",
+            format_bytes(cur),
+            format_bytes(max)
+        )),
+        (None, Some(max)) => limits.push(format!("Memory: {}", format_bytes(max))),
+        (Some(cur), None) => limits.push(format!("Memory used: {}", format_bytes(cur))),
+        (None, None) => {}
+    }
+    if let Some(peak) = proc.memory_peak {
+        limits.push(format!("Peak: {}", format_bytes(peak)));
+    }
+    match proc.swap_max {
+        Some(0) => limits.push("Swap: off".to_string()),
+        Some(swap) => limits.push(format!("Swap: {}", format_bytes(swap))),
+        None => {}
+    }
+    if proc.oom_group {
+        limits.push("OOM: whole group".to_string());
     }
-    if let Some(cpu) = proc.cpu_quota {
-        limits.push(format!("CPU: {}%", cpu));
+    let oom_kills = proc.memory_events.get(rlm_core::events::EventKind::OomKill);
+    if oom_kills > 0 {
+        limits.push(format!("OOM kills: {oom_kills}"));
+    }
+    if let Some(low) = proc.memory_low {
+        limits.push(format!("Reserved: {}", format_bytes(low)));
+    }
+    let cpu_used = proc.rates.and_then(|r| r.cpu_percent);
+    match (cpu_used, proc.cpu_quota) {
+        (Some(used), Some(cpu)) => limits.push(format!("CPU: {used:.0}% of {cpu}%")),
+        (None, Some(cpu)) => limits.push(format!("CPU: {}%", cpu)),
+        (Some(used), None) => limits.push(format!("CPU used: {used:.0}%")),
+        (None, None) => {}
+    }
+    if let Some(weight) = proc.cpu_weight {
+        limits.push(format!("CPU weight: {weight}"));
+    }
+    if proc.cpu_idle {
+        limits.push("CPU: idle only".to_string());
     }
     if let Some(r) = proc.io_read_bps {
         limits.push(format!("I/O Read: {}/s", format_bytes(r)));
@@ -149,6 +294,22 @@ fn create_process_row(
     if let Some(w) = proc.io_write_bps {
         limits.push(format!("I/O Write: {}/s", format_bytes(w)));
     }
+    if let Some(r) = proc.rates {
+        limits.push(format!(
+            "I/O now: {}/s read, {}/s write",
+            format_bytes(r.io_read_bps),
+            format_bytes(r.io_write_bps)
+        ));
+    }
+    if let Some(weight) = proc.io_weight {
+        limits.push(format!("I/O weight: {weight}"));
+    }
+    if let Some(n) = proc.pids_max {
+        limits.push(format!("Max PIDs: {n}"));
+    }
+    if let Some(ref mems) = proc.mems {
+        limits.push(format!("NUMA nodes: {mems}"));
+    }
 
     let mut subtitle = if limits.is_empty() {
         "No limits set".to_string()
@@ -164,23 +325,37 @@ fn create_process_row(
     row.set_subtitle(&subtitle);
 
     // Remove button
-    let remove_btn = gtk::Button::from_icon_name("user-trash-symbolic");
+    let remove_btn = create_icon_button(
+        "user-trash-symbolic",
+        &format!("Remove limits from {} (PID {})", proc.name, proc.pid),
+    );
     remove_btn.set_valign(gtk::Align::Center);
-    remove_btn.add_css_class("flat");
-    remove_btn.set_tooltip_text(Some("Remove limits"));
 
     let cgroup_name = proc.cgroup_name.clone();
     let list_box_clone = list_box.clone();
     let manager_clone = manager.clone();
     remove_btn.connect_clicked(move |_| {
-        if let Err(e) = manager_clone.cleanup_cgroup(&cgroup_name) {
+        if let Err(e) = crate::privileged::cleanup_cgroup(&manager_clone, &cgroup_name) {
             tracing::error!("Failed to remove limit: {e}");
         } else {
             do_refresh(&list_box_clone, manager_clone.clone());
         }
     });
 
-    row.add_suffix(&remove_btn);
-    row.set_activatable(false);
-    row
+    let Some(expander) = expander else {
+        row.add_suffix(&remove_btn);
+        row.set_activatable(false);
+        return row.upcast();
+    };
+    expander.set_title(&row.title());
+    expander.set_subtitle(&row.subtitle().unwrap_or_default());
+    expander.add_suffix(&remove_btn);
+    for &pid in &proc.members {
+        let member = adw::ActionRow::new();
+        let name = rlm_core::status::process_name(pid).unwrap_or_else(|| "?".into());
+        member.set_title(&glib::markup_escape_text(&name));
+        member.set_subtitle(&format!("PID {pid}"));
+        expander.add_row(&member);
+    }
+    expander.upcast()
 }
@@ -0,0 +1,79 @@
+//! Cgroup writes for the pages. With delegation they are made directly;
+//! without it, through the privileged helper (see [`rlm_core::helper`]),
+//! started on first use, which asks the user to authenticate once.
+
+use common::{Limit, Result};
+use rlm_core::helper::Helper;
+use rlm_core::CgroupManager;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    static HELPER: RefCell<Option<Helper>> = const { RefCell::new(None) };
+}
+
+/// Whether the helper is installed, for when there is no delegation.
+pub fn helper_available() -> bool {
+    Path::new(rlm_core::helper::HELPER_PATH).exists()
+}
+
+fn with_helper<T>(f: impl FnOnce(&mut Helper) -> Result<T>) -> Result<T> {
+    HELPER.with_borrow_mut(|slot| {
+        let helper = match slot {
+            Some(helper) => helper,
+            None => slot.insert(Helper::start()?),
+        };
+        let result = f(helper);
+        if result.is_err() {
+            // Start afresh next time if it died
+            *slot = None;
+        }
+        result
+    })
+}
+
+pub fn apply_limit(manager: &CgroupManager, pid: u32, limit: &Limit) -> Result<()> {
+    if manager.has_delegation() {
+        manager.apply_limit(pid, limit)
+    } else {
+        with_helper(|h| h.apply_limit(pid, limit))
+    }
+}
+
+pub fn apply_limit_to_multiple(
+    manager: &CgroupManager,
+    pids: &[u32],
+    limit: &Limit,
+    cgroup: &str,
+) -> Result<()> {
+    if manager.has_delegation() {
+        manager.apply_limit_to_multiple(pids, limit, cgroup)
+    } else {
+        with_helper(|h| h.apply_limit_to_multiple(pids, limit, cgroup))
+    }
+}
+
+pub fn prepare_cgroup(manager: &CgroupManager, cgroup: &str, limit: &Limit) -> Result<PathBuf> {
+    if manager.has_delegation() {
+        manager.prepare_cgroup(cgroup, limit)
+    } else {
+        with_helper(|h| h.prepare_cgroup(cgroup, limit))?;
+        Ok(manager.base_path().join(cgroup))
+    }
+}
+
+pub fn add_to_cgroup(manager: &CgroupManager, cgroup: &str, pid: u32) -> Result<()> {
+    if manager.has_delegation() {
+        manager.add_to_cgroup(&manager.base_path().join(cgroup), pid)
+    } else {
+        with_helper(|h| h.add_to_cgroup(cgroup, pid))
+    }
+}
+
+pub fn cleanup_cgroup(manager: &CgroupManager, cgroup: &str) -> Result<()> {
+    if manager.has_delegation() {
+        manager.cleanup_cgroup(cgroup)
+    } else {
+        with_helper(|h| h.remove_cgroup(cgroup))
+    }
+}
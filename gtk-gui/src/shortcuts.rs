@@ -0,0 +1,157 @@
+// Keyboard shortcuts: user-configurable accelerators and the Ctrl+? overview
+
+use adw::prelude::*;
+use gtk::{gio, glib};
+use std::fmt::Write;
+
+/// A configurable shortcut. `key` is the GSettings key holding its
+/// accelerators; `defaults` mirror the schema defaults and are used when the
+/// schema isn't installed (e.g. running from a source checkout).
+struct Binding {
+    key: &'static str,
+    action: &'static str,
+    title: &'static str,
+    group: &'static str,
+    defaults: &'static [&'static str],
+}
+
+const BINDINGS: &[Binding] = &[
+    Binding {
+        key: "show-help-overlay",
+        action: "win.show-help-overlay",
+        title: "Keyboard Shortcuts",
+        group: "General",
+        defaults: &["<Control>question"],
+    },
+    Binding {
+        key: "quit",
+        action: "win.quit",
+        title: "Quit",
+        group: "General",
+        defaults: &["<Control>q"],
+    },
+    Binding {
+        key: "goto-status",
+        action: "win.goto-status",
+        title: "Managed Processes",
+        group: "Navigation",
+        defaults: &["<Control>1"],
+    },
+    Binding {
+        key: "goto-limit",
+        action: "win.goto-limit",
+        title: "Limit Running",
+        group: "Navigation",
+        defaults: &["<Control>2"],
+    },
+    Binding {
+        key: "goto-run",
+        action: "win.goto-run",
+        title: "Launch New",
+        group: "Navigation",
+        defaults: &["<Control>3"],
+    },
+    Binding {
+        key: "goto-profiles",
+        action: "win.goto-profiles",
+        title: "Profiles",
+        group: "Navigation",
+        defaults: &["<Control>4"],
+    },
+    Binding {
+        key: "refresh",
+        action: "win.refresh",
+        title: "Refresh",
+        group: "Current Page",
+        defaults: &["<Control>r", "F5"],
+    },
+    Binding {
+        key: "focus-search",
+        action: "win.focus-search",
+        title: "Search",
+        group: "Current Page",
+        defaults: &["<Control>f"],
+    },
+    Binding {
+        key: "apply",
+        action: "win.apply",
+        title: "Apply Limits / Run Command",
+        group: "Current Page",
+        defaults: &["<Control>Return"],
+    },
+];
+
+/// The app's settings, or `None` if the schema isn't installed.
+/// `gio::Settings::new` aborts on a missing schema, so look it up first.
+pub fn settings() -> Option<gio::Settings> {
+    let source = gio::SettingsSchemaSource::default()?;
+    source.lookup(crate::APP_ID, true)?;
+    Some(gio::Settings::new(crate::APP_ID))
+}
+
+/// Accelerators for a binding: from settings when the key exists (an older
+/// installed schema may predate it), otherwise the built-in defaults.
+fn accels(settings: Option<&gio::Settings>, binding: &Binding) -> Vec<String> {
+    match settings {
+        Some(s)
+            if s.settings_schema()
+                .is_some_and(|sc| sc.has_key(binding.key)) =>
+        {
+            s.strv(binding.key).iter().map(|a| a.to_string()).collect()
+        }
+        _ => binding.defaults.iter().map(|a| a.to_string()).collect(),
+    }
+}
+
+/// Set every binding's accelerators on the application. Invalid accelerator
+/// strings from settings are dropped with a warning rather than applied.
+pub fn apply_accels(app: &adw::Application, settings: Option<&gio::Settings>) {
+    for binding in BINDINGS {
+        let accels: Vec<String> = accels(settings, binding)
+            .into_iter()
+            .filter(|a| {
+                let valid = gtk::accelerator_parse(a).is_some();
+                if !valid {
+                    tracing::warn!(key = binding.key, accel = %a, "ignoring invalid accelerator");
+                }
+                valid
+            })
+            .collect();
+        let refs: Vec<&str> = accels.iter().map(String::as_str).collect();
+        app.set_accels_for_action(binding.action, &refs);
+    }
+}
+
+/// Build the shortcuts overview from the current bindings. `GtkShortcutsWindow`
+/// can only be populated from UI XML on GTK < 4.14, hence the generated markup.
+pub fn build_overlay(settings: Option<&gio::Settings>) -> gtk::ShortcutsWindow {
+    let mut ui = String::from(
+        r#"<interface><object class="GtkShortcutsWindow" id="help_overlay"><property name="modal">1</property><child><object class="GtkShortcutsSection"><property name="section-name">shortcuts</property>"#,
+    );
+
+    let mut current_group = None;
+    for binding in BINDINGS {
+        if current_group != Some(binding.group) {
+            if current_group.is_some() {
+                ui.push_str("</object></child>");
+            }
+            let _ = write!(
+                ui,
+                r#"<child><object class="GtkShortcutsGroup"><property name="title">{}</property>"#,
+                glib::markup_escape_text(binding.group)
+            );
+            current_group = Some(binding.group);
+        }
+        let _ = write!(
+            ui,
+            r#"<child><object class="GtkShortcutsShortcut"><property name="title">{}</property><property name="accelerator">{}</property></object></child>"#,
+            glib::markup_escape_text(binding.title),
+            glib::markup_escape_text(&accels(settings, binding).join(" "))
+        );
+    }
+    ui.push_str("</object></child></object></child></object></interface>");
+
+    gtk::Builder::from_string(&ui)
+        .object("help_overlay")
+        .expect("generated shortcuts UI defines help_overlay")
+}
@@ -25,14 +25,80 @@ pub fn setup_number_validation(entry: &adw::EntryRow) {
     });
 }
 
-/// Create a unit dropdown (KB/MB/GB/TB)
-pub fn create_unit_dropdown() -> gtk::DropDown {
+/// Create a unit dropdown (KB/MB/GB/TB). `label` names it for screen readers,
+/// since the dropdown itself only shows the selected unit.
+pub fn create_unit_dropdown(label: &str) -> gtk::DropDown {
     let units = gtk::StringList::new(UNITS);
     let dropdown = gtk::DropDown::new(Some(units), gtk::Expression::NONE);
     dropdown.set_valign(gtk::Align::Center);
+    set_accessible_label(&dropdown, label);
     dropdown
 }
 
+/// Create a flat icon-only button. `label` is used as both the tooltip and the
+/// accessible label, otherwise a screen reader has nothing to read for it.
+pub fn create_icon_button(icon_name: &str, label: &str) -> gtk::Button {
+    let button = gtk::Button::from_icon_name(icon_name);
+    button.add_css_class("flat");
+    button.set_tooltip_text(Some(label));
+    set_accessible_label(&button, label);
+    button
+}
+
+/// Set the accessible label of a widget that has no visible text of its own
+pub fn set_accessible_label(widget: &impl IsA<gtk::Accessible>, label: &str) {
+    widget.update_property(&[gtk::accessible::Property::Label(label)]);
+}
+
+/// Widget names the window's refresh/search/apply shortcuts look up on the
+/// visible page. A page that lacks one simply ignores that shortcut.
+pub const PAGE_REFRESH: &str = "page-refresh";
+pub const PAGE_SEARCH: &str = "page-search";
+pub const PAGE_APPLY: &str = "page-apply";
+
+/// Find a descendant of `widget` (or `widget` itself) by widget name
+pub fn find_widget_by_name(widget: &gtk::Widget, name: &str) -> Option<gtk::Widget> {
+    if widget.widget_name() == name {
+        return Some(widget.clone());
+    }
+    let mut child = widget.first_child();
+    while let Some(c) = child {
+        if let Some(found) = find_widget_by_name(&c, name) {
+            return Some(found);
+        }
+        child = c.next_sibling();
+    }
+    None
+}
+
+/// Create the feedback label shown above a page's action button. It carries the
+/// `Status` role so screen readers announce its text whenever it changes.
+pub fn create_status_label() -> gtk::Label {
+    gtk::Label::builder()
+        .accessible_role(gtk::AccessibleRole::Status)
+        .wrap(true)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build()
+}
+
+/// Show a toast and mirror its message into the page's status label. Toasts
+/// are transient overlays that screen readers don't reliably announce, so the
+/// status label is what makes the feedback reach Orca.
+pub fn show_toast(
+    overlay: &adw::ToastOverlay,
+    status_label: &gtk::Label,
+    message: &str,
+    timeout: u32,
+) {
+    status_label.set_text(message);
+    status_label.remove_css_class("error");
+    status_label.add_css_class("success");
+    let toast = adw::Toast::new(message);
+    toast.set_timeout(timeout);
+    overlay.add_toast(toast);
+}
+
 /// Get the unit suffix for cgroup (K, M, G, T)
 pub fn get_unit_suffix(dropdown: &gtk::DropDown) -> &'static str {
     let idx = dropdown.selected() as usize;
@@ -1,4 +1,6 @@
 use crate::pages;
+use crate::shortcuts;
+use crate::widgets::{find_widget_by_name, PAGE_APPLY, PAGE_REFRESH, PAGE_SEARCH};
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gtk::{gio, glib};
@@ -12,6 +14,8 @@ mod imp {
     #[derive(Default)]
     pub struct Window {
         pub manager: RefCell<Option<Arc<CgroupManager>>>,
+        // Held so the keybinding change handler stays connected
+        pub settings: RefCell<Option<gio::Settings>>,
     }
 
     #[glib::object_subclass]
@@ -57,13 +61,24 @@ impl Window {
             window.close();
         });
         self.add_action(&quit_action);
-        app.set_accels_for_action("win.quit", &["<Control>q"]);
 
-        // Page navigation shortcuts (Ctrl+1 through Ctrl+5)
-        for (i, page) in ["status", "limit", "run", "profiles", "about"]
-            .iter()
-            .enumerate()
-        {
+        // About window and debug info (primary menu)
+        let about_action = gio::SimpleAction::new("about", None);
+        let window = self.clone();
+        about_action.connect_activate(move |_, _| {
+            pages::about::present(&window, window.manager().as_deref());
+        });
+        self.add_action(&about_action);
+
+        let debug_action = gio::SimpleAction::new("copy-debug-info", None);
+        let window = self.clone();
+        debug_action.connect_activate(move |_, _| {
+            pages::about::copy_debug_info(&window, window.manager().as_deref());
+        });
+        self.add_action(&debug_action);
+
+        // Page navigation shortcuts
+        for page in ["status", "limit", "run", "profiles"] {
             let action = gio::SimpleAction::new(&format!("goto-{page}"), None);
             let page_name = page.to_string();
             let window_clone = self.clone();
@@ -73,11 +88,63 @@ impl Window {
                 }
             });
             self.add_action(&action);
-            app.set_accels_for_action(
-                &format!("win.goto-{page}"),
-                &[&format!("<Control>{}", i + 1)],
-            );
         }
+
+        // Actions on the visible page, routed to its named widgets
+        let refresh_action = gio::SimpleAction::new("refresh", None);
+        let window = self.clone();
+        refresh_action.connect_activate(move |_, _| {
+            if let Some(btn) = window
+                .find_page_widget(PAGE_REFRESH)
+                .and_downcast::<gtk::Button>()
+            {
+                btn.emit_clicked();
+            }
+        });
+        self.add_action(&refresh_action);
+
+        let search_action = gio::SimpleAction::new("focus-search", None);
+        let window = self.clone();
+        search_action.connect_activate(move |_, _| {
+            if let Some(entry) = window.find_page_widget(PAGE_SEARCH) {
+                entry.grab_focus();
+            }
+        });
+        self.add_action(&search_action);
+
+        let apply_action = gio::SimpleAction::new("apply", None);
+        let window = self.clone();
+        apply_action.connect_activate(move |_, _| {
+            if let Some(btn) = window
+                .find_page_widget(PAGE_APPLY)
+                .and_downcast::<gtk::Button>()
+            {
+                if btn.is_sensitive() {
+                    btn.emit_clicked();
+                }
+            }
+        });
+        self.add_action(&apply_action);
+
+        // Accelerators come from GSettings; re-apply them and rebuild the
+        // shortcuts overview whenever the user changes a binding.
+        let settings = shortcuts::settings();
+        shortcuts::apply_accels(app, settings.as_ref());
+        self.set_help_overlay(Some(&shortcuts::build_overlay(settings.as_ref())));
+        if let Some(settings) = &settings {
+            let window = self.clone();
+            let app = app.clone();
+            settings.connect_changed(None, move |settings, _| {
+                shortcuts::apply_accels(&app, Some(settings));
+                window.set_help_overlay(Some(&shortcuts::build_overlay(Some(settings))));
+            });
+        }
+        self.imp().settings.replace(settings);
+    }
+
+    fn find_page_widget(&self, name: &str) -> Option<gtk::Widget> {
+        let page = self.find_content_stack()?.visible_child()?;
+        find_widget_by_name(&page, name)
     }
 
     fn find_content_stack(&self) -> Option<gtk::Stack> {
@@ -103,18 +170,17 @@ impl Window {
         let limit_page = pages::limit::create(self.manager());
         let run_page = pages::run::create(self.manager());
         let profiles_page = pages::profiles::create();
-        let about_page = pages::about::create();
 
         content_stack.add_named(&status_page, Some("status"));
         content_stack.add_named(&limit_page, Some("limit"));
         content_stack.add_named(&run_page, Some("run"));
         content_stack.add_named(&profiles_page, Some("profiles"));
-        content_stack.add_named(&about_page, Some("about"));
 
         // Create sidebar
         let sidebar_list = gtk::ListBox::new();
         sidebar_list.set_selection_mode(gtk::SelectionMode::Single);
         sidebar_list.add_css_class("navigation-sidebar");
+        crate::widgets::set_accessible_label(&sidebar_list, "Pages");
 
         let nav_items = [
             (
@@ -125,7 +191,6 @@ impl Window {
             ("limit", "Limit Running", "speedometer-symbolic"),
             ("run", "Launch New", "media-playback-start-symbolic"),
             ("profiles", "Profiles", "document-properties-symbolic"),
-            ("about", "About", "help-about-symbolic"),
         ];
 
         for (id, title, icon) in nav_items {
@@ -161,6 +226,23 @@ impl Window {
             }
         });
 
+        // Activating a page (Enter, or a click) moves focus onto it: to its
+        // search field if it has one, otherwise to its first control. Arrow
+        // keys only select, so the sidebar can still be browsed.
+        let content_stack_clone = content_stack.clone();
+        sidebar_list.connect_row_activated(move |_, _| {
+            if let Some(page) = content_stack_clone.visible_child() {
+                match find_widget_by_name(&page, PAGE_SEARCH) {
+                    Some(search) => {
+                        search.grab_focus();
+                    }
+                    None => {
+                        page.child_focus(gtk::DirectionType::TabForward);
+                    }
+                }
+            }
+        });
+
         // Select first item by default
         if let Some(first_row) = sidebar_list.row_at_index(0) {
             sidebar_list.select_row(Some(&first_row));
@@ -181,6 +263,17 @@ impl Window {
 
         // Content area with header
         let content_header = adw::HeaderBar::new();
+        let menu = gio::Menu::new();
+        menu.append(Some("Keyboard Shortcuts"), Some("win.show-help-overlay"));
+        menu.append(Some("Copy Debug Info"), Some("win.copy-debug-info"));
+        menu.append(Some("About Resource Limit Manager"), Some("win.about"));
+        let menu_btn = gtk::MenuButton::builder()
+            .icon_name("open-menu-symbolic")
+            .menu_model(&menu)
+            .tooltip_text("Main Menu")
+            .build();
+        crate::widgets::set_accessible_label(&menu_btn, "Main Menu");
+        content_header.pack_end(&menu_btn);
         let content_toolbar = adw::ToolbarView::new();
         content_toolbar.add_top_bar(&content_header);
         content_toolbar.set_content(Some(&content_stack));
@@ -212,7 +305,11 @@ impl Window {
         hbox.set_margin_start(12);
         hbox.set_margin_end(12);
 
-        let icon = gtk::Image::from_icon_name(icon_name);
+        // The row's label already names the page; the icon is decorative.
+        let icon = gtk::Image::builder()
+            .icon_name(icon_name)
+            .accessible_role(gtk::AccessibleRole::Presentation)
+            .build();
         let label = gtk::Label::new(Some(title));
         label.set_halign(gtk::Align::Start);
         label.set_hexpand(true);
@@ -229,7 +326,34 @@ impl Window {
         let status_page_clone = status_page.clone();
         let manager = self.manager();
 
-        glib::timeout_add_local(std::time::Duration::from_secs(2), move || {
+        // Cgroups appearing, emptying, or hitting limits refresh the list
+        // right away; the timer then only keeps usage figures current.
+        let watcher = manager.as_ref().and_then(|m| m.watch().ok());
+        let interval = if watcher.is_some() { 5 } else { 2 };
+        if let Some(mut watcher) = watcher {
+            use std::os::unix::io::AsRawFd;
+            let stack = stack.clone();
+            let status_page = status_page.clone();
+            let manager = manager.clone();
+            let fd = watcher.as_raw_fd();
+            glib::unix_fd_add_local(fd, glib::IOCondition::IN, move |_, _| {
+                let changed = match watcher.wait(Some(std::time::Duration::ZERO)) {
+                    Ok(events) => !events.is_empty(),
+                    Err(e) => {
+                        tracing::warn!("Stopped watching cgroups: {e}");
+                        return glib::ControlFlow::Break;
+                    }
+                };
+                if changed && stack.visible_child().as_ref() == Some(&status_page) {
+                    if let Some(ref mgr) = manager {
+                        pages::status::refresh(&status_page, mgr.clone());
+                    }
+                }
+                glib::ControlFlow::Continue
+            });
+        }
+
+        glib::timeout_add_local(std::time::Duration::from_secs(interval), move || {
             if stack_clone.visible_child().as_ref() == Some(&status_page_clone) {
                 if let Some(ref mgr) = manager {
                     pages::status::refresh(&status_page_clone, mgr.clone());
@@ -6,8 +6,13 @@
 //! every intervention so nothing is left frozen.
 
 use common::Config;
+use rlm_core::escape::EscapeWatcher;
 use rlm_core::guard::{Effector, PolicyEngine, Sampler};
+use rlm_core::hooks::HookWatcher;
+use rlm_core::reload::ConfigReload;
 use rlm_core::rules::RulesEnforcer;
+use rlm_core::sd_notify::Watchdog;
+use rlm_core::supervise::Supervisor;
 use rlm_core::CgroupManager;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -31,12 +36,26 @@ fn run() -> common::Result<()> {
     let config = Config::load().unwrap_or_default();
     let gcfg = config.guard.clone();
 
-    // The daemon does two jobs: freeze protection (when enabled) and enforcing
-    // persistent application rules. Only exit if BOTH are off.
-    let enforcer = RulesEnforcer::new(&config);
-    if !gcfg.enabled && enforcer.rule_count() == 0 {
-        tracing::info!("guard disabled and no rules configured; exiting");
-        return Ok(());
+    // The daemon does five jobs: freeze protection (when enabled), enforcing
+    // persistent application rules, supervising apps (`rlm supervise`),
+    // keeping desktop protection mode (`rlm protect on`) current, and running
+    // OOM-kill and process-exit hooks. Only exit if ALL are off. Whenever it
+    // runs, it also moves back processes that escape their cgroup.
+    let mut enforcer = RulesEnforcer::new(&config);
+    let mut supervisor = Supervisor::new(&config);
+    let mut hooks = HookWatcher::new(&config);
+    if !gcfg.enabled
+        && enforcer.rule_count() == 0
+        && supervisor.app_count() == 0
+        && !hooks.is_active()
+    {
+        let protecting = CgroupManager::new().is_ok_and(|m| m.protect_enabled());
+        if !protecting {
+            tracing::info!("guard disabled, nothing configured, protection off; exiting");
+            // A clean exit, not a failed start, to a Type=notify service
+            rlm_core::sd_notify::ready();
+            return Ok(());
+        }
     }
 
     let self_pid = std::process::id();
@@ -47,19 +66,26 @@ fn run() -> common::Result<()> {
     let effector = Effector::new(&manager);
     let sampler = Sampler::new(gcfg.clone(), self_pid, uid);
     let mut engine = PolicyEngine::new(gcfg.clone());
+    let mut escapes = EscapeWatcher::new();
 
     // Startup recovery: thaw/clean anything a prior crash left behind so no
     // process stays frozen across a restart.
     if let Err(e) = effector.sweep_leftovers() {
         tracing::warn!("startup sweep failed: {e}");
     }
+    // Likewise for cgroups of `rlm run`/GUI sessions that were killed
+    if let Err(e) = rlm_core::state::gc(&manager, false) {
+        tracing::warn!("startup cgroup gc failed: {e}");
+    }
 
-    // Graceful shutdown on SIGINT/SIGTERM/SIGHUP (ctrlc "termination" feature).
+    // Graceful shutdown on SIGINT/SIGTERM (ctrlc "termination" feature);
+    // SIGHUP is then taken over to reload the config.
     let shutdown = Arc::new(AtomicBool::new(false));
     {
         let s = Arc::clone(&shutdown);
         let _ = ctrlc::set_handler(move || s.store(true, Ordering::SeqCst));
     }
+    let mut reload = ConfigReload::on_hangup();
 
     let interval = Duration::from_millis(gcfg.timing.sample_interval_ms.max(100));
     let start = Instant::now();
@@ -70,13 +96,39 @@ fn run() -> common::Result<()> {
         interval_ms = interval.as_millis() as u64,
         freeze_guard = gcfg.enabled,
         rules = enforcer.rule_count(),
+        supervised = supervisor.app_count(),
+        hooks = hooks.is_active(),
         "rlm-guard started"
     );
+    rlm_core::sd_notify::ready();
+    let mut watchdog = Watchdog::from_env();
 
     while !shutdown.load(Ordering::SeqCst) {
         // Monotonic, injected into the pure engine for deterministic behavior.
         let now_ms = start.elapsed().as_millis() as u64;
 
+        // SIGHUP: pick up changed rules, supervised apps and hooks. Managed
+        // cgroups stay; freeze guard settings need a restart.
+        if reload.requested() {
+            rlm_core::sd_notify::reloading();
+            match Config::load() {
+                Ok(cfg) => {
+                    enforcer = RulesEnforcer::new(&cfg);
+                    enforcer.refresh(&manager);
+                    supervisor.reload(&cfg);
+                    hooks = HookWatcher::new(&cfg);
+                    tracing::info!(
+                        rules = enforcer.rule_count(),
+                        supervised = supervisor.app_count(),
+                        hooks = hooks.is_active(),
+                        "config reloaded"
+                    );
+                }
+                Err(e) => tracing::warn!("config not reloaded, keeping the old one: {e}"),
+            }
+            rlm_core::sd_notify::ready();
+        }
+
         // Freeze protection (PSI-driven), only when enabled.
         if gcfg.enabled {
             if let Some(sample) = sampler.sample() {
@@ -96,24 +148,39 @@ fn run() -> common::Result<()> {
         // logs internally). Absorbs newly-launched matching instances.
         enforcer.reconcile(&manager);
 
-        sleep_responsive(interval, &shutdown);
+        // Supervised apps: restart/tighten on OOM kills and repeated breaches.
+        supervisor.tick(&manager);
+
+        // Desktop protection (`rlm protect on`): pull newly launched apps in.
+        rlm_core::protect::sweep(&manager);
+
+        // `on_oom_kill` and `on_process_exit` hooks.
+        hooks.tick(&manager);
+
+        // Processes systemd moved back to their scope (logs each one).
+        escapes.tick(&manager);
+
+        sleep_responsive(interval, &shutdown, &mut watchdog);
     }
 
     tracing::info!("rlm-guard shutting down; undoing all interventions");
+    rlm_core::sd_notify::stopping();
     if let Err(e) = effector.undo_all() {
         tracing::warn!("undo_all failed: {e}");
     }
     Ok(())
 }
 
-/// Sleep up to `total`, waking early if shutdown is requested.
-fn sleep_responsive(total: Duration, shutdown: &AtomicBool) {
+/// Sleep up to `total`, waking early if shutdown is requested and keeping
+/// systemd's watchdog fed through long sample intervals.
+fn sleep_responsive(total: Duration, shutdown: &AtomicBool, watchdog: &mut Watchdog) {
     let step = Duration::from_millis(100);
     let mut slept = Duration::ZERO;
     while slept < total {
         if shutdown.load(Ordering::SeqCst) {
             break;
         }
+        watchdog.tick();
         let chunk = step.min(total - slept);
         std::thread::sleep(chunk);
         slept += chunk;
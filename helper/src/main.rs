@@ -0,0 +1,43 @@
+//! `rlm-helper` — the GUI's privileged helper.
+//!
+//! Started by the GUI through pkexec when the user has no cgroup delegation.
+//! Runs as root and makes the GUI's cgroup writes, speaking the line-based
+//! JSON protocol of [`rlm_core::helper`] over stdin and stdout, for the user
+//! pkexec names in `PKEXEC_UID`.
+
+use rlm_core::CgroupManager;
+
+fn main() {
+    // stdout carries the protocol; logs go to stderr
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::from_default_env()
+                .add_directive(tracing::Level::INFO.into()),
+        )
+        .init();
+
+    if let Err(e) = run() {
+        tracing::error!("rlm-helper exiting: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> common::Result<()> {
+    let uid = match std::env::var("PKEXEC_UID") {
+        Ok(uid) => uid
+            .parse()
+            .map_err(|_| common::Error::InvalidArgs(format!("bad PKEXEC_UID: {uid}")))?,
+        // Run without pkexec: act for whoever ran it.
+        // SAFETY: getuid() is always safe; it just reads our real UID from the kernel.
+        Err(_) => unsafe { libc::getuid() },
+    };
+    let manager = CgroupManager::system()?;
+    tracing::info!(uid, "rlm-helper started");
+    rlm_core::helper::serve(
+        &manager,
+        uid,
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+    )
+}
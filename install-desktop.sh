@@ -1,5 +1,5 @@
 #!/bin/bash
-# Install rlm-gui desktop entry and icons
+# Install rlm-gui desktop entry, icons, and settings schema
 
 set -e
 
@@ -10,9 +10,11 @@ APP_ID="io.github.rlm.gtk"
 if [ "$1" = "--system" ] || [ "$(id -u)" = "0" ]; then
     DESKTOP_DIR="/usr/share/applications"
     ICON_DIR="/usr/share/icons/hicolor"
+    SCHEMA_DIR="/usr/share/glib-2.0/schemas"
 else
     DESKTOP_DIR="${XDG_DATA_HOME:-$HOME/.local/share}/applications"
     ICON_DIR="${XDG_DATA_HOME:-$HOME/.local/share}/icons/hicolor"
+    SCHEMA_DIR="${XDG_DATA_HOME:-$HOME/.local/share}/glib-2.0/schemas"
 fi
 
 echo "Installing desktop entry to: $DESKTOP_DIR"
@@ -22,6 +24,7 @@ echo "Installing icons to: $ICON_DIR"
 mkdir -p "$DESKTOP_DIR"
 mkdir -p "$ICON_DIR/scalable/apps"
 mkdir -p "$ICON_DIR/symbolic/apps"
+mkdir -p "$SCHEMA_DIR"
 
 # Install desktop file
 cp "$SCRIPT_DIR/gtk-gui/assets/$APP_ID.desktop" "$DESKTOP_DIR/"
@@ -30,6 +33,12 @@ cp "$SCRIPT_DIR/gtk-gui/assets/$APP_ID.desktop" "$DESKTOP_DIR/"
 cp "$SCRIPT_DIR/gtk-gui/assets/rlm-icon.svg" "$ICON_DIR/scalable/apps/$APP_ID.svg"
 cp "$SCRIPT_DIR/gtk-gui/assets/rlm-symbolic.svg" "$ICON_DIR/symbolic/apps/$APP_ID-symbolic.svg"
 
+# Install keybinding settings schema
+cp "$SCRIPT_DIR/gtk-gui/assets/$APP_ID.gschema.xml" "$SCHEMA_DIR/"
+if command -v glib-compile-schemas &> /dev/null; then
+    glib-compile-schemas "$SCHEMA_DIR" 2>/dev/null || true
+fi
+
 # Update icon cache (if gtk-update-icon-cache is available)
 if command -v gtk-update-icon-cache &> /dev/null; then
     gtk-update-icon-cache -f -t "$ICON_DIR" 2>/dev/null || true
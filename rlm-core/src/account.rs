@@ -0,0 +1,85 @@
+//! A user account to start a command as, for the callers that run as root
+//! on someone's behalf: `rlm run --user` and the D-Bus `RunLimited`.
+
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// What it takes to start a command as a user.
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub uid: libc::uid_t,
+    pub name: CString,
+    pub home: PathBuf,
+    pub gid: libc::gid_t,
+    /// Supplementary groups, the primary one included
+    pub groups: Vec<libc::gid_t>,
+}
+
+impl Account {
+    pub fn user(&self) -> &OsStr {
+        OsStr::from_bytes(self.name.as_bytes())
+    }
+
+    pub fn of(uid: u32) -> io::Result<Self> {
+        let not_found = || io::Error::other(format!("no user with uid {uid}"));
+        // SAFETY: passwd is plain data, filled in by getpwuid_r.
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut buf = vec![0 as libc::c_char; 16 * 1024];
+        let mut result = std::ptr::null_mut();
+        // SAFETY: every pointer is to a live buffer of the length passed.
+        let rc =
+            unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+        if rc != 0 || result.is_null() {
+            return Err(not_found());
+        }
+        // SAFETY: getpwuid_r succeeded, so both point into `buf`, NUL-terminated.
+        let (name, home) = unsafe { (CStr::from_ptr(pwd.pw_name), CStr::from_ptr(pwd.pw_dir)) };
+        let name = name.to_owned();
+        let home = PathBuf::from(OsStr::from_bytes(home.to_bytes()));
+
+        let mut groups = vec![0 as libc::gid_t; 64];
+        for _ in 0..2 {
+            let mut count = groups.len() as libc::c_int;
+            // SAFETY: `groups` holds `count` entries; on overflow count is
+            // set to the number needed and -1 returned.
+            let rc = unsafe {
+                libc::getgrouplist(name.as_ptr(), pwd.pw_gid, groups.as_mut_ptr(), &mut count)
+            };
+            groups.resize(count.max(1) as usize, 0);
+            if rc >= 0 {
+                return Ok(Self {
+                    uid,
+                    name,
+                    home,
+                    gid: pwd.pw_gid,
+                    groups,
+                });
+            }
+        }
+        Err(not_found())
+    }
+
+    /// Have `command`'s process take on this account's groups and user
+    /// before it execs. Hooks added earlier (cgroup placement) still run
+    /// with rlm's privileges.
+    pub fn run_as(&self, command: &mut Command) {
+        let (uid, gid, groups) = (self.uid, self.gid, self.groups.clone());
+        // SAFETY: the closure only makes the setgroups/setgid/setuid system
+        // calls, which are async-signal-safe, on data prepared in the parent.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setgroups(groups.len(), groups.as_ptr()) != 0
+                    || libc::setgid(gid) != 0
+                    || libc::setuid(uid) != 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Audit trail of limit changes in the systemd journal, enabled with
+//! `audit: { journald: true }` in the config.
+//!
+//! Every [`history`](crate::history) entry is also sent as a structured
+//! journal entry (`SYSLOG_IDENTIFIER=rlm`, `RLM_*` fields) over journald's
+//! native socket, so on a machine with several admins `journalctl
+//! RLM_ACTION=apply` shows who limited what. Failures are logged at warning
+//! priority. Like the history, sending is best effort.
+
+use crate::history::Entry;
+use std::ffi::CStr;
+use std::os::unix::net::UnixDatagram;
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Whether journald auditing is switched on.
+pub(crate) fn enabled() -> bool {
+    common::Config::load().is_ok_and(|c| c.audit.journald)
+}
+
+/// Send one entry to the journal.
+pub(crate) fn send(entry: &Entry) {
+    let payload = encode(&fields(entry));
+    let result = UnixDatagram::unbound().and_then(|s| s.send_to(&payload, JOURNAL_SOCKET));
+    if let Err(e) = result {
+        tracing::debug!(error = %e, "could not write audit entry to the journal");
+    }
+}
+
+/// Journal fields for an entry.
+fn fields(entry: &Entry) -> Vec<(&'static str, String)> {
+    // SAFETY: getuid never fails
+    let uid = unsafe { libc::getuid() };
+    let user = user_name(uid).unwrap_or_else(|| uid.to_string());
+    let target = match &entry.name {
+        Some(name) => format!("{} ({name})", entry.cgroup),
+        None => entry.cgroup.clone(),
+    };
+    let limits = entry
+        .limit
+        .as_ref()
+        .map(crate::history::summary)
+        .unwrap_or_default();
+
+    let message = match &entry.error {
+        Some(err) => format!("{user}: {} on {target} failed: {err}", entry.action.name()),
+        None if limits.is_empty() => format!("{user}: {} on {target}", entry.action.name()),
+        None => format!("{user}: {} {limits} on {target}", entry.action.name()),
+    };
+    let mut fields = vec![
+        ("MESSAGE", message),
+        // warning for failures, info otherwise
+        (
+            "PRIORITY",
+            if entry.error.is_some() { "4" } else { "6" }.into(),
+        ),
+        ("SYSLOG_IDENTIFIER", "rlm".into()),
+        ("RLM_ACTION", entry.action.name().into()),
+        ("RLM_CGROUP", entry.cgroup.clone()),
+        ("RLM_USER", user),
+        ("RLM_UID", uid.to_string()),
+        ("RLM_PROGRAM", entry.by.clone()),
+    ];
+    if !entry.pids.is_empty() {
+        let pids: Vec<String> = entry.pids.iter().map(|p| p.to_string()).collect();
+        fields.push(("RLM_PIDS", pids.join(" ")));
+    }
+    if let Some(name) = &entry.name {
+        fields.push(("RLM_NAME", name.clone()));
+    }
+    if !limits.is_empty() {
+        fields.push(("RLM_LIMITS", limits));
+    }
+    if let Some(err) = &entry.error {
+        fields.push(("RLM_ERROR", err.clone()));
+    }
+    // Who ran sudo, when acting as root on someone's behalf
+    if let Ok(sudo_user) = std::env::var("SUDO_USER") {
+        fields.push(("RLM_SUDO_USER", sudo_user));
+    }
+    fields
+}
+
+/// Encode fields in the journal's native protocol: `KEY=value\n`, or for
+/// values containing a newline `KEY\n<u64 LE length><value>\n`.
+fn encode(fields: &[(&str, String)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (key, value) in fields {
+        out.extend_from_slice(key.as_bytes());
+        if value.contains('\n') {
+            out.push(b'\n');
+            out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            out.push(b'=');
+        }
+        out.extend_from_slice(value.as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+pub(crate) fn user_name(uid: libc::uid_t) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+    // SAFETY: zeroed passwd is a valid out-parameter for getpwuid_r
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: all pointers are valid for the call; `buf` outlives `pwd`'s use
+    let rc = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if rc != 0 || result.is_null() {
+        return None;
+    }
+    // SAFETY: on success `pw_name` points to a NUL-terminated string in `buf`
+    let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_native_protocol() {
+        let out = encode(&[
+            ("MESSAGE", "hi".to_string()),
+            ("RLM_ERROR", "a\nb".to_string()),
+        ]);
+        let mut expected = b"MESSAGE=hi\nRLM_ERROR\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert_eq!(out, expected);
+    }
+}
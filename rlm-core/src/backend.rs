@@ -0,0 +1,262 @@
+//! The cgroup filesystem as [`CgroupManager`](crate::CgroupManager) and
+//! status reading see it. [`FsBackend`], the real cgroupfs, is the default;
+//! [`FakeBackend`] keeps a hierarchy in memory, so apply, cleanup, and
+//! status logic can be tested without root or cgroup v2.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Operations on a cgroup hierarchy, by full path. Interface files are read
+/// and written whole, one value per write, as the kernel parses them.
+pub trait CgroupBackend: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<String>;
+    fn write(&self, path: &Path, value: &str) -> io::Result<()>;
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Remove a cgroup; fails while it has processes or children.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// Child cgroups of `path`.
+    fn children(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    /// When the cgroup at `path` was last modified.
+    fn modified(&self, path: &Path) -> Option<SystemTime>;
+}
+
+/// The real cgroupfs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsBackend;
+
+impl CgroupBackend for FsBackend {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, value: &str) -> io::Result<()> {
+        fs::write(path, value)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn children(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(fs::read_dir(path)?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect())
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+}
+
+/// An in-memory hierarchy for tests. Like the kernel's, a new cgroup has
+/// `cgroup.procs` and its parent's `cgroup.controllers`; writing a PID to
+/// `cgroup.procs` moves it out of whichever cgroup held it; and a cgroup
+/// with processes or children can't be removed. Any other file may be
+/// written and reads back what was written.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    state: Mutex<FakeState>,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    /// Cgroups and when they were created
+    dirs: BTreeMap<PathBuf, SystemTime>,
+    files: HashMap<PathBuf, String>,
+}
+
+impl FakeBackend {
+    /// A hierarchy rooted at `root` whose `cgroup.controllers` lists
+    /// `controllers` (e.g. "memory cpu io pids").
+    pub fn new(root: impl Into<PathBuf>, controllers: &str) -> Self {
+        let root = root.into();
+        let mut state = FakeState::default();
+        state.files.insert(root.join("cgroup.procs"), String::new());
+        state
+            .files
+            .insert(root.join("cgroup.controllers"), controllers.to_string());
+        state.dirs.insert(root, SystemTime::now());
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Contents of the file at `path`, if it has been written.
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.lock().files.get(path.as_ref()).cloned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FakeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::from(io::ErrorKind::NotFound)
+}
+
+impl FakeState {
+    fn create(&mut self, path: &Path) -> io::Result<()> {
+        if self.dirs.contains_key(path) {
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+        }
+        let parent = path.parent().ok_or_else(not_found)?;
+        if !self.dirs.contains_key(parent) {
+            return Err(not_found());
+        }
+        let controllers = self
+            .files
+            .get(&parent.join("cgroup.controllers"))
+            .cloned()
+            .unwrap_or_default();
+        self.files
+            .insert(path.join("cgroup.controllers"), controllers);
+        self.files.insert(path.join("cgroup.procs"), String::new());
+        self.dirs.insert(path.to_path_buf(), SystemTime::now());
+        Ok(())
+    }
+}
+
+impl CgroupBackend for FakeBackend {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.lock().files.get(path).cloned().ok_or_else(not_found)
+    }
+
+    fn write(&self, path: &Path, value: &str) -> io::Result<()> {
+        let mut state = self.lock();
+        let dir = path.parent().ok_or_else(not_found)?;
+        if !state.dirs.contains_key(dir) {
+            return Err(not_found());
+        }
+        if path.file_name().is_some_and(|n| n == "cgroup.procs") {
+            let pid: u32 = value
+                .trim()
+                .parse()
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+            for (file, procs) in state.files.iter_mut() {
+                if file.file_name().is_some_and(|n| n == "cgroup.procs") {
+                    *procs = procs
+                        .lines()
+                        .filter(|l| l.trim() != pid.to_string())
+                        .map(|l| format!("{l}\n"))
+                        .collect();
+                }
+            }
+            if let Some(procs) = state.files.get_mut(path) {
+                procs.push_str(&format!("{pid}\n"));
+            }
+            return Ok(());
+        }
+        state.files.insert(path.to_path_buf(), value.to_string());
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.lock().create(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        let missing: Vec<PathBuf> = path
+            .ancestors()
+            .take_while(|p| !state.dirs.contains_key(*p))
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in missing.iter().rev() {
+            state.create(dir)?;
+        }
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        if !state.dirs.contains_key(path) {
+            return Err(not_found());
+        }
+        let busy = state.dirs.keys().any(|d| d.parent() == Some(path))
+            || state
+                .files
+                .get(&path.join("cgroup.procs"))
+                .is_some_and(|p| !p.trim().is_empty());
+        if busy {
+            return Err(io::Error::from_raw_os_error(libc::EBUSY));
+        }
+        state.dirs.remove(path);
+        state.files.retain(|f, _| f.parent() != Some(path));
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.lock().dirs.contains_key(path)
+    }
+
+    fn children(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let state = self.lock();
+        if !state.dirs.contains_key(path) {
+            return Err(not_found());
+        }
+        Ok(state
+            .dirs
+            .keys()
+            .filter(|d| d.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        self.lock().dirs.get(path).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_moves_processes_and_refuses_busy_removal() {
+        let fake = FakeBackend::new("/cg", "memory cpu");
+        fake.create_dir_all(Path::new("/cg/rlm/a")).unwrap();
+        fake.create_dir(Path::new("/cg/rlm/b")).unwrap();
+        assert_eq!(
+            fake.read(Path::new("/cg/rlm/a/cgroup.controllers"))
+                .unwrap(),
+            "memory cpu"
+        );
+
+        fake.write(Path::new("/cg/rlm/a/cgroup.procs"), "42")
+            .unwrap();
+        fake.write(Path::new("/cg/rlm/b/cgroup.procs"), "42")
+            .unwrap();
+        assert_eq!(fake.file("/cg/rlm/a/cgroup.procs").unwrap(), "");
+        assert_eq!(fake.file("/cg/rlm/b/cgroup.procs").unwrap(), "42\n");
+
+        assert!(fake.remove_dir(Path::new("/cg/rlm/b")).is_err());
+        assert!(fake.remove_dir(Path::new("/cg/rlm")).is_err());
+        assert!(fake.remove_dir(Path::new("/cg/rlm/a")).is_ok());
+        assert_eq!(
+            fake.children(Path::new("/cg/rlm")).unwrap(),
+            [PathBuf::from("/cg/rlm/b")]
+        );
+        assert!(fake.write(Path::new("/cg/rlm/a/memory.max"), "1").is_err());
+    }
+}
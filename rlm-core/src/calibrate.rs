@@ -0,0 +1,284 @@
+//! I/O calibration: a short fio-like probe behind `rlm calibrate io`.
+//!
+//! Measures sequential and random throughput, then suggests `io.max`
+//! bandwidth limits for a few tiers. A directory is probed through a
+//! temporary file (read and write); a block device is only ever read.
+//! Page cache is dropped with `posix_fadvise` before each read pass, so
+//! the numbers reflect the device rather than RAM. They are rough: good
+//! enough to pick limits, not a benchmark.
+
+use common::{Error, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Bytes read (and, for directories, written) by the sequential passes.
+pub const DEFAULT_PROBE_SIZE: u64 = 256 * 1024 * 1024;
+
+const SEQ_BLOCK: usize = 1024 * 1024;
+const RAND_BLOCK: usize = 4096;
+const RAND_DURATION: Duration = Duration::from_secs(2);
+
+/// Measured throughput in bytes per second.
+#[derive(Debug, Clone, Copy)]
+pub struct Measurement {
+    pub seq_read_bps: u64,
+    /// `None` when probing a block device, which is never written to.
+    pub seq_write_bps: Option<u64>,
+    pub rand_read_bps: u64,
+    pub rand_read_iops: u64,
+}
+
+/// Suggested limit tiers, from loosest to tightest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// Half the device: a heavy job that must not monopolize the disk.
+    Balanced,
+    /// A quarter: batch jobs and indexers that can wait.
+    Background,
+    /// A tenth: known I/O hogs.
+    Strict,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 3] = [Tier::Balanced, Tier::Background, Tier::Strict];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tier::Balanced => "balanced",
+            Tier::Background => "background",
+            Tier::Strict => "strict",
+        }
+    }
+
+    fn percent(self) -> u64 {
+        match self {
+            Tier::Balanced => 50,
+            Tier::Background => 25,
+            Tier::Strict => 10,
+        }
+    }
+}
+
+/// Suggested `io.max` values for one tier, as strings `IoLimit::parse_bps`
+/// accepts (e.g. "120M").
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub tier: Tier,
+    pub io_read: String,
+    pub io_write: Option<String>,
+}
+
+/// Suggest limits for every tier. Limits are a share of sequential
+/// throughput: that is the device's ceiling, and random workloads are
+/// slower anyway.
+pub fn suggest(m: &Measurement) -> Vec<Suggestion> {
+    Tier::ALL
+        .iter()
+        .map(|&tier| Suggestion {
+            tier,
+            io_read: format_limit(m.seq_read_bps / 100 * tier.percent()),
+            io_write: m
+                .seq_write_bps
+                .map(|w| format_limit(w / 100 * tier.percent())),
+        })
+        .collect()
+}
+
+/// Round a bandwidth down to whole MiB/s, never below 1M, so the value reads
+/// well in a profile and never means "unlimited".
+fn format_limit(bps: u64) -> String {
+    format!("{}M", (bps / (1024 * 1024)).max(1))
+}
+
+/// Where to probe when no directory is given: the user's cache directory,
+/// which lives on the home disk, unlike a tmpfs `/tmp`.
+pub fn default_dir() -> PathBuf {
+    dirs::cache_dir().unwrap_or_else(std::env::temp_dir)
+}
+
+/// Probe the filesystem holding `dir` through a temporary file of `size`
+/// bytes, which is removed afterwards.
+pub fn probe_dir(dir: &Path, size: u64) -> Result<Measurement> {
+    let probe = ProbeFile(dir.join(format!(".rlm-calibrate-{}", std::process::id())));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&probe.0)?;
+
+    let block = pattern_block();
+    let start = Instant::now();
+    let mut written = 0;
+    while written < size {
+        let n = (size - written).min(SEQ_BLOCK as u64) as usize;
+        file.write_all(&block[..n])?;
+        written += n as u64;
+    }
+    // Time the flush too, or we'd measure the page cache
+    file.sync_all()?;
+    let seq_write_bps = rate(written, start.elapsed());
+
+    let (seq_read_bps, rand) = probe_reads(&mut file, size)?;
+    Ok(Measurement {
+        seq_read_bps,
+        seq_write_bps: Some(seq_write_bps),
+        rand_read_bps: rand.0,
+        rand_read_iops: rand.1,
+    })
+}
+
+/// Probe a block device (e.g. `/dev/nvme0n1`) read-only. Reading a device
+/// node usually needs root or membership in the `disk` group.
+pub fn probe_device(device: &Path, size: u64) -> Result<Measurement> {
+    let mut file = File::open(device).map_err(|e| {
+        std::io::Error::new(e.kind(), format!("cannot open {}: {e}", device.display()))
+    })?;
+    let len = file.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Err(Error::InvalidArgs(format!(
+            "{} is not a block device (or is empty)",
+            device.display()
+        )));
+    }
+
+    let (seq_read_bps, rand) = probe_reads(&mut file, size.min(len))?;
+    Ok(Measurement {
+        seq_read_bps,
+        seq_write_bps: None,
+        rand_read_bps: rand.0,
+        rand_read_iops: rand.1,
+    })
+}
+
+/// Sequential read of the first `size` bytes, then 4 KiB reads at random
+/// offsets for a fixed time. Returns the sequential rate and the random
+/// (rate, IOPS).
+fn probe_reads(file: &mut File, size: u64) -> Result<(u64, (u64, u64))> {
+    let mut buf = vec![0u8; SEQ_BLOCK];
+
+    drop_cache(file, libc::POSIX_FADV_SEQUENTIAL);
+    file.seek(SeekFrom::Start(0))?;
+    let start = Instant::now();
+    let mut read = 0;
+    while read < size {
+        let want = (size - read).min(SEQ_BLOCK as u64) as usize;
+        let n = file.read(&mut buf[..want])?;
+        if n == 0 {
+            break;
+        }
+        read += n as u64;
+    }
+    let seq = rate(read, start.elapsed());
+
+    // Readahead would turn random reads into sequential ones
+    drop_cache(file, libc::POSIX_FADV_RANDOM);
+    let blocks = (size / RAND_BLOCK as u64).max(1);
+    let mut rng = XorShift::seeded();
+    let start = Instant::now();
+    let mut ops = 0u64;
+    while start.elapsed() < RAND_DURATION {
+        let offset = rng.next() % blocks * RAND_BLOCK as u64;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf[..RAND_BLOCK])?;
+        ops += 1;
+    }
+    let elapsed = start.elapsed();
+    Ok((
+        seq,
+        (rate(ops * RAND_BLOCK as u64, elapsed), rate(ops, elapsed)),
+    ))
+}
+
+/// Evict the file's pages from the page cache and set the access pattern
+/// hint. Best effort: a failure only makes the reads look faster.
+fn drop_cache(file: &File, advice: libc::c_int) {
+    let fd = file.as_raw_fd();
+    // SAFETY: `fd` is a valid open descriptor for the lifetime of `file`.
+    unsafe {
+        libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_DONTNEED);
+        libc::posix_fadvise(fd, 0, 0, advice);
+    }
+}
+
+fn rate(amount: u64, elapsed: Duration) -> u64 {
+    (amount as f64 / elapsed.as_secs_f64().max(1e-6)) as u64
+}
+
+/// Random filler, so compressing storage can't cheat.
+fn pattern_block() -> Vec<u8> {
+    let mut rng = XorShift::seeded();
+    (0..SEQ_BLOCK / 8)
+        .flat_map(|_| rng.next().to_le_bytes())
+        .collect()
+}
+
+/// Removes the probe file however the probe ends.
+struct ProbeFile(PathBuf);
+
+impl Drop for ProbeFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Tiny PRNG for offsets and filler data; quality doesn't matter here.
+struct XorShift(u64);
+
+impl XorShift {
+    fn seeded() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self(nanos | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn suggestions_scale_with_tier() {
+        let m = Measurement {
+            seq_read_bps: 1000 * MIB,
+            seq_write_bps: Some(400 * MIB),
+            rand_read_bps: 50 * MIB,
+            rand_read_iops: 12800,
+        };
+        let s = suggest(&m);
+        assert_eq!(s[0].tier, Tier::Balanced);
+        assert_eq!(s[0].io_read, "500M");
+        assert_eq!(s[0].io_write.as_deref(), Some("200M"));
+        assert_eq!(s[1].io_read, "250M");
+        assert_eq!(s[2].io_read, "100M");
+        assert_eq!(s[2].io_write.as_deref(), Some("40M"));
+    }
+
+    #[test]
+    fn read_only_probe_suggests_no_write_limit() {
+        let m = Measurement {
+            seq_read_bps: 5 * MIB,
+            seq_write_bps: None,
+            rand_read_bps: MIB,
+            rand_read_iops: 256,
+        };
+        let s = suggest(&m);
+        assert!(s.iter().all(|s| s.io_write.is_none()));
+        // 10% of 5 MiB/s rounds down to 0; never suggest "0M"
+        assert_eq!(s[2].io_read, "1M");
+        assert!(common::IoLimit::parse_bps(&s[2].io_read).is_ok());
+    }
+}
@@ -1,9 +1,56 @@
-use common::{CpuLimit, Error, IoLimit, Limit, MemoryLimit, Result};
+use crate::backend::{CgroupBackend, FsBackend};
+use crate::hierarchy;
+use crate::history::{self, Action};
+use common::{
+    CpuLimit, CpuWeight, Error, IoLimit, IoWeight, Limit, MemNodes, MemoryLimit, PidsLimit, Result,
+    SwapLimit,
+};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, OnceLock};
+
+/// Parent cgroup for desktop protection mode (see [`crate::protect`]).
+const PROTECT_CGROUP: &str = "protect";
+/// Protected session's `memory.high`, as a percentage of total RAM. The rest
+/// stays available to the compositor and shell without any throttling.
+const PROTECT_MEMORY_HIGH_PERCENT: u64 = 85;
+/// Protected session's `cpu.weight` (default is 100), so the exempt desktop
+/// processes win CPU contention.
+const PROTECT_CPU_WEIGHT: u32 = 50;
+
+/// Cgroup `rlm rescue` throws the worst offenders into (see [`crate::rescue`]).
+pub(crate) const RESCUE_CGROUP: &str = "rescue";
+/// Rescued processes' `memory.high`. Memory they already use stays charged
+/// where it was, so this caps their growth rather than their total.
+const RESCUE_MEMORY_HIGH: u64 = 256 * 1024 * 1024;
+/// CPU all rescued processes share, as a percentage of one core.
+const RESCUE_CPU_PERCENT: u32 = 25;
+/// Rescued processes' `cpu.weight` and `io.weight`: the lowest there is.
+const RESCUE_WEIGHT: u32 = 1;
+
+/// Root set by [`CgroupManager::set_root`], ahead of `RLM_CGROUP_ROOT` and the
+/// config.
+static ROOT_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// A controller whose limits `rlm unlimit --memory/--cpu/--io` lifts on its
+/// own, leaving the cgroup's others in force.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    Memory,
+    Cpu,
+    Io,
+}
 
-const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+impl Controller {
+    pub fn name(self) -> &'static str {
+        match self {
+            Controller::Memory => "memory",
+            Controller::Cpu => "cpu",
+            Controller::Io => "io",
+        }
+    }
+}
 
 /// Sanitize cgroup name to prevent path traversal attacks.
 /// Only allows alphanumeric characters, dashes, and underscores.
@@ -46,20 +93,126 @@ fn reject_critical_pid(pid: u32) -> Result<()> {
 
 pub struct CgroupManager {
     base_path: PathBuf,
+    backend: Arc<dyn CgroupBackend>,
+    /// Whether actions go into the history and crash-recovery state; not
+    /// for a fake hierarchy.
+    recording: bool,
 }
 
 impl CgroupManager {
     pub fn new() -> Result<Self> {
         // Verify cgroups v2 is available
-        let controllers_path = PathBuf::from(CGROUP_ROOT).join("cgroup.controllers");
-        if !controllers_path.exists() {
-            return Err(Error::CgroupsV2NotAvailable(PathBuf::from(CGROUP_ROOT)));
+        let root = hierarchy::root();
+        if !root.join("cgroup.controllers").exists() {
+            return Err(Error::CgroupsV2NotAvailable(root.to_path_buf()));
+        }
+
+        if let Some(base_path) = configured_root() {
+            return Self::at(base_path);
         }
 
         // Try to find a suitable cgroup path with delegated controllers
         let base_path = Self::find_delegated_cgroup()?;
 
-        Ok(Self { base_path })
+        Ok(Self::on_fs(base_path))
+    }
+
+    /// Make every manager [`CgroupManager::new`] creates in this process,
+    /// on any thread, use `root` (e.g. `rlm --cgroup-root`). Unlike setting
+    /// `RLM_CGROUP_ROOT`, processes rlm starts don't inherit it. Only the
+    /// first call counts.
+    pub fn set_root(root: PathBuf) {
+        let _ = ROOT_OVERRIDE.set(root);
+    }
+
+    /// Create cgroups in `base_path`, which must be in a cgroup v2
+    /// hierarchy: either a cgroup or, until rlm creates it, a new one in one.
+    pub fn at(base_path: PathBuf) -> Result<Self> {
+        let in_hierarchy = |dir: &Path| dir.join("cgroup.controllers").exists();
+        if !in_hierarchy(&base_path) && !base_path.parent().is_some_and(in_hierarchy) {
+            return Err(Error::Config(format!(
+                "cgroup root {} is not in a cgroup v2 hierarchy",
+                base_path.display()
+            )));
+        }
+        Ok(Self::on_fs(base_path))
+    }
+
+    /// Create cgroups in `base_path` of `backend`, e.g. a
+    /// [`FakeBackend`](crate::backend::FakeBackend) in tests. Nothing is
+    /// recorded in the history or crash-recovery state.
+    pub fn with_backend(base_path: PathBuf, backend: Arc<dyn CgroupBackend>) -> Self {
+        Self {
+            base_path,
+            backend,
+            recording: false,
+        }
+    }
+
+    fn on_fs(base_path: PathBuf) -> Self {
+        Self {
+            base_path,
+            backend: Arc::new(FsBackend),
+            recording: true,
+        }
+    }
+
+    /// The hierarchy this manager works on.
+    pub fn backend(&self) -> &dyn CgroupBackend {
+        self.backend.as_ref()
+    }
+
+    fn read(&self, path: impl AsRef<Path>) -> std::io::Result<String> {
+        self.backend.read(path.as_ref())
+    }
+
+    fn write(&self, path: impl AsRef<Path>, value: impl AsRef<str>) -> std::io::Result<()> {
+        self.backend.write(path.as_ref(), value.as_ref())
+    }
+
+    /// [`history::record`], unless this manager doesn't record.
+    fn record<T>(
+        &self,
+        action: Action,
+        cgroup: &str,
+        pids: &[u32],
+        limit: Option<&Limit>,
+        result: &Result<T>,
+    ) {
+        if self.recording {
+            history::record(action, cgroup, pids, limit, result);
+        }
+    }
+
+    /// The system-wide tree under `/sys/fs/cgroup/rlm`, whoever runs it: the
+    /// one root-run services write, and the one a user without delegation
+    /// reads while the privileged helper writes it for them.
+    pub fn system() -> Result<Self> {
+        let root = hierarchy::root();
+        if !root.join("cgroup.controllers").exists() {
+            return Err(Error::CgroupsV2NotAvailable(root.to_path_buf()));
+        }
+        Ok(Self::on_fs(root.join("rlm")))
+    }
+
+    /// Whether limits can be set here without help: the base path (or, until
+    /// it exists, its parent) is writable and has the memory and cpu
+    /// controllers.
+    pub fn has_delegation(&self) -> bool {
+        let dir = if self.backend.is_dir(&self.base_path) {
+            self.base_path.as_path()
+        } else {
+            match self.base_path.parent() {
+                Some(parent) => parent,
+                None => return false,
+            }
+        };
+        let writable = hierarchy::writable(dir);
+        let controllers = self
+            .read(dir.join("cgroup.controllers"))
+            .unwrap_or_default();
+        let available = |c| controllers.split_whitespace().any(|a| a == c);
+        writable && available("memory") && available("cpu")
     }
 
     /// Find a cgroup path where we have write access and controllers are delegated
@@ -75,9 +228,11 @@ impl CgroupManager {
                 .and_then(|u| u.parse::<u32>().ok())
         });
 
+        let root = hierarchy::root();
+
         // Try the user's systemd scope (for non-root with cgroup delegation).
         if let Some(uid) = uid {
-            let user_slice = PathBuf::from(CGROUP_ROOT).join(format!(
+            let user_slice = root.join(format!(
                 "user.slice/user-{uid}.slice/user@{uid}.service/rlm"
             ));
 
@@ -88,9 +243,15 @@ impl CgroupManager {
             }
         }
 
+        // A subtree delegated some other way: a container's own cgroup, or
+        // that of a service with Delegate=yes. For root on a host this is
+        // the hierarchy's root.
+        if let Some(subtree) = hierarchy::delegated_subtree() {
+            return Ok(subtree.join("rlm"));
+        }
+
         // Fallback: try directly under cgroup root (requires root or delegation)
-        let root_path = PathBuf::from(CGROUP_ROOT).join("rlm");
-        Ok(root_path)
+        Ok(root.join("rlm"))
     }
 
     /// Get the base path (for testing/status)
@@ -101,6 +262,24 @@ impl CgroupManager {
     /// Create a cgroup for a process and set limits BEFORE adding the process
     /// Returns the cgroup path for later cleanup
     pub fn prepare_cgroup(&self, name: &str, limit: &Limit) -> Result<PathBuf> {
+        let result = self.create_limited_cgroup(name, limit);
+        self.record(Action::Apply, name, &[], Some(limit), &result);
+        result
+    }
+
+    /// `prepare_cgroup` for reconcile loops that re-assert limits on every
+    /// pass: only creating the cgroup goes into the history.
+    pub(crate) fn ensure_cgroup(&self, name: &str, limit: &Limit) -> Result<PathBuf> {
+        if self.cgroup_exists(name) {
+            self.create_limited_cgroup(name, limit)
+        } else {
+            self.prepare_cgroup(name, limit)
+        }
+    }
+
+    /// `prepare_cgroup` without the history record, for callers that record
+    /// the whole action themselves.
+    fn create_limited_cgroup(&self, name: &str, limit: &Limit) -> Result<PathBuf> {
         // Sanitize name to prevent path traversal
         let safe_name = sanitize_cgroup_name(name)?;
         let cgroup_path = self.base_path.join(safe_name);
@@ -120,16 +299,52 @@ impl CgroupManager {
             self.set_memory_limit(cgroup_path, *mem)?;
         }
 
+        // After memory: overrides the swap.max=0 default of a memory limit
+        if let Some(swap) = limit.swap {
+            self.set_swap_limit(cgroup_path, swap)?;
+        }
+
+        if limit.oom_group {
+            self.write(cgroup_path.join("memory.oom.group"), "1")
+                .map_err(|e| Error::Cgroup(format!("failed to set memory.oom.group: {e}")))?;
+        }
+
         if let Some(cpu) = &limit.cpu {
             self.set_cpu_limit(cgroup_path, *cpu)?;
         }
 
+        if let Some(weight) = limit.cpu_weight {
+            self.set_cpu_weight(cgroup_path, weight)?;
+        }
+
+        if limit.idle {
+            self.set_cpu_idle(cgroup_path)?;
+        }
+
         if let Some(io) = &limit.io {
             if !io.is_empty() {
-                self.set_io_limit(cgroup_path, *io)?;
+                self.set_io_limit(cgroup_path, *io, limit.io_path.as_deref())?;
             }
         }
 
+        if let Some(weight) = limit.io_weight {
+            self.set_io_weight(cgroup_path, weight)?;
+        }
+
+        if let Some(pids) = limit.pids {
+            self.set_pids_limit(cgroup_path, pids)?;
+        }
+
+        if let Some(mems) = &limit.mems {
+            self.set_mem_nodes(cgroup_path, mems)?;
+        }
+
+        // A fresh or updated limit resets memory.high; keep it under any
+        // active reservation's ceiling.
+        if self.reserved_total() > 0 || crate::session::headroom() > 0 {
+            let _ = self.apply_reservation_ceilings();
+        }
+
         Ok(())
     }
 
@@ -144,29 +359,39 @@ impl CgroupManager {
     /// Writing "0" to `cgroup.procs` from the post-fork, pre-exec child moves it
     /// into the cgroup. The file is opened in the parent so the closure performs
     /// only an async-signal-safe `write` to an already-open fd (no allocation, no
-    /// locks). Placement is best-effort: on failure the process still launches,
-    /// so callers should still call [`add_to_cgroup`](Self::add_to_cgroup) after
-    /// spawn as a fallback. Add command arguments to the returned `Command`.
-    pub fn placement_command(&self, cgroup_path: &Path, program: &str) -> Command {
+    /// locks). Placement is not best-effort: if the write fails the program is
+    /// never run and `spawn` returns the error, so nothing runs unlimited, and
+    /// there is nothing to add to the cgroup afterwards. Fails here when
+    /// `cgroup.procs` can't be opened (e.g. it belongs to root and only a
+    /// privileged helper may write it). Add command arguments to the returned
+    /// `Command`.
+    pub fn placement_command(&self, cgroup_path: &Path, program: &str) -> Result<Command> {
         use std::os::unix::process::CommandExt;
 
-        let mut cmd = Command::new(program);
-        if let Ok(file) = fs::OpenOptions::new()
-            .write(true)
-            .open(cgroup_path.join("cgroup.procs"))
-        {
-            // SAFETY: the closure only writes a fixed byte slice to an already-open
-            // file descriptor — an async-signal-safe operation that allocates
-            // nothing and takes no locks. Errors are ignored (best-effort).
-            unsafe {
-                cmd.pre_exec(move || {
-                    use std::io::Write;
-                    let _ = (&file).write_all(b"0");
-                    Ok(())
-                });
+        let procs = cgroup_path.join("cgroup.procs");
+        let file = match fs::OpenOptions::new().write(true).open(&procs) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(Error::PermissionDenied { path: procs });
             }
+            Err(e) => {
+                return Err(Error::Cgroup(format!(
+                    "failed to open {}: {e}",
+                    procs.display()
+                )))
+            }
+        };
+        let mut cmd = Command::new(program);
+        // SAFETY: the closure only writes a fixed byte slice to an already-open
+        // file descriptor — an async-signal-safe operation that allocates
+        // nothing and takes no locks. An error aborts the exec.
+        unsafe {
+            cmd.pre_exec(move || {
+                use std::io::Write;
+                (&file).write_all(b"0")
+            });
         }
-        cmd
+        Ok(cmd)
     }
 
     /// Add a process to an existing cgroup
@@ -178,16 +403,9 @@ impl CgroupManager {
 
     /// Find if a PID is already in an rlm-managed cgroup
     pub fn find_cgroup_for_pid(&self, pid: u32) -> Option<String> {
-        let entries = fs::read_dir(&self.base_path).ok()?;
-
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if !path.is_dir() {
-                continue;
-            }
-
+        for path in self.backend.children(&self.base_path).ok()? {
             let procs_file = path.join("cgroup.procs");
-            if let Ok(content) = fs::read_to_string(&procs_file) {
+            if let Ok(content) = self.read(&procs_file) {
                 for line in content.lines() {
                     if line.trim().parse::<u32>().ok() == Some(pid) {
                         return path.file_name()?.to_str().map(String::from);
@@ -198,8 +416,61 @@ impl CgroupManager {
         None
     }
 
+    /// The cgroup holding `pid`: its rlm cgroup if managed, otherwise the
+    /// one it is in (e.g. the app's systemd scope).
+    pub fn cgroup_path_for_pid(&self, pid: u32) -> Result<PathBuf> {
+        if let Some(name) = self.find_cgroup_for_pid(pid) {
+            return Ok(self.base_path.join(name));
+        }
+        let content = fs::read_to_string(format!("/proc/{pid}/cgroup"))
+            .map_err(|_| Error::ProcessNotFound(pid))?;
+        unified_cgroup(&content)
+            .and_then(hierarchy::dir_of)
+            .ok_or_else(|| Error::Cgroup(format!("process {pid} is not in a cgroup v2 hierarchy")))
+    }
+
+    /// Whether `pid` is in one of rlm's cgroups. Unlike `find_cgroup_for_pid`
+    /// this reads only `/proc/<pid>/cgroup`.
+    pub fn manages_pid(&self, pid: u32) -> bool {
+        self.proc_cgroup_path(pid)
+            .is_some_and(|p| p.starts_with(&self.base_path))
+    }
+
+    /// `find_cgroup_for_pid` reading only `/proc/<pid>/cgroup`, for callers
+    /// that ask on every exec.
+    pub(crate) fn managed_cgroup_of(&self, pid: u32) -> Option<String> {
+        let path = self.proc_cgroup_path(pid)?;
+        let name = path
+            .strip_prefix(&self.base_path)
+            .ok()?
+            .components()
+            .next()?;
+        Some(name.as_os_str().to_string_lossy().into_owned())
+    }
+
+    fn proc_cgroup_path(&self, pid: u32) -> Option<PathBuf> {
+        let content = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+        hierarchy::dir_of(unified_cgroup(&content)?)
+    }
+
+    /// Path of the managed cgroup `name` (e.g. "pid-1234"), if it exists.
+    pub fn managed_cgroup_path(&self, name: &str) -> Result<PathBuf> {
+        let path = self.base_path.join(sanitize_cgroup_name(name)?);
+        if !self.backend.is_dir(&path) {
+            return Err(Error::Cgroup(format!("no managed cgroup named {name}")));
+        }
+        Ok(path)
+    }
+
     /// Apply resource limits to a process (creates cgroup and adds process)
     pub fn apply_limit(&self, pid: u32, limit: &Limit) -> Result<()> {
+        let result = self.apply_limit_inner(pid, limit);
+        let action = *result.as_ref().unwrap_or(&Action::Apply);
+        self.record(action, &format!("pid-{pid}"), &[pid], Some(limit), &result);
+        result.map(|_| ())
+    }
+
+    fn apply_limit_inner(&self, pid: u32, limit: &Limit) -> Result<Action> {
         reject_critical_pid(pid)?;
 
         // Check if process is already managed
@@ -209,7 +480,7 @@ impl CgroupManager {
                 let cgroup_path = self.base_path.join(&existing_cgroup);
                 self.set_limits(&cgroup_path, limit)?;
                 tracing::info!(pid, "updated existing limits");
-                return Ok(());
+                return Ok(Action::Update);
             }
             // Process is in a different cgroup (run-* or gtk-*)
             return Err(Error::InvalidArgs(format!(
@@ -218,7 +489,7 @@ impl CgroupManager {
             )));
         }
 
-        let cgroup_path = self.prepare_cgroup(&format!("pid-{pid}"), limit)?;
+        let cgroup_path = self.create_limited_cgroup(&format!("pid-{pid}"), limit)?;
 
         // Try to add process - if it fails because process doesn't exist,
         // clean up the cgroup and return appropriate error
@@ -233,7 +504,7 @@ impl CgroupManager {
         }
 
         tracing::info!(pid, ?cgroup_path, "applied limits");
-        Ok(())
+        Ok(Action::Apply)
     }
 
     /// Apply resource limits to multiple processes (all share the same limit pool)
@@ -244,6 +515,17 @@ impl CgroupManager {
         pids: &[u32],
         limit: &Limit,
         cgroup_name: &str,
+    ) -> Result<()> {
+        let result = self.apply_limit_to_multiple_inner(pids, limit, cgroup_name);
+        self.record(Action::Apply, cgroup_name, pids, Some(limit), &result);
+        result
+    }
+
+    fn apply_limit_to_multiple_inner(
+        &self,
+        pids: &[u32],
+        limit: &Limit,
+        cgroup_name: &str,
     ) -> Result<()> {
         if pids.is_empty() {
             return Err(Error::InvalidArgs("no processes specified".into()));
@@ -270,7 +552,7 @@ impl CgroupManager {
         }
 
         // Create cgroup and set limits
-        let cgroup_path = self.prepare_cgroup(safe_name, limit)?;
+        let cgroup_path = self.create_limited_cgroup(safe_name, limit)?;
 
         // Add all processes to the cgroup
         let mut failed_pids = Vec::new();
@@ -305,27 +587,63 @@ impl CgroupManager {
 
     /// Remove limits from a process
     pub fn remove_limit(&self, pid: u32) -> Result<()> {
-        self.cleanup_cgroup(&format!("pid-{pid}"))
+        let cgroup = format!("pid-{pid}");
+        let result = self.cleanup_cgroup(&cgroup);
+        self.record(Action::Remove, &cgroup, &[pid], None, &result);
+        result
     }
 
     /// Remove limits from an application cgroup (removes all processes in the cgroup)
     pub fn remove_application_limit(&self, cgroup_name: &str) -> Result<()> {
-        self.cleanup_cgroup(cgroup_name)
+        let pids = self.pids_in_cgroup(cgroup_name);
+        let result = self.cleanup_cgroup(cgroup_name);
+        self.record(Action::Remove, cgroup_name, &pids, None, &result);
+        result
     }
 
     /// Clean up a cgroup by name (moves processes out and deletes cgroup)
     pub fn cleanup_cgroup(&self, name: &str) -> Result<()> {
         // Sanitize name to prevent path traversal
         let safe_name = sanitize_cgroup_name(name)?;
+        let result = self.remove_cgroup(safe_name);
+
+        // However a reservation goes away (unreserve, unlimit, status reaping),
+        // the ceilings it imposed on the other cgroups must go with it.
+        if safe_name.starts_with("reserve-") {
+            let _ = self.apply_reservation_ceilings();
+        }
+        result
+    }
+
+    /// Remove the cgroup `name` if nothing is left in it, for cgroups several
+    /// invocations share. Unlike [`cleanup_cgroup`](Self::cleanup_cgroup) it
+    /// never moves processes out: the kernel refuses the removal while any
+    /// remain, so one that joins meanwhile keeps its limits. Returns whether
+    /// the cgroup is gone.
+    pub fn remove_if_empty(&self, name: &str) -> Result<bool> {
+        let safe_name = sanitize_cgroup_name(name)?;
+        match self.backend.remove_dir(&self.base_path.join(safe_name)) {
+            Ok(()) => tracing::info!(cgroup = safe_name, "removed cgroup"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) if e.raw_os_error() == Some(libc::EBUSY) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+        if self.recording {
+            crate::state::forget(safe_name);
+        }
+        Ok(true)
+    }
+
+    fn remove_cgroup(&self, safe_name: &str) -> Result<()> {
         let cgroup_path = self.base_path.join(safe_name);
 
-        if !cgroup_path.exists() {
+        if !self.backend.is_dir(&cgroup_path) {
             return Ok(());
         }
 
         // Move any processes out to the controller-free "unlimit" cgroup so this
         // cgroup becomes empty and can be removed.
-        if let Ok(content) = fs::read_to_string(cgroup_path.join("cgroup.procs")) {
+        if let Ok(content) = self.read(cgroup_path.join("cgroup.procs")) {
             let pids: Vec<u32> = content
                 .lines()
                 .filter_map(|l| l.trim().parse().ok())
@@ -334,11 +652,11 @@ impl CgroupManager {
             if !pids.is_empty() {
                 // Create/use an "unlimit" leaf cgroup (no controllers = no limits)
                 let unlimit_path = self.base_path.join("unlimit");
-                let _ = fs::create_dir(&unlimit_path);
+                let _ = self.backend.create_dir(&unlimit_path);
                 let unlimit_procs = unlimit_path.join("cgroup.procs");
 
                 for pid in pids {
-                    if fs::write(&unlimit_procs, pid.to_string()).is_ok() {
+                    if self.write(&unlimit_procs, pid.to_string()).is_ok() {
                         tracing::debug!(pid, "moved process to unlimit cgroup");
                     }
                 }
@@ -347,12 +665,20 @@ impl CgroupManager {
 
         // Try to remove the (now hopefully empty) cgroup.
         for _ in 0..3 {
-            match fs::remove_dir(&cgroup_path) {
+            match self.backend.remove_dir(&cgroup_path) {
                 Ok(()) => {
                     tracing::info!(?cgroup_path, "removed cgroup");
+                    if self.recording {
+                        crate::state::forget(safe_name);
+                    }
+                    return Ok(());
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    if self.recording {
+                        crate::state::forget(safe_name);
+                    }
                     return Ok(());
                 }
-                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
                 Err(_) => std::thread::sleep(std::time::Duration::from_millis(50)),
             }
         }
@@ -360,19 +686,16 @@ impl CgroupManager {
         // Removal failed. If processes are still inside (couldn't be moved out),
         // reset the limits in place so the caller's "remove limits" intent is
         // still satisfied — report success but warn that the cgroup lingers.
-        let still_has_procs = fs::read_to_string(cgroup_path.join("cgroup.procs"))
+        let still_has_procs = self
+            .read(cgroup_path.join("cgroup.procs"))
             .map(|c| c.lines().any(|l| !l.trim().is_empty()))
             .unwrap_or(false);
 
         if still_has_procs {
             // Defensive: if this is a frozen guard cgroup we couldn't empty, at
             // least unfreeze it so its tasks are never stuck paused.
-            let _ = fs::write(cgroup_path.join("cgroup.freeze"), "0");
-            let _ = fs::write(cgroup_path.join("memory.high"), "max");
-            let _ = fs::write(cgroup_path.join("memory.max"), "max");
-            let _ = fs::write(cgroup_path.join("memory.swap.max"), "max");
-            let _ = fs::write(cgroup_path.join("cpu.max"), "max");
-            let _ = fs::write(cgroup_path.join("io.max"), "");
+            let _ = self.write(cgroup_path.join("cgroup.freeze"), "0");
+            self.reset_limits(&cgroup_path);
             tracing::warn!(
                 ?cgroup_path,
                 "could not remove cgroup (still has live processes); limits reset in place"
@@ -386,6 +709,123 @@ impl CgroupManager {
         )))
     }
 
+    /// Replace the limits of the managed cgroup `name`: settings `limit`
+    /// leaves unset go back to unlimited. Its processes stay where they are.
+    pub(crate) fn replace_limits(&self, name: &str, limit: &Limit) -> Result<()> {
+        let path = self.managed_cgroup_path(name)?;
+        self.reset_limits(&path);
+        self.set_limits(&path, limit)
+    }
+
+    /// Lift the limits `controllers` set on the managed cgroup `name`, leaving
+    /// its other limits and its processes where they are. Reservations
+    /// (`memory.low`/`memory.min`) are not limits and stay, as does the
+    /// ceiling of any active reservation on it.
+    pub fn reset_controllers(&self, name: &str, controllers: &[Controller]) -> Result<()> {
+        let path = self.managed_cgroup_path(name)?;
+        let result = controllers
+            .iter()
+            .try_for_each(|&controller| self.reset_controller(&path, controller));
+        // Lifting memory.high lifts the ceiling too; put it back
+        if controllers.contains(&Controller::Memory)
+            && (self.reserved_total() > 0 || crate::session::headroom() > 0)
+        {
+            let _ = self.apply_reservation_ceilings();
+        }
+        self.record(
+            Action::Update,
+            name,
+            &self.pids_in_cgroup(name),
+            None,
+            &result,
+        );
+        result
+    }
+
+    fn reset_controller(&self, cgroup_path: &Path, controller: Controller) -> Result<()> {
+        let reset = |file: &str, value: &str| {
+            self.write(cgroup_path.join(file), value)
+                .map_err(|e| Error::Cgroup(format!("failed to reset {file}: {e}")))
+        };
+        match controller {
+            Controller::Memory => {
+                reset("memory.max", "max")?;
+                // Best-effort, as when set
+                let _ = self.write(cgroup_path.join("memory.high"), "max");
+                let _ = self.write(cgroup_path.join("memory.swap.max"), "max");
+                let _ = self.write(cgroup_path.join("memory.oom.group"), "0");
+            }
+            Controller::Cpu => {
+                reset("cpu.max", "max")?;
+                reset("cpu.weight", "100")?;
+                let _ = self.write(cgroup_path.join("cpu.idle"), "0");
+            }
+            Controller::Io => {
+                // io.max lists only the devices with a limit; each is lifted
+                // by writing max for all four of its keys
+                let io_max = self.read(cgroup_path.join("io.max")).unwrap_or_default();
+                for device in io_max.lines().filter_map(|l| l.split_whitespace().next()) {
+                    reset(
+                        "io.max",
+                        &format!("{device} rbps=max wbps=max riops=max wiops=max"),
+                    )?;
+                }
+                let _ = self.write(cgroup_path.join("io.weight"), "default 100");
+            }
+        }
+        Ok(())
+    }
+
+    /// `cpu.weight` of the managed cgroup `name`.
+    pub(crate) fn cpu_weight_of(&self, name: &str) -> Option<u32> {
+        let path = self.managed_cgroup_path(name).ok()?;
+        self.read(path.join("cpu.weight")).ok()?.trim().parse().ok()
+    }
+
+    /// Set `cpu.weight` of the managed cgroup `name`, leaving its other
+    /// limits alone.
+    pub(crate) fn set_cpu_weight_of(&self, name: &str, weight: u32) -> Result<()> {
+        let path = self.managed_cgroup_path(name)?;
+        self.set_cpu_weight(&path, CpuWeight::new(weight)?)
+    }
+
+    /// Freeze or thaw every process in the managed cgroup `name`.
+    pub fn set_frozen(&self, name: &str, frozen: bool) -> Result<()> {
+        let path = self.managed_cgroup_path(name)?;
+        self.write(path.join("cgroup.freeze"), if frozen { "1" } else { "0" })
+            .map_err(|e| Error::Cgroup(format!("failed to freeze {name}: {e}")))?;
+        tracing::info!(cgroup = name, frozen, "set cgroup frozen state");
+        Ok(())
+    }
+
+    /// Whether the managed cgroup `name` is frozen.
+    pub fn is_frozen(&self, name: &str) -> bool {
+        self.read(self.base_path.join(name).join("cgroup.freeze"))
+            .is_ok_and(|c| c.trim() == "1")
+    }
+
+    /// SIGKILL every process in the managed cgroup `name`, through
+    /// `cgroup.kill` (Linux 5.14+) or one by one on older kernels.
+    pub fn kill_cgroup(&self, name: &str) -> Result<()> {
+        let path = self.managed_cgroup_path(name)?;
+        if self.write(path.join("cgroup.kill"), "1").is_err() {
+            self.signal_cgroup(name, libc::SIGKILL)?;
+        }
+        tracing::info!(cgroup = name, "killed cgroup");
+        Ok(())
+    }
+
+    /// Send `signal` to every process in the managed cgroup `name`.
+    pub fn signal_cgroup(&self, name: &str, signal: i32) -> Result<()> {
+        let path = self.managed_cgroup_path(name)?;
+        let content = self.read(path.join("cgroup.procs"))?;
+        for pid in content.lines().filter_map(|l| l.trim().parse::<i32>().ok()) {
+            // SAFETY: plain syscall; a pid that already exited just fails
+            unsafe { libc::kill(pid, signal) };
+        }
+        Ok(())
+    }
+
     // ---- Freeze-guard primitives -----------------------------------------
     // Used by the guard Effector. A guard target lives in its own `guard-<pid>`
     // cgroup: freeze toggles `cgroup.freeze`, soft-cap sets `memory.high`.
@@ -405,7 +845,7 @@ impl CgroupManager {
     /// Move `pid` into its guard cgroup and freeze it (cgroup v2 freezer).
     pub fn freeze_pid(&self, pid: u32) -> Result<()> {
         let path = self.ensure_guard_cgroup(pid)?;
-        fs::write(path.join("cgroup.freeze"), "1")
+        self.write(path.join("cgroup.freeze"), "1")
             .map_err(|e| Error::Cgroup(format!("failed to freeze {pid}: {e}")))?;
         tracing::info!(pid, "froze process");
         Ok(())
@@ -414,8 +854,8 @@ impl CgroupManager {
     /// Resume a frozen process. The process stays in its guard cgroup.
     pub fn thaw_pid(&self, pid: u32) -> Result<()> {
         let path = self.guard_path(pid);
-        if path.exists() {
-            fs::write(path.join("cgroup.freeze"), "0")
+        if self.backend.is_dir(&path) {
+            self.write(path.join("cgroup.freeze"), "0")
                 .map_err(|e| Error::Cgroup(format!("failed to thaw {pid}: {e}")))?;
             tracing::info!(pid, "thawed process");
         }
@@ -425,7 +865,7 @@ impl CgroupManager {
     /// Soft-cap a process via `memory.high` (throttle/reclaim, never OOM-kill).
     pub fn soft_cap_pid(&self, pid: u32, high_bytes: u64) -> Result<()> {
         let path = self.ensure_guard_cgroup(pid)?;
-        fs::write(path.join("memory.high"), high_bytes.to_string())
+        self.write(path.join("memory.high"), high_bytes.to_string())
             .map_err(|e| Error::Cgroup(format!("failed to cap {pid}: {e}")))?;
         tracing::info!(pid, high_bytes, "soft-capped process");
         Ok(())
@@ -434,8 +874,8 @@ impl CgroupManager {
     /// Remove a soft cap (set `memory.high=max`).
     pub fn lift_cap_pid(&self, pid: u32) -> Result<()> {
         let path = self.guard_path(pid);
-        if path.exists() {
-            let _ = fs::write(path.join("memory.high"), "max");
+        if self.backend.is_dir(&path) {
+            let _ = self.write(path.join("memory.high"), "max");
             tracing::info!(pid, "lifted soft cap");
         }
         Ok(())
@@ -452,9 +892,9 @@ impl CgroupManager {
     /// List PIDs that currently have a `guard-<pid>` cgroup.
     pub fn list_guard_pids(&self) -> Vec<u32> {
         let mut pids = Vec::new();
-        if let Ok(entries) = fs::read_dir(&self.base_path) {
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
+        if let Ok(children) = self.backend.children(&self.base_path) {
+            for child in children {
+                if let Some(name) = child.file_name().and_then(|n| n.to_str()) {
                     if let Some(rest) = name.strip_prefix("guard-") {
                         if let Ok(pid) = rest.parse::<u32>() {
                             pids.push(pid);
@@ -468,13 +908,13 @@ impl CgroupManager {
 
     /// Whether a child cgroup with this name currently exists.
     pub fn cgroup_exists(&self, name: &str) -> bool {
-        self.base_path.join(name).is_dir()
+        self.backend.is_dir(&self.base_path.join(name))
     }
 
     /// PIDs currently in the named child cgroup (empty if it doesn't exist).
     pub fn pids_in_cgroup(&self, name: &str) -> Vec<u32> {
         let procs = self.base_path.join(name).join("cgroup.procs");
-        match fs::read_to_string(procs) {
+        match self.read(procs) {
             Ok(content) => content
                 .lines()
                 .filter_map(|l| l.trim().parse::<u32>().ok())
@@ -483,6 +923,25 @@ impl CgroupManager {
         }
     }
 
+    /// The managed cgroups that hold limits, by name, sorted: all but the
+    /// guard's freezer cgroups and the `protect`, `rescue`, and `unlimit`
+    /// cgroups, which are switches and holding pens rather than limits.
+    pub fn limit_cgroups(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .backend
+            .children(&self.base_path)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|path| path.file_name()?.to_str().map(String::from))
+            .filter(|name| {
+                !name.starts_with("guard-")
+                    && ![PROTECT_CGROUP, RESCUE_CGROUP, "unlimit"].contains(&name.as_str())
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
     /// Startup recovery: thaw and clean up every leftover guard cgroup so no
     /// process is left frozen after a prior crash.
     pub fn sweep_guard_leftovers(&self) -> Result<()> {
@@ -493,9 +952,312 @@ impl CgroupManager {
         Ok(())
     }
 
+    // ---- Memory reservations -----------------------------------------------
+    // A reserved app lives in `reserve-<name>` with `memory.low` set, so the
+    // kernel reclaims from it last. Every other managed cgroup gets a
+    // `memory.high` ceiling of MemTotal minus everything reserved (session
+    // headroom included), so together they can't eat into the headroom.
+    // Ceilings are recomputed from each cgroup's own `memory.max`, so undoing
+    // a reservation needs no saved state.
+
+    /// Reserve `bytes` of memory for `pids` (one application) in
+    /// `reserve-<name>`. Returns the ceiling now applied to other cgroups.
+    ///
+    /// With `hard`, `memory.min` is set as well: the kernel then never
+    /// reclaims the reserved memory, and OOM-kills elsewhere instead.
+    pub fn reserve_memory(&self, pids: &[u32], name: &str, bytes: u64, hard: bool) -> Result<u64> {
+        if pids.is_empty() {
+            return Err(Error::InvalidArgs("no processes specified".into()));
+        }
+        for pid in pids {
+            reject_critical_pid(*pid)?;
+        }
+
+        let cgroup_name = format!("reserve-{name}");
+        let safe_name = sanitize_cgroup_name(&cgroup_name)?;
+
+        for pid in pids {
+            if let Some(existing_cgroup) = self.find_cgroup_for_pid(*pid) {
+                if existing_cgroup != safe_name {
+                    return Err(Error::InvalidArgs(format!(
+                        "process {} is already managed in cgroup '{}'",
+                        pid, existing_cgroup
+                    )));
+                }
+            }
+        }
+
+        // Re-reserving replaces this app's previous amount rather than adding to it.
+        let cgroup_path = self.base_path.join(safe_name);
+        let others = (self.reserved_total() + crate::session::headroom())
+            .saturating_sub(self.memory_low(&cgroup_path).unwrap_or(0));
+        let mem_total = mem_total_bytes()?;
+        if others.saturating_add(bytes) >= mem_total {
+            return Err(Error::InvalidMemory(format!(
+                "cannot reserve {} ({} already reserved, {} total RAM)",
+                common::format_bytes(bytes),
+                common::format_bytes(others),
+                common::format_bytes(mem_total)
+            )));
+        }
+
+        self.create_cgroup(&cgroup_path)?;
+        if let Err(e) = self.write(cgroup_path.join("memory.low"), bytes.to_string()) {
+            let _ = self.cleanup_cgroup(safe_name);
+            return Err(Error::Cgroup(format!("failed to set memory.low: {e}")));
+        }
+        // Written either way, so re-reserving without `hard` drops it again
+        let min = if hard { bytes } else { 0 };
+        if let Err(e) = self.write(cgroup_path.join("memory.min"), min.to_string()) {
+            if hard {
+                let _ = self.cleanup_cgroup(safe_name);
+                return Err(Error::Cgroup(format!("failed to set memory.min: {e}")));
+            }
+        }
+
+        let mut added = 0;
+        for pid in pids {
+            match self.add_process(&cgroup_path, *pid) {
+                Ok(()) => added += 1,
+                Err(e) => tracing::warn!(pid, error = %e, "failed to add process to reservation"),
+            }
+        }
+        if added == 0 {
+            let _ = self.cleanup_cgroup(safe_name);
+            return Err(Error::InvalidArgs(
+                "failed to add any processes to cgroup".into(),
+            ));
+        }
+
+        self.apply_reservation_ceilings()?;
+        tracing::info!(?cgroup_path, bytes, hard, "reserved memory");
+        Ok(mem_total - others - bytes)
+    }
+
+    /// Undo [`reserve_memory`](Self::reserve_memory): release the app's
+    /// processes and lift the ceilings on everything else.
+    pub fn release_reservation(&self, name: &str) -> Result<()> {
+        self.cleanup_cgroup(&format!("reserve-{name}"))
+    }
+
+    /// Sum of `memory.low` across all `reserve-*` cgroups.
+    pub(crate) fn reserved_total(&self) -> u64 {
+        let Ok(children) = self.backend.children(&self.base_path) else {
+            return 0;
+        };
+        children
+            .iter()
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with("reserve-"))
+            })
+            .filter_map(|p| self.memory_low(p))
+            .sum()
+    }
+
+    /// Recompute `memory.high` on every non-reserved child from the current
+    /// reservations and session headroom. Guard cgroups are skipped: the
+    /// guard owns their `memory.high` for soft caps.
+    pub(crate) fn apply_reservation_ceilings(&self) -> Result<()> {
+        let reserved = self.reserved_total();
+        let mem_total = mem_total_bytes()?;
+        let ceiling = self.reservation_ceiling(mem_total);
+
+        // Protection is bounded by the parent's: without this, memory.low only
+        // ranks the reservation against its siblings, not the rest of the session.
+        let _ = self.write(self.base_path.join("memory.low"), reserved.to_string());
+
+        let Ok(children) = self.backend.children(&self.base_path) else {
+            return Ok(());
+        };
+        for path in children {
+            let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            if name.starts_with("reserve-") || name.starts_with("guard-") {
+                continue;
+            }
+            let own = if name == PROTECT_CGROUP {
+                Some(mem_total / 100 * PROTECT_MEMORY_HIGH_PERCENT)
+            } else if name == RESCUE_CGROUP {
+                Some(RESCUE_MEMORY_HIGH)
+            } else {
+                self.read(path.join("memory.max"))
+                    .ok()
+                    .and_then(|max| soft_limit_of(max.trim()))
+            };
+            let high = ceiling_high(own, ceiling);
+            if let Err(e) = self.write(path.join("memory.high"), &high) {
+                tracing::warn!(?path, error = %e, "failed to set reservation ceiling");
+            }
+        }
+        Ok(())
+    }
+
+    /// `memory.high` ceiling for non-reserved cgroups, if anything is
+    /// reserved (including headroom for the session, see [`crate::session`]).
+    fn reservation_ceiling(&self, mem_total: u64) -> Option<u64> {
+        let reserved = self.reserved_total() + crate::session::headroom();
+        (reserved > 0).then(|| mem_total.saturating_sub(reserved))
+    }
+
+    // ---- Desktop protection -----------------------------------------------
+    // The `protect` cgroup holds the user's session apps under one
+    // conservative `memory.high`/`cpu.weight`; [`crate::protect`] decides
+    // which processes go in.
+
+    /// Create the `protect` cgroup and apply its settings. Idempotent.
+    pub fn enable_protect(&self) -> Result<PathBuf> {
+        let path = self.base_path.join(PROTECT_CGROUP);
+        self.create_cgroup(&path)?;
+
+        let mem_total = mem_total_bytes()?;
+        let high = ceiling_high(
+            Some(mem_total / 100 * PROTECT_MEMORY_HIGH_PERCENT),
+            self.reservation_ceiling(mem_total),
+        );
+        self.write(path.join("memory.high"), high)
+            .map_err(|e| Error::Cgroup(format!("failed to set memory.high: {e}")))?;
+        self.write(path.join("cpu.weight"), PROTECT_CPU_WEIGHT.to_string())
+            .map_err(|e| Error::Cgroup(format!("failed to set cpu.weight: {e}")))?;
+
+        tracing::info!(?path, "desktop protection enabled");
+        Ok(path)
+    }
+
+    /// Remove the `protect` cgroup, releasing every process in it.
+    pub fn disable_protect(&self) -> Result<()> {
+        self.cleanup_cgroup(PROTECT_CGROUP)
+    }
+
+    /// Whether desktop protection is on.
+    pub fn protect_enabled(&self) -> bool {
+        self.cgroup_exists(PROTECT_CGROUP)
+    }
+
+    /// Move a process into the `protect` cgroup. Unlike
+    /// [`add_to_cgroup`](Self::add_to_cgroup) this logs at debug level, since
+    /// enabling protection moves hundreds of processes at once.
+    pub fn move_to_protect(&self, pid: u32) -> Result<()> {
+        reject_critical_pid(pid)?;
+        self.add_process(&self.base_path.join(PROTECT_CGROUP), pid)?;
+        tracing::debug!(pid, "moved process to protect cgroup");
+        Ok(())
+    }
+
+    // ---- Rescue -------------------------------------------------------------
+    // The `rescue` cgroup holds the processes `rlm rescue` picked, throttled
+    // hard on every resource; [`crate::rescue`] picks them and records where
+    // they came from.
+
+    /// Create the `rescue` cgroup and apply its settings. Idempotent.
+    pub fn enable_rescue(&self) -> Result<PathBuf> {
+        let limit = Limit {
+            cpu: Some(CpuLimit::parse(&format!("{RESCUE_CPU_PERCENT}%"))?),
+            cpu_weight: Some(CpuWeight::new(RESCUE_WEIGHT)?),
+            ..Default::default()
+        };
+        let path = self.ensure_cgroup(RESCUE_CGROUP, &limit)?;
+        let high = ceiling_high(
+            Some(RESCUE_MEMORY_HIGH),
+            self.reservation_ceiling(mem_total_bytes()?),
+        );
+        self.write(path.join("memory.high"), high)
+            .map_err(|e| Error::Cgroup(format!("failed to set memory.high: {e}")))?;
+        // Best-effort: io.weight needs the io controller and a weight-based
+        // I/O scheduler
+        let _ = self.set_io_weight(&path, IoWeight::new(RESCUE_WEIGHT)?);
+        Ok(path)
+    }
+
+    /// Move a process into the `rescue` cgroup.
+    pub fn move_to_rescue(&self, pid: u32) -> Result<()> {
+        reject_critical_pid(pid)?;
+        self.add_to_cgroup(&self.base_path.join(RESCUE_CGROUP), pid)
+    }
+
+    /// Whether anything is held in the `rescue` cgroup.
+    pub fn rescue_active(&self) -> bool {
+        self.cgroup_exists(RESCUE_CGROUP)
+    }
+
+    // ---- Proactive reclaim ------------------------------------------------
+
+    /// Ask the kernel to reclaim up to `bytes` from the cgroup holding `pid`
+    /// (`memory.reclaim`, Linux 5.19+), pushing an idle app's memory out to
+    /// swap or disk without restarting it. Uses the rlm cgroup if the process
+    /// is managed, otherwise its own (e.g. the app's systemd scope). Returns
+    /// that cgroup and how much its usage dropped.
+    pub fn reclaim_pid(&self, pid: u32, bytes: u64) -> Result<(PathBuf, u64)> {
+        let cgroup_path = self.cgroup_path_for_pid(pid)?;
+
+        let before = self.memory_current(&cgroup_path).unwrap_or(0);
+        match self.write(cgroup_path.join("memory.reclaim"), bytes.to_string()) {
+            Ok(()) => {}
+            // Less than asked for was reclaimable; what did go still counts
+            Err(e) if e.raw_os_error() == Some(libc::EAGAIN) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::Cgroup(format!(
+                    "memory.reclaim not available in {} (needs Linux 5.19+ and the memory controller)",
+                    cgroup_path.display()
+                )));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(Error::PermissionDenied { path: cgroup_path });
+            }
+            Err(e) => {
+                return Err(Error::Cgroup(format!(
+                    "failed to write memory.reclaim: {e}"
+                )))
+            }
+        }
+        let after = self.memory_current(&cgroup_path).unwrap_or(before);
+        tracing::info!(?cgroup_path, bytes, "reclaimed memory");
+        Ok((cgroup_path, before.saturating_sub(after)))
+    }
+
+    /// Put every limit of the cgroup at `cgroup_path` back to the kernel
+    /// default. Best-effort: controllers that are not enabled have no files
+    /// to write.
+    fn reset_limits(&self, cgroup_path: &Path) {
+        for (file, value) in [
+            ("memory.high", "max"),
+            ("memory.max", "max"),
+            ("memory.low", "0"),
+            ("memory.min", "0"),
+            ("memory.swap.max", "max"),
+            ("memory.oom.group", "0"),
+            ("cpu.max", "max"),
+            ("cpu.weight", "100"),
+            ("cpu.idle", "0"),
+            ("io.max", ""),
+            ("io.weight", "default 100"),
+            ("pids.max", "max"),
+            ("cpuset.mems", ""),
+        ] {
+            let _ = self.write(cgroup_path.join(file), value);
+        }
+    }
+
+    fn memory_current(&self, cgroup_path: &Path) -> Option<u64> {
+        self.read(cgroup_path.join("memory.current"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// `memory.low` of a cgroup in bytes, if set (0 counts as unset).
+    fn memory_low(&self, cgroup_path: &Path) -> Option<u64> {
+        self.read(cgroup_path.join("memory.low"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .filter(|&b| b > 0)
+    }
+
     fn create_cgroup(&self, path: &Path) -> Result<()> {
         // Ensure base path exists (create_dir_all is idempotent, avoids TOCTOU)
-        if let Err(e) = fs::create_dir_all(&self.base_path) {
+        if let Err(e) = self.backend.create_dir_all(&self.base_path) {
             if e.kind() == std::io::ErrorKind::PermissionDenied {
                 return Err(Error::PermissionDenied {
                     path: self.base_path.clone(),
@@ -509,8 +1271,15 @@ impl CgroupManager {
         self.enable_controllers(&self.base_path)?;
 
         // Create cgroup directory (handle AlreadyExists to avoid TOCTOU)
-        match fs::create_dir(path) {
-            Ok(()) => Ok(()),
+        match self.backend.create_dir(path) {
+            Ok(()) => {
+                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                    if self.recording {
+                        crate::state::record(name);
+                    }
+                }
+                Ok(())
+            }
             Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
             Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
                 Err(Error::PermissionDenied {
@@ -526,11 +1295,11 @@ impl CgroupManager {
 
         // Read available controllers first
         let controllers_file = path.join("cgroup.controllers");
-        let available = fs::read_to_string(&controllers_file).unwrap_or_default();
+        let available = self.read(&controllers_file).unwrap_or_default();
 
         // Only enable controllers that are available
         let mut to_enable = Vec::new();
-        for controller in ["memory", "cpu", "io"] {
+        for controller in ["memory", "cpu", "io", "pids", "cpuset"] {
             if available.contains(controller) {
                 to_enable.push(format!("+{controller}"));
             }
@@ -542,7 +1311,7 @@ impl CgroupManager {
             ));
         }
 
-        fs::write(&subtree_control, to_enable.join(" ")).map_err(|e| {
+        self.write(&subtree_control, to_enable.join(" ")).map_err(|e| {
             if e.kind() == std::io::ErrorKind::PermissionDenied {
                 Error::Cgroup(
                     "cannot enable cgroup controllers - run as root or configure systemd cgroup delegation".into()
@@ -563,18 +1332,18 @@ impl CgroupManager {
         // instead of being killed outright. Best-effort.
         let high = bytes / 100 * 90;
         if high > 0 {
-            let _ = fs::write(cgroup_path.join("memory.high"), high.to_string());
+            let _ = self.write(cgroup_path.join("memory.high"), high.to_string());
         }
 
         // memory.max: hard cap. Process is OOM-killed if it exceeds this.
         let memory_max = cgroup_path.join("memory.max");
-        fs::write(&memory_max, bytes.to_string())
+        self.write(&memory_max, bytes.to_string())
             .map_err(|e| Error::Cgroup(format!("failed to set memory.max: {e}")))?;
 
         // memory.swap.max=0: prevent the limited process from spilling to swap, so
         // memory.max is a true RAM ceiling rather than an invitation to thrash.
         // Best-effort: absent on kernels without swap accounting.
-        let _ = fs::write(cgroup_path.join("memory.swap.max"), "0");
+        let _ = self.write(cgroup_path.join("memory.swap.max"), "0");
 
         Ok(())
     }
@@ -590,22 +1359,99 @@ impl CgroupManager {
             .ok_or_else(|| Error::InvalidCpu("CPU percentage too large".into()))?;
 
         let cpu_max = cgroup_path.join("cpu.max");
-        fs::write(&cpu_max, format!("{quota} {period}"))
+        self.write(&cpu_max, format!("{quota} {period}"))
             .map_err(|e| Error::Cgroup(format!("failed to set cpu.max: {e}")))?;
         Ok(())
     }
 
+    fn set_swap_limit(&self, cgroup_path: &Path, limit: SwapLimit) -> Result<()> {
+        self.write(
+            cgroup_path.join("memory.swap.max"),
+            limit.bytes().to_string(),
+        )
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::Cgroup("memory.swap.max not available (swap accounting disabled?)".into())
+            } else {
+                Error::Cgroup(format!("failed to set memory.swap.max: {e}"))
+            }
+        })
+    }
+
+    fn set_cpu_weight(&self, cgroup_path: &Path, weight: CpuWeight) -> Result<()> {
+        self.write(cgroup_path.join("cpu.weight"), weight.weight().to_string())
+            .map_err(|e| Error::Cgroup(format!("failed to set cpu.weight: {e}")))
+    }
+
+    fn set_cpu_idle(&self, cgroup_path: &Path) -> Result<()> {
+        self.write(cgroup_path.join("cpu.idle"), "1").map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::Cgroup("cpu.idle not supported (needs Linux 5.15+)".into())
+            } else {
+                Error::Cgroup(format!("failed to set cpu.idle: {e}"))
+            }
+        })
+    }
+
+    fn set_io_weight(&self, cgroup_path: &Path, weight: IoWeight) -> Result<()> {
+        self.write(
+            cgroup_path.join("io.weight"),
+            format!("default {}", weight.weight()),
+        )
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                Error::Cgroup("io.weight not available (needs the BFQ scheduler or io.cost)".into())
+            } else {
+                Error::Cgroup(format!("failed to set io.weight: {e}"))
+            }
+        })
+    }
+
+    fn set_pids_limit(&self, cgroup_path: &Path, limit: PidsLimit) -> Result<()> {
+        self.write(cgroup_path.join("pids.max"), limit.count().to_string())
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    Error::Cgroup("pids controller not available - check cgroup delegation".into())
+                } else {
+                    Error::Cgroup(format!("failed to set pids.max: {e}"))
+                }
+            })
+    }
+
+    fn set_mem_nodes(&self, cgroup_path: &Path, mems: &MemNodes) -> Result<()> {
+        self.write(cgroup_path.join("cpuset.mems"), mems.as_str())
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Error::Cgroup(
+                    "cpuset controller not available - check cgroup delegation".into(),
+                ),
+                // EINVAL: a node that doesn't exist or isn't allowed to the parent
+                std::io::ErrorKind::InvalidInput => Error::InvalidArgs(format!(
+                    "NUMA node(s) '{}' not available (see /sys/devices/system/node/online)",
+                    mems.as_str()
+                )),
+                _ => Error::Cgroup(format!("failed to set cpuset.mems: {e}")),
+            })
+    }
+
     fn add_process(&self, cgroup_path: &Path, pid: u32) -> Result<()> {
         let procs = cgroup_path.join("cgroup.procs");
-        fs::write(&procs, pid.to_string())
+        self.write(&procs, pid.to_string())
             .map_err(|e| Error::Cgroup(format!("failed to add process {pid}: {e}")))?;
         Ok(())
     }
 
-    fn set_io_limit(&self, cgroup_path: &Path, limit: IoLimit) -> Result<()> {
+    fn set_io_limit(
+        &self,
+        cgroup_path: &Path,
+        limit: IoLimit,
+        io_path: Option<&Path>,
+    ) -> Result<()> {
         let io_max = cgroup_path.join("io.max");
 
-        let devices = Self::get_real_block_devices()?;
+        let devices = match io_path {
+            Some(path) => vec![block_device_for_path(path)?],
+            None => Self::get_real_block_devices()?,
+        };
         if devices.is_empty() {
             tracing::warn!(
                 "no eligible block devices found; I/O limits were NOT applied \
@@ -614,44 +1460,76 @@ impl CgroupManager {
             return Ok(());
         }
 
-        let mut content = String::new();
-        for (major, minor) in devices {
-            let mut line = format!("{major}:{minor}");
-            if let Some(rbps) = limit.read_bps {
-                line.push_str(&format!(" rbps={rbps}"));
+        let mut params = String::new();
+        if let Some(rbps) = limit.read_bps {
+            params.push_str(&format!(" rbps={rbps}"));
+        }
+        if let Some(wbps) = limit.write_bps {
+            params.push_str(&format!(" wbps={wbps}"));
+        }
+
+        // The kernel parses one device per write, so each gets its own.
+        let mut limited = Vec::new();
+        let mut rejected = Vec::new();
+        let mut last_err = None;
+        for name in &devices {
+            let Some(dev) = device_number(name) else {
+                continue;
+            };
+            match self.write(&io_max, format!("{dev}{params}")) {
+                Ok(()) => limited.push(name.clone()),
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    // I/O throttling (io.max) typically requires root and is often
+                    // not permitted under systemd user cgroup delegation. Treat that
+                    // as a clear, non-fatal warning so memory/CPU limits still apply.
+                    tracing::warn!(
+                        "I/O limits NOT applied: permission denied. I/O throttling usually \
+                         requires root and is commonly unavailable under user cgroup \
+                         delegation; memory/CPU limits (if any) were still applied."
+                    );
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::debug!(device = %name, error = %e, "io.max rejected");
+                    rejected.push(name.clone());
+                    last_err = Some(e);
+                }
             }
-            if let Some(wbps) = limit.write_bps {
-                line.push_str(&format!(" wbps={wbps}"));
+        }
+
+        // Kernels that can't throttle a dm/md device still throttle the disks
+        // under it: make sure those carry the limit.
+        for name in &rejected {
+            for disk in backing_disks(name, &sysfs_slaves, &sysfs_parent_disk) {
+                if limited.contains(&disk) {
+                    continue;
+                }
+                let Some(dev) = device_number(&disk) else {
+                    continue;
+                };
+                if self.write(&io_max, format!("{dev}{params}")).is_ok() {
+                    tracing::info!(device = %name, backing = %disk, "limited backing disk");
+                    limited.push(disk);
+                }
             }
-            content.push_str(&line);
-            content.push('\n');
         }
 
-        if let Err(e) = fs::write(&io_max, content) {
-            // I/O throttling (io.max) typically requires root and is often not
-            // permitted under systemd user cgroup delegation. Treat that as a
-            // clear, non-fatal warning so memory/CPU limits still apply, rather
-            // than failing the whole operation. Other errors remain fatal.
-            if e.kind() == std::io::ErrorKind::PermissionDenied {
-                tracing::warn!(
-                    "I/O limits NOT applied: permission denied. I/O throttling usually \
-                     requires root and is commonly unavailable under user cgroup \
-                     delegation; memory/CPU limits (if any) were still applied."
-                );
-                return Ok(());
+        match last_err {
+            Some(e) if limited.is_empty() => {
+                Err(Error::Cgroup(format!("failed to set io.max: {e}")))
             }
-            return Err(Error::Cgroup(format!("failed to set io.max: {e}")));
+            _ => Ok(()),
         }
-        Ok(())
     }
 
-    /// Get block devices eligible for I/O throttling.
+    /// Get block devices eligible for I/O throttling, by name (e.g. "nvme0n1").
     ///
-    /// Note: device-mapper (`dm-*`) devices are intentionally included — on the
-    /// very common LVM and LUKS-encrypted-root setups, filesystem I/O is issued
-    /// to a dm device, so excluding them would silently disable I/O limiting.
-    /// Only purely virtual/pseudo devices are skipped.
-    fn get_real_block_devices() -> Result<Vec<(u32, u32)>> {
+    /// Note: device-mapper (`dm-*`) and md devices are intentionally included —
+    /// on the very common LVM and LUKS-encrypted-root setups, filesystem I/O is
+    /// issued to a dm device, so excluding them would silently disable I/O
+    /// limiting. Where the kernel refuses one, `set_io_limit` falls back to the
+    /// disks underneath. Only purely virtual/pseudo devices are skipped.
+    fn get_real_block_devices() -> Result<Vec<String>> {
         let mut devices = Vec::new();
 
         let sys_block = Path::new("/sys/block");
@@ -661,36 +1539,269 @@ impl CgroupManager {
 
         for entry in fs::read_dir(sys_block)? {
             let entry = entry?;
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
+            let name = entry.file_name().to_string_lossy().into_owned();
 
             // Skip virtual/pseudo devices that never carry real filesystem I/O.
-            if name_str.starts_with("loop")
-                || name_str.starts_with("ram")
-                || name_str.starts_with("nbd")
-                || name_str.starts_with("zram")
+            if name.starts_with("loop")
+                || name.starts_with("ram")
+                || name.starts_with("nbd")
+                || name.starts_with("zram")
             {
                 continue;
             }
 
-            let dev_file = entry.path().join("dev");
-            if let Ok(content) = fs::read_to_string(&dev_file) {
-                if let Some((major, minor)) = content.trim().split_once(':') {
-                    if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
-                        devices.push((major, minor));
-                    }
-                }
-            }
+            devices.push(name);
         }
 
         Ok(devices)
     }
 }
 
+/// The block device holding the filesystem at `path`, by name: the dm/md
+/// device for LVM, LUKS, or RAID (`set_io_limit` falls back to the disks
+/// under it), otherwise the whole disk. Goes by the mount's source device
+/// rather than `st_dev`, which is an anonymous number on btrfs.
+pub fn block_device_for_path(path: &Path) -> Result<String> {
+    let canonical = fs::canonicalize(path)
+        .map_err(|e| Error::InvalidArgs(format!("{}: {e}", path.display())))?;
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")?;
+    let source = mount_source(&mountinfo, &canonical)
+        .ok_or_else(|| Error::InvalidArgs(format!("no mount found for {}", canonical.display())))?;
+    let not_a_disk = || {
+        Error::InvalidArgs(format!(
+            "{} is on '{source}', which is not a local block device",
+            path.display()
+        ))
+    };
+
+    // e.g. /dev/mapper/root -> /dev/dm-0
+    let device = fs::canonicalize(&source).map_err(|_| not_a_disk())?;
+    let name = device
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(not_a_disk)?;
+    if device_number(&name).is_none() {
+        return Err(not_a_disk());
+    }
+    Ok(sysfs_parent_disk(&name).unwrap_or(name))
+}
+
+/// Source device of the mount holding `path` (already canonical), from
+/// `/proc/self/mountinfo`. The deepest mount point wins; of mounts stacked
+/// on the same point, the last one.
+fn mount_source(mountinfo: &str, path: &Path) -> Option<String> {
+    let mut best: Option<(usize, String)> = None;
+    for line in mountinfo.lines() {
+        // "36 35 98:0 /root /mnt rw,noatime master:1 - ext4 /dev/sda1 rw"
+        let Some((mount, fs_part)) = line.split_once(" - ") else {
+            continue;
+        };
+        let (Some(mount_point), Some(source)) =
+            (mount.split(' ').nth(4), fs_part.split(' ').nth(1))
+        else {
+            continue;
+        };
+        let mount_point = unescape_mount(mount_point);
+        if !path.starts_with(&mount_point) {
+            continue;
+        }
+        let depth = Path::new(&mount_point).components().count();
+        if best.as_ref().is_none_or(|(d, _)| depth >= *d) {
+            best = Some((depth, unescape_mount(source)));
+        }
+    }
+    best.map(|(_, source)| source)
+}
+
+/// Undo mountinfo's octal escapes (`\040` for a space, and so on).
+pub(crate) fn unescape_mount(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 4 <= bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(digits, 8) {
+                out.push(b);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// `major:minor` of a block device or partition, from sysfs.
+pub(crate) fn device_number(name: &str) -> Option<String> {
+    let content = fs::read_to_string(format!("/sys/class/block/{name}/dev")).ok()?;
+    let (major, minor) = content.trim().split_once(':')?;
+    let (major, minor): (u32, u32) = (major.parse().ok()?, minor.parse().ok()?);
+    Some(format!("{major}:{minor}"))
+}
+
+/// Devices a dm/md device is built on (`/sys/class/block/<name>/slaves`).
+pub(crate) fn sysfs_slaves(name: &str) -> Vec<String> {
+    fs::read_dir(format!("/sys/class/block/{name}/slaves"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The whole disk a partition belongs to, or `None` if `name` is not a
+/// partition. A partition's sysfs directory sits inside its disk's.
+pub(crate) fn sysfs_parent_disk(name: &str) -> Option<String> {
+    let dir = Path::new("/sys/class/block").join(name);
+    if !dir.join("partition").exists() {
+        return None;
+    }
+    let real = fs::canonicalize(&dir).ok()?;
+    Some(real.parent()?.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Resolve a (possibly stacked) block device to the whole disks under it:
+/// dm/md devices are followed through their slaves (LVM on LUKS on a
+/// partition is three levels deep), and partitions map to their disk, since
+/// `io.max` only accepts whole disks. A plain disk resolves to itself.
+pub(crate) fn backing_disks(
+    name: &str,
+    slaves: &dyn Fn(&str) -> Vec<String>,
+    parent_disk: &dyn Fn(&str) -> Option<String>,
+) -> Vec<String> {
+    let mut disks = Vec::new();
+    let mut stack = vec![name.to_string()];
+    // Bounded: a sysfs loop is impossible, but don't trust that blindly.
+    let mut budget = 64;
+    while let Some(dev) = stack.pop() {
+        budget -= 1;
+        if budget == 0 {
+            break;
+        }
+        let under = slaves(&dev);
+        if !under.is_empty() {
+            stack.extend(under);
+            continue;
+        }
+        let disk = parent_disk(&dev).unwrap_or(dev);
+        if !disks.contains(&disk) {
+            disks.push(disk);
+        }
+    }
+    disks
+}
+
+/// Where the user told rlm to create its cgroups: [`CgroupManager::set_root`],
+/// else `RLM_CGROUP_ROOT`, else `cgroup_root` in the config.
+fn configured_root() -> Option<PathBuf> {
+    if let Some(root) = ROOT_OVERRIDE.get() {
+        return Some(root.clone());
+    }
+    match std::env::var_os("RLM_CGROUP_ROOT") {
+        Some(root) if !root.is_empty() => Some(PathBuf::from(root)),
+        _ => common::Config::load().ok()?.cgroup_root,
+    }
+}
+
+/// The unified-hierarchy path ("0::<path>") from a `/proc/<pid>/cgroup` body.
+pub(crate) fn unified_cgroup(proc_cgroup: &str) -> Option<&str> {
+    proc_cgroup.lines().find_map(|l| l.strip_prefix("0::"))
+}
+
+pub(crate) fn mem_total_bytes() -> Result<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo")?;
+    parse_mem_total_bytes(&meminfo)
+        .ok_or_else(|| Error::Cgroup("cannot read MemTotal from /proc/meminfo".into()))
+}
+
+/// Parse `MemTotal:` (in kB) from `/proc/meminfo` into bytes.
+fn parse_mem_total_bytes(meminfo: &str) -> Option<u64> {
+    let rest = meminfo.lines().find_map(|l| l.strip_prefix("MemTotal:"))?;
+    let kb: u64 = rest.split_whitespace().next()?.parse().ok()?;
+    kb.checked_mul(1024)
+}
+
+/// The soft limit `set_memory_limit` derives from a `memory.max` value (90%),
+/// or `None` for an unlimited cgroup.
+fn soft_limit_of(memory_max: &str) -> Option<u64> {
+    memory_max
+        .parse::<u64>()
+        .ok()
+        .map(|b| b / 100 * 90)
+        .filter(|&h| h > 0)
+}
+
+/// `memory.high` for a non-reserved cgroup: its own soft limit lowered to the
+/// reservation ceiling, if there is one.
+fn ceiling_high(own: Option<u64>, ceiling: Option<u64>) -> String {
+    match (own, ceiling) {
+        (Some(own), Some(ceiling)) => own.min(ceiling).to_string(),
+        (Some(own), None) => own.to_string(),
+        (None, Some(ceiling)) => ceiling.to_string(),
+        (None, None) => "max".into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn unified_cgroup_skips_v1_lines() {
+        let body = "12:pids:/user.slice\n0::/user.slice/user-1000.slice/app.scope\n";
+        assert_eq!(
+            unified_cgroup(body),
+            Some("/user.slice/user-1000.slice/app.scope")
+        );
+        assert_eq!(unified_cgroup("1:name=systemd:/\n"), None);
+    }
+
+    #[test]
+    fn mount_source_picks_deepest_mount() {
+        let mountinfo = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+30 22 254:1 / /home rw,relatime shared:2 - ext4 /dev/mapper/home rw
+31 30 0:40 / /home/me/tmp rw shared:3 - tmpfs tmpfs rw
+32 22 8:17 / /mnt/my\\040disk rw shared:4 - xfs /dev/sdb1 rw
+";
+        let src = |p: &str| mount_source(mountinfo, Path::new(p));
+        assert_eq!(src("/home/me/docs").as_deref(), Some("/dev/mapper/home"));
+        assert_eq!(src("/home").as_deref(), Some("/dev/mapper/home"));
+        assert_eq!(src("/homework").as_deref(), Some("/dev/nvme0n1p2"));
+        assert_eq!(src("/home/me/tmp/x").as_deref(), Some("tmpfs"));
+        assert_eq!(src("/mnt/my disk/a").as_deref(), Some("/dev/sdb1"));
+    }
+
+    #[test]
+    fn backing_disks_walks_dm_stack() {
+        // LVM (dm-1) on LUKS (dm-0) on nvme0n1p3, plus md0 over two disks
+        let slaves = |name: &str| -> Vec<String> {
+            match name {
+                "dm-1" => vec!["dm-0".into()],
+                "dm-0" => vec!["nvme0n1p3".into()],
+                "md0" => vec!["sda1".into(), "sdb1".into()],
+                _ => Vec::new(),
+            }
+        };
+        let parent = |name: &str| -> Option<String> {
+            match name {
+                "nvme0n1p3" => Some("nvme0n1".into()),
+                "sda1" => Some("sda".into()),
+                "sdb1" => Some("sdb".into()),
+                _ => None,
+            }
+        };
+        assert_eq!(backing_disks("dm-1", &slaves, &parent), vec!["nvme0n1"]);
+        let mut md = backing_disks("md0", &slaves, &parent);
+        md.sort();
+        assert_eq!(md, vec!["sda", "sdb"]);
+        assert_eq!(backing_disks("sdc", &slaves, &parent), vec!["sdc"]);
+    }
+
     #[test]
     fn rejects_init_and_kernel_pids() {
         assert!(reject_critical_pid(0).is_err()); // kernel/swapper
@@ -718,4 +1829,154 @@ mod tests {
         assert_eq!(sanitize_cgroup_name("app_firefox").unwrap(), "app_firefox");
         assert_eq!(sanitize_cgroup_name("run-42-99").unwrap(), "run-42-99");
     }
+
+    #[test]
+    fn mem_total_parses_kb_to_bytes() {
+        let m = "MemTotal:       16000000 kB\nMemFree:         1000000 kB\n";
+        assert_eq!(parse_mem_total_bytes(m), Some(16_000_000 * 1024));
+        assert_eq!(parse_mem_total_bytes("MemFree: 1 kB\n"), None);
+    }
+
+    #[test]
+    fn ceiling_lowers_but_never_raises_soft_limit() {
+        // Unlimited cgroup: ceiling applies, or nothing without reservations
+        assert_eq!(ceiling_high(soft_limit_of("max"), Some(1000)), "1000");
+        assert_eq!(ceiling_high(soft_limit_of("max"), None), "max");
+        // Limited cgroup: 90% soft limit, capped by the ceiling
+        assert_eq!(ceiling_high(soft_limit_of("2000"), Some(1000)), "1000");
+        assert_eq!(ceiling_high(soft_limit_of("1000"), Some(5000)), "900");
+        // Undo restores the soft limit set_memory_limit wrote
+        assert_eq!(ceiling_high(soft_limit_of("1000"), None), "900");
+    }
+
+    #[test]
+    fn applies_lists_and_removes_limits_on_a_fake_hierarchy() {
+        use crate::backend::FakeBackend;
+
+        let fake = Arc::new(FakeBackend::new("/cg", "memory cpu io pids"));
+        let manager = CgroupManager::with_backend("/cg/rlm".into(), fake.clone());
+        fake.create_dir(Path::new("/cg/rlm")).unwrap();
+        let pid = std::process::id();
+
+        let limit = Limit {
+            memory: Some(common::MemoryLimit::parse("512M").unwrap()),
+            ..Limit::default()
+        };
+        manager.apply_limit(pid, &limit).unwrap();
+        let cgroup = PathBuf::from(format!("/cg/rlm/pid-{pid}"));
+        assert_eq!(
+            fake.file(cgroup.join("memory.max")).as_deref(),
+            Some("536870912")
+        );
+        assert_eq!(
+            fake.file(cgroup.join("cgroup.procs")),
+            Some(format!("{pid}\n"))
+        );
+
+        let managed = crate::status::get_managed_processes(&manager).unwrap();
+        assert_eq!(managed.len(), 1);
+        assert_eq!(managed[0].pid, pid);
+        assert_eq!(managed[0].memory_max, Some(536870912));
+
+        manager.remove_limit(pid).unwrap();
+        assert!(!fake.is_dir(&cgroup));
+        assert_eq!(
+            fake.file("/cg/rlm/unlimit/cgroup.procs"),
+            Some(format!("{pid}\n"))
+        );
+    }
+
+    #[test]
+    fn shared_cgroup_is_only_removed_once_empty() {
+        use crate::backend::FakeBackend;
+
+        let fake = Arc::new(FakeBackend::new("/cg", "memory"));
+        let manager = CgroupManager::with_backend("/cg".into(), fake.clone());
+        let path = manager
+            .prepare_cgroup("run-join-build", &Limit::default())
+            .unwrap();
+        fake.write(&path.join("cgroup.procs"), "42").unwrap();
+        assert!(!manager.remove_if_empty("run-join-build").unwrap());
+        assert_eq!(manager.pids_in_cgroup("run-join-build"), [42]);
+
+        fake.write(Path::new("/cg/cgroup.procs"), "42").unwrap();
+        assert!(manager.remove_if_empty("run-join-build").unwrap());
+        assert!(!manager.cgroup_exists("run-join-build"));
+        assert!(manager.remove_if_empty("run-join-build").unwrap());
+    }
+
+    #[test]
+    fn limit_cgroups_leave_out_switches_and_holding_pens() {
+        use crate::backend::FakeBackend;
+
+        let fake = Arc::new(FakeBackend::new("/cg", "memory"));
+        let manager = CgroupManager::with_backend("/cg".into(), fake.clone());
+        for name in [
+            "pid-7",
+            "app-firefox",
+            "guard-9",
+            "protect",
+            "unlimit",
+            "run-1-2",
+        ] {
+            fake.create_dir(&Path::new("/cg").join(name)).unwrap();
+        }
+        assert_eq!(manager.limit_cgroups(), ["app-firefox", "pid-7", "run-1-2"]);
+    }
+
+    #[test]
+    fn reset_controllers_lifts_only_those_given() {
+        use crate::backend::FakeBackend;
+
+        let fake = Arc::new(FakeBackend::new("/cg", "memory cpu io"));
+        let manager = CgroupManager::with_backend("/cg".into(), fake.clone());
+        let cgroup = Path::new("/cg/pid-7");
+        fake.create_dir(cgroup).unwrap();
+        for (file, value) in [
+            ("memory.max", "1073741824"),
+            ("memory.swap.max", "0"),
+            ("cpu.max", "50000 100000"),
+            ("io.max", "8:0 rbps=1048576 wbps=max riops=max wiops=max"),
+        ] {
+            fake.write(&cgroup.join(file), value).unwrap();
+        }
+
+        manager
+            .reset_controllers("pid-7", &[Controller::Memory, Controller::Io])
+            .unwrap();
+        assert_eq!(fake.file("/cg/pid-7/memory.max").unwrap(), "max");
+        assert_eq!(fake.file("/cg/pid-7/memory.swap.max").unwrap(), "max");
+        assert_eq!(
+            fake.file("/cg/pid-7/io.max").unwrap(),
+            "8:0 rbps=max wbps=max riops=max wiops=max"
+        );
+        assert_eq!(fake.file("/cg/pid-7/cpu.max").unwrap(), "50000 100000");
+        assert!(manager
+            .reset_controllers("pid-8", &[Controller::Cpu])
+            .is_err());
+
+        // Under a reservation, memory.high goes back to its ceiling
+        fake.create_dir(Path::new("/cg/reserve-db")).unwrap();
+        fake.write(Path::new("/cg/reserve-db/memory.low"), "1048576")
+            .unwrap();
+        manager
+            .reset_controllers("pid-7", &[Controller::Memory])
+            .unwrap();
+        assert_ne!(fake.file("/cg/pid-7/memory.high").unwrap(), "max");
+    }
+
+    #[test]
+    fn placement_failure_stops_the_spawn() {
+        let dir = std::env::temp_dir().join(format!("rlm-placement-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let manager = CgroupManager::with_backend(dir.clone(), Arc::new(FsBackend));
+
+        assert!(manager.placement_command(&dir, "true").is_err());
+
+        // Every write to /dev/full fails, as a write to a full cgroup would
+        std::os::unix::fs::symlink("/dev/full", dir.join("cgroup.procs")).unwrap();
+        let spawned = manager.placement_command(&dir, "true").unwrap().status();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(spawned.is_err());
+    }
 }
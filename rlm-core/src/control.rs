@@ -0,0 +1,286 @@
+//! Control API, served by `rlm daemon` on a unix socket at
+//! `$XDG_RUNTIME_DIR/rlm/control.sock` so scripts can manage limits without
+//! parsing CLI output. Messages are JSON-RPC 2.0, one per line, and a
+//! connection may send any number of them. `rlm ctl` is a client.
+//!
+//! Methods, stable within an [`API_VERSION`]:
+//!
+//! - `version`: `{"api": 1, "rlm": "0.1.0"}`
+//! - `list`: every managed cgroup: its first pid, name, members, limits
+//!   and memory use (`null` where unset)
+//! - `apply`: `{"pid": 4242, "limits": {...}}`, or `{"pids": [...],
+//!   "cgroup": "name", "limits": {...}}` for a shared budget; `limits` takes
+//!   the keys of a config profile. Returns `{"cgroup": ...}`.
+//! - `remove`: `{"pid": 4242}` or `{"cgroup": "name"}`
+//!
+//! The socket is only accessible to the user running the daemon.
+
+use crate::CgroupManager;
+use common::{Error, Profile, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Bumped on incompatible changes to methods or their messages.
+pub const API_VERSION: u32 = 1;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// An rlm operation failed; the message says why.
+const FAILED: i64 = -32000;
+
+/// Where the daemon listens.
+pub fn socket_path() -> Option<PathBuf> {
+    dirs::runtime_dir().map(|d| d.join("rlm").join("control.sock"))
+}
+
+/// A listening control socket. Requests are served on background threads;
+/// dropping it removes the socket.
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Listen on [`socket_path`].
+    pub fn start() -> Result<Self> {
+        let path = socket_path().ok_or_else(|| {
+            Error::Config("no runtime directory (XDG_RUNTIME_DIR) for the control socket".into())
+        })?;
+        Self::start_at(path)
+    }
+
+    pub fn start_at(path: PathBuf) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if UnixStream::connect(&path).is_ok() {
+            return Err(Error::Config(format!(
+                "another daemon is listening on {}",
+                path.display()
+            )));
+        }
+        let manager = Arc::new(CgroupManager::new()?);
+        // Left behind by one that died
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let manager = Arc::clone(&manager);
+                std::thread::spawn(move || serve(&manager, stream));
+            }
+        });
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Call `method` on the daemon's socket and return its result.
+pub fn call(method: &str, params: Value) -> Result<Value> {
+    let path = socket_path().ok_or_else(|| {
+        Error::Config("no runtime directory (XDG_RUNTIME_DIR) for the control socket".into())
+    })?;
+    let mut stream = UnixStream::connect(&path).map_err(|e| {
+        Error::Config(format!(
+            "cannot reach rlm daemon at {}: {e}\n  hint: start it with `rlm daemon`",
+            path.display()
+        ))
+    })?;
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+    writeln!(stream, "{request}")?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let mut response: Value = serde_json::from_str(&line)
+        .map_err(|e| Error::Config(format!("bad response from the daemon: {e}")))?;
+    if let Some(error) = response.get("error") {
+        let message = error["message"].as_str().unwrap_or("unknown error");
+        return Err(Error::Config(format!("{method}: {message}")));
+    }
+    Ok(response["result"].take())
+}
+
+fn serve(manager: &CgroupManager, stream: UnixStream) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = respond(&line, |method, params| dispatch(manager, method, params));
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+/// A JSON-RPC error: code and message.
+type RpcError = (i64, String);
+
+/// The response to one request line, with `call` running the method.
+fn respond(
+    line: &str,
+    call: impl FnOnce(&str, Value) -> std::result::Result<Value, RpcError>,
+) -> Value {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return error(Value::Null, (PARSE_ERROR, e.to_string())),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return error(id, (INVALID_REQUEST, "no method".into()));
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    match call(method, params) {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => error(id, e),
+    }
+}
+
+fn error(id: Value, (code, message): RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApplyParams {
+    pid: Option<u32>,
+    pids: Option<Vec<u32>>,
+    cgroup: Option<String>,
+    limits: Profile,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RemoveParams {
+    pid: Option<u32>,
+    cgroup: Option<String>,
+}
+
+fn dispatch(
+    manager: &CgroupManager,
+    method: &str,
+    params: Value,
+) -> std::result::Result<Value, RpcError> {
+    let failed = |e: Error| (FAILED, e.to_string());
+    match method {
+        "version" => Ok(json!({"api": API_VERSION, "rlm": env!("CARGO_PKG_VERSION")})),
+        "list" => {
+            let processes = crate::status::get_managed_processes(manager).map_err(failed)?;
+            Ok(Value::Array(processes.iter().map(managed).collect()))
+        }
+        "apply" => {
+            let p: ApplyParams = params_of(params)?;
+            let limit = p
+                .limits
+                .to_limit()
+                .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            if limit.is_empty() {
+                return Err((INVALID_PARAMS, "no limits given".into()));
+            }
+            let cgroup = match (p.pid, p.pids, p.cgroup) {
+                (Some(pid), None, None) => {
+                    manager.apply_limit(pid, &limit).map_err(failed)?;
+                    format!("pid-{pid}")
+                }
+                (None, Some(pids), Some(cgroup)) => {
+                    manager
+                        .apply_limit_to_multiple(&pids, &limit, &cgroup)
+                        .map_err(failed)?;
+                    cgroup
+                }
+                _ => return Err((INVALID_PARAMS, "give either pid, or pids and cgroup".into())),
+            };
+            Ok(json!({"cgroup": cgroup}))
+        }
+        "remove" => {
+            let p: RemoveParams = params_of(params)?;
+            match (p.pid, p.cgroup) {
+                (Some(pid), None) => manager.remove_limit(pid),
+                (None, Some(cgroup)) => manager.remove_application_limit(&cgroup),
+                _ => return Err((INVALID_PARAMS, "give either pid or cgroup".into())),
+            }
+            .map_err(failed)?;
+            Ok(Value::Null)
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("no method '{method}'"))),
+    }
+}
+
+fn managed(p: &crate::status::ProcessStatus) -> Value {
+    json!({
+        "pid": p.pid,
+        "name": p.name,
+        "cgroup": p.cgroup_name,
+        "members": p.members,
+        "memory_current": p.usage.memory_current,
+        "memory_max": p.memory_max,
+        "memory_low": p.memory_low,
+        "cpu_percent": p.cpu_quota,
+        "cpu_weight": p.cpu_weight,
+        "io_read_bps": p.io_read_bps,
+        "io_write_bps": p.io_write_bps,
+        "pids_max": p.pids_max,
+    })
+}
+
+fn params_of<T: serde::de::DeserializeOwned>(params: Value) -> std::result::Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_json_rpc_with_the_request_id() {
+        let echo = |method: &str, params: Value| Ok(json!({"method": method, "params": params}));
+        assert_eq!(
+            respond(
+                r#"{"jsonrpc":"2.0","id":7,"method":"list","params":[]}"#,
+                echo
+            ),
+            json!({"jsonrpc": "2.0", "id": 7, "result": {"method": "list", "params": []}})
+        );
+
+        let code = |response: Value| response["error"]["code"].as_i64();
+        assert_eq!(code(respond("{not json", echo)), Some(PARSE_ERROR));
+        assert_eq!(code(respond(r#"{"id":1}"#, echo)), Some(INVALID_REQUEST));
+        let unknown = respond(r#"{"id":"a","method":"nope"}"#, |m, _| {
+            Err((METHOD_NOT_FOUND, format!("no method '{m}'")))
+        });
+        assert_eq!(unknown["id"], "a");
+        assert_eq!(code(unknown), Some(METHOD_NOT_FOUND));
+    }
+
+    #[test]
+    fn apply_params_take_profile_keys() {
+        let p: ApplyParams =
+            params_of(json!({"pid": 42, "limits": {"memory": "1G", "cpu": "50%"}})).unwrap();
+        assert_eq!(p.pid, Some(42));
+        let limit = p.limits.to_limit().unwrap();
+        assert_eq!(limit.memory.map(|m| m.bytes()), Some(1 << 30));
+        assert!(params_of::<ApplyParams>(json!({"pid": 42, "limit": {}})).is_err());
+    }
+}
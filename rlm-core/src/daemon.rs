@@ -0,0 +1,582 @@
+//! Automatic profiles, behind `rlm daemon`: processes whose executable is in
+//! a user profile's `match_exe` (and that meet its match conditions) are
+//! moved into that profile's shared `profile-<name>` cgroup when they exec
+//! (see [`crate::proc_events`]).
+//!
+//! Persistent limits (`rlm limit --persistent`, saved as rules) are
+//! re-applied the same way, in their `app-<name>` cgroup, and take precedence
+//! over profiles. Among rules, and among profiles, a higher `priority` wins,
+//! then the name. Processes already in an rlm cgroup are left alone, except
+//! that one in a profile's cgroup that execs a binary matching another
+//! profile (a wrapper script, `sh -c`) moves there with its children.
+//!
+//! A profile with a `schedule` only has its limits while the window is open,
+//! and one with `on_battery` or `when_idle` overrides switches to them when
+//! the machine is unplugged or the user is away;
+//! [`ProfileDaemon::update_conditions`] re-applies limits as these change,
+//! leaving the processes in place.
+//!
+//! [`ProfileDaemon::reload`] switches to a changed config the same way:
+//! managed cgroups and their processes stay where they are.
+
+use crate::proc_events::ProcessEvent;
+use crate::process::{self, ProcessInfo};
+use crate::rules::{self, CompiledRule};
+use crate::schedule::Schedule;
+use crate::CgroupManager;
+use common::{Config, Error, Limit, Profile, Result};
+use std::collections::HashMap;
+
+/// A process moved into a profile's cgroup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub pid: u32,
+    pub name: String,
+    pub profile: String,
+    pub cgroup: String,
+    /// The profile cgroup it was moved out of, when an exec re-matched it
+    pub from: Option<String>,
+}
+
+/// What conditional limits depend on, for [`ProfileDaemon::update_conditions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conditions {
+    /// Minutes since local midnight
+    pub minute: u16,
+    pub on_battery: bool,
+    /// The user has been away long enough for `when_idle` limits
+    pub idle: bool,
+}
+
+/// A profile whose limits changed with the time of day, the power source,
+/// or the user going idle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitSwitch {
+    pub profile: String,
+    /// Its schedule's window is open (always, without a schedule)
+    pub window_open: bool,
+    /// Its `on_battery` limits are in force
+    pub on_battery: bool,
+    /// Its `when_idle` limits are in force
+    pub idle: bool,
+}
+
+/// Which of a profile's limits are in force: (window open, on battery, idle).
+type State = (bool, bool, bool);
+
+/// What a profile's limits depend on besides the profile itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Conditional {
+    schedule: Option<Schedule>,
+    /// The limits with `on_battery` applied
+    battery_limit: Option<Limit>,
+    /// The limits with `when_idle` applied
+    idle_limit: Option<Limit>,
+    /// The limits with both applied, `when_idle` last
+    battery_idle_limit: Option<Limit>,
+}
+
+/// Places new processes according to user profiles.
+pub struct ProfileDaemon {
+    /// What gets applied, first match wins
+    profiles: Vec<CompiledRule>,
+    /// Persistent rules not applied here; their executables are left alone
+    rules: Vec<CompiledRule>,
+    /// Profile name -> its schedule and conditional limits, if it has any
+    conditions: HashMap<String, Conditional>,
+    /// Profile name -> its state when last updated
+    applied: HashMap<String, State>,
+    self_pid: u32,
+}
+
+impl ProfileDaemon {
+    /// Compile persistent rules and user profiles that have `match_exe` or
+    /// match conditions.
+    /// Built-in presets are not applied automatically. Profiles with invalid
+    /// limits or schedules are skipped.
+    pub fn new(cfg: &Config) -> Self {
+        let mut conditions = HashMap::new();
+        let mut profiles: Vec<CompiledRule> = cfg
+            .profiles
+            .iter()
+            .filter(|(_, p)| !p.match_exe.is_empty() || !p.conditions.is_empty())
+            .filter_map(|(name, p)| {
+                let conds = match conditions_of(name, p) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        tracing::warn!(profile = %name, error = %e, "skipping profile");
+                        return None;
+                    }
+                };
+                let compiled = CompiledRule::from_profile(name, p)?;
+                if let Some(c) = conds {
+                    conditions.insert(name.clone(), c);
+                }
+                Some(compiled)
+            })
+            .collect();
+        rules::sort_by_priority(&mut profiles);
+        let mut rules = compile_rules(cfg);
+        rules::sort_by_priority(&mut rules);
+        rules.extend(profiles);
+        Self {
+            profiles: rules,
+            rules: Vec::new(),
+            conditions,
+            applied: HashMap::new(),
+            self_pid: std::process::id(),
+        }
+    }
+
+    /// Only the profile `name`, which may be a built-in preset, for
+    /// `rlm apply --profile`.
+    pub fn for_profile(cfg: &Config, name: &str) -> Result<Self> {
+        let profile = cfg
+            .all_profiles()
+            .remove(name)
+            .ok_or_else(|| Error::Config(format!("profile '{name}' not found")))?;
+        if profile.match_exe.is_empty() && profile.conditions.is_empty() {
+            return Err(Error::Config(format!(
+                "profile '{name}' has no match_exe or match conditions to match processes by"
+            )));
+        }
+        let conds = conditions_of(name, &profile)?;
+        let compiled = CompiledRule::from_profile(name, &profile)
+            .ok_or_else(|| Error::Config(format!("profile '{name}' has invalid limits")))?;
+        Ok(Self {
+            profiles: vec![compiled],
+            rules: compile_rules(cfg),
+            conditions: conds
+                .map(|c| HashMap::from([(name.to_string(), c)]))
+                .unwrap_or_default(),
+            applied: HashMap::new(),
+            self_pid: std::process::id(),
+        })
+    }
+
+    /// Limits for one executable, placed in the `app-<exe>` cgroup that
+    /// `rlm limit --application` uses, for `rlm watch`.
+    pub fn for_application(cfg: &Config, exe: &str, limit: Limit) -> Self {
+        Self {
+            profiles: vec![CompiledRule {
+                name: exe.to_string(),
+                match_exe: vec![exe.to_string()],
+                limit,
+                cgroup: rules::cgroup_name_for(exe),
+                ..Default::default()
+            }],
+            rules: compile_rules(cfg),
+            conditions: HashMap::new(),
+            applied: HashMap::new(),
+            self_pid: std::process::id(),
+        }
+    }
+
+    /// Switch to the profiles and persistent rules of `cfg`, e.g. after the
+    /// config file changed, then place running processes that match now.
+    /// Nothing is dropped: existing cgroups get their profile's new limits
+    /// if these changed, and those of profiles no longer configured keep
+    /// their processes and limits. Returns the profiles whose limits were
+    /// re-applied, and the processes placed.
+    pub fn reload(&mut self, mgr: &CgroupManager, cfg: &Config) -> (Vec<String>, Vec<Placement>) {
+        let old = std::mem::replace(self, Self::new(cfg));
+        let mut changed = Vec::new();
+        for profile in &self.profiles {
+            let name = &profile.name;
+            let before = old.profiles.iter().find(|p| &p.name == name);
+            let same = before
+                .is_some_and(|b| b.limit == profile.limit && b.cgroup == profile.cgroup)
+                && old.conditions.get(name) == self.conditions.get(name);
+            if same {
+                if let Some(&state) = old.applied.get(name) {
+                    self.applied.insert(name.clone(), state);
+                }
+                continue;
+            }
+            if !mgr.cgroup_exists(&profile.cgroup) {
+                continue;
+            }
+            // Conditional limits are set by the next `update_conditions`,
+            // which finds no state for the profile
+            if !self.conditions.contains_key(name) {
+                if let Err(e) = mgr.replace_limits(&profile.cgroup, &profile.limit) {
+                    tracing::warn!(profile = %name, error = %e, "daemon: updating limits failed");
+                    continue;
+                }
+            }
+            changed.push(name.clone());
+        }
+        (changed, self.place_running(mgr))
+    }
+
+    /// Names of the profiles (and rules) being applied, in match order.
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.profiles.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// Running processes that would be placed, with their profile's name.
+    pub fn matching(&self, mgr: &CgroupManager) -> Vec<(ProcessInfo, &str)> {
+        process::list_all()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| p.pid != self.self_pid && !mgr.manages_pid(p.pid))
+            .filter_map(|p| {
+                let profile = pick(&self.profiles, &self.rules, &p)?;
+                Some((p, profile.name.as_str()))
+            })
+            .collect()
+    }
+
+    /// Place matching processes that are already running.
+    pub fn place_running(&self, mgr: &CgroupManager) -> Vec<Placement> {
+        process::list_all()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|p| self.place(mgr, p))
+            .collect()
+    }
+
+    /// Place the process behind an exec event, if a profile matches it, or
+    /// move it to another profile's cgroup if its new binary matches that.
+    pub fn handle(&self, mgr: &CgroupManager, event: &ProcessEvent) -> Option<Placement> {
+        match *event {
+            ProcessEvent::Exec { pid } => {
+                let proc = process::info(pid)?;
+                match mgr.managed_cgroup_of(pid) {
+                    Some(cgroup) => self.rematch(mgr, proc, &cgroup),
+                    None => self.place(mgr, proc),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Place one process if a profile matches it.
+    pub fn place(&self, mgr: &CgroupManager, proc: ProcessInfo) -> Option<Placement> {
+        if proc.pid == self.self_pid || mgr.manages_pid(proc.pid) {
+            return None;
+        }
+        let profile = pick(&self.profiles, &self.rules, &proc)?;
+        let result = mgr
+            .ensure_cgroup(&profile.cgroup, &self.placing_limit(profile))
+            .and_then(|path| mgr.add_to_cgroup(&path, proc.pid));
+        if let Err(e) = result {
+            tracing::warn!(pid = proc.pid, profile = %profile.name, error = %e, "daemon: placing failed");
+            return None;
+        }
+        Some(Placement {
+            pid: proc.pid,
+            name: proc.name,
+            profile: profile.name.clone(),
+            cgroup: profile.cgroup.clone(),
+            from: None,
+        })
+    }
+
+    /// Move a process that exec'd inside the cgroup of one of these profiles
+    /// to the profile its new binary matches, if that is another one, with
+    /// its children still in the old cgroup. A new binary matching nothing
+    /// stays put: children of an app share its budget whatever they run.
+    /// Cgroups not made by this daemon (`rlm limit`, `rlm run`) are left alone.
+    fn rematch(&self, mgr: &CgroupManager, proc: ProcessInfo, cgroup: &str) -> Option<Placement> {
+        if !self.profiles.iter().any(|p| p.cgroup == cgroup) {
+            return None;
+        }
+        let profile = pick(&self.profiles, &self.rules, &proc)?;
+        if profile.cgroup == cgroup {
+            return None;
+        }
+        let path = match mgr.ensure_cgroup(&profile.cgroup, &self.placing_limit(profile)) {
+            Ok(path) => path,
+            Err(e) => {
+                tracing::warn!(pid = proc.pid, profile = %profile.name, error = %e, "daemon: re-matching failed");
+                return None;
+            }
+        };
+        let tree = process::find_process_tree(proc.pid).unwrap_or_else(|_| vec![proc.pid]);
+        for pid in tree {
+            if mgr.managed_cgroup_of(pid).as_deref() != Some(cgroup) {
+                continue;
+            }
+            if let Err(e) = mgr.add_to_cgroup(&path, pid) {
+                tracing::warn!(pid, profile = %profile.name, error = %e, "daemon: re-matching failed");
+            }
+        }
+        Some(Placement {
+            pid: proc.pid,
+            name: proc.name,
+            profile: profile.name.clone(),
+            cgroup: profile.cgroup.clone(),
+            from: Some(cgroup.to_string()),
+        })
+    }
+
+    /// The limits of `profile` for a process placed now. Idleness is left
+    /// to the next update: placing means someone just started a program.
+    fn placing_limit(&self, profile: &CompiledRule) -> Limit {
+        let state = match self.conditions.get(&profile.name) {
+            Some(c) => (
+                c.schedule.is_none_or(|s| s.is_active()),
+                c.battery_limit.is_some() && crate::power::on_battery(),
+                false,
+            ),
+            None => (true, false, false),
+        };
+        self.limit(profile, state)
+    }
+
+    /// The schedule of profile `name`, if it has one.
+    pub fn schedule(&self, name: &str) -> Option<Schedule> {
+        self.conditions.get(name)?.schedule
+    }
+
+    /// Whether profile `name` has `on_battery` limits.
+    pub fn has_battery_limits(&self, name: &str) -> bool {
+        self.conditions
+            .get(name)
+            .is_some_and(|c| c.battery_limit.is_some())
+    }
+
+    /// Whether profile `name` has `when_idle` limits.
+    pub fn has_idle_limits(&self, name: &str) -> bool {
+        self.conditions
+            .get(name)
+            .is_some_and(|c| c.idle_limit.is_some())
+    }
+
+    /// Whether any profile has `when_idle` limits, so idleness is worth
+    /// checking.
+    pub fn uses_idle(&self) -> bool {
+        self.conditions.values().any(|c| c.idle_limit.is_some())
+    }
+
+    /// Re-apply the limits of profiles whose schedule window opened or
+    /// closed, or whose on-battery or idle limits came into or out of force,
+    /// since the last call. The first call sets every existing cgroup of
+    /// such a profile.
+    pub fn update_conditions(&mut self, mgr: &CgroupManager, now: Conditions) -> Vec<LimitSwitch> {
+        let mut changed = Vec::new();
+        for profile in &self.profiles {
+            let Some(c) = self.conditions.get(&profile.name) else {
+                continue;
+            };
+            let state = (
+                c.schedule.is_none_or(|s| s.contains(now.minute)),
+                now.on_battery && c.battery_limit.is_some(),
+                now.idle && c.idle_limit.is_some(),
+            );
+            if self.applied.insert(profile.name.clone(), state) == Some(state) {
+                continue;
+            }
+            let (window_open, on_battery, idle) = state;
+            if mgr.cgroup_exists(&profile.cgroup) {
+                let limit = self.limit(profile, state);
+                if let Err(e) = mgr.replace_limits(&profile.cgroup, &limit) {
+                    tracing::warn!(profile = %profile.name, error = %e, "daemon: switching limits failed");
+                    continue;
+                }
+            }
+            changed.push(LimitSwitch {
+                profile: profile.name.clone(),
+                window_open,
+                on_battery,
+                idle,
+            });
+        }
+        changed
+    }
+
+    /// The limits `profile` has in `state`: none at all outside its
+    /// schedule's window, else its on-battery and idle ones as they apply.
+    fn limit(&self, profile: &CompiledRule, (window_open, on_battery, idle): State) -> Limit {
+        if !window_open {
+            return Limit::default();
+        }
+        let Some(c) = self.conditions.get(&profile.name) else {
+            return profile.limit.clone();
+        };
+        let conditional = match (on_battery, idle) {
+            (true, true) => c.battery_idle_limit.as_ref(),
+            (true, false) => c.battery_limit.as_ref(),
+            (false, true) => c.idle_limit.as_ref(),
+            (false, false) => None,
+        };
+        conditional.unwrap_or(&profile.limit).clone()
+    }
+}
+
+/// A profile's parsed `schedule` and conditional limits, or `None` if it has
+/// none of them.
+fn conditions_of(name: &str, profile: &Profile) -> Result<Option<Conditional>> {
+    let in_profile = |e: Error| Error::Config(format!("profile '{name}': {e}"));
+    let schedule = profile
+        .schedule
+        .as_deref()
+        .map(|s| s.parse::<Schedule>().map_err(in_profile))
+        .transpose()?;
+    let on_battery = profile
+        .on_battery
+        .as_deref()
+        .map(|b| profile.with_overrides(b));
+    let compile = |p: Option<Profile>| p.map(|p| p.to_limit().map_err(in_profile)).transpose();
+    let idle = |p: &Profile| profile.when_idle.as_deref().map(|i| p.with_overrides(i));
+    let c = Conditional {
+        schedule,
+        idle_limit: compile(idle(profile))?,
+        battery_idle_limit: compile(on_battery.as_ref().and_then(idle))?,
+        battery_limit: compile(on_battery)?,
+    };
+    let any = c.schedule.is_some() || c.battery_limit.is_some() || c.idle_limit.is_some();
+    Ok(any.then_some(c))
+}
+
+fn compile_rules(cfg: &Config) -> Vec<CompiledRule> {
+    cfg.rules
+        .iter()
+        .filter_map(|(name, rule)| CompiledRule::compile(name, rule))
+        .collect()
+}
+
+/// The first profile (by name) matching `proc`, unless a rule claims it.
+fn pick<'a>(
+    profiles: &'a [CompiledRule],
+    rules: &[CompiledRule],
+    proc: &ProcessInfo,
+) -> Option<&'a CompiledRule> {
+    if rules.iter().any(|r| r.matches(proc)) {
+        return None;
+    }
+    profiles.iter().find(|p| p.matches(proc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn compiled(name: &str, exes: &[&str]) -> CompiledRule {
+        CompiledRule {
+            name: name.to_string(),
+            match_exe: exes.iter().map(|s| s.to_string()).collect(),
+            cgroup: format!("profile-{name}"),
+            ..Default::default()
+        }
+    }
+
+    fn proc(pid: u32, name: &str, exe: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            ppid: None,
+            session: None,
+            executable: Some(PathBuf::from(exe)),
+        }
+    }
+
+    #[test]
+    fn rules_take_precedence_over_profiles() {
+        let profiles = [
+            compiled("browser", &["firefox"]),
+            compiled("build", &["cc1"]),
+        ];
+        let rules = [compiled("ff", &["firefox"])];
+        let firefox = proc(10, "firefox", "/usr/lib/firefox/firefox");
+        let cc1 = proc(11, "cc1", "/usr/libexec/gcc/cc1");
+        assert!(pick(&profiles, &rules, &firefox).is_none());
+        assert_eq!(
+            pick(&profiles, &rules, &cc1).map(|p| p.name.as_str()),
+            Some("build")
+        );
+        assert!(pick(&profiles, &[], &proc(12, "bash", "/bin/bash")).is_none());
+    }
+
+    #[test]
+    fn scheduled_profiles_lift_limits_outside_their_window() {
+        let mut cfg = Config::default();
+        for (name, schedule) in [("backup", Some("22:00-07:00")), ("broken", Some("late"))] {
+            cfg.profiles.insert(
+                name.into(),
+                Profile {
+                    match_exe: vec![name.into()],
+                    cpu: Some("25%".into()),
+                    schedule: schedule.map(String::from),
+                    ..Default::default()
+                },
+            );
+        }
+        let daemon = ProfileDaemon::new(&cfg);
+        assert_eq!(daemon.profile_names(), ["backup"]);
+        assert_eq!(
+            daemon.schedule("backup").map(|s| s.to_string()).as_deref(),
+            Some("22:00-07:00")
+        );
+        let backup = &daemon.profiles[0];
+        assert!(daemon.limit(backup, (true, false, false)).cpu.is_some());
+        assert!(daemon.limit(backup, (false, false, false)).is_empty());
+        assert!(ProfileDaemon::for_profile(&cfg, "broken").is_err());
+    }
+
+    #[test]
+    fn conditional_limits_replace_the_profiles_own() {
+        let mut cfg = Config::default();
+        cfg.profiles.insert(
+            "build".into(),
+            Profile {
+                match_exe: vec!["cc1".into()],
+                memory: Some("8G".into()),
+                cpu: Some("100%".into()),
+                on_battery: Some(Box::new(Profile {
+                    cpu: Some("25%".into()),
+                    ..Default::default()
+                })),
+                when_idle: Some(Box::new(Profile {
+                    cpu: Some("400%".into()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        );
+        let daemon = ProfileDaemon::new(&cfg);
+        assert!(daemon.has_battery_limits("build"));
+        let build = &daemon.profiles[0];
+        let cpu = |on_battery, idle| {
+            daemon
+                .limit(build, (true, on_battery, idle))
+                .cpu
+                .map(|c| c.percent())
+        };
+        assert_eq!(cpu(false, false), Some(100));
+        assert_eq!(cpu(true, false), Some(25));
+        assert_eq!(cpu(false, true), Some(400));
+        assert_eq!(cpu(true, true), Some(400));
+        let on_battery = daemon.limit(build, (true, true, false));
+        assert!(on_battery.memory.is_some());
+        assert!(daemon.uses_idle());
+    }
+
+    #[test]
+    fn daemon_applies_persistent_rules_first() {
+        let mut cfg = Config::default();
+        cfg.profiles.insert(
+            "browser".into(),
+            common::Profile {
+                match_exe: vec!["firefox".into()],
+                memory: Some("4G".into()),
+                ..Default::default()
+            },
+        );
+        cfg.add_rule(
+            "firefox",
+            common::AppRule {
+                match_exe: vec!["firefox".into()],
+                memory: Some("2G".into()),
+                ..Default::default()
+            },
+        );
+        let daemon = ProfileDaemon::new(&cfg);
+        assert_eq!(daemon.profile_names(), ["firefox", "browser"]);
+        let firefox = proc(10, "firefox", "/usr/lib/firefox/firefox");
+        let picked = pick(&daemon.profiles, &daemon.rules, &firefox).map(|p| p.cgroup.as_str());
+        assert_eq!(picked, Some("app-firefox"));
+    }
+}
@@ -1,4 +1,4 @@
-use common::Result;
+use common::{Error, Result};
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
@@ -11,6 +11,13 @@ pub struct DesktopApp {
     pub is_cli: bool,
 }
 
+impl DesktopApp {
+    /// The program and arguments to run it with.
+    pub fn command(&self) -> Vec<String> {
+        self.exec.split_whitespace().map(str::to_string).collect()
+    }
+}
+
 /// List installed applications from .desktop files
 pub fn list_applications() -> Result<Vec<DesktopApp>> {
     let mut apps = Vec::new();
@@ -20,10 +27,21 @@ pub fn list_applications() -> Result<Vec<DesktopApp>> {
         "/var/lib/flatpak/exports/share/applications",
     ];
 
-    // Also check user's local applications
-    let home_apps = dirs::data_dir().map(|d| d.join("applications"));
-
-    for dir in dirs.iter().map(Path::new).chain(home_apps.as_deref()) {
+    // Also check user's local applications, and their flatpaks
+    let home_dirs: Vec<_> = dirs::data_dir()
+        .map(|d| {
+            vec![
+                d.join("applications"),
+                d.join("flatpak/exports/share/applications"),
+            ]
+        })
+        .unwrap_or_default();
+
+    for dir in dirs
+        .iter()
+        .map(Path::new)
+        .chain(home_dirs.iter().map(|d| d.as_path()))
+    {
         if let Ok(entries) = fs::read_dir(dir) {
             for entry in entries.flatten() {
                 let path = entry.path();
@@ -41,8 +59,39 @@ pub fn list_applications() -> Result<Vec<DesktopApp>> {
     Ok(apps)
 }
 
+/// The installed application called `name`, ignoring case, or else the only
+/// one whose name contains it.
+pub fn find_application(name: &str) -> Result<DesktopApp> {
+    let apps = list_applications()?;
+    let wanted = name.to_lowercase();
+    if let Some(app) = apps.iter().find(|a| a.name.to_lowercase() == wanted) {
+        return Ok(app.clone());
+    }
+    let matching: Vec<&DesktopApp> = apps
+        .iter()
+        .filter(|a| a.name.to_lowercase().contains(&wanted))
+        .collect();
+    match matching[..] {
+        [app] => Ok(app.clone()),
+        [] => Err(Error::InvalidArgs(format!(
+            "no application named '{name}' (`rlm launch` lists them)"
+        ))),
+        _ => Err(Error::InvalidArgs(format!(
+            "'{name}' matches {}; give the full name",
+            matching
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
 fn parse_desktop_file(path: &Path) -> Option<DesktopApp> {
-    let content = fs::read_to_string(path).ok()?;
+    parse_desktop_entry(&fs::read_to_string(path).ok()?)
+}
+
+fn parse_desktop_entry(content: &str) -> Option<DesktopApp> {
     let mut name = None;
     let mut exec = None;
     let mut no_display = false;
@@ -69,6 +118,9 @@ fn parse_desktop_file(path: &Path) -> Option<DesktopApp> {
                 .split_whitespace()
                 .map(|arg| arg.trim_matches('"'))
                 .filter(|arg| !arg.is_empty() && !arg.starts_with('%'))
+                // Flatpak's markers around the field codes, for forwarding
+                // files into the sandbox; without files they are noise
+                .filter(|arg| !arg.starts_with("@@") && *arg != "--file-forwarding")
                 .collect();
 
             // Handle env wrappers (e.g., "env VAR=val app args")
@@ -144,3 +196,35 @@ pub fn search_cli_apps(query: &str) -> Vec<DesktopApp> {
     apps.dedup_by(|a, b| a.exec == b.exec);
     apps
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exec_without_field_codes_or_flatpak_markers() {
+        let entry = "[Desktop Entry]\n\
+                     Name=Firefox\n\
+                     Exec=/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=firefox \
+                     --file-forwarding org.mozilla.firefox @@u %u @@\n\
+                     [Desktop Action new-window]\n\
+                     Exec=firefox --new-window\n";
+        let app = parse_desktop_entry(entry).unwrap();
+        assert_eq!(app.name, "Firefox");
+        assert_eq!(
+            app.command(),
+            [
+                "/usr/bin/flatpak",
+                "run",
+                "--branch=stable",
+                "--arch=x86_64",
+                "--command=firefox",
+                "org.mozilla.firefox"
+            ]
+        );
+
+        let entry = "[Desktop Entry]\nName=Editor\nExec=env GDK_BACKEND=x11 gedit %U\n";
+        assert_eq!(parse_desktop_entry(entry).unwrap().command(), ["gedit"]);
+        assert!(parse_desktop_entry("[Desktop Entry]\nName=X\nExec=x\nNoDisplay=true\n").is_none());
+    }
+}
@@ -0,0 +1,303 @@
+//! Device access for `rlm run --deny-device`. Cgroup v2 has no device
+//! controller file; access is decided by eBPF programs attached to the
+//! cgroup, run on every open and mknod of a device node by a process in it
+//! (or below it). rlm attaches a small one that refuses the given devices
+//! and allows the rest, alongside any the system has attached already:
+//! a device is usable only if every program allows it.
+//!
+//! Descriptors opened before the command started are unaffected, and the
+//! program goes away with the cgroup.
+
+use common::{Error, Result};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+
+const BPF_PROG_LOAD: libc::c_int = 5;
+const BPF_PROG_ATTACH: libc::c_int = 8;
+const BPF_PROG_TYPE_CGROUP_DEVICE: u32 = 15;
+const BPF_CGROUP_DEVICE: u32 = 6;
+/// Run ours as well as any attached above or beside it, rather than instead
+const BPF_F_ALLOW_MULTI: u32 = 2;
+
+/// A character or block device, as the kernel tells the program of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Device {
+    pub kind: DeviceKind,
+    pub major: u32,
+    pub minor: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Block,
+    Char,
+}
+
+impl DeviceKind {
+    /// `BPF_DEVCG_DEV_BLOCK` and `BPF_DEVCG_DEV_CHAR`
+    fn code(self) -> i32 {
+        match self {
+            DeviceKind::Block => 1,
+            DeviceKind::Char => 2,
+        }
+    }
+}
+
+impl Device {
+    /// The device the node at `path` (e.g. `/dev/video0`) stands for.
+    pub fn of(path: &Path) -> Result<Self> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| Error::InvalidArgs(format!("{}: {e}", path.display())))?;
+        let kind = if metadata.file_type().is_char_device() {
+            DeviceKind::Char
+        } else if metadata.file_type().is_block_device() {
+            DeviceKind::Block
+        } else {
+            return Err(Error::InvalidArgs(format!(
+                "{} is not a device node",
+                path.display()
+            )));
+        };
+        let rdev = metadata.rdev();
+        Ok(Self {
+            kind,
+            major: libc::major(rdev),
+            minor: libc::minor(rdev),
+        })
+    }
+}
+
+/// One eBPF instruction, as `struct bpf_insn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+struct Insn {
+    code: u8,
+    /// Destination register in the low four bits, source in the high
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+impl Insn {
+    /// `dst = *(u32 *)(src + off)`
+    fn load_u32(dst: u8, src: u8, off: i16) -> Self {
+        Self {
+            code: 0x61,
+            regs: src << 4 | dst,
+            off,
+            imm: 0,
+        }
+    }
+
+    /// `dst &= imm`, as 32 bits
+    fn and(dst: u8, imm: i32) -> Self {
+        Self {
+            code: 0x54,
+            regs: dst,
+            off: 0,
+            imm,
+        }
+    }
+
+    /// `if dst != imm goto pc + off`
+    fn jump_ne(dst: u8, imm: i32, off: i16) -> Self {
+        Self {
+            code: 0x55,
+            regs: dst,
+            off,
+            imm,
+        }
+    }
+
+    /// `dst = imm`
+    fn mov(dst: u8, imm: i32) -> Self {
+        Self {
+            code: 0xb7,
+            regs: dst,
+            off: 0,
+            imm,
+        }
+    }
+
+    fn exit() -> Self {
+        Self {
+            code: 0x95,
+            regs: 0,
+            off: 0,
+            imm: 0,
+        }
+    }
+}
+
+/// The program: 0 (refuse) for any of `denied`, 1 (allow) for the rest.
+/// It is given `struct bpf_cgroup_dev_ctx`: the access and device type, then
+/// the major and minor number, as u32s.
+fn program(denied: &[Device]) -> Vec<Insn> {
+    let mut insns = vec![
+        Insn::load_u32(2, 1, 0),
+        // The access (read, write, mknod) is in the upper half
+        Insn::and(2, 0xffff),
+        Insn::load_u32(3, 1, 4),
+        Insn::load_u32(4, 1, 8),
+    ];
+    for device in denied {
+        // Each mismatch skips the rest of this device's five instructions
+        insns.extend([
+            Insn::jump_ne(2, device.kind.code(), 4),
+            Insn::jump_ne(3, device.major as i32, 3),
+            Insn::jump_ne(4, device.minor as i32, 2),
+            Insn::mov(0, 0),
+            Insn::exit(),
+        ]);
+    }
+    insns.extend([Insn::mov(0, 1), Insn::exit()]);
+    insns
+}
+
+/// The start of `union bpf_attr` for `BPF_PROG_LOAD`.
+#[repr(C)]
+#[derive(Default)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+    prog_ifindex: u32,
+    expected_attach_type: u32,
+}
+
+/// The start of `union bpf_attr` for `BPF_PROG_ATTACH`.
+#[repr(C)]
+struct ProgAttachAttr {
+    target_fd: u32,
+    attach_bpf_fd: u32,
+    attach_type: u32,
+    attach_flags: u32,
+}
+
+fn bpf<T>(cmd: libc::c_int, attr: &T) -> io::Result<libc::c_long> {
+    // SAFETY: `attr` is a bpf_attr prefix valid for the call's duration, and
+    // the kernel treats the fields past the size given as zero.
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *const T,
+            std::mem::size_of::<T>() as libc::c_uint,
+        )
+    };
+    if rc < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(rc)
+    }
+}
+
+/// Load the program for `denied`, for attaching.
+fn load(denied: &[Device]) -> io::Result<OwnedFd> {
+    let insns = program(denied);
+    let mut prog_name = [0; 16];
+    prog_name[..11].copy_from_slice(b"rlm_devices");
+    let attr = ProgLoadAttr {
+        prog_type: BPF_PROG_TYPE_CGROUP_DEVICE,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: c"Apache-2.0".as_ptr() as u64,
+        prog_name,
+        expected_attach_type: BPF_CGROUP_DEVICE,
+        ..ProgLoadAttr::default()
+    };
+    let fd = bpf(BPF_PROG_LOAD, &attr)?;
+    // SAFETY: the kernel just returned this descriptor, and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+}
+
+/// Refuse the processes in the cgroup at `cgroup_path` (and below it) the
+/// `denied` devices, for as long as the cgroup lasts.
+pub fn deny(cgroup_path: &Path, denied: &[Device]) -> Result<()> {
+    if denied.is_empty() {
+        return Ok(());
+    }
+    let program = load(denied).map_err(|e| match e.raw_os_error() {
+        Some(libc::EPERM) => Error::Cgroup(
+            "loading a device filter needs root (or CAP_BPF and CAP_SYS_ADMIN)".into(),
+        ),
+        _ => Error::Cgroup(format!("failed to load the device filter: {e}")),
+    })?;
+    let cgroup = std::fs::File::open(cgroup_path)?;
+    let attr = ProgAttachAttr {
+        target_fd: cgroup.as_raw_fd() as u32,
+        attach_bpf_fd: program.as_raw_fd() as u32,
+        attach_type: BPF_CGROUP_DEVICE,
+        attach_flags: BPF_F_ALLOW_MULTI,
+    };
+    // The cgroup keeps the program once attached; our descriptor can go
+    bpf(BPF_PROG_ATTACH, &attr).map_err(|e| {
+        Error::Cgroup(format!(
+            "failed to attach the device filter to {}: {e}{}",
+            cgroup_path.display(),
+            // A program attached above without BPF_F_ALLOW_MULTI (systemd's
+            // DevicePolicy, say) rules out any below it
+            if e.raw_os_error() == Some(libc::EPERM) {
+                " (a device policy above it may not allow others)"
+            } else {
+                ""
+            }
+        ))
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_of_a_node() {
+        let null = Device::of(Path::new("/dev/null")).unwrap();
+        assert_eq!(
+            null,
+            Device {
+                kind: DeviceKind::Char,
+                major: 1,
+                minor: 3
+            }
+        );
+        assert!(Device::of(Path::new("/etc/hostname")).is_err());
+    }
+
+    #[test]
+    fn program_refuses_each_device_and_allows_the_rest() {
+        let video = Device {
+            kind: DeviceKind::Char,
+            major: 81,
+            minor: 0,
+        };
+        let insns = program(&[video, Device { minor: 1, ..video }]);
+        assert_eq!(insns.len(), 4 + 2 * 5 + 2);
+        assert_eq!(insns[4], Insn::jump_ne(2, 2, 4));
+        assert_eq!(insns[11], Insn::jump_ne(4, 1, 2));
+        // Every jump lands on the next device's checks or the final allow
+        for (i, insn) in insns.iter().enumerate().filter(|(_, i)| i.code == 0x55) {
+            let target = i + 1 + insn.off as usize;
+            assert!(target == insns.len() - 2 || insns[target].off == 4, "{i}");
+        }
+        assert_eq!(insns[insns.len() - 2], Insn::mov(0, 1));
+    }
+
+    /// Needs root; the verifier checks the program is one it accepts.
+    #[test]
+    #[ignore = "requires root to load eBPF programs; run manually"]
+    fn kernel_accepts_the_program() {
+        let null = Device::of(Path::new("/dev/null")).unwrap();
+        load(&[null]).unwrap();
+    }
+}
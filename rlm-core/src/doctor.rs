@@ -0,0 +1,226 @@
+//! System requirement checks shared by `rlm doctor` and the GUI's debug info.
+//!
+//! Checks only read `/sys`, `/proc`, and the config path; nothing here needs
+//! privileges or a working [`CgroupManager`].
+
+use crate::CgroupManager;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Outcome of a single requirement check.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    /// Whether a failure should fail the overall result. Optional checks
+    /// (config file, PSI) only print their hints.
+    pub required: bool,
+    /// Remediation lines printed under a failed check.
+    pub hints: Vec<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, ok: bool, required: bool) -> Self {
+        Self {
+            name: name.into(),
+            ok,
+            required,
+            hints: Vec::new(),
+        }
+    }
+
+    fn hint(mut self, line: impl Into<String>) -> Self {
+        self.hints.push(line.into());
+        self
+    }
+}
+
+/// Whether every required check passed.
+pub fn all_ok(checks: &[Check]) -> bool {
+    checks.iter().all(|c| c.ok || !c.required)
+}
+
+/// Run all system requirement checks, in display order.
+pub fn run_checks() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    // Check cgroups v2
+    let root = crate::hierarchy::root();
+    let cgroup_check = root.join("cgroup.controllers").exists();
+    checks.push(
+        Check::new("cgroups v2 available", cgroup_check, true)
+            .hint("ensure kernel supports cgroups v2 and unified hierarchy is mounted"),
+    );
+
+    // Check available controllers
+    if cgroup_check {
+        if let Ok(controllers) = fs::read_to_string(root.join("cgroup.controllers")) {
+            for controller in ["memory", "cpu", "io"] {
+                checks.push(Check::new(
+                    format!("{controller} controller"),
+                    controllers.contains(controller),
+                    true,
+                ));
+            }
+            // Only needed for --max-pids and --mems
+            checks.push(
+                Check::new("pids controller", controllers.contains("pids"), false)
+                    .hint("--max-pids limits will not work without it"),
+            );
+            checks.push(
+                Check::new("cpuset controller", controllers.contains("cpuset"), false)
+                    .hint("--mems (NUMA node) limits will not work without it"),
+            );
+        }
+    }
+
+    // Check user cgroup delegation (for non-root)
+    if let Some(uid) = current_uid() {
+        if uid != 0 {
+            let user_slice = root.join(format!("user.slice/user-{uid}.slice/user@{uid}.service"));
+            let delegation_ok =
+                user_slice.exists() || crate::hierarchy::delegated_subtree().is_some();
+            checks.push(
+                Check::new("user cgroup delegation", delegation_ok, true)
+                    .hint("run these commands to enable delegation:")
+                    .hint("   sudo mkdir -p /etc/systemd/system/user@.service.d")
+                    .hint("   echo '[Service]' | sudo tee /etc/systemd/system/user@.service.d/delegate.conf")
+                    .hint("   echo 'Delegate=cpu memory io pids cpuset' | sudo tee -a /etc/systemd/system/user@.service.d/delegate.conf")
+                    .hint("   sudo systemctl daemon-reload")
+                    .hint("   # then log out and back in"),
+            );
+        } else {
+            checks.push(Check::new("running as root", true, true));
+        }
+    }
+
+    // Check config file
+    let config_path = dirs::config_dir()
+        .map(|p| p.join("rlm/config.yaml"))
+        .unwrap_or_default();
+    checks.push(
+        Check::new(
+            format!("config file ({})", config_path.display()),
+            config_path.exists(),
+            false,
+        )
+        .hint("optional: create config for profiles"),
+    );
+
+    // Check PSI availability (required by the freeze guard, rlm-guard)
+    checks.push(
+        Check::new(
+            "memory pressure info (PSI, for rlm-guard)",
+            Path::new("/proc/pressure/memory").exists(),
+            false,
+        )
+        .hint("the freeze guard needs PSI; boot with `psi=1` if your kernel disables it"),
+    );
+
+    checks
+}
+
+/// Real UID from the kernel (not `$UID`, which the caller controls).
+fn current_uid() -> Option<u32> {
+    fs::read_to_string("/proc/self/status").ok().and_then(|s| {
+        s.lines()
+            .find(|l| l.starts_with("Uid:"))
+            .and_then(|l| l.split_whitespace().nth(1))
+            .and_then(|u| u.parse::<u32>().ok())
+    })
+}
+
+/// Plain-text report for bug reports: system info, cgroup backend details, and
+/// the doctor results. `manager` is `None` when cgroup setup failed, which is
+/// itself worth reporting.
+pub fn debug_report(manager: Option<&CgroupManager>) -> String {
+    let mut out = String::new();
+
+    let (kernel, os) = kernel_and_os();
+    let _ = writeln!(out, "Kernel: {kernel}");
+    let _ = writeln!(out, "OS: {os}");
+
+    let _ = writeln!(out, "\nCgroup backend:");
+    match manager {
+        Some(mgr) => {
+            let base = mgr.base_path();
+            let _ = writeln!(out, "  base path: {}", base.display());
+            let _ = writeln!(out, "  base exists: {}", base.exists());
+            // Controllers are read from the base's parent when the base hasn't
+            // been created yet (nothing limited so far).
+            let dir = if base.exists() {
+                base
+            } else {
+                base.parent().unwrap_or(base)
+            };
+            for file in ["cgroup.controllers", "cgroup.subtree_control"] {
+                let value = fs::read_to_string(dir.join(file))
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_else(|e| format!("unreadable ({e})"));
+                let _ = writeln!(out, "  {file}: {value}");
+            }
+        }
+        None => {
+            let _ = writeln!(out, "  unavailable (cgroup manager failed to initialize)");
+        }
+    }
+
+    let _ = writeln!(out, "\nDoctor:");
+    for check in run_checks() {
+        let status = if check.ok { "[ok]" } else { "[FAIL]" };
+        let _ = writeln!(out, "  {status} {}", check.name);
+    }
+
+    out
+}
+
+/// Kernel release and OS name, "unknown" where unreadable.
+pub(crate) fn kernel_and_os() -> (String, String) {
+    let kernel = fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|_| "unknown".into());
+    let os = fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|s| os_pretty_name(&s))
+        .unwrap_or_else(|| "unknown".into());
+    (kernel, os)
+}
+
+/// Extract `PRETTY_NAME` from an `/etc/os-release` body.
+fn os_pretty_name(os_release: &str) -> Option<String> {
+    os_release.lines().find_map(|line| {
+        line.strip_prefix("PRETTY_NAME=")
+            .map(|v| v.trim().trim_matches('"').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pretty_name_is_unquoted() {
+        let s = "NAME=\"Fedora Linux\"\nPRETTY_NAME=\"Fedora Linux 40 (Workstation Edition)\"\nID=fedora\n";
+        assert_eq!(
+            os_pretty_name(s).as_deref(),
+            Some("Fedora Linux 40 (Workstation Edition)")
+        );
+    }
+
+    #[test]
+    fn pretty_name_missing_is_none() {
+        assert_eq!(os_pretty_name("NAME=Arch\nID=arch\n"), None);
+    }
+
+    #[test]
+    fn optional_failures_do_not_fail_overall() {
+        let checks = vec![
+            Check::new("required", true, true),
+            Check::new("optional", false, false),
+        ];
+        assert!(all_ok(&checks));
+        let checks = vec![Check::new("required", false, true)];
+        assert!(!all_ok(&checks));
+    }
+}
@@ -0,0 +1,127 @@
+//! Re-attaching processes that escape their cgroup. systemd sometimes moves a
+//! process back into its original scope (on `daemon-reload`, or when a unit
+//! is restarted around it), which silently drops every limit rlm set. The
+//! kernel sends no inotify event for `cgroup.procs` changes, so membership of
+//! the managed cgroups is polled: a live process that has left one for a
+//! cgroup outside rlm is moved back, with a warning.
+//!
+//! Moves rlm makes itself always land in a managed cgroup (`unlimit` when
+//! limits are removed), so they never count as escapes. The guard's freezer
+//! cgroups and `rescue` are left alone: processes leave those on purpose.
+
+use crate::CgroupManager;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// A process found outside the cgroup it was in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Escape {
+    pub pid: u32,
+    pub name: String,
+    /// Managed cgroup it left
+    pub cgroup: String,
+    /// Where it was found, relative to the cgroup root
+    pub found_in: String,
+    /// Whether moving it back worked
+    pub reattached: bool,
+}
+
+/// Remembers which processes each managed cgroup held at the last tick.
+#[derive(Debug, Default)]
+pub struct EscapeWatcher {
+    members: HashMap<String, HashSet<u32>>,
+    /// Whether a tick has set the baseline
+    primed: bool,
+}
+
+impl EscapeWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move back every process that left a managed cgroup for one outside
+    /// rlm since the last tick. The first tick only takes the baseline.
+    pub fn tick(&mut self, mgr: &CgroupManager) -> Vec<Escape> {
+        let now: HashMap<String, HashSet<u32>> = crate::events::cgroup_names(mgr.base_path())
+            .into_iter()
+            .filter(|name| watched(name))
+            .map(|name| {
+                let pids = mgr.pids_in_cgroup(&name).into_iter().collect();
+                (name, pids)
+            })
+            .collect();
+
+        let mut escapes = Vec::new();
+        if self.primed {
+            for (cgroup, before) in &self.members {
+                let Some(after) = now.get(cgroup) else {
+                    // Removed along with its processes
+                    continue;
+                };
+                for &pid in before.difference(after) {
+                    if let Some(escape) = reattach(mgr, cgroup, pid) {
+                        escapes.push(escape);
+                    }
+                }
+            }
+        }
+        self.members = now;
+        // Moved back: count them as members again
+        for escape in escapes.iter().filter(|e| e.reattached) {
+            if let Some(pids) = self.members.get_mut(&escape.cgroup) {
+                pids.insert(escape.pid);
+            }
+        }
+        self.primed = true;
+        escapes
+    }
+}
+
+/// Move `pid` back into `cgroup` if it left for a cgroup outside rlm. `None`
+/// when it exited or moved to another managed cgroup.
+fn reattach(mgr: &CgroupManager, cgroup: &str, pid: u32) -> Option<Escape> {
+    let found_in = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    let found_in = crate::cgroup::unified_cgroup(&found_in)?.to_string();
+    if mgr.manages_pid(pid) {
+        return None;
+    }
+    let name = crate::status::process_name(pid).unwrap_or_default();
+    let reattached = match mgr.add_to_cgroup(&mgr.base_path().join(cgroup), pid) {
+        Ok(()) => {
+            tracing::warn!(pid, name = %name, cgroup, found_in = %found_in, "process escaped its cgroup; moved it back");
+            true
+        }
+        Err(e) => {
+            tracing::warn!(pid, name = %name, cgroup, found_in = %found_in, error = %e, "process escaped its cgroup and could not be moved back");
+            false
+        }
+    };
+    Some(Escape {
+        pid,
+        name,
+        cgroup: cgroup.to_string(),
+        found_in,
+        reattached,
+    })
+}
+
+/// Whether processes leaving `name` are brought back.
+fn watched(name: &str) -> bool {
+    name != "unlimit" && name != crate::cgroup::RESCUE_CGROUP && !name.starts_with("guard-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_cgroups_processes_stay_in_are_watched() {
+        assert!(watched("app-firefox"));
+        assert!(watched("profile-build"));
+        assert!(watched("pid-4242"));
+        assert!(watched("protect"));
+        assert!(!watched("unlimit"));
+        assert!(!watched("rescue"));
+        assert!(!watched("guard-4242"));
+    }
+}
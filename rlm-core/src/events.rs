@@ -0,0 +1,286 @@
+//! Memory events of managed cgroups as they happen, behind `rlm events`.
+//!
+//! Each cgroup's `memory.events.local` (`memory.events` on kernels without
+//! it) is watched with inotify: the kernel signals a modify whenever one of
+//! its counters moves. Changes are reported as deltas against the previous
+//! read, so a burst of breaches between two reads is one event with a count.
+//! New cgroups are picked up through a watch on the rlm base directory.
+
+use crate::watch::Inotify;
+use crate::CgroupManager;
+use common::{Error, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A `memory.events` counter worth reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Went over `memory.high` and was throttled.
+    High,
+    /// Hit `memory.max`.
+    Max,
+    /// The OOM killer was invoked.
+    Oom,
+    /// A process was OOM-killed.
+    OomKill,
+    /// The whole cgroup was OOM-killed (`memory.oom.group`).
+    OomGroupKill,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 5] = [
+        EventKind::High,
+        EventKind::Max,
+        EventKind::Oom,
+        EventKind::OomKill,
+        EventKind::OomGroupKill,
+    ];
+
+    /// The counter's key in `memory.events`.
+    pub fn key(self) -> &'static str {
+        match self {
+            EventKind::High => "high",
+            EventKind::Max => "max",
+            EventKind::Oom => "oom",
+            EventKind::OomKill => "oom_kill",
+            EventKind::OomGroupKill => "oom_group_kill",
+        }
+    }
+}
+
+/// Counter values from one read of `memory.events`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters([u64; 5]);
+
+impl Counters {
+    pub fn get(&self, kind: EventKind) -> u64 {
+        self.0[kind as usize]
+    }
+
+    /// Counters that moved since `self`, with by how much. Counters only
+    /// grow; one that went down belongs to a recreated cgroup, so its whole
+    /// value is new.
+    pub fn changes(&self, newer: &Counters) -> Vec<(EventKind, u64)> {
+        EventKind::ALL
+            .iter()
+            .filter_map(|&kind| {
+                let (old, new) = (self.get(kind), newer.get(kind));
+                let delta = if new >= old { new - old } else { new };
+                (delta > 0).then_some((kind, delta))
+            })
+            .collect()
+    }
+
+    /// One line of JSON with the cgroup's name, for `rlm events --json`.
+    pub fn to_json(&self, cgroup: &str) -> String {
+        let mut obj = serde_json::Map::new();
+        obj.insert("cgroup".into(), cgroup.into());
+        for kind in EventKind::ALL {
+            obj.insert(kind.key().into(), self.get(kind).into());
+        }
+        serde_json::Value::Object(obj).to_string()
+    }
+}
+
+/// Parse `memory.events` (`key value` per line). Unknown keys are ignored.
+pub fn parse_counters(content: &str) -> Counters {
+    let mut counters = Counters::default();
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(value) = value.trim().parse() else {
+            continue;
+        };
+        if let Some(kind) = EventKind::ALL.iter().find(|k| k.key() == key) {
+            counters.0[*kind as usize] = value;
+        }
+    }
+    counters
+}
+
+/// One counter moving in one cgroup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemoryEvent {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub cgroup: String,
+    pub kind: EventKind,
+    /// How many times it happened since the previous event.
+    pub count: u64,
+    /// The counter's value now.
+    pub total: u64,
+}
+
+impl MemoryEvent {
+    /// One line of JSON, for `rlm events --json`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Current counters of every managed cgroup, sorted by name.
+pub fn snapshot(manager: &CgroupManager) -> Vec<(String, Counters)> {
+    let mut all: Vec<_> = cgroup_names(manager.base_path())
+        .into_iter()
+        .filter_map(|name| {
+            let file = events_file(&manager.base_path().join(&name))?;
+            let counters = parse_counters(&fs::read_to_string(file).ok()?);
+            Some((name, counters))
+        })
+        .collect();
+    all.sort_by(|a, b| a.0.cmp(&b.0));
+    all
+}
+
+/// Watches the memory events of all managed cgroups, including ones
+/// created after it started.
+pub struct EventWatcher {
+    inotify: Inotify,
+    base: PathBuf,
+    base_wd: i32,
+    /// Watch descriptor -> cgroup name and the file watched.
+    watches: HashMap<i32, (String, PathBuf)>,
+    last: HashMap<String, Counters>,
+}
+
+impl EventWatcher {
+    pub fn new(manager: &CgroupManager) -> Result<Self> {
+        let base = manager.base_path().to_path_buf();
+        if !base.is_dir() {
+            return Err(Error::Cgroup(format!(
+                "{} does not exist yet: nothing is managed",
+                base.display()
+            )));
+        }
+
+        let inotify = Inotify::new()?;
+        let base_wd = inotify.add_watch(&base, libc::IN_CREATE | libc::IN_MOVED_TO)?;
+        let mut watcher = Self {
+            inotify,
+            base_wd,
+            base,
+            watches: HashMap::new(),
+            last: HashMap::new(),
+        };
+        for name in cgroup_names(&watcher.base) {
+            watcher.watch_cgroup(&name);
+        }
+        Ok(watcher)
+    }
+
+    /// Block until something changes and return the events it caused
+    /// (possibly none, e.g. when only a new cgroup appeared).
+    pub fn wait(&mut self) -> Result<Vec<MemoryEvent>> {
+        let mut changed = Vec::new();
+        for (wd, mask, name) in self.inotify.read(None)? {
+            if wd == self.base_wd {
+                if let Some(name) = name {
+                    self.watch_cgroup(&name);
+                }
+            } else if mask & libc::IN_IGNORED != 0 {
+                // The cgroup was removed
+                if let Some((name, _)) = self.watches.remove(&wd) {
+                    self.last.remove(&name);
+                }
+            } else if !changed.contains(&wd) {
+                changed.push(wd);
+            }
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut events = Vec::new();
+        for wd in changed {
+            let Some((name, file)) = self.watches.get(&wd) else {
+                continue;
+            };
+            let Ok(content) = fs::read_to_string(file) else {
+                continue;
+            };
+            let now = parse_counters(&content);
+            let before = self.last.insert(name.clone(), now).unwrap_or_default();
+            for (kind, count) in before.changes(&now) {
+                events.push(MemoryEvent {
+                    time,
+                    cgroup: name.clone(),
+                    kind,
+                    count,
+                    total: now.get(kind),
+                });
+            }
+        }
+        Ok(events)
+    }
+
+    /// Start watching one cgroup; its current counters are the baseline.
+    fn watch_cgroup(&mut self, name: &str) {
+        if name == "unlimit" || self.watches.values().any(|(n, _)| n == name) {
+            return;
+        }
+        let Some(file) = events_file(&self.base.join(name)) else {
+            return;
+        };
+        match self.inotify.add_watch(&file, libc::IN_MODIFY) {
+            Ok(wd) => {
+                let counters = fs::read_to_string(&file)
+                    .map(|c| parse_counters(&c))
+                    .unwrap_or_default();
+                self.last.insert(name.to_string(), counters);
+                self.watches.insert(wd, (name.to_string(), file));
+            }
+            Err(e) => tracing::debug!(cgroup = name, error = %e, "cannot watch memory events"),
+        }
+    }
+}
+
+/// Child cgroup directory names under `base`.
+pub(crate) fn cgroup_names(base: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(base) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| name != "unlimit")
+        .collect()
+}
+
+/// `memory.events.local` counts only the cgroup's own events; older kernels
+/// only have the hierarchical `memory.events`.
+pub(crate) fn events_file(cgroup_path: &Path) -> Option<PathBuf> {
+    ["memory.events.local", "memory.events"]
+        .iter()
+        .map(|f| cgroup_path.join(f))
+        .find(|p| p.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_report_deltas() {
+        let before = parse_counters("low 0\nhigh 10\nmax 2\noom 0\noom_kill 0\n");
+        let after = parse_counters("low 0\nhigh 14\nmax 2\noom 1\noom_kill 1\noom_group_kill 0\n");
+        assert_eq!(
+            before.changes(&after),
+            vec![
+                (EventKind::High, 4),
+                (EventKind::Oom, 1),
+                (EventKind::OomKill, 1)
+            ]
+        );
+        assert!(after.changes(&after).is_empty());
+        // A recreated cgroup starts over: its counts are all new
+        let reset = parse_counters("high 3\n");
+        assert_eq!(after.changes(&reset), vec![(EventKind::High, 3)]);
+    }
+}
@@ -0,0 +1,205 @@
+//! Focus boost, behind `rlm daemon --boost-focus`: the managed cgroup owning
+//! the focused window gets a high `cpu.weight` and the other managed cgroups a
+//! low one, so under contention the app in use wins. Weights only matter when
+//! the CPU is contended and never cap anything; the ones rlm found are put
+//! back when the daemon stops.
+//!
+//! The focused window's process comes from the compositor: Hyprland
+//! (`hyprctl`), Sway (`swaymsg`), or any EWMH X11 window manager (`xprop`).
+//! GNOME and KDE on Wayland expose no focus API without an extension.
+
+use crate::CgroupManager;
+use std::collections::HashMap;
+use std::process::Command;
+
+/// `cpu.weight` of the focused app's cgroup.
+pub const FOCUS_WEIGHT: u32 = 1000;
+/// `cpu.weight` of the other managed cgroups.
+pub const BACKGROUND_WEIGHT: u32 = 50;
+
+/// Where the focused window comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Hyprland,
+    Sway,
+    X11,
+}
+
+impl Backend {
+    /// The backend for this session, from its environment.
+    pub fn detect() -> Option<Self> {
+        let set = |var| std::env::var_os(var).is_some_and(|v| !v.is_empty());
+        if set("HYPRLAND_INSTANCE_SIGNATURE") {
+            Some(Backend::Hyprland)
+        } else if set("SWAYSOCK") {
+            Some(Backend::Sway)
+        } else if set("DISPLAY") && !set("WAYLAND_DISPLAY") {
+            Some(Backend::X11)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Hyprland => "Hyprland",
+            Backend::Sway => "Sway",
+            Backend::X11 => "X11",
+        }
+    }
+
+    /// PID of the process owning the focused window.
+    pub fn focused_pid(self) -> Option<u32> {
+        match self {
+            Backend::Hyprland => {
+                let out = run("hyprctl", &["activewindow", "-j"])?;
+                let window: serde_json::Value = serde_json::from_str(&out).ok()?;
+                window_pid(&window)
+            }
+            Backend::Sway => {
+                let out = run("swaymsg", &["-t", "get_tree", "-r"])?;
+                sway_focused_pid(&serde_json::from_str(&out).ok()?)
+            }
+            Backend::X11 => {
+                let root = run("xprop", &["-root", "_NET_ACTIVE_WINDOW"])?;
+                let window = xprop_active_window(&root)?;
+                xprop_pid(&run("xprop", &["-id", window, "_NET_WM_PID"])?)
+            }
+        }
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A positive `pid` field of a window in JSON.
+fn window_pid(window: &serde_json::Value) -> Option<u32> {
+    let pid = window.get("pid")?.as_u64()?;
+    u32::try_from(pid).ok().filter(|&p| p > 0)
+}
+
+/// The focused node of a `swaymsg -t get_tree` tree.
+pub fn sway_focused_pid(node: &serde_json::Value) -> Option<u32> {
+    if node.get("focused").and_then(|f| f.as_bool()) == Some(true) {
+        return window_pid(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(key)?.as_array())
+        .flatten()
+        .find_map(sway_focused_pid)
+}
+
+/// The window id in `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007`.
+pub fn xprop_active_window(output: &str) -> Option<&str> {
+    let id = output.split('#').nth(1)?.split(',').next()?.trim();
+    (id.starts_with("0x") && id != "0x0").then_some(id)
+}
+
+/// The PID in `_NET_WM_PID(CARDINAL) = 12345`.
+pub fn xprop_pid(output: &str) -> Option<u32> {
+    output.split('=').nth(1)?.trim().parse().ok()
+}
+
+/// Moves the weight boost along with focus.
+#[derive(Debug, Default)]
+pub struct FocusBooster {
+    /// Cgroup name -> the `cpu.weight` it had before it was touched
+    original: HashMap<String, u32>,
+    focused: Option<String>,
+}
+
+impl FocusBooster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The managed cgroup currently boosted.
+    pub fn focused(&self) -> Option<&str> {
+        self.focused.as_deref()
+    }
+
+    /// Boost the cgroup holding `focused_pid` (none if it is unmanaged) and
+    /// lower the others, including cgroups created since the last call.
+    /// Returns whether the boosted cgroup changed.
+    pub fn update(&mut self, mgr: &CgroupManager, focused_pid: Option<u32>) -> bool {
+        let focused = focused_pid
+            .and_then(|pid| mgr.find_cgroup_for_pid(pid))
+            .filter(|name| boostable(name));
+        let names: Vec<String> = crate::events::cgroup_names(mgr.base_path())
+            .into_iter()
+            .filter(|name| boostable(name))
+            .collect();
+        self.original.retain(|name, _| names.contains(name));
+
+        for name in &names {
+            let Some(current) = mgr.cpu_weight_of(name) else {
+                continue;
+            };
+            let original = *self.original.entry(name.clone()).or_insert(current);
+            let wanted = if Some(name) == focused.as_ref() {
+                FOCUS_WEIGHT.max(original)
+            } else {
+                BACKGROUND_WEIGHT.min(original)
+            };
+            if current != wanted {
+                if let Err(e) = mgr.set_cpu_weight_of(name, wanted) {
+                    tracing::debug!(cgroup = %name, error = %e, "focus: cannot set cpu.weight");
+                }
+            }
+        }
+
+        let changed = focused != self.focused;
+        self.focused = focused;
+        changed
+    }
+
+    /// Put back every weight changed, e.g. when the daemon stops.
+    pub fn restore(&mut self, mgr: &CgroupManager) {
+        for (name, weight) in self.original.drain() {
+            let _ = mgr.set_cpu_weight_of(&name, weight);
+        }
+        self.focused = None;
+    }
+}
+
+/// Cgroups with a role of their own keep their weights: the shared desktop
+/// cgroup, rescued processes, reservations, and the guard's freezer cgroups.
+fn boostable(name: &str) -> bool {
+    name != "protect"
+        && name != "rescue"
+        && !name.starts_with("reserve-")
+        && !name.starts_with("guard-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_focused_window_pid() {
+        let tree = serde_json::json!({
+            "focused": false,
+            "nodes": [{
+                "focused": false,
+                "nodes": [{"focused": false, "pid": 10}],
+                "floating_nodes": [{"focused": true, "pid": 42}]
+            }]
+        });
+        assert_eq!(sway_focused_pid(&tree), Some(42));
+
+        let root = "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007\n";
+        assert_eq!(xprop_active_window(root), Some("0x3a00007"));
+        assert_eq!(
+            xprop_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0"),
+            None
+        );
+        assert_eq!(xprop_pid("_NET_WM_PID(CARDINAL) = 12345\n"), Some(12345));
+        assert_eq!(xprop_pid("_NET_WM_PID:  not found.\n"), None);
+    }
+}
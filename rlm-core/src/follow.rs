@@ -0,0 +1,151 @@
+//! `rlm limit --children follow`: keeping a limited process's descendants in
+//! its cgroup as they start. Processes forked inside the cgroup stay there
+//! anyway; this catches the rest: children of the tree's processes that
+//! were left outside, and ones a launcher or systemd moves away.
+//!
+//! Driven by [`ProcessEvent`]s: with the proc connector each fork is seen as
+//! it happens; when polling, a new process is checked by walking its
+//! parents, so one that started and forked again between polls still counts.
+
+use crate::proc_events::ProcessEvent;
+use crate::{process, CgroupManager};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Parents to walk up before giving up on a process being in the tree
+const MAX_DEPTH: usize = 64;
+
+/// The processes descended from one root, and the cgroup they belong in.
+pub struct TreeFollower {
+    root: u32,
+    cgroup: String,
+    cgroup_path: PathBuf,
+    members: HashSet<u32>,
+}
+
+impl TreeFollower {
+    /// Follow the tree of `root`, already in the managed cgroup `cgroup`
+    /// along with `members`, its descendants so far.
+    pub fn new(
+        root: u32,
+        cgroup: &str,
+        cgroup_path: PathBuf,
+        members: impl IntoIterator<Item = u32>,
+    ) -> Self {
+        let mut members: HashSet<u32> = members.into_iter().collect();
+        members.insert(root);
+        Self {
+            root,
+            cgroup: cgroup.to_string(),
+            cgroup_path,
+            members,
+        }
+    }
+
+    /// Whether the root process has exited, after which nothing is followed.
+    pub fn root_exited(&self) -> bool {
+        process::info(self.root).is_none()
+    }
+
+    /// Take in `event`; returns the PID moved into the cgroup, if any.
+    pub fn handle(&mut self, manager: &CgroupManager, event: &ProcessEvent) -> Option<u32> {
+        match *event {
+            ProcessEvent::Fork { parent, child } if self.members.contains(&parent) => {
+                self.adopt(manager, child)
+            }
+            ProcessEvent::Exec { pid } if !self.members.contains(&pid) && self.descends(pid) => {
+                self.adopt(manager, pid)
+            }
+            ProcessEvent::Exit { pid, .. } => {
+                self.members.remove(&pid);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `pid` has a member of the tree among its ancestors.
+    fn descends(&self, pid: u32) -> bool {
+        let mut current = pid;
+        for _ in 0..MAX_DEPTH {
+            match process::info(current).and_then(|p| p.ppid) {
+                Some(ppid) if self.members.contains(&ppid) => return true,
+                Some(ppid) if ppid > 1 => current = ppid,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    fn adopt(&mut self, manager: &CgroupManager, pid: u32) -> Option<u32> {
+        self.members.insert(pid);
+        // Forked inside the cgroup, or limited on its own since: leave it
+        if manager.managed_cgroup_of(pid).is_some() {
+            return None;
+        }
+        match manager.add_to_cgroup(&self.cgroup_path, pid) {
+            Ok(()) => Some(pid),
+            Err(e) => {
+                // Most likely gone already
+                tracing::debug!(pid, cgroup = %self.cgroup, error = %e, "could not follow child");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{CgroupBackend, FakeBackend};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn moves_descendants_and_ignores_the_rest() {
+        let fake = Arc::new(FakeBackend::new("/cg", ""));
+        fake.create_dir_all(Path::new("/cg/pid-1")).unwrap();
+        let manager = CgroupManager::with_backend("/cg".into(), fake.clone());
+
+        let mut shell = std::process::Command::new("sh")
+            .args(["-c", "sleep 5 & wait"])
+            .spawn()
+            .unwrap();
+        let root = shell.id();
+        let sleep = loop {
+            let tree = process::find_process_tree(root).unwrap();
+            if let Some(&pid) = tree.iter().find(|&&p| p != root) {
+                break pid;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+
+        let mut follower = TreeFollower::new(root, "pid-1", "/cg/pid-1".into(), []);
+        let stranger = std::process::id();
+        assert_eq!(
+            follower.handle(&manager, &ProcessEvent::Exec { pid: stranger }),
+            None
+        );
+        assert_eq!(
+            follower.handle(&manager, &ProcessEvent::Exec { pid: sleep }),
+            Some(sleep)
+        );
+        assert_eq!(
+            fake.file("/cg/pid-1/cgroup.procs").unwrap(),
+            format!("{sleep}\n")
+        );
+        // Known now, so its own fork counts
+        let fork = ProcessEvent::Fork {
+            parent: sleep,
+            child: stranger,
+        };
+        assert_eq!(follower.handle(&manager, &fork), Some(stranger));
+
+        shell.kill().unwrap();
+        shell.wait().unwrap();
+        assert!(follower.root_exited());
+        let _ = std::process::Command::new("kill")
+            .arg(sleep.to_string())
+            .status();
+    }
+}
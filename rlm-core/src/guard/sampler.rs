@@ -112,6 +112,11 @@ impl Sampler {
             if self.protect.contains(&pname) {
                 continue;
             }
+            // Reserved with `rlm reserve`: the user asked for this one to be
+            // protected, so it is never the victim.
+            if fs::read_to_string(format!("/proc/{pid}/cgroup")).is_ok_and(|c| in_reservation(&c)) {
+                continue;
+            }
 
             out.push(ProcInfo {
                 pid,
@@ -126,6 +131,18 @@ impl Sampler {
     }
 }
 
+/// Whether a `/proc/<pid>/cgroup` body places the process in an rlm
+/// `reserve-*` cgroup (e.g. `0::/user.slice/.../rlm/reserve-code`).
+fn in_reservation(proc_cgroup: &str) -> bool {
+    proc_cgroup.lines().any(|l| {
+        l.starts_with("0::")
+            && l.rsplit('/')
+                .next()
+                .is_some_and(|leaf| leaf.starts_with("reserve-"))
+            && l.contains("/rlm/")
+    })
+}
+
 /// Parse `/proc/pressure/memory`, returning `(some_avg10, full_avg10)`.
 ///
 /// Expected format (the `full` line may be absent on some kernels):
@@ -263,6 +280,17 @@ mod tests {
 
     // ---- parse_mem_available_mb -----------------------------------------
 
+    #[test]
+    fn reservation_detected_from_cgroup_path() {
+        assert!(in_reservation(
+            "0::/user.slice/user-1000.slice/user@1000.service/rlm/reserve-code\n"
+        ));
+        assert!(!in_reservation(
+            "0::/user.slice/user-1000.slice/user@1000.service/rlm/app-code\n"
+        ));
+        assert!(!in_reservation("0::/user.slice/reserve-code\n"));
+    }
+
     #[test]
     fn mem_available_basic() {
         // 2_097_152 kB == 2048 MB
@@ -0,0 +1,306 @@
+//! The privileged helper, for a GUI without cgroup delegation. `rlm-helper`
+//! is started through pkexec, so the user authenticates once, and then makes
+//! the GUI's cgroup writes in the system-wide tree
+//! ([`CgroupManager::system`]) for as long as the GUI runs; the GUI reads
+//! that tree itself. Requests and replies are JSON, one per line, over the
+//! helper's stdin and stdout. Closing its stdin stops it.
+//!
+//! The helper only touches processes of the user who started it
+//! (`PKEXEC_UID`), and only the kinds of cgroup the GUI creates.
+
+use crate::CgroupManager;
+use common::{Error, Limit, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::MetadataExt;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Where packages install the helper; pkexec's policy names this path.
+pub const HELPER_PATH: &str = "/usr/libexec/rlm-helper";
+
+/// Bumped on incompatible changes. The helper greets with it.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Cgroup name prefixes the GUI creates, the only ones the helper touches.
+const GUI_PREFIXES: &[&str] = &["pid-", "app-", "multi-", "gtk-"];
+
+/// One request to the helper.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    ApplyLimit {
+        pid: u32,
+        limit: Limit,
+    },
+    ApplyShared {
+        pids: Vec<u32>,
+        limit: Limit,
+        cgroup: String,
+    },
+    PrepareCgroup {
+        cgroup: String,
+        limit: Limit,
+    },
+    AddToCgroup {
+        cgroup: String,
+        pid: u32,
+    },
+    RemoveCgroup {
+        cgroup: String,
+    },
+}
+
+/// The helper's answer: a greeting once started, then one per request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+pub enum Reply {
+    Ready { version: u32 },
+    Done,
+    Failed { error: String },
+}
+
+/// A running helper, from the GUI's side.
+pub struct Helper {
+    // Dropping stdin (EOF) makes the helper exit; it runs as root, so it
+    // can't be killed from here.
+    _child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+}
+
+impl Helper {
+    /// Start the helper through pkexec, which asks the user to authenticate.
+    /// Blocks until they have.
+    pub fn start() -> Result<Self> {
+        let mut child = Command::new("pkexec")
+            .arg(HELPER_PATH)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::Cgroup(format!("failed to run pkexec: {e}")))?;
+        let (Some(input), Some(output)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(Error::Cgroup("no pipe to the helper".into()));
+        };
+        let mut helper = Self {
+            _child: child,
+            input,
+            output: BufReader::new(output),
+        };
+        match helper.read()? {
+            Reply::Ready { version } if version == PROTOCOL_VERSION => Ok(helper),
+            Reply::Ready { version } => Err(Error::Cgroup(format!(
+                "the helper speaks protocol {version}, expected {PROTOCOL_VERSION}"
+            ))),
+            reply => Err(Error::Cgroup(format!(
+                "unexpected greeting from the helper: {reply:?}"
+            ))),
+        }
+    }
+
+    pub fn apply_limit(&mut self, pid: u32, limit: &Limit) -> Result<()> {
+        self.call(&Request::ApplyLimit {
+            pid,
+            limit: limit.clone(),
+        })
+    }
+
+    pub fn apply_limit_to_multiple(
+        &mut self,
+        pids: &[u32],
+        limit: &Limit,
+        cgroup: &str,
+    ) -> Result<()> {
+        self.call(&Request::ApplyShared {
+            pids: pids.to_vec(),
+            limit: limit.clone(),
+            cgroup: cgroup.to_string(),
+        })
+    }
+
+    /// Create `cgroup` with its limits, to start a process in.
+    pub fn prepare_cgroup(&mut self, cgroup: &str, limit: &Limit) -> Result<()> {
+        self.call(&Request::PrepareCgroup {
+            cgroup: cgroup.to_string(),
+            limit: limit.clone(),
+        })
+    }
+
+    pub fn add_to_cgroup(&mut self, cgroup: &str, pid: u32) -> Result<()> {
+        self.call(&Request::AddToCgroup {
+            cgroup: cgroup.to_string(),
+            pid,
+        })
+    }
+
+    /// Release every process in `cgroup` and remove it.
+    pub fn remove_cgroup(&mut self, cgroup: &str) -> Result<()> {
+        self.call(&Request::RemoveCgroup {
+            cgroup: cgroup.to_string(),
+        })
+    }
+
+    fn call(&mut self, request: &Request) -> Result<()> {
+        let json = serde_json::to_string(request)
+            .map_err(|e| Error::Config(format!("failed to serialize the request: {e}")))?;
+        writeln!(self.input, "{json}")?;
+        self.input.flush()?;
+        match self.read()? {
+            Reply::Done => Ok(()),
+            Reply::Failed { error } => Err(Error::Cgroup(error)),
+            reply => Err(Error::Cgroup(format!(
+                "unexpected reply from the helper: {reply:?}"
+            ))),
+        }
+    }
+
+    fn read(&mut self) -> Result<Reply> {
+        let mut line = String::new();
+        if self.output.read_line(&mut line)? == 0 {
+            return Err(Error::Cgroup(
+                "the privileged helper is not running (authentication refused?)".into(),
+            ));
+        }
+        serde_json::from_str(&line)
+            .map_err(|e| Error::Cgroup(format!("bad reply from the helper: {e}")))
+    }
+}
+
+/// The helper's side: greet, then answer requests from `input` until it
+/// closes, acting for user `uid`.
+pub fn serve(
+    manager: &CgroupManager,
+    uid: u32,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<()> {
+    send(
+        &mut output,
+        &Reply::Ready {
+            version: PROTOCOL_VERSION,
+        },
+    )?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str(&line) {
+            Ok(request) => match handle(manager, uid, request) {
+                Ok(()) => Reply::Done,
+                Err(e) => Reply::Failed {
+                    error: e.to_string(),
+                },
+            },
+            Err(e) => Reply::Failed {
+                error: format!("bad request: {e}"),
+            },
+        };
+        send(&mut output, &reply)?;
+    }
+    Ok(())
+}
+
+fn send(output: &mut impl Write, reply: &Reply) -> Result<()> {
+    let json = serde_json::to_string(reply)
+        .map_err(|e| Error::Config(format!("failed to serialize the reply: {e}")))?;
+    writeln!(output, "{json}")?;
+    output.flush()?;
+    Ok(())
+}
+
+fn handle(manager: &CgroupManager, uid: u32, request: Request) -> Result<()> {
+    match request {
+        Request::ApplyLimit { pid, limit } => {
+            check_process(uid, pid)?;
+            manager.apply_limit(pid, &limit)
+        }
+        Request::ApplyShared {
+            pids,
+            limit,
+            cgroup,
+        } => {
+            for &pid in &pids {
+                check_process(uid, pid)?;
+            }
+            check_cgroup(manager, uid, &cgroup)?;
+            manager.apply_limit_to_multiple(&pids, &limit, &cgroup)
+        }
+        Request::PrepareCgroup { cgroup, limit } => {
+            check_cgroup(manager, uid, &cgroup)?;
+            manager.prepare_cgroup(&cgroup, &limit).map(|_| ())
+        }
+        Request::AddToCgroup { cgroup, pid } => {
+            check_process(uid, pid)?;
+            check_cgroup(manager, uid, &cgroup)?;
+            manager.add_to_cgroup(&manager.managed_cgroup_path(&cgroup)?, pid)
+        }
+        Request::RemoveCgroup { cgroup } => {
+            check_cgroup(manager, uid, &cgroup)?;
+            manager.remove_application_limit(&cgroup)
+        }
+    }
+}
+
+/// `pid` must belong to `uid` (any process, for root).
+fn check_process(uid: u32, pid: u32) -> Result<()> {
+    match std::fs::metadata(format!("/proc/{pid}")).map(|m| m.uid()) {
+        Ok(owner) if owner == uid || uid == 0 => Ok(()),
+        Ok(_) => Err(Error::InvalidArgs(format!(
+            "process {pid} belongs to another user"
+        ))),
+        Err(_) => Err(Error::ProcessNotFound(pid)),
+    }
+}
+
+/// `cgroup` must be one the GUI creates, holding only processes of `uid`.
+fn check_cgroup(manager: &CgroupManager, uid: u32, cgroup: &str) -> Result<()> {
+    if !gui_cgroup(cgroup) {
+        return Err(Error::InvalidArgs(format!(
+            "the helper does not manage cgroup '{cgroup}'"
+        )));
+    }
+    for pid in manager.pids_in_cgroup(cgroup) {
+        match check_process(uid, pid) {
+            // Exited since it was listed
+            Err(Error::ProcessNotFound(_)) => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+fn gui_cgroup(name: &str) -> bool {
+    GUI_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_tagged_json_lines() {
+        let request = Request::AddToCgroup {
+            cgroup: "gtk-42-0".into(),
+            pid: 4242,
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            json,
+            r#"{"op":"add_to_cgroup","cgroup":"gtk-42-0","pid":4242}"#
+        );
+        assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+
+        let reply: Reply = serde_json::from_str(r#"{"reply":"failed","error":"no"}"#).unwrap();
+        assert_eq!(reply, Reply::Failed { error: "no".into() });
+    }
+
+    #[test]
+    fn only_the_guis_own_cgroups_are_touched() {
+        assert!(gui_cgroup("pid-4242"));
+        assert!(gui_cgroup("app-firefox"));
+        assert!(gui_cgroup("gtk-42-0"));
+        assert!(!gui_cgroup("protect"));
+        assert!(!gui_cgroup("profile-build"));
+        assert!(!gui_cgroup("unlimit"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Where the cgroup v2 hierarchy is mounted, and where in it rlm may create
+//! cgroups. On a host the whole hierarchy is mounted at `/sys/fs/cgroup`.
+//! In a container it may be mounted elsewhere, or only the container's own
+//! subtree may be visible (the mount's root is then that cgroup, unless a
+//! cgroup namespace makes it `/`), and a systemd service with `Delegate=yes`
+//! owns its cgroup rather than a `user@.service`. Both are read from
+//! `/proc/self/mountinfo` and `/proc/self/cgroup` rather than assumed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where the hierarchy is usually mounted, used when mountinfo has no
+/// cgroup2 mount (so the error names the usual place).
+const DEFAULT_MOUNT: &str = "/sys/fs/cgroup";
+
+/// A cgroup2 mount.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mount {
+    /// Where it is mounted
+    pub point: PathBuf,
+    /// The hierarchy path shown at `point`, as `/proc/<pid>/cgroup` names it
+    pub root: String,
+}
+
+impl Mount {
+    /// Directory of the hierarchy path `path` (from `/proc/<pid>/cgroup`),
+    /// if it is under this mount.
+    pub fn dir(&self, path: &str) -> Option<PathBuf> {
+        let rel = if self.root == "/" {
+            path
+        } else {
+            let rest = path.strip_prefix(self.root.as_str())?;
+            if !rest.is_empty() && !rest.starts_with('/') {
+                return None;
+            }
+            rest
+        };
+        Some(self.point.join(rel.trim_start_matches('/')))
+    }
+}
+
+/// The cgroup2 mount, read once.
+pub(crate) fn mount() -> &'static Mount {
+    static MOUNT: OnceLock<Mount> = OnceLock::new();
+    MOUNT.get_or_init(|| {
+        fs::read_to_string("/proc/self/mountinfo")
+            .ok()
+            .and_then(|m| parse_mount(&m))
+            .unwrap_or_else(|| Mount {
+                point: PathBuf::from(DEFAULT_MOUNT),
+                root: "/".into(),
+            })
+    })
+}
+
+/// Where the hierarchy is mounted.
+pub(crate) fn root() -> &'static Path {
+    &mount().point
+}
+
+/// Directory of the hierarchy path `path` (from `/proc/<pid>/cgroup`).
+pub(crate) fn dir_of(path: &str) -> Option<PathBuf> {
+    mount().dir(path)
+}
+
+/// The topmost cgroup above (or at) our own that we may write: the subtree
+/// delegated to a container or a `Delegate=yes` service. `None` when we may
+/// write none of them.
+pub(crate) fn delegated_subtree() -> Option<PathBuf> {
+    let own = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let own = dir_of(crate::cgroup::unified_cgroup(&own)?)?;
+    let root = root();
+    own.ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .filter(|dir| writable(dir))
+        .last()
+        .map(Path::to_path_buf)
+}
+
+/// Whether we may create cgroups in `dir`.
+pub(crate) fn writable(dir: &Path) -> bool {
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_encoded_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` is a valid NUL-terminated string.
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// The cgroup2 mount from `/proc/self/mountinfo`, preferring one at the
+/// usual place when there are several.
+fn parse_mount(mountinfo: &str) -> Option<Mount> {
+    let mounts: Vec<Mount> = mountinfo
+        .lines()
+        .filter_map(|line| {
+            // "35 24 0:30 / /sys/fs/cgroup rw,nosuid shared:9 - cgroup2 cgroup2 rw"
+            let (mount, fs_part) = line.split_once(" - ")?;
+            if fs_part.split(' ').next()? != "cgroup2" {
+                return None;
+            }
+            let mut fields = mount.split(' ').skip(3);
+            let root = crate::cgroup::unescape_mount(fields.next()?);
+            let point = crate::cgroup::unescape_mount(fields.next()?);
+            Some(Mount {
+                point: PathBuf::from(point),
+                root,
+            })
+        })
+        .collect();
+    mounts
+        .iter()
+        .find(|m| m.point == Path::new(DEFAULT_MOUNT))
+        .or(mounts.first())
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_cgroup2_mount() {
+        let host = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+35 24 0:30 / /sys/fs/cgroup rw,nosuid,nodev shared:9 - cgroup2 cgroup2 rw,nsdelegate
+";
+        let mount = parse_mount(host).unwrap();
+        assert_eq!(mount.point, Path::new("/sys/fs/cgroup"));
+        assert_eq!(
+            mount.dir("/user.slice/user-1000.slice"),
+            Some(PathBuf::from("/sys/fs/cgroup/user.slice/user-1000.slice"))
+        );
+
+        // A container without a cgroup namespace sees its own subtree
+        let container = "\
+900 800 0:30 /system.slice/docker-abc.scope /sys/fs/cgroup ro,nosuid - cgroup2 cgroup rw
+";
+        let mount = parse_mount(container).unwrap();
+        assert_eq!(mount.root, "/system.slice/docker-abc.scope");
+        assert_eq!(
+            mount.dir("/system.slice/docker-abc.scope/rlm"),
+            Some(PathBuf::from("/sys/fs/cgroup/rlm"))
+        );
+        assert_eq!(
+            mount.dir("/system.slice/docker-abc.scope"),
+            Some(PathBuf::from("/sys/fs/cgroup"))
+        );
+        assert_eq!(mount.dir("/system.slice/docker-abcd.scope"), None);
+        assert_eq!(mount.dir("/user.slice"), None);
+
+        assert_eq!(parse_mount("22 1 259:2 / / rw - ext4 /dev/sda1 rw\n"), None);
+    }
+}
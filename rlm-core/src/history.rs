@@ -0,0 +1,223 @@
+//! Persistent log of the limits rlm applied, updated, and removed, behind
+//! `rlm history`.
+//!
+//! Each action is one JSON line appended to
+//! `$XDG_DATA_HOME/rlm/history.jsonl`, whichever rlm program took it (CLI,
+//! GUI, or guard). Once the file passes [`MAX_SIZE`] it is rotated to
+//! `history.jsonl.1`, so the log stays small. Like the state file, this is
+//! best effort: a failure to write never fails the action itself. With
+//! auditing on, each entry also goes to the journal (see `audit`).
+
+use common::{Limit, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Size at which the log is rotated.
+pub const MAX_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Limits set on a new cgroup
+    Apply,
+    /// Limits of an already managed process changed
+    Update,
+    /// Limits removed
+    Remove,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Apply => "apply",
+            Action::Update => "update",
+            Action::Remove => "remove",
+        }
+    }
+}
+
+/// One recorded action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix time.
+    pub time: u64,
+    pub action: Action,
+    pub cgroup: String,
+    /// Processes acted on, where known (not for `rlm run`, whose process
+    /// starts after its cgroup is set up).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pids: Vec<u32>,
+    /// Name of the first of `pids`, at the time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<Limit>,
+    /// The error, if the action failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The program that acted (`rlm`, `rlm-gtk`, `rlm-guard`).
+    pub by: String,
+}
+
+impl Entry {
+    /// One line of JSON, for `rlm history --json`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("rlm").join("history.jsonl"))
+}
+
+/// Record the outcome of an action.
+pub(crate) fn record<T>(
+    action: Action,
+    cgroup: &str,
+    pids: &[u32],
+    limit: Option<&Limit>,
+    result: &Result<T>,
+) {
+    let entry = Entry {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        action,
+        cgroup: cgroup.to_string(),
+        pids: pids.to_vec(),
+        name: pids
+            .first()
+            .and_then(|&pid| crate::status::process_name(pid)),
+        limit: limit.cloned(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        by: std::env::args()
+            .next()
+            .and_then(|a| {
+                std::path::Path::new(&a)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| "rlm".into()),
+    };
+    if let Err(e) = append(&entry) {
+        tracing::debug!(cgroup, error = %e, "could not write history");
+    }
+    if crate::audit::enabled() {
+        crate::audit::send(&entry);
+    }
+    crate::hooks::for_entry(&entry);
+}
+
+fn append(entry: &Entry) -> Result<()> {
+    let Some(path) = history_path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_SIZE) {
+        fs::rename(&path, path.with_extension("jsonl.1"))?;
+    }
+    let line = serde_json::to_string(entry)
+        .map_err(|e| common::Error::Config(format!("failed to serialize history: {e}")))?;
+    // One write of a whole line, so concurrent writers don't interleave
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    file.write_all(format!("{line}\n").as_bytes())?;
+    Ok(())
+}
+
+/// All recorded actions, oldest first. Lines that don't parse are skipped.
+pub fn entries() -> Vec<Entry> {
+    let Some(path) = history_path() else {
+        return Vec::new();
+    };
+    [path.with_extension("jsonl.1"), path]
+        .iter()
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .flat_map(|content| parse(&content))
+        .collect()
+}
+
+fn parse(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+/// Short description of a limit, e.g. "memory=2.0G cpu=50% io-read=10.0M/s".
+pub fn summary(limit: &Limit) -> String {
+    use common::format_bytes;
+
+    let mut parts = Vec::new();
+    if let Some(m) = limit.memory {
+        parts.push(format!("memory={}", format_bytes(m.bytes())));
+    }
+    if let Some(s) = limit.swap {
+        parts.push(format!("swap={}", format_bytes(s.bytes())));
+    }
+    if limit.oom_group {
+        parts.push("oom-group".into());
+    }
+    if let Some(c) = limit.cpu {
+        parts.push(format!("cpu={}%", c.percent()));
+    }
+    if let Some(w) = limit.cpu_weight {
+        parts.push(format!("cpu-weight={}", w.weight()));
+    }
+    if limit.idle {
+        parts.push("idle".into());
+    }
+    if let Some(io) = limit.io {
+        if let Some(r) = io.read_bps {
+            parts.push(format!("io-read={}/s", format_bytes(r)));
+        }
+        if let Some(w) = io.write_bps {
+            parts.push(format!("io-write={}/s", format_bytes(w)));
+        }
+    }
+    if let Some(w) = limit.io_weight {
+        parts.push(format!("io-weight={}", w.weight()));
+    }
+    if let Some(p) = limit.pids {
+        parts.push(format!("pids={}", p.count()));
+    }
+    if let Some(m) = &limit.mems {
+        parts.push(format!("mems={}", m.as_str()));
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines_and_skips_garbage() {
+        let content = r#"{"time":1,"action":"apply","cgroup":"pid-42","pids":[42],"name":"make","by":"rlm"}
+not json
+{"time":2,"action":"remove","cgroup":"pid-42","error":"Permission denied","by":"rlm-gtk"}
+"#;
+        let entries = parse(content);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, Action::Apply);
+        assert_eq!(entries[0].pids, vec![42]);
+        assert_eq!(entries[1].error.as_deref(), Some("Permission denied"));
+        assert!(entries[1].pids.is_empty());
+    }
+
+    #[test]
+    fn summarizes_limits() {
+        let limit = Limit {
+            memory: Some(common::MemoryLimit::parse("2G").unwrap()),
+            cpu: Some(common::CpuLimit::parse("50").unwrap()),
+            idle: true,
+            ..Limit::default()
+        };
+        assert_eq!(summary(&limit), "memory=2.0G cpu=50% idle");
+    }
+}
@@ -0,0 +1,233 @@
+//! User scripts on limit events, configured under `hooks:`.
+//!
+//! Each hook is a shell command, run with `sh -c` in the background with
+//! `RLM_EVENT` and other `RLM_*` variables describing the event. Limits
+//! applied and removed fire wherever the change is made (the CLI, the GUI,
+//! or a daemon), next to the [`history`](crate::history) entry. OOM kills
+//! and exits of managed processes are noticed by rlm-guard, which checks for
+//! them every tick with a [`HookWatcher`].
+
+use crate::history::{Action, Entry};
+use crate::CgroupManager;
+use common::{Config, HooksConfig};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// An event a hook can be set for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    LimitApplied,
+    LimitRemoved,
+    OomKill,
+    ProcessExit,
+}
+
+impl Hook {
+    /// The event's name, as passed in `RLM_EVENT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::LimitApplied => "limit_applied",
+            Hook::LimitRemoved => "limit_removed",
+            Hook::OomKill => "oom_kill",
+            Hook::ProcessExit => "process_exit",
+        }
+    }
+
+    fn command(self, hooks: &HooksConfig) -> Option<&str> {
+        match self {
+            Hook::LimitApplied => hooks.on_limit_applied.as_deref(),
+            Hook::LimitRemoved => hooks.on_limit_removed.as_deref(),
+            Hook::OomKill => hooks.on_oom_kill.as_deref(),
+            Hook::ProcessExit => hooks.on_process_exit.as_deref(),
+        }
+    }
+}
+
+/// Run the command set for `hook`, if any, without waiting for it.
+pub fn run(hooks: &HooksConfig, hook: Hook, vars: &[(&str, String)]) {
+    let Some(command) = hook.command(hooks) else {
+        return;
+    };
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("RLM_EVENT", hook.name())
+        .envs(vars.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .spawn();
+    match child {
+        // Reap it whenever it finishes, so daemons collect no zombies
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => tracing::warn!(hook = hook.name(), error = %e, "could not run hook"),
+    }
+}
+
+/// Run the hook for a recorded action, if one is set.
+pub(crate) fn for_entry(entry: &Entry) {
+    let Some((hook, vars)) = entry_hook(entry) else {
+        return;
+    };
+    if let Ok(config) = Config::load() {
+        run(&config.hooks, hook, &vars);
+    }
+}
+
+/// The hook a recorded action fires and its variables. Failed actions fire
+/// none.
+fn entry_hook(entry: &Entry) -> Option<(Hook, Vec<(&'static str, String)>)> {
+    if entry.error.is_some() {
+        return None;
+    }
+    let hook = match entry.action {
+        Action::Apply | Action::Update => Hook::LimitApplied,
+        Action::Remove => Hook::LimitRemoved,
+    };
+    let mut vars = vec![
+        ("RLM_ACTION", entry.action.name().to_string()),
+        ("RLM_CGROUP", entry.cgroup.clone()),
+    ];
+    if !entry.pids.is_empty() {
+        let pids: Vec<String> = entry.pids.iter().map(|p| p.to_string()).collect();
+        vars.push(("RLM_PIDS", pids.join(" ")));
+    }
+    if let Some(name) = &entry.name {
+        vars.push(("RLM_NAME", name.clone()));
+    }
+    if let Some(limit) = &entry.limit {
+        vars.push(("RLM_LIMITS", crate::history::summary(limit)));
+    }
+    Some((hook, vars))
+}
+
+/// Notices OOM kills and process exits in managed cgroups, for rlm-guard.
+pub struct HookWatcher {
+    hooks: HooksConfig,
+    /// Cgroup name -> its `oom_kill` count at the last tick
+    oom_kills: HashMap<String, u64>,
+    /// Managed PID -> its cgroup and name at the last tick
+    pids: HashMap<u32, (String, String)>,
+    /// Whether a tick has set the baseline
+    primed: bool,
+}
+
+impl HookWatcher {
+    pub fn new(cfg: &Config) -> Self {
+        Self {
+            hooks: cfg.hooks.clone(),
+            oom_kills: HashMap::new(),
+            pids: HashMap::new(),
+            primed: false,
+        }
+    }
+
+    /// Whether any hook needs watching.
+    pub fn is_active(&self) -> bool {
+        self.hooks.on_oom_kill.is_some() || self.hooks.on_process_exit.is_some()
+    }
+
+    /// Fire hooks for what happened since the last tick. The first tick only
+    /// takes the baseline.
+    pub fn tick(&mut self, mgr: &CgroupManager) {
+        if self.hooks.on_oom_kill.is_some() {
+            self.check_oom_kills(mgr);
+        }
+        if self.hooks.on_process_exit.is_some() {
+            self.check_exits(mgr);
+        }
+        self.primed = true;
+    }
+
+    fn check_oom_kills(&mut self, mgr: &CgroupManager) {
+        let now: HashMap<String, u64> = crate::events::snapshot(mgr)
+            .into_iter()
+            .map(|(name, c)| (name, c.get(crate::events::EventKind::OomKill)))
+            .collect();
+        for (cgroup, &total) in &now {
+            let before = self.oom_kills.get(cgroup).copied();
+            let new = match before {
+                Some(before) => total.saturating_sub(before),
+                // A cgroup created since the last tick starts from zero
+                None if self.primed => total,
+                None => 0,
+            };
+            if new > 0 {
+                let vars = [
+                    ("RLM_CGROUP", cgroup.clone()),
+                    ("RLM_COUNT", new.to_string()),
+                    ("RLM_TOTAL", total.to_string()),
+                ];
+                run(&self.hooks, Hook::OomKill, &vars);
+            }
+        }
+        self.oom_kills = now;
+    }
+
+    fn check_exits(&mut self, mgr: &CgroupManager) {
+        let mut now = HashMap::new();
+        for cgroup in crate::events::cgroup_names(mgr.base_path()) {
+            for pid in mgr.pids_in_cgroup(&cgroup) {
+                let name = match self.pids.remove(&pid) {
+                    Some((_, name)) => name,
+                    None => crate::status::process_name(pid).unwrap_or_default(),
+                };
+                now.insert(pid, (cgroup.clone(), name));
+            }
+        }
+        // What is left was managed last tick and is not now: exited, unless
+        // it was only moved out
+        let gone = std::mem::replace(&mut self.pids, now);
+        for (pid, (cgroup, name)) in gone {
+            if Path::new(&format!("/proc/{pid}")).exists() {
+                continue;
+            }
+            let vars = [
+                ("RLM_PID", pid.to_string()),
+                ("RLM_NAME", name),
+                ("RLM_CGROUP", cgroup),
+            ];
+            run(&self.hooks, Hook::ProcessExit, &vars);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(action: Action, error: Option<&str>) -> Entry {
+        Entry {
+            time: 0,
+            action,
+            cgroup: "app-firefox".into(),
+            pids: vec![10, 11],
+            name: Some("firefox".into()),
+            limit: Some(common::Limit {
+                memory: Some(common::MemoryLimit::parse("2G").unwrap()),
+                ..Default::default()
+            }),
+            error: error.map(String::from),
+            by: "rlm".into(),
+        }
+    }
+
+    #[test]
+    fn recorded_actions_fire_their_hook() {
+        let (hook, vars) = entry_hook(&entry(Action::Update, None)).unwrap();
+        assert_eq!(hook, Hook::LimitApplied);
+        let get = |key| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(get("RLM_CGROUP"), Some("app-firefox"));
+        assert_eq!(get("RLM_PIDS"), Some("10 11"));
+        assert_eq!(get("RLM_LIMITS"), Some("memory=2.0G"));
+
+        let removed = entry_hook(&entry(Action::Remove, None)).map(|(h, _)| h);
+        assert_eq!(removed, Some(Hook::LimitRemoved));
+        assert!(entry_hook(&entry(Action::Apply, Some("denied"))).is_none());
+    }
+}
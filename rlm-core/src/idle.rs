@@ -0,0 +1,119 @@
+//! Whether the user is away from the keyboard, for profiles with `when_idle`
+//! limits.
+//!
+//! Read from logind's `IdleHint`, which GNOME, KDE and most screen lockers
+//! set for the graphical session after the desktop's own idle delay, through
+//! `loginctl` so no D-Bus binding is needed. It covers X11 and Wayland alike.
+
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What `loginctl show-session` says about one session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    /// `Type` is x11 or wayland
+    pub graphical: bool,
+    pub active: bool,
+    pub idle: bool,
+    /// `IdleSinceHint`: microseconds since the Unix epoch
+    pub idle_since_usec: u64,
+}
+
+/// How long the user has been idle, or `None` while they are active or
+/// there is no graphical session to tell.
+pub fn idle_for() -> Option<Duration> {
+    let list = loginctl(&["list-sessions", "--no-legend"])?;
+    let ids: Vec<&str> = list
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .collect();
+    if ids.is_empty() {
+        return None;
+    }
+    let mut args = vec!["show-session"];
+    args.extend(&ids);
+    args.extend([
+        "-p",
+        "Type",
+        "-p",
+        "Active",
+        "-p",
+        "IdleHint",
+        "-p",
+        "IdleSinceHint",
+    ]);
+    let sessions = parse_sessions(&loginctl(&args)?);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    idle_duration(&sessions, now.as_micros() as u64)
+}
+
+fn loginctl(args: &[&str]) -> Option<String> {
+    let output = Command::new("loginctl").args(args).output().ok()?;
+    if !output.status.success() {
+        tracing::debug!(?args, "loginctl failed");
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parse `loginctl show-session` output: `Key=value` lines, one block per
+/// session separated by blank lines.
+pub fn parse_sessions(output: &str) -> Vec<Session> {
+    output
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| {
+            let mut s = Session::default();
+            for (key, value) in block.lines().filter_map(|l| l.split_once('=')) {
+                match key {
+                    "Type" => s.graphical = matches!(value, "x11" | "wayland"),
+                    "Active" => s.active = value == "yes",
+                    "IdleHint" => s.idle = value == "yes",
+                    "IdleSinceHint" => s.idle_since_usec = value.parse().unwrap_or(0),
+                    _ => {}
+                }
+            }
+            s
+        })
+        .collect()
+}
+
+/// Idle time when every active graphical session is idle, measured from the
+/// most recent one to go idle.
+pub fn idle_duration(sessions: &[Session], now_usec: u64) -> Option<Duration> {
+    let mut seen = sessions
+        .iter()
+        .filter(|s| s.graphical && s.active)
+        .peekable();
+    seen.peek()?;
+    let mut latest = 0;
+    for s in seen {
+        if !s.idle {
+            return None;
+        }
+        latest = latest.max(s.idle_since_usec);
+    }
+    Some(Duration::from_micros(now_usec.saturating_sub(latest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_only_when_every_active_graphical_session_is() {
+        let out = "Type=wayland\nActive=yes\nIdleHint=yes\nIdleSinceHint=1000000\n\n\
+                   Type=tty\nActive=yes\nIdleHint=no\nIdleSinceHint=0\n";
+        let sessions = parse_sessions(out);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(
+            idle_duration(&sessions, 61_000_000),
+            Some(Duration::from_secs(60))
+        );
+
+        let busy = parse_sessions("Type=x11\nActive=yes\nIdleHint=no\nIdleSinceHint=0\n");
+        assert_eq!(idle_duration(&busy, 61_000_000), None);
+        // Only a text console: nothing to tell idleness by
+        assert_eq!(idle_duration(&sessions[1..], 61_000_000), None);
+    }
+}
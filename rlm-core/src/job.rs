@@ -0,0 +1,422 @@
+//! Limits on Windows, which has no cgroups: a limited process is put in a
+//! Job Object named after it ([`job_name`]), and the processes it starts
+//! join the job too. A job caps committed memory, CPU rate, and how many
+//! processes run at once; the rest of a [`Limit`] has no equivalent there
+//! and is refused. A process can't leave its job, so removing its limits
+//! clears the job's instead.
+
+use common::{Error, Limit, Result};
+
+#[cfg(windows)]
+pub use sys::{limit, spawn, status, unlimit};
+
+/// What a job enforces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JobLimits {
+    /// Memory all the job's processes may commit together, in bytes
+    pub memory: Option<u64>,
+    /// CPU time, in hundredths of a percent of all CPUs (1-10000)
+    pub cpu_rate: Option<u32>,
+    /// Processes at once (not threads, unlike `pids.max`)
+    pub processes: Option<u32>,
+}
+
+impl JobLimits {
+    /// `limit` on a machine with `cpus` CPUs.
+    pub fn from_limit(limit: &Limit, cpus: usize) -> Result<Self> {
+        let unsupported: Vec<&str> = [
+            (limit.swap.is_some(), "swap"),
+            (limit.oom_group, "oom_group"),
+            (limit.cpu_weight.is_some(), "cpu_weight"),
+            (limit.idle, "idle"),
+            (limit.io.is_some() || limit.io_path.is_some(), "io"),
+            (limit.io_weight.is_some(), "io_weight"),
+            (limit.mems.is_some(), "mems"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect();
+        if !unsupported.is_empty() {
+            return Err(Error::InvalidArgs(format!(
+                "not supported on Windows: {} (only memory, cpu, and max pids are)",
+                unsupported.join(", ")
+            )));
+        }
+        Ok(Self {
+            memory: limit.memory.map(|m| m.bytes()),
+            cpu_rate: limit.cpu.map(|c| cpu_rate(c.percent(), cpus)),
+            processes: limit
+                .pids
+                .map(|p| u32::try_from(p.count()).unwrap_or(u32::MAX)),
+        })
+    }
+
+    /// The CPU rate as a percentage of one CPU, like `--cpu`.
+    pub fn cpu_percent(&self, cpus: usize) -> Option<u32> {
+        self.cpu_rate
+            .map(|rate| (u64::from(rate) * cpus as u64 / 100) as u32)
+    }
+}
+
+/// `percent` of one CPU as a job CPU rate.
+fn cpu_rate(percent: u32, cpus: usize) -> u32 {
+    (u64::from(percent) * 100 / cpus.max(1) as u64).clamp(1, 10_000) as u32
+}
+
+/// The job holding process `pid`'s limits.
+pub fn job_name(pid: u32) -> String {
+    format!("Local\\rlm-pid-{pid}")
+}
+
+/// A limited process, as `rlm status` shows it.
+#[derive(Debug, Clone)]
+pub struct Limited {
+    /// Always set; `None` is a jail on FreeBSD
+    pub pid: Option<u32>,
+    pub name: String,
+    pub memory_max: Option<u64>,
+    /// The most memory the job has had committed at once, as Windows keeps
+    /// no current total
+    pub memory_used: u64,
+    /// Percent of one CPU, like `--cpu`
+    pub cpu_percent: Option<u32>,
+    /// Processes in the job now
+    pub processes: u32,
+    pub processes_max: Option<u32>,
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::{job_name, JobLimits, Limited};
+    use common::{Error, Limit, Result};
+    use std::io;
+    use std::os::windows::process::CommandExt;
+    use std::process::{Child, Command};
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, DuplicateHandle, DUPLICATE_SAME_ACCESS, ERROR_INVALID_PARAMETER, HANDLE,
+        INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, Thread32First, Thread32Next,
+        PROCESSENTRY32W, TH32CS_SNAPPROCESS, TH32CS_SNAPTHREAD, THREADENTRY32,
+    };
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicAccountingInformation,
+        JobObjectCpuRateControlInformation, JobObjectExtendedLimitInformation, OpenJobObjectW,
+        QueryInformationJobObject, SetInformationJobObject, JOBOBJECTINFOCLASS,
+        JOBOBJECT_BASIC_ACCOUNTING_INFORMATION, JOBOBJECT_CPU_RATE_CONTROL_INFORMATION,
+        JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_CPU_RATE_CONTROL_ENABLE,
+        JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP, JOB_OBJECT_LIMIT_ACTIVE_PROCESS,
+        JOB_OBJECT_LIMIT_JOB_MEMORY,
+    };
+    use windows_sys::Win32::System::SystemServices::{JOB_OBJECT_QUERY, JOB_OBJECT_SET_ATTRIBUTES};
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, OpenThread, ResumeThread, CREATE_SUSPENDED,
+        PROCESS_DUP_HANDLE, PROCESS_SET_QUOTA, PROCESS_TERMINATE, THREAD_SUSPEND_RESUME,
+    };
+
+    /// An open handle, closed on drop.
+    struct Handle(HANDLE);
+
+    impl Handle {
+        /// `handle`, or the last OS error as `what` failing.
+        fn new(handle: HANDLE, what: impl FnOnce() -> String) -> Result<Self> {
+            if handle.is_null() || handle == INVALID_HANDLE_VALUE {
+                return Err(os_error(&what()));
+            }
+            Ok(Self(handle))
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            // SAFETY: the handle is open and only closed here.
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    fn os_error(what: &str) -> Error {
+        let e = io::Error::last_os_error();
+        Error::Io(io::Error::new(e.kind(), format!("{what}: {e}")))
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn cpus() -> usize {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    /// Put process `pid`, and the processes it starts from now on, under
+    /// `limit`; or change its limits if it has some already.
+    pub fn limit(pid: u32, limit: &Limit) -> Result<()> {
+        apply(pid, &JobLimits::from_limit(limit, cpus())?)
+    }
+
+    /// Clear process `pid`'s limits.
+    pub fn unlimit(pid: u32) -> Result<()> {
+        let job = open_job(pid, JOB_OBJECT_SET_ATTRIBUTES)
+            .ok_or_else(|| Error::InvalidArgs(format!("process {pid} is not limited")))?;
+        set(&job, &JobLimits::default())
+    }
+
+    /// Start `command` under `limit`. It is started suspended and only
+    /// resumed once in its job, so nothing it runs escapes the limits.
+    pub fn spawn(command: &mut Command, limit: &Limit) -> Result<Child> {
+        let limits = JobLimits::from_limit(limit, cpus())?;
+        let mut child = command.creation_flags(CREATE_SUSPENDED).spawn()?;
+        if let Err(e) = apply(child.id(), &limits).and_then(|()| resume(child.id())) {
+            let _ = child.kill();
+            return Err(e);
+        }
+        Ok(child)
+    }
+
+    /// Every process rlm has limited that is still running.
+    pub fn status() -> Result<Vec<Limited>> {
+        // SAFETY: plain FFI call; the handle is checked.
+        let snapshot = Handle::new(
+            unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) },
+            || "cannot list processes".into(),
+        )?;
+        let mut entry = PROCESSENTRY32W {
+            dwSize: size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut limited = Vec::new();
+        // SAFETY: `entry.dwSize` is set, as the snapshot API requires.
+        let mut more = unsafe { Process32FirstW(snapshot.0, &mut entry) } != 0;
+        while more {
+            let pid = entry.th32ProcessID;
+            if let Some(job) = open_job(pid, JOB_OBJECT_QUERY) {
+                let info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION =
+                    query(&job, JobObjectExtendedLimitInformation).unwrap_or_default();
+                let accounting: JOBOBJECT_BASIC_ACCOUNTING_INFORMATION =
+                    query(&job, JobObjectBasicAccountingInformation).unwrap_or_default();
+                let limits = limits_of(&job, &info);
+                // Unlimited since: `unlimit` clears a job's limits, as a
+                // process can't leave its job
+                if limits != JobLimits::default() {
+                    let exe = &entry.szExeFile;
+                    let len = exe.iter().position(|&c| c == 0).unwrap_or(exe.len());
+                    limited.push(Limited {
+                        pid: Some(pid),
+                        name: String::from_utf16_lossy(&exe[..len]),
+                        memory_max: limits.memory,
+                        memory_used: info.PeakJobMemoryUsed as u64,
+                        cpu_percent: limits.cpu_percent(cpus()),
+                        processes: accounting.ActiveProcesses,
+                        processes_max: limits.processes,
+                    });
+                }
+            }
+            // SAFETY: as above.
+            more = unsafe { Process32NextW(snapshot.0, &mut entry) } != 0;
+        }
+        Ok(limited)
+    }
+
+    fn apply(pid: u32, limits: &JobLimits) -> Result<()> {
+        if let Some(job) = open_job(pid, JOB_OBJECT_SET_ATTRIBUTES) {
+            return set(&job, limits);
+        }
+        // SAFETY: plain FFI call; the handle is checked.
+        let process = unsafe {
+            OpenProcess(
+                PROCESS_SET_QUOTA | PROCESS_TERMINATE | PROCESS_DUP_HANDLE,
+                0,
+                pid,
+            )
+        };
+        if process.is_null()
+            && io::Error::last_os_error().raw_os_error() == Some(ERROR_INVALID_PARAMETER as i32)
+        {
+            return Err(Error::ProcessNotFound(pid));
+        }
+        let process = Handle::new(process, || format!("cannot open process {pid}"))?;
+
+        let name = wide(&job_name(pid));
+        // SAFETY: `name` is NUL-terminated and outlives the call.
+        let job = Handle::new(
+            unsafe { CreateJobObjectW(std::ptr::null(), name.as_ptr()) },
+            || format!("cannot create a job for process {pid}"),
+        )?;
+        set(&job, limits)?;
+        // SAFETY: both handles are open with the access this needs.
+        if unsafe { AssignProcessToJobObject(job.0, process.0) } == 0 {
+            return Err(os_error(&format!("cannot put process {pid} in its job")));
+        }
+        // A job's name goes away with its last handle, even while processes
+        // are in it, so the process keeps one; `unlimit` and `status` find
+        // the job by name.
+        let mut kept: HANDLE = std::ptr::null_mut();
+        // SAFETY: all three handles are open; `kept` receives the copy.
+        let ok = unsafe {
+            DuplicateHandle(
+                GetCurrentProcess(),
+                job.0,
+                process.0,
+                &mut kept,
+                0,
+                0,
+                DUPLICATE_SAME_ACCESS,
+            )
+        };
+        if ok == 0 {
+            tracing::warn!(pid, "{}", os_error("cannot keep the job open"));
+        }
+        Ok(())
+    }
+
+    fn open_job(pid: u32, access: u32) -> Option<Handle> {
+        let name = wide(&job_name(pid));
+        // SAFETY: `name` is NUL-terminated and outlives the call.
+        let handle = unsafe { OpenJobObjectW(access, 0, name.as_ptr()) };
+        (!handle.is_null()).then_some(Handle(handle))
+    }
+
+    fn set(job: &Handle, limits: &JobLimits) -> Result<()> {
+        let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        if let Some(bytes) = limits.memory {
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+            info.JobMemoryLimit = usize::try_from(bytes).unwrap_or(usize::MAX);
+        }
+        if let Some(processes) = limits.processes {
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+            info.BasicLimitInformation.ActiveProcessLimit = processes;
+        }
+        // SAFETY: `info` is the struct this class takes, at its full size.
+        let ok = unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                std::ptr::from_ref(&info).cast(),
+                size_of_val(&info) as u32,
+            )
+        };
+        if ok == 0 {
+            return Err(os_error("cannot set the job's limits"));
+        }
+
+        // No flags turns rate control off
+        let mut cpu = JOBOBJECT_CPU_RATE_CONTROL_INFORMATION::default();
+        if let Some(rate) = limits.cpu_rate {
+            cpu.ControlFlags =
+                JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+            cpu.Anonymous.CpuRate = rate;
+        }
+        // SAFETY: as above.
+        let ok = unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectCpuRateControlInformation,
+                std::ptr::from_ref(&cpu).cast(),
+                size_of_val(&cpu) as u32,
+            )
+        };
+        if ok == 0 {
+            return Err(os_error("cannot set the job's CPU rate"));
+        }
+        Ok(())
+    }
+
+    /// Information of `class`, whose struct is `T`.
+    fn query<T: Default>(job: &Handle, class: JOBOBJECTINFOCLASS) -> Option<T> {
+        let mut info = T::default();
+        // SAFETY: `T` is the struct `class` fills, at its full size.
+        let ok = unsafe {
+            QueryInformationJobObject(
+                job.0,
+                class,
+                std::ptr::from_mut(&mut info).cast(),
+                size_of::<T>() as u32,
+                std::ptr::null_mut(),
+            )
+        };
+        (ok != 0).then_some(info)
+    }
+
+    fn limits_of(job: &Handle, info: &JOBOBJECT_EXTENDED_LIMIT_INFORMATION) -> JobLimits {
+        let flags = info.BasicLimitInformation.LimitFlags;
+        let cpu: Option<JOBOBJECT_CPU_RATE_CONTROL_INFORMATION> =
+            query(job, JobObjectCpuRateControlInformation);
+        JobLimits {
+            memory: (flags & JOB_OBJECT_LIMIT_JOB_MEMORY != 0)
+                .then_some(info.JobMemoryLimit as u64),
+            cpu_rate: cpu
+                .filter(|c| c.ControlFlags & JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP != 0)
+                // SAFETY: a hard cap is given as a CPU rate.
+                .map(|c| unsafe { c.Anonymous.CpuRate }),
+            processes: (flags & JOB_OBJECT_LIMIT_ACTIVE_PROCESS != 0)
+                .then_some(info.BasicLimitInformation.ActiveProcessLimit),
+        }
+    }
+
+    /// Resume the threads of a process started suspended.
+    fn resume(pid: u32) -> Result<()> {
+        // SAFETY: plain FFI call; the handle is checked.
+        let snapshot = Handle::new(
+            unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) },
+            || "cannot list threads".into(),
+        )?;
+        let mut entry = THREADENTRY32 {
+            dwSize: size_of::<THREADENTRY32>() as u32,
+            ..Default::default()
+        };
+        // SAFETY: `entry.dwSize` is set, as the snapshot API requires.
+        let mut more = unsafe { Thread32First(snapshot.0, &mut entry) } != 0;
+        while more {
+            if entry.th32OwnerProcessID == pid {
+                // SAFETY: plain FFI call; the handle is checked.
+                let thread = Handle::new(
+                    unsafe { OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID) },
+                    || format!("cannot resume process {pid}"),
+                )?;
+                // SAFETY: `thread` is open with THREAD_SUSPEND_RESUME.
+                if unsafe { ResumeThread(thread.0) } == u32::MAX {
+                    return Err(os_error(&format!("cannot resume process {pid}")));
+                }
+            }
+            // SAFETY: as above.
+            more = unsafe { Thread32Next(snapshot.0, &mut entry) } != 0;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{CpuLimit, IoLimit, MemoryLimit, PidsLimit};
+
+    #[test]
+    fn maps_limits_onto_a_job() {
+        let limit = Limit {
+            memory: Some(MemoryLimit::parse("1G").unwrap()),
+            cpu: Some(CpuLimit::parse("50%").unwrap()),
+            pids: Some(PidsLimit::new(64).unwrap()),
+            ..Limit::default()
+        };
+        let job = JobLimits::from_limit(&limit, 4).unwrap();
+        assert_eq!(job.memory, Some(1 << 30));
+        // Half a CPU of four is 12.5% of the machine
+        assert_eq!(job.cpu_rate, Some(1250));
+        assert_eq!(job.cpu_percent(4), Some(50));
+        assert_eq!(job.processes, Some(64));
+
+        assert_eq!(cpu_rate(800, 4), 10_000);
+        assert_eq!(cpu_rate(1, 128), 1);
+
+        let io = Limit {
+            io: Some(IoLimit {
+                read_bps: Some(1),
+                write_bps: None,
+            }),
+            idle: true,
+            ..limit
+        };
+        let err = JobLimits::from_limit(&io, 4).unwrap_err().to_string();
+        assert!(err.contains("idle, io"), "{err}");
+    }
+}
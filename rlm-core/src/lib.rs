@@ -1,8 +1,64 @@
-mod cgroup;
-pub mod desktop;
-pub mod guard;
-pub mod process;
-pub mod rules;
-pub mod status;
+//! Resource limits for processes. On Linux they are cgroups v2, and
+//! everything here builds on [`CgroupManager`]; elsewhere only the
+//! platform's own backend is available (`job` on Windows, `rctl` on FreeBSD).
 
-pub use cgroup::CgroupManager;
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        pub mod account;
+        mod audit;
+        pub mod backend;
+        pub mod calibrate;
+        mod cgroup;
+        pub mod control;
+        pub mod daemon;
+        pub mod desktop;
+        pub mod devices;
+        pub mod doctor;
+        pub mod escape;
+        pub mod events;
+        pub mod focus;
+        pub mod follow;
+        pub mod guard;
+        pub mod helper;
+        mod hierarchy;
+        pub mod history;
+        pub mod hooks;
+        pub mod idle;
+        pub mod power;
+        pub mod pressure;
+        pub mod proc_events;
+        pub mod process;
+        pub mod protect;
+        pub mod record;
+        pub mod reload;
+        pub mod report;
+        pub mod rescue;
+        pub mod responder;
+        pub mod rlimit;
+        pub mod rules;
+        pub mod runtime_env;
+        pub mod sandbox;
+        pub mod sched;
+        pub mod schedule;
+        pub mod sd_notify;
+        pub mod session;
+        pub mod shell_init;
+        pub mod snapshot;
+        pub mod stat;
+        pub mod state;
+        pub mod stats;
+        pub mod status;
+        pub mod supervise;
+        pub mod units;
+        pub mod watch;
+        pub mod watchdog;
+
+        pub use cgroup::{CgroupManager, Controller};
+        pub use stat::{CpuStat, IoStat, MemoryStat};
+    }
+}
+
+#[cfg(any(windows, test))]
+pub mod job;
+#[cfg(any(target_os = "freebsd", test))]
+pub mod rctl;
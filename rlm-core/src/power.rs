@@ -0,0 +1,119 @@
+//! Whether the machine runs on battery, for profiles with `on_battery`
+//! overrides.
+//!
+//! Read from `/sys/class/power_supply`, the same source UPower and
+//! power-profiles-daemon use, so no D-Bus session is needed.
+
+use std::fs;
+use std::path::Path;
+
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+/// Where the power comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+/// One entry of `/sys/class/power_supply`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Supply {
+    /// `type`: Mains, Battery, USB, ...
+    pub kind: String,
+    /// `online` of a mains or USB adapter
+    pub online: bool,
+    /// `status` of a battery: Charging, Discharging, Full, ...
+    pub status: String,
+    /// `scope` is Device: a mouse or headset battery, not the machine's
+    pub peripheral: bool,
+}
+
+/// The current power source. Machines without a battery are on AC.
+pub fn current() -> PowerSource {
+    let Ok(entries) = fs::read_dir(POWER_SUPPLY) else {
+        return PowerSource::Ac;
+    };
+    let supplies: Vec<Supply> = entries.flatten().map(|e| read_supply(&e.path())).collect();
+    source(&supplies)
+}
+
+/// Whether the machine runs on battery now.
+pub fn on_battery() -> bool {
+    current() == PowerSource::Battery
+}
+
+fn read_supply(path: &Path) -> Supply {
+    let read = |file| {
+        fs::read_to_string(path.join(file))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    Supply {
+        kind: read("type"),
+        online: read("online") == "1",
+        status: read("status"),
+        peripheral: read("scope") == "Device",
+    }
+}
+
+/// An online adapter means AC. Otherwise the machine is on battery if it has
+/// one and either it discharges or an adapter exists but is offline.
+/// Peripheral batteries are ignored.
+pub fn source(supplies: &[Supply]) -> PowerSource {
+    let adapters = || {
+        supplies
+            .iter()
+            .filter(|s| s.kind == "Mains" || s.kind == "USB")
+    };
+    if adapters().any(|s| s.online) {
+        return PowerSource::Ac;
+    }
+    let mut batteries = supplies
+        .iter()
+        .filter(|s| s.kind == "Battery" && !s.peripheral)
+        .peekable();
+    if batteries.peek().is_none() {
+        return PowerSource::Ac;
+    }
+    if batteries.any(|s| s.status == "Discharging") || adapters().next().is_some() {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(kind: &str, online: bool, status: &str) -> Supply {
+        Supply {
+            kind: kind.into(),
+            online,
+            status: status.into(),
+            peripheral: false,
+        }
+    }
+
+    #[test]
+    fn follows_the_adapter_and_battery() {
+        assert_eq!(source(&[]), PowerSource::Ac);
+        let unplugged = [
+            supply("Mains", false, ""),
+            supply("Battery", false, "Discharging"),
+        ];
+        assert_eq!(source(&unplugged), PowerSource::Battery);
+        let plugged = [
+            supply("Mains", true, ""),
+            supply("Battery", false, "Charging"),
+        ];
+        assert_eq!(source(&plugged), PowerSource::Ac);
+        // No adapter entry at all: trust the battery's own status
+        assert_eq!(source(&[supply("Battery", false, "Full")]), PowerSource::Ac);
+        assert_eq!(
+            source(&[supply("Battery", false, "Discharging")]),
+            PowerSource::Battery
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Pressure stall information (PSI) behind `rlm pressure`: how much of the
+//! time tasks were stalled waiting for CPU, memory, or I/O, system-wide
+//! (`/proc/pressure/*`) and per managed cgroup (`*.pressure`).
+//!
+//! `some` is the share of time at least one task was stalled, `full` the
+//! share all of them were. A limited cgroup with high `some` is being
+//! starved, whether by its own limit or by contention.
+
+use crate::CgroupManager;
+use std::fs;
+use std::path::Path;
+
+/// A resource PSI is reported for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Cpu,
+    Memory,
+    Io,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 3] = [Resource::Cpu, Resource::Memory, Resource::Io];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::Io => "io",
+        }
+    }
+}
+
+/// Stall percentages over the last 10 and 60 seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Psi {
+    pub some_avg10: f64,
+    pub some_avg60: f64,
+    /// `None` where the kernel has no `full` line (system-wide CPU before 5.13).
+    pub full_avg10: Option<f64>,
+    pub full_avg60: Option<f64>,
+}
+
+/// PSI of one target (the system or a cgroup) for each resource; `None`
+/// where it can't be read.
+#[derive(Debug, Clone)]
+pub struct Pressure {
+    pub target: String,
+    pub cpu: Option<Psi>,
+    pub memory: Option<Psi>,
+    pub io: Option<Psi>,
+}
+
+impl Pressure {
+    pub fn get(&self, resource: Resource) -> Option<Psi> {
+        match resource {
+            Resource::Cpu => self.cpu,
+            Resource::Memory => self.memory,
+            Resource::Io => self.io,
+        }
+    }
+
+    /// Highest `some` avg10 across resources, for ranking the most starved.
+    pub fn worst(&self) -> f64 {
+        Resource::ALL
+            .iter()
+            .filter_map(|&r| self.get(r))
+            .map(|p| p.some_avg10)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// System-wide pressure from `/proc/pressure`.
+pub fn system() -> Pressure {
+    let read = |r: Resource| {
+        fs::read_to_string(format!("/proc/pressure/{}", r.name()))
+            .ok()
+            .and_then(|c| parse_psi(&c))
+    };
+    Pressure {
+        target: "system".into(),
+        cpu: read(Resource::Cpu),
+        memory: read(Resource::Memory),
+        io: read(Resource::Io),
+    }
+}
+
+/// Pressure of every managed cgroup, most starved first.
+pub fn cgroups(manager: &CgroupManager) -> Vec<Pressure> {
+    let Ok(entries) = fs::read_dir(manager.base_path()) else {
+        return Vec::new();
+    };
+    let mut all: Vec<Pressure> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter(|e| e.file_name() != "unlimit")
+        .map(|e| of_cgroup(&e.path(), &e.file_name().to_string_lossy()))
+        .collect();
+    all.sort_by(|a, b| b.worst().total_cmp(&a.worst()));
+    all
+}
+
+/// Pressure of the cgroup at `cgroup_path`, labeled `target`.
+pub fn of_cgroup(cgroup_path: &Path, target: &str) -> Pressure {
+    let read = |r: Resource| {
+        fs::read_to_string(cgroup_path.join(format!("{}.pressure", r.name())))
+            .ok()
+            .and_then(|c| parse_psi(&c))
+    };
+    Pressure {
+        target: target.to_string(),
+        cpu: read(Resource::Cpu),
+        memory: read(Resource::Memory),
+        io: read(Resource::Io),
+    }
+}
+
+/// Parse a PSI file:
+///
+/// ```text
+/// some avg10=1.23 avg60=0.50 avg300=0.10 total=12345
+/// full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// ```
+///
+/// `None` without a readable `some` line.
+pub fn parse_psi(content: &str) -> Option<Psi> {
+    let mut psi = None;
+    let mut full = (None, None);
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("some ") {
+            psi = Some(Psi {
+                some_avg10: field(rest, "avg10")?,
+                some_avg60: field(rest, "avg60")?,
+                ..Psi::default()
+            });
+        } else if let Some(rest) = line.strip_prefix("full ") {
+            full = (field(rest, "avg10"), field(rest, "avg60"));
+        }
+    }
+    psi.map(|p| Psi {
+        full_avg10: full.0,
+        full_avg60: full.1,
+        ..p
+    })
+}
+
+/// Find `key=<number>` among space-separated `k=v` tokens.
+fn field(tokens: &str, key: &str) -> Option<f64> {
+    tokens.split_whitespace().find_map(|tok| {
+        tok.strip_prefix(key)
+            .and_then(|r| r.strip_prefix('='))
+            .and_then(|v| v.parse().ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_some_and_full() {
+        let psi = parse_psi(
+            "some avg10=12.50 avg60=3.25 avg300=1.00 total=99\n\
+             full avg10=4.00 avg60=1.50 avg300=0.20 total=42\n",
+        )
+        .unwrap();
+        assert_eq!(psi.some_avg10, 12.5);
+        assert_eq!(psi.some_avg60, 3.25);
+        assert_eq!(psi.full_avg10, Some(4.0));
+        assert_eq!(psi.full_avg60, Some(1.5));
+    }
+
+    #[test]
+    fn full_line_is_optional() {
+        let psi = parse_psi("some avg10=0.10 avg60=0.00 avg300=0.00 total=1\n").unwrap();
+        assert_eq!(psi.full_avg10, None);
+        assert!(parse_psi("").is_none());
+        assert!(parse_psi("some total=1\n").is_none());
+    }
+}
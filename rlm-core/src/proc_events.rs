@@ -0,0 +1,402 @@
+//! Process lifecycle events (fork, exec, exit) for `rlm daemon`.
+//!
+//! The kernel's proc connector (a netlink socket) reports every exec the
+//! moment it happens, including short-lived processes, but subscribing needs
+//! CAP_NET_ADMIN. Without it, `/proc` is polled instead: PIDs that appeared
+//! are reported as execs, and a PID seen on the previous poll is reported
+//! again if its name changed (a fork that exec'd after it was first seen).
+
+use common::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+
+// From <linux/cn_proc.h> and <linux/connector.h>
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_FORK: u32 = 0x1;
+const PROC_EVENT_EXEC: u32 = 0x2;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+
+/// `struct nlmsghdr` and `struct cn_msg`; the `proc_event` follows.
+const NLMSG_HEADER: usize = 16;
+const CN_MSG_HEADER: usize = 20;
+
+/// Something that happened to a process. Threads are not reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessEvent {
+    Fork {
+        parent: u32,
+        child: u32,
+    },
+    Exec {
+        pid: u32,
+    },
+    /// `status` is the wait status, when known (not when polling)
+    Exit {
+        pid: u32,
+        status: Option<u32>,
+    },
+}
+
+enum Source {
+    Netlink(OwnedFd),
+    Poll(Poller),
+}
+
+/// Process events from the proc connector, or from polling `/proc` every
+/// `interval` when that is not allowed.
+pub struct ProcessEvents {
+    source: Source,
+    pending: VecDeque<ProcessEvent>,
+}
+
+impl ProcessEvents {
+    pub fn new(poll_interval: Duration) -> Self {
+        let source = match subscribe() {
+            Ok(fd) => Source::Netlink(fd),
+            Err(e) => {
+                tracing::debug!(error = %e, "proc connector unavailable; polling /proc");
+                Source::Poll(Poller::new(poll_interval))
+            }
+        };
+        Self {
+            source,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Whether events come from the kernel rather than polling.
+    pub fn is_netlink(&self) -> bool {
+        matches!(self.source, Source::Netlink(_))
+    }
+
+    /// Wait up to `timeout` (forever for `None`) and return what happened;
+    /// empty on timeout.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<Vec<ProcessEvent>> {
+        if !self.pending.is_empty() {
+            return Ok(self.pending.drain(..).collect());
+        }
+        match &mut self.source {
+            Source::Netlink(fd) => {
+                if !readable(fd, timeout)? {
+                    return Ok(Vec::new());
+                }
+                let mut buf = [0u8; 4096];
+                // SAFETY: the buffer outlives the call and its length is passed.
+                let n = unsafe {
+                    libc::recv(
+                        fd.as_raw_fd(),
+                        buf.as_mut_ptr().cast(),
+                        buf.len(),
+                        libc::MSG_DONTWAIT,
+                    )
+                };
+                if n < 0 {
+                    let err = io::Error::last_os_error();
+                    return match err.kind() {
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => Ok(Vec::new()),
+                        // ENOBUFS: events were dropped under load; keep going
+                        _ if err.raw_os_error() == Some(libc::ENOBUFS) => Ok(Vec::new()),
+                        _ => Err(err.into()),
+                    };
+                }
+                Ok(parse_messages(&buf[..n as usize]))
+            }
+            Source::Poll(poller) => {
+                let deadline = timeout.map(|t| Instant::now() + t);
+                loop {
+                    let wait = poller.until_next();
+                    let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+                    if left.is_some_and(|l| l < wait) {
+                        std::thread::sleep(left.unwrap_or_default());
+                        return Ok(Vec::new());
+                    }
+                    std::thread::sleep(wait);
+                    let events = poller.poll();
+                    if !events.is_empty() {
+                        return Ok(events);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for ProcessEvents {
+    type Item = Result<ProcessEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            match self.wait(None) {
+                Ok(events) => self.pending.extend(events),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+/// Open a proc connector socket and ask for events.
+fn subscribe() -> io::Result<OwnedFd> {
+    // SAFETY: plain syscall; the descriptor is owned by the OwnedFd below.
+    let raw = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_CONNECTOR,
+        )
+    };
+    if raw < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `raw` is a fresh descriptor nothing else owns.
+    let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+    // SAFETY: sockaddr_nl is plain data; all-zero is valid.
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = CN_IDX_PROC;
+    // SAFETY: `addr` is a valid sockaddr_nl of the given size.
+    let rc = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            (&addr as *const libc::sockaddr_nl).cast(),
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let msg = listen_message(std::process::id());
+    // SAFETY: the message outlives the call and its length is passed.
+    let sent = unsafe { libc::send(fd.as_raw_fd(), msg.as_ptr().cast(), msg.len(), 0) };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fd)
+}
+
+/// The netlink message subscribing to proc events.
+fn listen_message(pid: u32) -> Vec<u8> {
+    let payload = PROC_CN_MCAST_LISTEN.to_ne_bytes();
+    let len = NLMSG_HEADER + CN_MSG_HEADER + payload.len();
+    let mut msg = Vec::with_capacity(len);
+    msg.extend_from_slice(&(len as u32).to_ne_bytes());
+    msg.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+    msg.extend_from_slice(&0u16.to_ne_bytes()); // flags
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // seq
+    msg.extend_from_slice(&pid.to_ne_bytes());
+    msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+    msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // seq
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // ack
+    msg.extend_from_slice(&(payload.len() as u16).to_ne_bytes());
+    msg.extend_from_slice(&0u16.to_ne_bytes()); // flags
+    msg.extend_from_slice(&payload);
+    msg
+}
+
+/// Split a datagram into netlink messages and decode their proc events.
+fn parse_messages(buf: &[u8]) -> Vec<ProcessEvent> {
+    let u32_at = |b: &[u8], at: usize| -> Option<u32> {
+        Some(u32::from_ne_bytes(b.get(at..at + 4)?.try_into().ok()?))
+    };
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while let Some(len) = u32_at(buf, offset).map(|l| l as usize) {
+        if len < NLMSG_HEADER || offset + len > buf.len() {
+            break;
+        }
+        let start = offset + NLMSG_HEADER + CN_MSG_HEADER;
+        let event = buf.get(start..offset + len).unwrap_or_default();
+        // proc_event: what, cpu, timestamp_ns (u64), then the event data
+        let data = 16;
+        let field = |i: usize| u32_at(event, data + i * 4);
+        let parsed = match u32_at(event, 0) {
+            Some(PROC_EVENT_FORK) => match (field(0), field(2), field(3)) {
+                // A new thread group, not a thread
+                (Some(parent), Some(child), Some(tgid)) if child == tgid => {
+                    Some(ProcessEvent::Fork { parent, child })
+                }
+                _ => None,
+            },
+            Some(PROC_EVENT_EXEC) => field(0).map(|pid| ProcessEvent::Exec { pid }),
+            Some(PROC_EVENT_EXIT) => match (field(0), field(1), field(2)) {
+                (Some(pid), Some(tgid), status) if pid == tgid => {
+                    Some(ProcessEvent::Exit { pid, status })
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        events.extend(parsed);
+        offset += (len + 3) & !3;
+    }
+    events
+}
+
+fn readable(fd: &OwnedFd, timeout: Option<Duration>) -> io::Result<bool> {
+    let mut pfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ms = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+    // SAFETY: one valid pollfd for the call's duration.
+    match unsafe { libc::poll(&mut pfd, 1, ms) } {
+        n if n > 0 => Ok(true),
+        0 => Ok(false),
+        _ => {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                Ok(false)
+            } else {
+                Err(err)
+            }
+        }
+    }
+}
+
+/// The polling fallback.
+struct Poller {
+    interval: Duration,
+    last: Option<Instant>,
+    known: HashSet<u32>,
+    /// Names of PIDs first seen on the previous poll
+    young: HashMap<u32, String>,
+}
+
+impl Poller {
+    fn new(interval: Duration) -> Self {
+        let mut poller = Self {
+            interval,
+            last: None,
+            known: HashSet::new(),
+            young: HashMap::new(),
+        };
+        // Processes running now are not events
+        poller.poll();
+        poller
+    }
+
+    fn until_next(&self) -> Duration {
+        self.last.map_or(Duration::ZERO, |t| {
+            self.interval.saturating_sub(t.elapsed())
+        })
+    }
+
+    fn poll(&mut self) -> Vec<ProcessEvent> {
+        let first = self.last.is_none();
+        self.last = Some(Instant::now());
+        let Ok(entries) = fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+        let current: HashSet<u32> = entries
+            .flatten()
+            .filter_map(|e| e.file_name().to_str()?.parse().ok())
+            .collect();
+        let comm = |pid: u32| {
+            fs::read_to_string(format!("/proc/{pid}/comm"))
+                .map(|c| c.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let (new, gone) = diff(&self.known, &current);
+        let mut events = Vec::new();
+        for (&pid, name) in &self.young {
+            if current.contains(&pid) && comm(pid) != *name {
+                events.push(ProcessEvent::Exec { pid });
+            }
+        }
+        self.young = if first {
+            HashMap::new()
+        } else {
+            new.iter().map(|&pid| (pid, comm(pid))).collect()
+        };
+        if !first {
+            events.extend(new.into_iter().map(|pid| ProcessEvent::Exec { pid }));
+            events.extend(
+                gone.into_iter()
+                    .map(|pid| ProcessEvent::Exit { pid, status: None }),
+            );
+        }
+        self.known = current;
+        events
+    }
+}
+
+/// PIDs in `current` but not `known`, and in `known` but not `current`,
+/// each sorted.
+fn diff(known: &HashSet<u32>, current: &HashSet<u32>) -> (Vec<u32>, Vec<u32>) {
+    let mut new: Vec<u32> = current.difference(known).copied().collect();
+    let mut gone: Vec<u32> = known.difference(current).copied().collect();
+    new.sort_unstable();
+    gone.sort_unstable();
+    (new, gone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One netlink message carrying a proc_event with `data` after the
+    /// timestamp.
+    fn message(what: u32, data: &[u32]) -> Vec<u8> {
+        let len = NLMSG_HEADER + CN_MSG_HEADER + 16 + data.len() * 4;
+        let mut msg = vec![0u8; NLMSG_HEADER + CN_MSG_HEADER];
+        msg[..4].copy_from_slice(&(len as u32).to_ne_bytes());
+        msg.extend_from_slice(&what.to_ne_bytes());
+        msg.extend_from_slice(&[0u8; 12]); // cpu, timestamp
+        for d in data {
+            msg.extend_from_slice(&d.to_ne_bytes());
+        }
+        msg
+    }
+
+    #[test]
+    fn parses_proc_events() {
+        let mut buf = message(PROC_EVENT_FORK, &[1, 1, 200, 200]);
+        buf.extend(message(PROC_EVENT_FORK, &[1, 1, 201, 200])); // a thread
+        buf.extend(message(PROC_EVENT_EXEC, &[200, 200]));
+        buf.extend(message(PROC_EVENT_EXIT, &[200, 200, 256, 17]));
+        assert_eq!(
+            parse_messages(&buf),
+            vec![
+                ProcessEvent::Fork {
+                    parent: 1,
+                    child: 200
+                },
+                ProcessEvent::Exec { pid: 200 },
+                ProcessEvent::Exit {
+                    pid: 200,
+                    status: Some(256)
+                },
+            ]
+        );
+        assert!(parse_messages(&[1, 2, 3]).is_empty());
+    }
+
+    #[test]
+    fn listen_message_layout() {
+        let msg = listen_message(42);
+        assert_eq!(msg.len(), 40);
+        assert_eq!(u32::from_ne_bytes(msg[..4].try_into().unwrap()), 40);
+        assert_eq!(u32::from_ne_bytes(msg[12..16].try_into().unwrap()), 42);
+        assert_eq!(
+            u32::from_ne_bytes(msg[36..40].try_into().unwrap()),
+            PROC_CN_MCAST_LISTEN
+        );
+    }
+
+    #[test]
+    fn diffs_pid_sets() {
+        let known: HashSet<u32> = [1, 2, 3].into();
+        let current: HashSet<u32> = [2, 3, 9, 4].into();
+        assert_eq!(diff(&known, &current), (vec![4, 9], vec![1]));
+    }
+}
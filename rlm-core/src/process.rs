@@ -1,6 +1,8 @@
 use common::{Error, Result};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
 use std::fs;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 
 /// Basic process info
@@ -22,14 +24,17 @@ pub struct ProcessGroup {
 
 /// Read process stat file to get PPID and session
 fn read_process_stat(proc_path: &Path) -> Option<(u32, u32)> {
-    // Format: pid comm state ppid pgrp session ...
-    // Fields: 0   1    2     3    4    5
-    if let Ok(content) = fs::read_to_string(proc_path.join("stat")) {
-        let parts: Vec<&str> = content.split_whitespace().collect();
-        if parts.len() >= 6 {
-            if let (Ok(ppid), Ok(session)) = (parts[3].parse(), parts[5].parse()) {
-                return Some((ppid, session));
-            }
+    parse_stat(&fs::read_to_string(proc_path.join("stat")).ok()?)
+}
+
+fn parse_stat(stat: &str) -> Option<(u32, u32)> {
+    // Format: pid (comm) state ppid pgrp session ...
+    // The comm can hold spaces and parentheses ("Web Content"), so the
+    // fields are counted from the last ')': state ppid pgrp session
+    let parts: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    if parts.len() >= 4 {
+        if let (Ok(ppid), Ok(session)) = (parts[1].parse(), parts[3].parse()) {
+            return Some((ppid, session));
         }
     }
     None
@@ -54,30 +59,41 @@ pub fn list_all() -> Result<Vec<ProcessInfo>> {
         let Ok(pid) = pid_str.parse::<u32>() else {
             continue;
         };
-
-        let name = fs::read_to_string(path.join("comm"))
-            .ok()
-            .map(|s| s.trim().to_string())
-            .unwrap_or_else(|| "?".to_string());
-
-        let (ppid, session) = read_process_stat(&path).unwrap_or((0, 0));
-        let executable = get_executable(&path);
-
-        processes.push(ProcessInfo {
-            pid,
-            name,
-            ppid: if ppid > 0 { Some(ppid) } else { None },
-            session: if session > 0 { Some(session) } else { None },
-            executable,
-        });
+        processes.push(read_info(pid, &path));
     }
 
     processes.sort_by(|a, b| a.name.cmp(&b.name));
     Ok(processes)
 }
 
-/// Find all PIDs matching a process name
+/// Info about one process, or `None` if it has exited.
+pub fn info(pid: u32) -> Option<ProcessInfo> {
+    let path = PathBuf::from(format!("/proc/{pid}"));
+    path.is_dir().then(|| read_info(pid, &path))
+}
+
+fn read_info(pid: u32, proc_path: &Path) -> ProcessInfo {
+    let name = fs::read_to_string(proc_path.join("comm"))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|| "?".to_string());
+
+    let (ppid, session) = read_process_stat(proc_path).unwrap_or((0, 0));
+    let executable = get_executable(proc_path);
+
+    ProcessInfo {
+        pid,
+        name,
+        ppid: if ppid > 0 { Some(ppid) } else { None },
+        session: if session > 0 { Some(session) } else { None },
+        executable,
+    }
+}
+
+/// Find all PIDs matching a process name, leaving out rlm and the processes
+/// it runs under, so `rlm limit --name bash` spares the shell it was typed in.
 pub fn find_by_name(name: &str) -> Result<Vec<u32>> {
+    let own = own_lineage();
     let mut pids = Vec::new();
 
     for entry in fs::read_dir("/proc")? {
@@ -92,7 +108,7 @@ pub fn find_by_name(name: &str) -> Result<Vec<u32>> {
             continue;
         };
 
-        if matches_name(&path, name) {
+        if !own.contains(&pid) && matches_name(&path, name) {
             pids.push(pid);
         }
     }
@@ -104,6 +120,74 @@ pub fn find_by_name(name: &str) -> Result<Vec<u32>> {
     Ok(pids)
 }
 
+/// Find all PIDs whose command line, arguments joined by spaces, contains
+/// `pattern`: for telling apart processes of one interpreter (`python3
+/// manage.py runserver`). rlm and the processes it runs under (`sudo`) are
+/// left out, as their own command lines contain the pattern.
+pub fn find_by_cmdline(pattern: &str) -> Result<Vec<u32>> {
+    if pattern.trim().is_empty() {
+        return Err(Error::InvalidArgs("--cmdline needs a pattern".into()));
+    }
+    let own = own_lineage();
+    let mut pids = Vec::new();
+
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if !own.contains(&pid) && cmdline(pid).is_some_and(|c| c.contains(pattern)) {
+            pids.push(pid);
+        }
+    }
+
+    if pids.is_empty() {
+        return Err(Error::ProcessNameNotFound(pattern.to_string()));
+    }
+
+    Ok(pids)
+}
+
+/// Command line of `pid`, arguments joined by spaces.
+pub fn cmdline(pid: u32) -> Option<String> {
+    let raw = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let args: Vec<_> = raw
+        .split(|&b| b == 0)
+        .filter(|a| !a.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect();
+    Some(args.join(" "))
+}
+
+/// Whether `pid` matches one of the `--exclude` patterns: its PID, or text
+/// found in its name or command line.
+pub fn matches_any(pid: u32, patterns: &[String]) -> bool {
+    let name = fs::read_to_string(format!("/proc/{pid}/comm")).unwrap_or_default();
+    let cmdline = cmdline(pid).unwrap_or_default();
+    patterns.iter().any(|pattern| {
+        pattern.parse::<u32>().ok() == Some(pid)
+            || name.trim().contains(pattern.as_str())
+            || cmdline.contains(pattern.as_str())
+    })
+}
+
+/// This process and its ancestors, up to but not including init.
+fn own_lineage() -> HashSet<u32> {
+    let mut lineage = HashSet::new();
+    let mut current = std::process::id();
+    while current > 1 && lineage.insert(current) {
+        match info(current).and_then(|p| p.ppid) {
+            Some(ppid) => current = ppid,
+            None => break,
+        }
+    }
+    lineage
+}
+
 fn matches_name(proc_path: &Path, name: &str) -> bool {
     // Try /proc/PID/comm first (max 15 chars, may be truncated)
     if let Ok(comm) = fs::read_to_string(proc_path.join("comm")) {
@@ -216,12 +300,65 @@ pub fn find_process_tree(root_pid: u32) -> Result<Vec<u32>> {
     Ok(result)
 }
 
-/// Find all processes matching an executable name (all instances)
+/// All processes owned by `uid`, kernel threads aside.
+pub fn owned_by(uid: u32) -> Result<Vec<u32>> {
+    let mut pids = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        let path = entry?.path();
+        let Some(pid) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if fs::metadata(&path).is_ok_and(|m| m.uid() == uid)
+            // kthreadd and its children
+            && pid != 2
+            && read_process_stat(&path).is_some_and(|(ppid, _)| ppid != 2)
+        {
+            pids.push(pid);
+        }
+    }
+    pids.sort_unstable();
+    Ok(pids)
+}
+
+/// UID of a user given by name or number.
+pub fn user_id(user: &str) -> Result<u32> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    let unknown = || Error::InvalidArgs(format!("no user '{user}'"));
+    let name = CString::new(user).map_err(|_| unknown())?;
+    let mut buf = vec![0 as libc::c_char; 1024];
+    // SAFETY: zeroed passwd is a valid out-parameter for getpwnam_r
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: all pointers are valid for the call; `buf` outlives `pwd`'s use
+    let rc = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() {
+        return Err(unknown());
+    }
+    Ok(pwd.pw_uid)
+}
+
+/// Find all processes matching an executable name (all instances), but for
+/// rlm and the processes it runs under
 pub fn find_all_by_executable(executable_name: &str) -> Result<Vec<ProcessInfo>> {
     let all = list_all()?;
+    let own = own_lineage();
     let mut matches = Vec::new();
 
-    for proc in all {
+    for proc in all.into_iter().filter(|p| !own.contains(&p.pid)) {
         let matches_name = proc.name == executable_name
             || proc
                 .executable
@@ -242,3 +379,58 @@ pub fn find_all_by_executable(executable_name: &str) -> Result<Vec<ProcessInfo>>
 
     Ok(matches)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_fields_after_a_comm_with_spaces() {
+        let stat = "4242 (Web Content) S 4100 4100 3900 0 -1 4194560 1 0 0 0";
+        assert_eq!(parse_stat(stat), Some((4100, 3900)));
+        assert_eq!(parse_stat("1 (a) b) R 0 1 1 0"), Some((0, 1)));
+        assert_eq!(parse_stat("garbage"), None);
+    }
+
+    #[test]
+    fn cmdline_matches_arguments_but_not_rlm_itself() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30.25")
+            .spawn()
+            .unwrap();
+        // Until it execs, the child has the test binary's command line
+        while cmdline(child.id()).is_some_and(|c| !c.starts_with("sleep")) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let found = find_by_cmdline("sleep 30.25");
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(found.unwrap(), [child.id()]);
+
+        // The test binary's own command line is not a match (children other
+        // tests are spawning may still have it, until they exec)
+        let own = cmdline(std::process::id()).unwrap();
+        let found = find_by_cmdline(&own).unwrap_or_default();
+        assert!(!found.contains(&std::process::id()));
+        assert!(find_by_cmdline(" ").is_err());
+    }
+
+    #[test]
+    fn name_matching_spares_rlm_and_its_ancestors() {
+        let own = own_lineage();
+        assert!(own.contains(&std::process::id()));
+        let name = info(std::process::id()).unwrap().name;
+        let found = find_by_name(&name).unwrap_or_default();
+        assert!(!found.iter().any(|pid| own.contains(pid)));
+    }
+
+    #[test]
+    fn exclude_patterns_match_pid_name_or_cmdline() {
+        let pid = std::process::id();
+        let name = info(pid).unwrap().name;
+        assert!(matches_any(pid, &[pid.to_string()]));
+        assert!(matches_any(pid, &["nothing".into(), name]));
+        assert!(!matches_any(pid, &["no such process here".into()]));
+        assert!(!matches_any(pid, &[]));
+    }
+}
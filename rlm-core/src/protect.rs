@@ -0,0 +1,162 @@
+//! Desktop protection mode: one conservative parent cgroup for the session.
+//!
+//! `rlm protect on` moves the user's apps into the `protect` cgroup, whose
+//! `memory.high` and `cpu.weight` keep them from starving the compositor and
+//! desktop shell, which stay where they are. Only processes in transient
+//! `.scope` units of the user's systemd instance are moved: those are the
+//! launched apps and terminals. Services are left alone, because emptying a
+//! service's cgroup makes systemd consider it stopped.
+
+use crate::CgroupManager;
+use common::Result;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+/// Compositors, desktop shells, and session plumbing that are never moved,
+/// even when they run inside a scope.
+pub const EXEMPT: &[&str] = &[
+    "gnome-shell",
+    "gnome-session-b",
+    "mutter",
+    "kwin_wayland",
+    "kwin_x11",
+    "plasmashell",
+    "sway",
+    "Hyprland",
+    "niri",
+    "river",
+    "labwc",
+    "wayfire",
+    "weston",
+    "cinnamon",
+    "xfwm4",
+    "xfce4-panel",
+    "marco",
+    "budgie-wm",
+    "Xwayland",
+    "Xorg",
+    "systemd",
+    "dbus-daemon",
+    "dbus-broker",
+    "pipewire",
+    "pipewire-pulse",
+    "wireplumber",
+    "pulseaudio",
+    "rlm-guard",
+];
+
+/// Turn protection on and move the session into it. Returns how many
+/// processes were moved.
+pub fn enable(manager: &CgroupManager) -> Result<usize> {
+    manager.enable_protect()?;
+    Ok(move_candidates(manager))
+}
+
+/// Turn protection off, releasing every protected process.
+pub fn disable(manager: &CgroupManager) -> Result<()> {
+    manager.disable_protect()
+}
+
+/// Move apps launched since protection was enabled. A no-op when protection
+/// is off; rlm-guard calls this every tick.
+pub fn sweep(manager: &CgroupManager) -> usize {
+    if !manager.protect_enabled() {
+        return 0;
+    }
+    move_candidates(manager)
+}
+
+fn move_candidates(manager: &CgroupManager) -> usize {
+    let Ok(uid) = fs::metadata("/proc/self").map(|m| m.uid()) else {
+        return 0;
+    };
+    let self_pid = std::process::id();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return 0;
+    };
+
+    let mut moved = 0;
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid == self_pid || entry.metadata().map(|m| m.uid()).ok() != Some(uid) {
+            continue;
+        }
+        // The process may exit mid-scan; anything unreadable is skipped.
+        let path = entry.path();
+        let (Ok(name), Ok(cgroup)) = (
+            fs::read_to_string(path.join("comm")),
+            fs::read_to_string(path.join("cgroup")),
+        ) else {
+            continue;
+        };
+        if should_move(&cgroup, uid, name.trim()) {
+            match manager.move_to_protect(pid) {
+                Ok(()) => moved += 1,
+                Err(e) => tracing::debug!(pid, error = %e, "could not protect process"),
+            }
+        }
+    }
+    moved
+}
+
+/// Whether a process belongs in the protect cgroup, given its
+/// `/proc/<pid>/cgroup` body, the caller's UID, and its comm.
+fn should_move(proc_cgroup: &str, uid: u32, name: &str) -> bool {
+    if EXEMPT.contains(&name) {
+        return false;
+    }
+    let Some(path) = proc_cgroup.lines().find_map(|l| l.strip_prefix("0::")) else {
+        return false;
+    };
+    let Some((_, inside)) = path.split_once(&format!("/user@{uid}.service/")) else {
+        return false;
+    };
+    // Released by rlm earlier (e.g. a previous `protect off`): no limits apply.
+    if inside == "rlm/unlimit" {
+        return true;
+    }
+    // Already managed by rlm (including protect itself), or the user manager.
+    if inside.starts_with("rlm/") || inside == "init.scope" {
+        return false;
+    }
+    inside.ends_with(".scope")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP: &str =
+        "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-firefox-4242.scope\n";
+
+    #[test]
+    fn moves_app_scopes_of_own_user() {
+        assert!(should_move(APP, 1000, "firefox"));
+        assert!(!should_move(APP, 1001, "firefox"));
+    }
+
+    #[test]
+    fn leaves_services_and_exempt_processes() {
+        let service = "0::/user.slice/user-1000.slice/user@1000.service/session.slice/org.gnome.Shell@wayland.service\n";
+        assert!(!should_move(service, 1000, "gnome-shell"));
+        assert!(!should_move(APP, 1000, "Xwayland"));
+        let init = "0::/user.slice/user-1000.slice/user@1000.service/init.scope\n";
+        assert!(!should_move(init, 1000, "systemd"));
+    }
+
+    #[test]
+    fn skips_rlm_managed_and_other_sessions() {
+        let managed = "0::/user.slice/user-1000.slice/user@1000.service/rlm/pid-4242\n";
+        assert!(!should_move(managed, 1000, "firefox"));
+        let released = "0::/user.slice/user-1000.slice/user@1000.service/rlm/unlimit\n";
+        assert!(should_move(released, 1000, "firefox"));
+        let login = "0::/user.slice/user-1000.slice/session-2.scope\n";
+        assert!(!should_move(login, 1000, "bash"));
+    }
+}
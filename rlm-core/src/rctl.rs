@@ -0,0 +1,348 @@
+//! Limits on FreeBSD, as `rctl(8)` rules. RACCT/RCTL must be enabled
+//! (`kern.racct.enable=1` in `/boot/loader.conf`). A process's rules are
+//! copied to each process it starts, which gets a budget of its own; a
+//! jail's rules cap all of its processes together.
+
+use common::{Error, Limit, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::process::{Child, Command};
+
+/// What a rule applies to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Subject {
+    Process(u32),
+    /// By name or jail ID
+    Jail(String),
+}
+
+impl Subject {
+    fn filter(&self) -> String {
+        match self {
+            Self::Process(pid) => format!("process:{pid}"),
+            Self::Jail(jail) => format!("jail:{jail}"),
+        }
+    }
+
+    /// What `pids.max` counts, threads as well as processes: a process's
+    /// own threads, or a jail's processes.
+    fn tasks_resource(&self) -> &'static str {
+        match self {
+            Self::Process(_) => "nthr",
+            Self::Jail(_) => "maxproc",
+        }
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Process(pid) => write!(f, "process {pid}"),
+            Self::Jail(jail) => write!(f, "jail {jail}"),
+        }
+    }
+}
+
+/// One rule, as `rctl` lists it: `process:4242:memoryuse:deny=1073741824`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub subject: Subject,
+    pub resource: String,
+    pub amount: u64,
+}
+
+/// A limited process or jail, as `rlm status` shows it.
+#[derive(Debug, Clone)]
+pub struct Limited {
+    /// `None` for a jail
+    pub pid: Option<u32>,
+    pub name: String,
+    pub memory_max: Option<u64>,
+    /// Resident memory now
+    pub memory_used: u64,
+    /// Percent of one CPU, like `--cpu`
+    pub cpu_percent: Option<u32>,
+    /// Threads of a process, or processes in a jail, now
+    pub processes: u32,
+    pub processes_max: Option<u32>,
+}
+
+/// The rules that put `limit` on `subject`.
+pub fn rules(subject: &Subject, limit: &Limit) -> Result<Vec<String>> {
+    let unsupported: Vec<&str> = [
+        (limit.swap.is_some(), "swap"),
+        (limit.oom_group, "oom_group"),
+        (limit.cpu_weight.is_some(), "cpu_weight"),
+        (limit.idle, "idle"),
+        (limit.io_path.is_some(), "io_path"),
+        (limit.io_weight.is_some(), "io_weight"),
+        (limit.mems.is_some(), "mems"),
+    ]
+    .into_iter()
+    .filter_map(|(set, name)| set.then_some(name))
+    .collect();
+    if !unsupported.is_empty() {
+        return Err(Error::InvalidArgs(format!(
+            "not supported on FreeBSD: {} (only memory, cpu, io, and max pids are)",
+            unsupported.join(", ")
+        )));
+    }
+
+    let filter = subject.filter();
+    let rule = |resource: &str, action: &str, amount: u64| {
+        format!("{filter}:{resource}:{action}={amount}")
+    };
+    let mut rules = Vec::new();
+    if let Some(memory) = limit.memory {
+        rules.push(rule("memoryuse", "deny", memory.bytes()));
+    }
+    // `deny` on pcpu throttles rather than refuses
+    if let Some(cpu) = limit.cpu {
+        rules.push(rule("pcpu", "deny", u64::from(cpu.percent())));
+    }
+    if let Some(io) = &limit.io {
+        if let Some(bps) = io.read_bps {
+            rules.push(rule("readbps", "throttle", bps));
+        }
+        if let Some(bps) = io.write_bps {
+            rules.push(rule("writebps", "throttle", bps));
+        }
+    }
+    if let Some(pids) = limit.pids {
+        rules.push(rule(subject.tasks_resource(), "deny", pids.count()));
+    }
+    Ok(rules)
+}
+
+/// Put process `pid` under `limit`, replacing any limits it has. The
+/// processes it starts from now on get the same limits.
+pub fn limit(pid: u32, limit: &Limit) -> Result<()> {
+    apply(&Subject::Process(pid), limit)
+}
+
+/// Put all of jail `jail`'s processes together under `limit`, replacing
+/// any limits it has.
+pub fn limit_jail(jail: &str, limit: &Limit) -> Result<()> {
+    apply(&Subject::Jail(jail.to_string()), limit)
+}
+
+/// Remove process `pid`'s limits.
+pub fn unlimit(pid: u32) -> Result<()> {
+    remove(&Subject::Process(pid))
+}
+
+pub fn unlimit_jail(jail: &str) -> Result<()> {
+    remove(&Subject::Jail(jail.to_string()))
+}
+
+/// Start `command` under `limit`. The rules are put on rlm itself while it
+/// forks, so the child has them before it runs anything.
+pub fn spawn(command: &mut Command, limit: &Limit) -> Result<Child> {
+    let me = Subject::Process(std::process::id());
+    apply(&me, limit)?;
+    let child = command.spawn();
+    if let Err(e) = rctl(&["-r", &me.filter()]) {
+        tracing::warn!("failed to remove rlm's own limits: {e}");
+    }
+    Ok(child?)
+}
+
+/// Every process and jail with rules on the resources rlm limits.
+pub fn status() -> Result<Vec<Limited>> {
+    let mut subjects: BTreeMap<Subject, Vec<Rule>> = BTreeMap::new();
+    for rule in parse_rules(&rctl(&[])?) {
+        subjects.entry(rule.subject.clone()).or_default().push(rule);
+    }
+    Ok(subjects
+        .into_iter()
+        .filter_map(|(subject, rules)| {
+            let limit_of = |resource: &str| {
+                rules
+                    .iter()
+                    .find(|r| r.resource == resource)
+                    .map(|r| r.amount)
+            };
+            let tasks = subject.tasks_resource();
+            let memory_max = limit_of("memoryuse");
+            let cpu_percent = limit_of("pcpu").map(|p| p as u32);
+            let processes_max = limit_of(tasks).map(|n| n as u32);
+            let throttled = limit_of("readbps").or(limit_of("writebps"));
+            if memory_max.is_none()
+                && cpu_percent.is_none()
+                && processes_max.is_none()
+                && throttled.is_none()
+            {
+                return None;
+            }
+            let usage = rctl(&["-u", &subject.filter()])
+                .map(|u| parse_usage(&u))
+                .unwrap_or_default();
+            let (pid, name) = match &subject {
+                Subject::Process(pid) => (Some(*pid), process_name(*pid)),
+                Subject::Jail(jail) => (None, jail.clone()),
+            };
+            Some(Limited {
+                pid,
+                name,
+                memory_max,
+                memory_used: usage.get("memoryuse").copied().unwrap_or(0),
+                cpu_percent,
+                processes: usage.get(tasks).copied().unwrap_or(0) as u32,
+                processes_max,
+            })
+        })
+        .collect())
+}
+
+fn apply(subject: &Subject, limit: &Limit) -> Result<()> {
+    let rules = rules(subject, limit)?;
+    // Start over, so limits left out this time go
+    if !list(subject)?.is_empty() {
+        rctl(&["-r", &subject.filter()])?;
+    }
+    for rule in &rules {
+        rctl(&["-a", rule])?;
+    }
+    tracing::info!(subject = %subject.filter(), ?rules, "applied rctl rules");
+    Ok(())
+}
+
+fn remove(subject: &Subject) -> Result<()> {
+    if list(subject)?.is_empty() {
+        return Err(Error::InvalidArgs(format!("{subject} is not limited")));
+    }
+    rctl(&["-r", &subject.filter()])?;
+    Ok(())
+}
+
+fn list(subject: &Subject) -> Result<Vec<Rule>> {
+    Ok(parse_rules(&rctl(&[&subject.filter()])?)
+        .into_iter()
+        .filter(|r| &r.subject == subject)
+        .collect())
+}
+
+/// Rules on processes and jails from `rctl` output; others (users, login
+/// classes) aren't rlm's.
+fn parse_rules(output: &str) -> Vec<Rule> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().splitn(4, ':');
+            let (kind, id) = (fields.next()?, fields.next()?);
+            let resource = fields.next()?;
+            // "deny=1073741824", or "deny=100/process" for a per-process share
+            let (_, amount) = fields.next()?.split_once('=')?;
+            let amount = amount.split('/').next()?.parse().ok()?;
+            let subject = match kind {
+                "process" => Subject::Process(id.parse().ok()?),
+                "jail" => Subject::Jail(id.to_string()),
+                _ => return None,
+            };
+            Some(Rule {
+                subject,
+                resource: resource.to_string(),
+                amount,
+            })
+        })
+        .collect()
+}
+
+/// `rctl -u` output: one `resource=amount` per line.
+fn parse_usage(output: &str) -> HashMap<String, u64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (resource, amount) = line.trim().split_once('=')?;
+            Some((resource.to_string(), amount.parse().ok()?))
+        })
+        .collect()
+}
+
+fn process_name(pid: u32) -> String {
+    Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "?".into())
+}
+
+fn rctl(args: &[&str]) -> Result<String> {
+    let output = Command::new("rctl")
+        .args(args)
+        .output()
+        .map_err(|e| Error::InvalidArgs(format!("failed to run rctl: {e}")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let hint = if stderr.contains("disabled") || stderr.contains("not present") {
+            "\n  hint: add kern.racct.enable=1 to /boot/loader.conf and reboot"
+        } else {
+            ""
+        };
+        return Err(Error::Io(io::Error::other(format!(
+            "rctl {}: {}{hint}",
+            args.join(" "),
+            stderr.trim()
+        ))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{CpuLimit, IoLimit, MemoryLimit, PidsLimit};
+
+    #[test]
+    fn maps_limits_onto_rules() {
+        let limit = Limit {
+            memory: Some(MemoryLimit::parse("1G").unwrap()),
+            cpu: Some(CpuLimit::parse("150%").unwrap()),
+            io: Some(IoLimit {
+                read_bps: None,
+                write_bps: Some(1 << 20),
+            }),
+            pids: Some(PidsLimit::new(64).unwrap()),
+            ..Limit::default()
+        };
+        assert_eq!(
+            rules(&Subject::Process(4242), &limit).unwrap(),
+            [
+                "process:4242:memoryuse:deny=1073741824",
+                "process:4242:pcpu:deny=150",
+                "process:4242:writebps:throttle=1048576",
+                "process:4242:nthr:deny=64",
+            ]
+        );
+        let jail = rules(&Subject::Jail("www".into()), &limit).unwrap();
+        assert_eq!(jail[3], "jail:www:maxproc:deny=64");
+
+        let idle = Limit {
+            idle: true,
+            ..limit
+        };
+        assert!(rules(&Subject::Process(1), &idle).is_err());
+    }
+
+    #[test]
+    fn parses_rctl_output() {
+        let rules = parse_rules(
+            "process:4242:memoryuse:deny=1073741824\n\
+             jail:www:pcpu:deny=200\n\
+             user:1001:maxproc:deny=100/process\n\
+             loginclass:daemon:vmemoryuse:deny=1\n",
+        );
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].subject, Subject::Process(4242));
+        assert_eq!(rules[0].amount, 1 << 30);
+        assert_eq!(rules[1].subject, Subject::Jail("www".into()));
+        assert_eq!(rules[1].resource, "pcpu");
+
+        let usage = parse_usage("cputime=3\nmemoryuse=1048576\nnthr=4\n");
+        assert_eq!(usage["memoryuse"], 1 << 20);
+        assert_eq!(usage["nthr"], 4);
+    }
+}
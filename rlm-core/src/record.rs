@@ -0,0 +1,119 @@
+//! Usage samples over time, behind `rlm record`: one cgroup's memory, CPU,
+//! and I/O written as CSV or JSON lines for offline analysis.
+
+use crate::stat::MemoryStat;
+use crate::status::Usage;
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// CSV header matching `Sample::to_csv`.
+pub const CSV_HEADER: &str =
+    "time,memory_bytes,memory_peak_bytes,swap_bytes,cpu_percent,io_read_bps,io_write_bps,processes";
+
+/// Usage of a cgroup at one point in time. Rates are over the interval
+/// since the previous sample.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    /// Seconds since the Unix epoch, with milliseconds.
+    pub time: f64,
+    pub memory_bytes: Option<u64>,
+    pub memory_peak_bytes: Option<u64>,
+    pub swap_bytes: Option<u64>,
+    pub cpu_percent: Option<f64>,
+    pub io_read_bps: u64,
+    pub io_write_bps: u64,
+    pub processes: usize,
+}
+
+impl Sample {
+    pub fn to_csv(&self) -> String {
+        let opt = |v: Option<u64>| v.map_or(String::new(), |v| v.to_string());
+        format!(
+            "{:.3},{},{},{},{},{},{},{}",
+            self.time,
+            opt(self.memory_bytes),
+            opt(self.memory_peak_bytes),
+            opt(self.swap_bytes),
+            self.cpu_percent
+                .map_or(String::new(), |c| format!("{c:.1}")),
+            self.io_read_bps,
+            self.io_write_bps,
+            self.processes
+        )
+    }
+
+    /// One line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Takes successive samples of one cgroup.
+pub struct Recorder {
+    path: std::path::PathBuf,
+    last: Option<(std::time::Instant, Usage)>,
+}
+
+impl Recorder {
+    pub fn new(cgroup_path: &Path) -> Self {
+        Self {
+            path: cgroup_path.to_path_buf(),
+            last: None,
+        }
+    }
+
+    /// Sample now, or `None` once the cgroup is gone or empty.
+    pub fn sample(&mut self) -> Option<Sample> {
+        let procs = std::fs::read_to_string(self.path.join("cgroup.procs")).ok()?;
+        let processes = procs.lines().filter(|l| !l.trim().is_empty()).count();
+        if processes == 0 {
+            return None;
+        }
+
+        let now = std::time::Instant::now();
+        let usage = Usage::read(&self.path);
+        let rates = self
+            .last
+            .map(|(then, before)| usage.rates_since(&before, now - then));
+        self.last = Some((now, usage));
+
+        let memory = MemoryStat::from_cgroup(&self.path).ok();
+        Some(Sample {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or(Duration::ZERO)
+                .as_secs_f64(),
+            memory_bytes: usage.memory_current,
+            memory_peak_bytes: memory.as_ref().and_then(|m| m.peak),
+            swap_bytes: memory.as_ref().and_then(|m| m.swap_current),
+            cpu_percent: rates.and_then(|r| r.cpu_percent),
+            io_read_bps: rates.map_or(0, |r| r.io_read_bps),
+            io_write_bps: rates.map_or(0, |r| r.io_write_bps),
+            processes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_row_matches_header() {
+        let sample = Sample {
+            time: 1700000000.25,
+            memory_bytes: Some(1024),
+            memory_peak_bytes: None,
+            swap_bytes: Some(0),
+            cpu_percent: Some(12.34),
+            io_read_bps: 5,
+            io_write_bps: 6,
+            processes: 3,
+        };
+        let row = sample.to_csv();
+        assert_eq!(row, "1700000000.250,1024,,0,12.3,5,6,3");
+        assert_eq!(row.split(',').count(), CSV_HEADER.split(',').count());
+        assert!(sample.to_json().contains("\"cpu_percent\":12.34"));
+    }
+}
@@ -0,0 +1,94 @@
+//! Reloading the config while a daemon runs: on SIGHUP, and optionally as
+//! soon as config.yaml or a file in profiles.d/ changes (inotify on the
+//! directories, so editors that save by renaming are seen too).
+
+use crate::watch::Inotify;
+use common::{Config, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static HANGUP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_hangup(_: libc::c_int) {
+    HANGUP.store(true, Ordering::SeqCst);
+}
+
+/// Tells a daemon when to reload its config.
+pub struct ConfigReload {
+    inotify: Option<Inotify>,
+}
+
+impl ConfigReload {
+    /// Take SIGHUP as a request to reload. Call it after setting the Ctrl+C
+    /// handler, which would otherwise stop the daemon on SIGHUP.
+    pub fn on_hangup() -> Self {
+        // SAFETY: the handler only stores to an atomic, which is
+        // async-signal-safe.
+        unsafe { libc::signal(libc::SIGHUP, on_hangup as *const () as libc::sighandler_t) };
+        Self { inotify: None }
+    }
+
+    /// Also reload when a config file changes.
+    pub fn watch_files(mut self) -> Result<Self> {
+        let inotify = Inotify::new()?;
+        add_watches(&inotify);
+        self.inotify = Some(inotify);
+        Ok(self)
+    }
+
+    /// Whether a reload was asked for since the last call. Never blocks.
+    pub fn requested(&mut self) -> bool {
+        let mut requested = HANGUP.swap(false, Ordering::SeqCst);
+        if let Some(inotify) = &mut self.inotify {
+            match inotify.read(Some(Duration::ZERO)) {
+                Ok(records) => {
+                    if records.iter().any(|(_, _, name)| is_config_file(name)) {
+                        // Pick up a profiles.d created since
+                        add_watches(inotify);
+                        requested = true;
+                    }
+                }
+                Err(e) => tracing::debug!(error = %e, "config watch failed"),
+            }
+        }
+        requested
+    }
+}
+
+fn add_watches(inotify: &Inotify) {
+    let mask = libc::IN_CLOSE_WRITE
+        | libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MOVED_TO
+        | libc::IN_MOVED_FROM;
+    for dir in Config::source_dirs().iter().filter(|d| d.is_dir()) {
+        if let Err(e) = inotify.add_watch(dir, mask) {
+            tracing::debug!(dir = %dir.display(), error = %e, "cannot watch config");
+        }
+    }
+}
+
+/// Whether a change to `name` in a config directory can change the config:
+/// YAML files and profiles.d itself, not editors' swap and backup files.
+fn is_config_file(name: &Option<String>) -> bool {
+    name.as_deref().is_some_and(|n| {
+        !n.starts_with('.') && (n.ends_with(".yaml") || n.ends_with(".yml") || n == "profiles.d")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_yaml_changes_reload() {
+        let name = |n: &str| Some(n.to_string());
+        assert!(is_config_file(&name("config.yaml")));
+        assert!(is_config_file(&name("build.yml")));
+        assert!(is_config_file(&name("profiles.d")));
+        assert!(!is_config_file(&name(".config.yaml.swp")));
+        assert!(!is_config_file(&name("config.yaml~")));
+        assert!(!is_config_file(&name("4913")));
+        assert!(!is_config_file(&None));
+    }
+}
@@ -0,0 +1,345 @@
+//! Snapshot report behind `rlm report`: system resources, pressure, managed
+//! cgroups with their limits and usage, memory events, and doctor results,
+//! as Markdown or HTML to attach to a bug report or share.
+
+use crate::events::{self, EventKind};
+use crate::status::ProcessStatus;
+use crate::{doctor, pressure, CgroupManager};
+use common::format_bytes;
+use std::fmt::Write;
+use std::fs;
+use std::time::Duration;
+
+/// One section of the report: key-value lines, then an optional table.
+#[derive(Debug, Clone, Default)]
+pub struct Section {
+    pub title: String,
+    pub facts: Vec<(String, String)>,
+    /// Column headers and rows
+    pub table: Option<(Vec<String>, Vec<Vec<String>>)>,
+    /// Shown when there is nothing else to say
+    pub empty: Option<String>,
+}
+
+/// A whole report, ready to render.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub sections: Vec<Section>,
+}
+
+/// Collect a report. Managed processes are sampled over `interval` for CPU
+/// and I/O rates.
+pub fn collect(manager: &CgroupManager, interval: Duration) -> Report {
+    let processes =
+        crate::status::get_managed_processes_sampled(manager, interval).unwrap_or_default();
+    Report {
+        sections: vec![
+            system_section(),
+            pressure_section(manager),
+            cgroups_section(&processes),
+            events_section(manager),
+            doctor_section(),
+        ],
+    }
+}
+
+fn system_section() -> Section {
+    let (kernel, os) = doctor::kernel_and_os();
+    let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    let kb = |key: &str| {
+        meminfo
+            .lines()
+            .find_map(|l| l.strip_prefix(key))
+            .and_then(|r| r.split_whitespace().next()?.parse::<u64>().ok())
+            .map_or("unknown".to_string(), |kb| format_bytes(kb * 1024))
+    };
+    let load = fs::read_to_string("/proc/loadavg")
+        .map(|l| l.split_whitespace().take(3).collect::<Vec<_>>().join(" "))
+        .unwrap_or_else(|_| "unknown".into());
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Section {
+        title: "System".into(),
+        facts: vec![
+            ("rlm".into(), env!("CARGO_PKG_VERSION").into()),
+            ("Kernel".into(), kernel),
+            ("OS".into(), os),
+            ("CPUs".into(), cpus.to_string()),
+            ("Memory".into(), kb("MemTotal:")),
+            ("Available".into(), kb("MemAvailable:")),
+            ("Swap".into(), kb("SwapTotal:")),
+            ("Swap free".into(), kb("SwapFree:")),
+            ("Load (1/5/15 min)".into(), load),
+        ],
+        ..Section::default()
+    }
+}
+
+fn pressure_section(manager: &CgroupManager) -> Section {
+    let pct = |v: Option<f64>| v.map_or("-".into(), |v| format!("{v:.2}%"));
+    let mut rows = Vec::new();
+    let system = pressure::system();
+    for target in std::iter::once(system).chain(pressure::cgroups(manager)) {
+        for resource in pressure::Resource::ALL {
+            if let Some(psi) = target.get(resource) {
+                rows.push(vec![
+                    target.target.clone(),
+                    resource.name().into(),
+                    pct(Some(psi.some_avg10)),
+                    pct(Some(psi.some_avg60)),
+                    pct(psi.full_avg10),
+                    pct(psi.full_avg60),
+                ]);
+            }
+        }
+    }
+    Section {
+        title: "Pressure (PSI)".into(),
+        empty: rows.is_empty().then(|| "PSI unavailable".to_string()),
+        table: (!rows.is_empty()).then(|| {
+            (
+                [
+                    "Target", "Resource", "Some 10s", "Some 60s", "Full 10s", "Full 60s",
+                ]
+                .map(String::from)
+                .to_vec(),
+                rows,
+            )
+        }),
+        ..Section::default()
+    }
+}
+
+fn cgroups_section(processes: &[ProcessStatus]) -> Section {
+    let rows: Vec<Vec<String>> = processes
+        .iter()
+        .map(|p| {
+            let bytes = |v: Option<u64>| v.map_or("-".into(), format_bytes);
+            let limits = [
+                p.memory_max.map(|m| format!("memory {}", format_bytes(m))),
+                p.memory_low
+                    .map(|m| format!("reserved {}", format_bytes(m))),
+                p.swap_max.map(|s| format!("swap {}", format_bytes(s))),
+                p.cpu_quota.map(|c| format!("cpu {c}%")),
+                p.cpu_weight.map(|w| format!("cpu weight {w}")),
+                p.cpu_idle.then(|| "cpu idle".to_string()),
+                p.io_read_bps.map(|r| format!("read {}/s", format_bytes(r))),
+                p.io_write_bps
+                    .map(|w| format!("write {}/s", format_bytes(w))),
+                p.io_weight.map(|w| format!("io weight {w}")),
+                p.pids_max.map(|n| format!("pids {n}")),
+                p.mems.as_ref().map(|m| format!("nodes {m}")),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ");
+            vec![
+                p.cgroup_name.clone(),
+                format!("{} ({})", p.name, p.members.len().max(1)),
+                limits,
+                bytes(p.usage.memory_current),
+                bytes(p.memory_peak),
+                p.rates
+                    .and_then(|r| r.cpu_percent)
+                    .map_or("-".into(), |c| format!("{c:.0}%")),
+                p.rates.map_or("-".into(), |r| {
+                    format!(
+                        "{}/s / {}/s",
+                        format_bytes(r.io_read_bps),
+                        format_bytes(r.io_write_bps)
+                    )
+                }),
+            ]
+        })
+        .collect();
+    let totals = crate::status::totals(processes);
+    Section {
+        title: "Managed cgroups".into(),
+        facts: vec![
+            (
+                "Processes".into(),
+                format!("{} in {} cgroups", totals.processes, totals.cgroups),
+            ),
+            ("Memory used".into(), format_bytes(totals.memory_used)),
+            ("Memory limited".into(), format_bytes(totals.memory_limit)),
+            ("CPU quota".into(), format!("{}%", totals.cpu_quota)),
+        ],
+        empty: rows
+            .is_empty()
+            .then(|| "No processes currently managed".to_string()),
+        table: (!rows.is_empty()).then(|| {
+            (
+                [
+                    "Cgroup",
+                    "Process (count)",
+                    "Limits",
+                    "Memory",
+                    "Peak",
+                    "CPU",
+                    "I/O read / write",
+                ]
+                .map(String::from)
+                .to_vec(),
+                rows,
+            )
+        }),
+    }
+}
+
+fn events_section(manager: &CgroupManager) -> Section {
+    let rows: Vec<Vec<String>> = events::snapshot(manager)
+        .into_iter()
+        .filter(|(_, c)| EventKind::ALL.iter().any(|&k| c.get(k) > 0))
+        .map(|(name, c)| {
+            let mut row = vec![name];
+            row.extend(EventKind::ALL.iter().map(|&k| c.get(k).to_string()));
+            row
+        })
+        .collect();
+    let mut headers = vec!["Cgroup".to_string()];
+    headers.extend(EventKind::ALL.iter().map(|k| k.key().to_string()));
+    Section {
+        title: "Memory events".into(),
+        empty: rows
+            .is_empty()
+            .then(|| "No limit breaches or OOM kills in managed cgroups".to_string()),
+        table: (!rows.is_empty()).then_some((headers, rows)),
+        ..Section::default()
+    }
+}
+
+fn doctor_section() -> Section {
+    let rows = doctor::run_checks()
+        .into_iter()
+        .map(|c| {
+            let status = match (c.ok, c.required) {
+                (true, _) => "ok",
+                (false, true) => "FAIL",
+                (false, false) => "warn",
+            };
+            vec![status.to_string(), c.name]
+        })
+        .collect();
+    Section {
+        title: "Doctor".into(),
+        table: Some((vec!["Status".into(), "Check".into()], rows)),
+        ..Section::default()
+    }
+}
+
+impl Report {
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# rlm report\n");
+        for s in &self.sections {
+            let _ = writeln!(out, "\n## {}\n", s.title);
+            for (key, value) in &s.facts {
+                let _ = writeln!(out, "- **{key}:** {}", md_escape(value));
+            }
+            if !s.facts.is_empty() {
+                out.push('\n');
+            }
+            if let Some((headers, rows)) = &s.table {
+                let _ = writeln!(out, "| {} |", headers.join(" | "));
+                let _ = writeln!(out, "|{}", "---|".repeat(headers.len()));
+                for row in rows {
+                    let cells: Vec<String> = row.iter().map(|c| md_escape(c)).collect();
+                    let _ = writeln!(out, "| {} |", cells.join(" | "));
+                }
+            }
+            if let Some(empty) = &s.empty {
+                let _ = writeln!(out, "_{empty}_");
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>rlm report</title>\n\
+             <style>body{font-family:sans-serif}table{border-collapse:collapse}\
+             td,th{border:1px solid #ccc;padding:2px 8px;text-align:left}</style>\n\
+             </head><body>\n<h1>rlm report</h1>\n",
+        );
+        for s in &self.sections {
+            let _ = writeln!(out, "<h2>{}</h2>", html_escape(&s.title));
+            if !s.facts.is_empty() {
+                out.push_str("<ul>\n");
+                for (key, value) in &s.facts {
+                    let _ = writeln!(
+                        out,
+                        "<li><b>{}:</b> {}</li>",
+                        html_escape(key),
+                        html_escape(value)
+                    );
+                }
+                out.push_str("</ul>\n");
+            }
+            if let Some((headers, rows)) = &s.table {
+                out.push_str("<table>\n<tr>");
+                for h in headers {
+                    let _ = write!(out, "<th>{}</th>", html_escape(h));
+                }
+                out.push_str("</tr>\n");
+                for row in rows {
+                    out.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(out, "<td>{}</td>", html_escape(cell));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+            if let Some(empty) = &s.empty {
+                let _ = writeln!(out, "<p><i>{}</i></p>", html_escape(empty));
+            }
+        }
+        out.push_str("</body></html>\n");
+        out
+    }
+}
+
+/// Keep a value from breaking a Markdown table row.
+fn md_escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Report {
+        Report {
+            sections: vec![Section {
+                title: "Managed cgroups".into(),
+                facts: vec![("Processes".into(), "2 in 1 cgroups".into())],
+                table: Some((
+                    vec!["Cgroup".into(), "Process".into()],
+                    vec![vec!["app-a|b".into(), "<script>".into()]],
+                )),
+                empty: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn markdown_escapes_table_cells() {
+        let md = sample().to_markdown();
+        assert!(md.contains("## Managed cgroups"));
+        assert!(md.contains("- **Processes:** 2 in 1 cgroups"));
+        assert!(md.contains("| Cgroup | Process |\n|---|---|\n| app-a\\|b | <script> |"));
+    }
+
+    #[test]
+    fn html_escapes_cells() {
+        let html = sample().to_html();
+        assert!(html.contains("<td>&lt;script&gt;</td>"));
+        assert!(!html.contains("<script>"));
+    }
+}
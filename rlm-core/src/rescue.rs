@@ -0,0 +1,262 @@
+//! Emergency throttle, behind `rlm rescue`: for when the desktop already
+//! stutters. The processes using the most memory and CPU right now are moved
+//! into one `rescue` cgroup with a quarter of a core, the lowest CPU and I/O
+//! weight, and a tight `memory.high`, and optionally frozen.
+//!
+//! Where each one came from is recorded in `$XDG_RUNTIME_DIR/rlm/rescue.json`,
+//! so `rlm rescue --undo` can put it back in its own cgroup, limits and all.
+
+use crate::CgroupManager;
+use common::{Error, Result, BUILTIN_PROTECT};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long CPU use is measured over before picking.
+const SAMPLE: Duration = Duration::from_millis(500);
+/// Memory a process must use to count as an offender on memory.
+const MIN_MEMORY: u64 = 200 * 1024 * 1024;
+/// CPU a process must use to count as an offender on CPU, in percent of a
+/// core.
+const MIN_CPU_PERCENT: f64 = 25.0;
+
+/// A process picked by `rlm rescue`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Offender {
+    pub pid: u32,
+    pub name: String,
+    /// Resident memory when picked
+    pub memory: u64,
+    /// CPU use when picked, in percent of one core
+    pub cpu_percent: f64,
+    /// Its cgroup before the rescue, relative to the cgroup root
+    pub from: String,
+}
+
+/// Processes held in the `rescue` cgroup.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rescue {
+    pub offenders: Vec<Offender>,
+    pub frozen: bool,
+}
+
+/// Throttle the `count` worst memory and the `count` worst CPU consumers,
+/// freezing them too with `freeze`. Rescuing again adds to the ones already
+/// held. Returns the processes moved this time.
+pub fn rescue(manager: &CgroupManager, count: usize, freeze: bool) -> Result<Vec<Offender>> {
+    let picked = pick(measure(manager), count);
+    if picked.is_empty() {
+        return Ok(picked);
+    }
+    manager.enable_rescue()?;
+
+    let mut held = current().unwrap_or_default();
+    let mut moved = Vec::new();
+    for offender in picked {
+        match manager.move_to_rescue(offender.pid) {
+            Ok(()) => moved.push(offender),
+            Err(e) => tracing::warn!(pid = offender.pid, error = %e, "could not rescue process"),
+        }
+    }
+    if moved.is_empty() {
+        return Err(Error::Cgroup(
+            "could not move any process into the rescue cgroup".into(),
+        ));
+    }
+    held.offenders.extend(moved.iter().cloned());
+    if freeze {
+        manager.set_frozen(crate::cgroup::RESCUE_CGROUP, true)?;
+        held.frozen = true;
+    }
+    save(Some(&held))?;
+    Ok(moved)
+}
+
+/// Thaw the rescued processes and put each back in the cgroup it came from.
+/// Ones whose cgroup is gone are released without limits. Returns what was
+/// held, if anything.
+pub fn undo(manager: &CgroupManager) -> Result<Option<Rescue>> {
+    if !manager.rescue_active() {
+        save(None)?;
+        return Ok(None);
+    }
+    let held = current().unwrap_or_default();
+    manager.set_frozen(crate::cgroup::RESCUE_CGROUP, false)?;
+
+    let inside = manager.pids_in_cgroup(crate::cgroup::RESCUE_CGROUP);
+    for offender in held.offenders.iter().filter(|o| inside.contains(&o.pid)) {
+        let Some(from) = crate::hierarchy::dir_of(&offender.from) else {
+            continue;
+        };
+        if let Err(e) = fs::write(from.join("cgroup.procs"), offender.pid.to_string()) {
+            tracing::debug!(pid = offender.pid, error = %e, "could not return rescued process");
+        }
+    }
+    manager.remove_application_limit(crate::cgroup::RESCUE_CGROUP)?;
+    save(None)?;
+    Ok(Some(held))
+}
+
+/// The processes held, if any.
+pub fn current() -> Option<Rescue> {
+    let json = fs::read_to_string(record_path()?).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+fn record_path() -> Option<PathBuf> {
+    dirs::runtime_dir().map(|d| d.join("rlm").join("rescue.json"))
+}
+
+fn save(rescue: Option<&Rescue>) -> Result<()> {
+    let path = record_path().ok_or_else(|| {
+        Error::Config("no runtime directory (XDG_RUNTIME_DIR) to record the rescue in".into())
+    })?;
+    let Some(rescue) = rescue else {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(rescue)
+        .map_err(|e| Error::Config(format!("failed to serialize the rescue: {e}")))?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+/// Memory and CPU use of every process that may be rescued: the user's own
+/// (as root, every login user's), minus the desktop, the shells, reserved
+/// apps, and ones already rescued.
+fn measure(manager: &CgroupManager) -> Vec<Offender> {
+    let Ok(uid) = fs::metadata("/proc/self").map(|m| m.uid()) else {
+        return Vec::new();
+    };
+    let self_pid = std::process::id();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let mut candidates = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let owner = entry.metadata().map(|m| m.uid()).unwrap_or(0);
+        if pid == self_pid || !(owner == uid || (uid == 0 && owner >= 1000)) {
+            continue;
+        }
+        // The process may exit mid-scan; anything unreadable is skipped.
+        let path = entry.path();
+        let (Ok(name), Ok(cgroup)) = (
+            fs::read_to_string(path.join("comm")),
+            fs::read_to_string(path.join("cgroup")),
+        ) else {
+            continue;
+        };
+        let name = name.trim();
+        if BUILTIN_PROTECT.contains(&name) || crate::protect::EXEMPT.contains(&name) {
+            continue;
+        }
+        if manager
+            .find_cgroup_for_pid(pid)
+            .is_some_and(|c| c == crate::cgroup::RESCUE_CGROUP || c.starts_with("reserve-"))
+        {
+            continue;
+        }
+        let (Some(from), Some(memory), Some(ticks)) = (
+            crate::cgroup::unified_cgroup(&cgroup),
+            crate::watchdog::rss_bytes(pid),
+            crate::watchdog::cpu_ticks(pid),
+        ) else {
+            continue;
+        };
+        candidates.push((
+            ticks,
+            Offender {
+                pid,
+                name: name.to_string(),
+                memory,
+                cpu_percent: 0.0,
+                from: from.to_string(),
+            },
+        ));
+    }
+
+    let start = Instant::now();
+    std::thread::sleep(SAMPLE);
+    // SAFETY: sysconf has no preconditions.
+    let hz = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+    let secs = start.elapsed().as_secs_f64();
+    candidates
+        .into_iter()
+        .filter_map(|(before, mut offender)| {
+            let used = crate::watchdog::cpu_ticks(offender.pid)?.saturating_sub(before);
+            offender.cpu_percent = used as f64 / hz / secs * 100.0;
+            Some(offender)
+        })
+        .collect()
+}
+
+/// The top `count` by memory and the top `count` by CPU, each only above
+/// its threshold, biggest memory first.
+fn pick(mut candidates: Vec<Offender>, count: usize) -> Vec<Offender> {
+    candidates.sort_by_key(|o| std::cmp::Reverse(o.memory));
+    let mut picked: Vec<Offender> = candidates
+        .iter()
+        .filter(|o| o.memory >= MIN_MEMORY)
+        .take(count)
+        .cloned()
+        .collect();
+
+    candidates.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent));
+    for offender in candidates
+        .into_iter()
+        .filter(|o| o.cpu_percent >= MIN_CPU_PERCENT)
+        .take(count)
+    {
+        if !picked.iter().any(|p| p.pid == offender.pid) {
+            picked.push(offender);
+        }
+    }
+    picked.sort_by_key(|o| std::cmp::Reverse(o.memory));
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offender(pid: u32, mib: u64, cpu_percent: f64) -> Offender {
+        Offender {
+            pid,
+            name: format!("p{pid}"),
+            memory: mib * 1024 * 1024,
+            cpu_percent,
+            from: "/user.slice".into(),
+        }
+    }
+
+    #[test]
+    fn picks_the_worst_on_memory_and_on_cpu() {
+        let candidates = vec![
+            offender(1, 4000, 1.0),
+            offender(2, 3000, 90.0),
+            offender(3, 2000, 0.0),
+            offender(4, 50, 180.0),
+            offender(5, 100, 5.0),
+        ];
+        let pids = |picked: Vec<Offender>| picked.iter().map(|o| o.pid).collect::<Vec<_>>();
+        assert_eq!(pids(pick(candidates.clone(), 2)), vec![1, 2, 4]);
+        assert_eq!(pids(pick(candidates.clone(), 1)), vec![1, 4]);
+        // Nothing above either threshold: nothing to rescue
+        assert!(pick(vec![offender(5, 100, 5.0)], 3).is_empty());
+    }
+}
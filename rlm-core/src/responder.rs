@@ -0,0 +1,294 @@
+//! Memory pressure responder, behind `rlm daemon --respond-to-pressure`: a
+//! scoped oomd. When system-wide memory PSI `full` stays above
+//! `responder.freeze_full`, the cgroup of the most expendable profile (the
+//! highest `expendable:`) is frozen; above `kill_full` it is killed. Only
+//! profiles with `expendable` set are ever touched. Cgroups it froze are
+//! thawed once pressure has stayed low for a while, and when the daemon
+//! stops.
+
+use crate::CgroupManager;
+use common::{Config, ResponderConfig};
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// About how long PSI avg10 takes to show the effect of an action; the next
+/// one waits at least this long.
+const SETTLE: Duration = Duration::from_secs(10);
+
+/// What the responder did to a cgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Freeze,
+    Kill,
+    Thaw,
+}
+
+impl Response {
+    pub fn name(self) -> &'static str {
+        match self {
+            Response::Freeze => "froze",
+            Response::Kill => "killed",
+            Response::Thaw => "thawed",
+        }
+    }
+}
+
+/// One action taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intervention {
+    pub profile: String,
+    pub cgroup: String,
+    pub response: Response,
+    /// Memory PSI `full` avg10 when it was taken
+    pub full_avg10: f64,
+}
+
+/// An expendable profile's cgroup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    profile: String,
+    cgroup: String,
+    expendable: u32,
+}
+
+/// Freezes and kills expendable cgroups as memory pressure demands.
+pub struct PressureResponder {
+    cfg: ResponderConfig,
+    targets: Vec<Target>,
+    /// Since when pressure has been above `freeze_full`
+    above_since: Option<Instant>,
+    /// Since when pressure has been below half of `freeze_full`
+    below_since: Option<Instant>,
+    last_action: Option<Instant>,
+    /// Cgroups frozen, oldest first
+    frozen: Vec<Target>,
+}
+
+impl PressureResponder {
+    pub fn new(cfg: &Config) -> Self {
+        Self {
+            cfg: cfg.responder.clone(),
+            targets: targets(cfg),
+            above_since: None,
+            below_since: None,
+            last_action: None,
+            frozen: Vec::new(),
+        }
+    }
+
+    /// Whether any profile is marked expendable.
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Profile names it may act on, most expendable first.
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.targets.iter().map(|t| t.profile.as_str()).collect()
+    }
+
+    /// Switch to the thresholds and profiles of `cfg`. Cgroups already
+    /// frozen stay frozen until pressure allows.
+    pub fn reload(&mut self, cfg: &Config) {
+        self.cfg = cfg.responder.clone();
+        self.targets = targets(cfg);
+    }
+
+    /// Read memory pressure and act on it. Call every second or so.
+    pub fn tick(&mut self, mgr: &CgroupManager) -> Vec<Intervention> {
+        let Some(full) = crate::pressure::system().memory.and_then(|p| p.full_avg10) else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        match self.decide(full, now) {
+            Some(Response::Thaw) => self.thaw_all(mgr, full),
+            Some(response) => {
+                let candidates: Vec<(&Target, u64)> = self
+                    .targets
+                    .iter()
+                    .filter(|t| !mgr.pids_in_cgroup(&t.cgroup).is_empty())
+                    .filter(|t| response == Response::Kill || !self.frozen.contains(t))
+                    .map(|t| (t, memory_current(mgr, &t.cgroup)))
+                    .collect();
+                let Some(target) = victim(&candidates).cloned() else {
+                    return Vec::new();
+                };
+                self.last_action = Some(now);
+                self.act(mgr, target, response, full).into_iter().collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Thaw every cgroup it froze, e.g. when the daemon stops.
+    pub fn release(&mut self, mgr: &CgroupManager) -> Vec<Intervention> {
+        self.thaw_all(mgr, 0.0)
+    }
+
+    /// What pressure `full` calls for at `now`: act once it has stayed high
+    /// for `sustain_secs` (and the last action has had time to show), thaw
+    /// once it has stayed low for `thaw_after_secs`.
+    fn decide(&mut self, full: f64, now: Instant) -> Option<Response> {
+        if full >= self.cfg.freeze_full {
+            self.below_since = None;
+            let since = *self.above_since.get_or_insert(now);
+            let sustain = Duration::from_secs(self.cfg.sustain_secs);
+            let settled = self
+                .last_action
+                .is_none_or(|t| now.duration_since(t) >= sustain.max(SETTLE));
+            if now.duration_since(since) < sustain || !settled {
+                return None;
+            }
+            return Some(if full >= self.cfg.kill_full {
+                Response::Kill
+            } else {
+                Response::Freeze
+            });
+        }
+        self.above_since = None;
+        if full >= self.cfg.freeze_full / 2.0 || self.frozen.is_empty() {
+            self.below_since = None;
+            return None;
+        }
+        let since = *self.below_since.get_or_insert(now);
+        if now.duration_since(since) < Duration::from_secs(self.cfg.thaw_after_secs) {
+            return None;
+        }
+        self.below_since = None;
+        Some(Response::Thaw)
+    }
+
+    fn act(
+        &mut self,
+        mgr: &CgroupManager,
+        target: Target,
+        response: Response,
+        full: f64,
+    ) -> Option<Intervention> {
+        let result = match response {
+            Response::Kill => mgr.kill_cgroup(&target.cgroup).map(|()| {
+                self.frozen.retain(|t| t != &target);
+                // Processes placed there later must not start out frozen
+                let _ = mgr.set_frozen(&target.cgroup, false);
+            }),
+            _ => mgr
+                .set_frozen(&target.cgroup, true)
+                .map(|()| self.frozen.push(target.clone())),
+        };
+        if let Err(e) = result {
+            tracing::warn!(cgroup = %target.cgroup, error = %e, "responder: action failed");
+            return None;
+        }
+        tracing::info!(cgroup = %target.cgroup, action = response.name(), full, "memory pressure");
+        Some(Intervention {
+            profile: target.profile,
+            cgroup: target.cgroup,
+            response,
+            full_avg10: full,
+        })
+    }
+
+    fn thaw_all(&mut self, mgr: &CgroupManager, full: f64) -> Vec<Intervention> {
+        self.frozen
+            .drain(..)
+            .filter(|t| mgr.set_frozen(&t.cgroup, false).is_ok())
+            .map(|t| Intervention {
+                profile: t.profile,
+                cgroup: t.cgroup,
+                response: Response::Thaw,
+                full_avg10: full,
+            })
+            .collect()
+    }
+}
+
+/// Profiles marked expendable, most expendable first.
+fn targets(cfg: &Config) -> Vec<Target> {
+    let mut targets: Vec<Target> = cfg
+        .profiles
+        .iter()
+        .filter_map(|(name, p)| {
+            Some(Target {
+                profile: name.clone(),
+                cgroup: crate::rules::profile_cgroup_name(name),
+                expendable: p.expendable?,
+            })
+        })
+        .collect();
+    targets.sort_by(|a, b| {
+        b.expendable
+            .cmp(&a.expendable)
+            .then_with(|| a.profile.cmp(&b.profile))
+    });
+    targets
+}
+
+fn memory_current(mgr: &CgroupManager, cgroup: &str) -> u64 {
+    fs::read_to_string(mgr.base_path().join(cgroup).join("memory.current"))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// The most expendable candidate, the one using the most memory among
+/// equals.
+fn victim<'a>(candidates: &[(&'a Target, u64)]) -> Option<&'a Target> {
+    candidates
+        .iter()
+        .max_by_key(|(t, memory)| (t.expendable, *memory))
+        .map(|(t, _)| *t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(profile: &str, expendable: u32) -> Target {
+        Target {
+            profile: profile.into(),
+            cgroup: crate::rules::profile_cgroup_name(profile),
+            expendable,
+        }
+    }
+
+    #[test]
+    fn acts_on_sustained_pressure_and_thaws_after_calm() {
+        let mut r = PressureResponder::new(&Config::default());
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // Below the threshold, or not for long enough: nothing
+        assert_eq!(r.decide(5.0, at(0)), None);
+        assert_eq!(r.decide(15.0, at(1)), None);
+        assert_eq!(r.decide(15.0, at(6)), Some(Response::Freeze));
+        r.last_action = Some(at(6));
+        r.frozen.push(target("build", 1));
+        // The last action needs time to show in avg10
+        assert_eq!(r.decide(50.0, at(12)), None);
+        assert_eq!(r.decide(50.0, at(16)), Some(Response::Kill));
+
+        // Thaw only after staying under half the threshold
+        assert_eq!(r.decide(7.0, at(20)), None);
+        assert_eq!(r.decide(2.0, at(21)), None);
+        assert_eq!(r.decide(2.0, at(50)), None);
+        assert_eq!(r.decide(2.0, at(51)), Some(Response::Thaw));
+    }
+
+    #[test]
+    fn most_expendable_goes_first_then_the_biggest() {
+        let (build, batch, ide) = (target("build", 5), target("batch", 5), target("ide", 1));
+        let candidates = [(&ide, 8 << 30), (&build, 1 << 30), (&batch, 2 << 30)];
+        assert_eq!(victim(&candidates), Some(&batch));
+        assert_eq!(victim(&[]), None);
+
+        let mut cfg = Config::default();
+        for (name, expendable) in [("ide", Some(1)), ("build", Some(5)), ("editor", None)] {
+            let profile = common::Profile {
+                expendable,
+                ..Default::default()
+            };
+            cfg.profiles.insert(name.into(), profile);
+        }
+        let r = PressureResponder::new(&cfg);
+        assert_eq!(r.profile_names(), vec!["build", "ide"]);
+    }
+}
@@ -0,0 +1,163 @@
+//! setrlimit(2) limits. `rlm run` sets the ones cgroups have no equivalent
+//! of (open files, core dumps, processes per user) alongside its cgroup, and
+//! with `--fallback-rlimit` stands in for the cgroup with them where no
+//! controllers are writable. They are much weaker than a cgroup's: each
+//! process gets its own budget (copied to the processes it starts, not
+//! shared with them), memory is address space rather than RAM, and CPU is
+//! a total of seconds rather than a rate.
+
+use common::{Limit, MemoryLimit, Profile, Result};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// Resource limits a process sets on itself before it execs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rlimits {
+    /// `RLIMIT_AS`, in bytes
+    pub address_space: Option<u64>,
+    /// `RLIMIT_CPU`, in seconds: SIGXCPU when used up, SIGKILL a second later
+    pub cpu_seconds: Option<u64>,
+    /// `RLIMIT_NOFILE`
+    pub open_files: Option<u64>,
+    /// `RLIMIT_CORE`, in bytes; 0 means no core dumps
+    pub core_size: Option<u64>,
+    /// `RLIMIT_NPROC`: processes (and threads) the user has in all, not
+    /// just the ones this process starts
+    pub processes: Option<u64>,
+}
+
+impl Rlimits {
+    /// The ones `profile` sets.
+    pub fn from_profile(profile: &Profile) -> Result<Self> {
+        Ok(Self {
+            open_files: profile.max_open_files,
+            core_size: profile.max_core.as_deref().map(parse_core).transpose()?,
+            processes: profile.max_nproc,
+            ..Self::default()
+        })
+    }
+
+    /// These, with any `other` sets in their place.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            address_space: other.address_space.or(self.address_space),
+            cpu_seconds: other.cpu_seconds.or(self.cpu_seconds),
+            open_files: other.open_files.or(self.open_files),
+            core_size: other.core_size.or(self.core_size),
+            processes: other.processes.or(self.processes),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Have `command`'s process set these limits before it execs.
+    pub fn apply_to(self, command: &mut Command) {
+        // SAFETY: the hook only calls setrlimit, which is async-signal-safe.
+        unsafe {
+            command.pre_exec(move || self.set());
+        }
+    }
+
+    fn set(&self) -> io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_AS, self.address_space, 0),
+            (libc::RLIMIT_CPU, self.cpu_seconds, 1),
+            (libc::RLIMIT_NOFILE, self.open_files, 0),
+            (libc::RLIMIT_CORE, self.core_size, 0),
+            (libc::RLIMIT_NPROC, self.processes, 0),
+        ];
+        for (resource, value, grace) in limits {
+            let Some(value) = value else {
+                continue;
+            };
+            let limit = libc::rlimit {
+                rlim_cur: value,
+                rlim_max: value.saturating_add(grace),
+            };
+            // SAFETY: `limit` is a valid rlimit for the call's duration.
+            if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a core dump size: "0" for none, or a size like "512M".
+pub fn parse_core(s: &str) -> Result<u64> {
+    if s.trim() == "0" {
+        return Ok(0);
+    }
+    MemoryLimit::parse(s).map(|m| m.bytes())
+}
+
+/// The parts of `limit` setrlimit can't enforce, by their profile keys.
+/// Memory is the one it can (as address space).
+pub fn unenforced(limit: &Limit) -> Vec<&'static str> {
+    [
+        (limit.swap.is_some(), "swap"),
+        (limit.oom_group, "oom_group"),
+        (limit.cpu.is_some(), "cpu"),
+        (limit.cpu_weight.is_some(), "cpu_weight"),
+        (limit.idle, "idle"),
+        (limit.io.is_some(), "io"),
+        (limit.io_weight.is_some(), "io_weight"),
+        (limit.pids.is_some(), "pids"),
+        (limit.mems.is_some(), "mems"),
+    ]
+    .into_iter()
+    .filter_map(|(set, name)| set.then_some(name))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{CpuLimit, MemoryLimit};
+
+    #[test]
+    fn only_memory_carries_over_from_a_limit() {
+        let limit = Limit {
+            memory: Some(MemoryLimit::parse("1G").unwrap()),
+            cpu: Some(CpuLimit::parse("50%").unwrap()),
+            idle: true,
+            ..Limit::default()
+        };
+        assert_eq!(unenforced(&limit), ["cpu", "idle"]);
+        assert!(Rlimits::default().is_empty());
+    }
+
+    #[test]
+    fn flags_override_the_profile() {
+        let profile = Profile {
+            max_open_files: Some(1024),
+            max_core: Some("0".into()),
+            ..Profile::default()
+        };
+        let flags = Rlimits {
+            open_files: Some(64),
+            ..Rlimits::default()
+        };
+        let rlimits = Rlimits::from_profile(&profile).unwrap().or(flags);
+        assert_eq!(rlimits.open_files, Some(64));
+        assert_eq!(rlimits.core_size, Some(0));
+        assert_eq!(parse_core("1M").unwrap(), 1 << 20);
+        assert!(parse_core("lots").is_err());
+    }
+
+    #[test]
+    fn child_runs_under_the_limits() {
+        let rlimits = Rlimits {
+            open_files: Some(64),
+            ..Rlimits::default()
+        };
+        let mut command = Command::new("sh");
+        command.args(["-c", "ulimit -n"]);
+        rlimits.apply_to(&mut command);
+        let output = command.output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "64");
+    }
+}
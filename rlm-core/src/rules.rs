@@ -8,12 +8,21 @@
 
 use crate::process::{self, ProcessInfo};
 use crate::CgroupManager;
-use common::{AppRule, Config, Limit};
+use common::{AppRule, Config, Error, Limit, MatchConditions, Profile, Result};
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 /// A rule with its limits parsed once up front.
+#[derive(Debug, Clone, Default)]
 pub struct CompiledRule {
     pub name: String,
     pub match_exe: Vec<String>,
+    /// Conditions besides `match_exe`
+    pub matcher: Matcher,
+    /// Higher wins when several rules match a process
+    pub priority: i32,
     pub limit: Limit,
     /// Shared cgroup name for this rule (`app-<name>`).
     pub cgroup: String,
@@ -36,38 +45,205 @@ pub fn cgroup_name_for(rule_name: &str) -> String {
     format!("app-{}", rule_name.replace(['/', ' '], "_"))
 }
 
+/// The `profile-<name>` cgroup `rlm daemon` places a profile's processes in.
+pub fn profile_cgroup_name(profile_name: &str) -> String {
+    format!("profile-{}", profile_name.replace(['/', ' '], "_"))
+}
+
 impl CompiledRule {
-    fn compile(name: &str, rule: &AppRule) -> Option<Self> {
-        match rule.to_limit() {
-            Ok(limit) => Some(CompiledRule {
+    pub(crate) fn compile(name: &str, rule: &AppRule) -> Option<Self> {
+        let compiled = rule.to_limit().and_then(|limit| {
+            Ok(CompiledRule {
                 name: name.to_string(),
                 match_exe: rule.match_exe.clone(),
+                matcher: Matcher::compile(&rule.conditions)?,
+                priority: rule.conditions.priority,
                 limit,
                 cgroup: cgroup_name_for(name),
-            }),
+            })
+        });
+        match compiled {
+            Ok(compiled) => Some(compiled),
             Err(e) => {
-                tracing::warn!(rule = name, error = %e, "skipping rule with invalid limits");
+                tracing::warn!(rule = name, error = %e, "skipping invalid rule");
                 None
             }
         }
     }
 
-    fn matches(&self, proc: &ProcessInfo) -> bool {
-        self.match_exe.iter().any(|want| {
-            proc.name == *want
-                || proc
-                    .executable
-                    .as_ref()
-                    .and_then(|exe| exe.file_name())
-                    .and_then(|n| n.to_str())
-                    .map(|n| n == want)
-                    .unwrap_or(false)
+    /// A profile with `match_exe` or match conditions, placed in
+    /// `profile-<name>` by `rlm daemon`.
+    pub(crate) fn from_profile(name: &str, profile: &Profile) -> Option<Self> {
+        let compiled = profile.to_limit().and_then(|limit| {
+            Ok(CompiledRule {
+                name: name.to_string(),
+                match_exe: profile.match_exe.clone(),
+                matcher: Matcher::compile(&profile.conditions)?,
+                priority: profile.conditions.priority,
+                limit,
+                cgroup: profile_cgroup_name(name),
+            })
+        });
+        match compiled {
+            Ok(compiled) => Some(compiled),
+            Err(e) => {
+                tracing::warn!(profile = name, error = %e, "skipping invalid profile");
+                None
+            }
+        }
+    }
+
+    /// Whether `proc` has one of the `match_exe` names and meets every
+    /// condition. Without `match_exe`, the conditions alone decide; a rule
+    /// with neither matches nothing.
+    pub(crate) fn matches(&self, proc: &ProcessInfo) -> bool {
+        let by_exe = if self.match_exe.is_empty() {
+            !self.matcher.is_empty()
+        } else {
+            self.match_exe.iter().any(|want| exe_is(proc, want))
+        };
+        by_exe && self.matcher.matches(proc)
+    }
+}
+
+/// Order rules for matching: highest priority first, then by name.
+pub(crate) fn sort_by_priority(rules: &mut [CompiledRule]) {
+    rules.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Whether `proc` is called `want`, by name or executable basename.
+fn exe_is(proc: &ProcessInfo, want: &str) -> bool {
+    proc.name == want
+        || proc
+            .executable
+            .as_ref()
+            .and_then(|exe| exe.file_name())
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n == want)
+}
+
+/// [`MatchConditions`] ready to check. Each is read from `/proc` only when
+/// set, and only for processes `match_exe` let through.
+#[derive(Debug, Clone, Default)]
+pub struct Matcher {
+    cmdline: Option<Regex>,
+    user: Option<String>,
+    parent: Option<String>,
+    unit: Option<String>,
+    cwd: Option<PathBuf>,
+}
+
+impl Matcher {
+    fn compile(c: &MatchConditions) -> Result<Self> {
+        let cmdline = c
+            .match_cmdline
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| Error::Config(format!("invalid match_cmdline: {e}")))?;
+        Ok(Self {
+            cmdline,
+            user: c.match_user.clone(),
+            parent: c.match_parent.clone(),
+            unit: c.match_unit.clone(),
+            cwd: c.match_cwd.as_deref().map(expand_home),
         })
     }
+
+    fn is_empty(&self) -> bool {
+        self.cmdline.is_none()
+            && self.user.is_none()
+            && self.parent.is_none()
+            && self.unit.is_none()
+            && self.cwd.is_none()
+    }
+
+    fn matches(&self, proc: &ProcessInfo) -> bool {
+        let pid = proc.pid;
+        if let Some(parent) = &self.parent {
+            let info = proc.ppid.and_then(process::info);
+            if !info.is_some_and(|p| exe_is(&p, parent)) {
+                return false;
+            }
+        }
+        if let Some(user) = &self.user {
+            if !uid_of(pid).is_some_and(|uid| user_is(user, uid)) {
+                return false;
+            }
+        }
+        if let Some(dir) = &self.cwd {
+            let cwd = fs::read_link(format!("/proc/{pid}/cwd"));
+            if !cwd.is_ok_and(|cwd| cwd.starts_with(dir)) {
+                return false;
+            }
+        }
+        if let Some(unit) = &self.unit {
+            let cgroup = fs::read_to_string(format!("/proc/{pid}/cgroup")).unwrap_or_default();
+            let path = crate::cgroup::unified_cgroup(&cgroup).unwrap_or_default();
+            if !units(path).any(|u| unit_matches(unit, u)) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.cmdline {
+            if !process::cmdline(pid).is_some_and(|c| re.is_match(&c)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Real UID of `pid`.
+fn uid_of(pid: u32) -> Option<u32> {
+    fs::read_to_string(format!("/proc/{pid}/status"))
+        .ok()?
+        .lines()
+        .find_map(|l| l.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Whether `user` (a name or UID) is `uid`.
+fn user_is(user: &str, uid: u32) -> bool {
+    match user.parse::<u32>() {
+        Ok(n) => n == uid,
+        Err(_) => crate::audit::user_name(uid).is_some_and(|name| name == user),
+    }
+}
+
+/// systemd units along a cgroup path, outermost first.
+fn units(cgroup_path: &str) -> impl Iterator<Item = &str> {
+    Path::new(cgroup_path)
+        .iter()
+        .filter_map(|c| c.to_str())
+        .filter(|c| c.ends_with(".service") || c.ends_with(".scope") || c.ends_with(".slice"))
+}
+
+/// `pattern` is a unit name, or a prefix of one followed by `*`.
+fn unit_matches(pattern: &str, unit: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => unit.starts_with(prefix),
+        None => unit == pattern,
+    }
 }
 
 /// Pure planner: decide the actions for one rule given the current process
-/// snapshot and the set of PIDs already in this rule's cgroup.
+/// snapshot and the set of PIDs already in this rule's cgroup. Placed
+/// processes count as matches even if they no longer meet the rule's
+/// conditions (moving them changes their systemd unit, for one).
 ///
 /// - matches present, some not yet placed -> EnsureCgroup + AddPid(each new)
 /// - matches present, all already placed  -> EnsureCgroup only (idempotent)
@@ -79,7 +255,10 @@ pub fn plan(
     already_placed: &[u32],
     cgroup_exists: bool,
 ) -> Vec<RuleAction> {
-    let matches: Vec<&ProcessInfo> = procs.iter().filter(|p| rule.matches(p)).collect();
+    let matches: Vec<&ProcessInfo> = procs
+        .iter()
+        .filter(|p| already_placed.contains(&p.pid) || rule.matches(p))
+        .collect();
 
     if matches.is_empty() {
         return if cgroup_exists {
@@ -114,11 +293,12 @@ impl RulesEnforcer {
     /// Compile the rules from config. Rules with unparseable limits are skipped
     /// (logged once) rather than failing the whole enforcer.
     pub fn new(cfg: &Config) -> Self {
-        let rules = cfg
+        let mut rules: Vec<CompiledRule> = cfg
             .rules
             .iter()
             .filter_map(|(name, rule)| CompiledRule::compile(name, rule))
             .collect();
+        sort_by_priority(&mut rules);
         Self { rules }
     }
 
@@ -126,6 +306,17 @@ impl RulesEnforcer {
         self.rules.len()
     }
 
+    /// Set every existing rule cgroup's limits again, e.g. after the config
+    /// was reloaded ([`reconcile`](Self::reconcile) only sets them on
+    /// creation).
+    pub fn refresh(&self, mgr: &CgroupManager) {
+        for rule in self.rules.iter().filter(|r| mgr.cgroup_exists(&r.cgroup)) {
+            if let Err(e) = mgr.replace_limits(&rule.cgroup, &rule.limit) {
+                tracing::warn!(rule = %rule.name, error = %e, "rules: updating limits failed");
+            }
+        }
+    }
+
     /// Reconcile every rule once. Best-effort: a failure on one rule or PID is
     /// logged and never aborts the others. Returns the actions that were applied
     /// (useful for logging/tests).
@@ -140,13 +331,23 @@ impl RulesEnforcer {
         };
 
         let mut applied = Vec::new();
+        // Rules go highest priority first; a process one of them takes is no
+        // longer the others' to place.
+        let mut claimed = HashSet::new();
         for rule in &self.rules {
             // Which matching PIDs are already in this rule's cgroup?
             let placed = mgr.pids_in_cgroup(&rule.cgroup);
             let exists = !placed.is_empty() || mgr.cgroup_exists(&rule.cgroup);
+            let matching: Vec<ProcessInfo> = procs
+                .iter()
+                .filter(|p| !claimed.contains(&p.pid))
+                .filter(|p| placed.contains(&p.pid) || rule.matches(p))
+                .cloned()
+                .collect();
+            claimed.extend(matching.iter().map(|p| p.pid));
 
-            for action in plan(rule, &procs, &placed, exists) {
-                if let Err(e) = self.apply(mgr, rule, &action) {
+            for action in plan(rule, &matching, &placed, exists) {
+                if let Err(e) = apply(mgr, rule, &action) {
                     tracing::warn!(?action, error = %e, "rules: action failed");
                 } else {
                     applied.push(action);
@@ -155,25 +356,25 @@ impl RulesEnforcer {
         }
         applied
     }
+}
 
-    fn apply(
-        &self,
-        mgr: &CgroupManager,
-        rule: &CompiledRule,
-        action: &RuleAction,
-    ) -> common::Result<()> {
-        match action {
-            RuleAction::EnsureCgroup { .. } => {
-                // prepare_cgroup creates the cgroup (idempotent) and (re)sets limits.
-                mgr.prepare_cgroup(&rule.cgroup, &rule.limit)?;
-                Ok(())
-            }
-            RuleAction::AddPid { pid, .. } => {
-                let path = mgr.base_path().join(&rule.cgroup);
-                mgr.add_to_cgroup(&path, *pid)
-            }
-            RuleAction::TeardownEmpty { .. } => mgr.cleanup_cgroup(&rule.cgroup),
+/// Carry out one planned action against real cgroups.
+pub(crate) fn apply(
+    mgr: &CgroupManager,
+    rule: &CompiledRule,
+    action: &RuleAction,
+) -> common::Result<()> {
+    match action {
+        RuleAction::EnsureCgroup { .. } => {
+            // ensure_cgroup creates the cgroup (idempotent) and (re)sets limits.
+            mgr.ensure_cgroup(&rule.cgroup, &rule.limit)?;
+            Ok(())
+        }
+        RuleAction::AddPid { pid, .. } => {
+            let path = mgr.base_path().join(&rule.cgroup);
+            mgr.add_to_cgroup(&path, *pid)
         }
+        RuleAction::TeardownEmpty { .. } => mgr.cleanup_cgroup(&rule.cgroup),
     }
 }
 
@@ -186,8 +387,8 @@ mod tests {
         CompiledRule {
             name: name.to_string(),
             match_exe: exes.iter().map(|s| s.to_string()).collect(),
-            limit: Limit::default(),
             cgroup: cgroup_name_for(name),
+            ..Default::default()
         }
     }
 
@@ -215,6 +416,44 @@ mod tests {
         assert!(!r.matches(&proc(3, "code", Some("/usr/bin/code"))));
     }
 
+    #[test]
+    fn conditions_narrow_and_order_rules() {
+        let path =
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-code-4242.scope";
+        let found: Vec<&str> = units(path).collect();
+        assert_eq!(found[2], "user@1000.service");
+        assert!(units(path).any(|u| unit_matches("app-gnome-code-*", u)));
+        assert!(!units(path).any(|u| unit_matches("app-gnome-code", u)));
+        assert!(user_is("1000", 1000));
+        assert!(!user_is("1001", 1000));
+
+        // Neither match_exe nor conditions: matches nothing
+        assert!(!rule("none", &[]).matches(&proc(1, "firefox", None)));
+
+        let mut rules = vec![
+            rule("b", &["node"]),
+            rule("a", &["node"]),
+            rule("c", &["node"]),
+        ];
+        rules[2].priority = 10;
+        sort_by_priority(&mut rules);
+        let order: Vec<&str> = rules.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(order, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn plan_keeps_placed_processes_that_no_longer_match() {
+        let r = rule("firefox", &["firefox"]);
+        // Renamed itself after being placed; still the rule's
+        let actions = plan(&r, &[proc(10, "Web Content", None)], &[10], true);
+        assert_eq!(
+            actions,
+            vec![RuleAction::EnsureCgroup {
+                rule: "firefox".into()
+            }]
+        );
+    }
+
     #[test]
     fn plan_ensures_and_adds_unplaced_matches() {
         let r = rule("firefox", &["firefox"]);
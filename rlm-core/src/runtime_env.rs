@@ -0,0 +1,69 @@
+//! Memory limits as the managed runtimes' own settings, for `rlm run
+//! --export-env`. Go, Java, and Node size their heaps from the machine's
+//! memory, not the cgroup's, and so grow until the cgroup OOM-kills them;
+//! told the budget, they collect garbage before reaching it instead.
+
+/// Shares of the limit each runtime is given, in percent. Go's is a soft
+/// target its collector works towards; Java's and Node's are heap sizes,
+/// which leave the rest for stacks, code, and native memory.
+const GO_PERCENT: u64 = 90;
+const HEAP_PERCENT: u64 = 75;
+
+/// `GOMEMLIMIT`, `JAVA_TOOL_OPTIONS`, and `NODE_OPTIONS` for a memory limit
+/// of `bytes`. `inherited` looks up what the command would otherwise get:
+/// a `GOMEMLIMIT` of its own is left alone, and the options variables keep
+/// theirs after rlm's, so an explicit `-Xmx` or `--max-old-space-size` wins.
+pub fn for_memory(bytes: u64, inherited: impl Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+    let mib = |percent: u64| ((bytes >> 20) * percent / 100).max(1);
+    let with_inherited = |key: &str, ours: String| match inherited(key) {
+        Some(theirs) if !theirs.trim().is_empty() => format!("{ours} {theirs}"),
+        _ => ours,
+    };
+
+    let mut vars = Vec::new();
+    if inherited("GOMEMLIMIT").is_none() {
+        vars.push(("GOMEMLIMIT".to_string(), format!("{}MiB", mib(GO_PERCENT))));
+    }
+    let heap = mib(HEAP_PERCENT);
+    vars.push((
+        "JAVA_TOOL_OPTIONS".to_string(),
+        with_inherited("JAVA_TOOL_OPTIONS", format!("-Xmx{heap}m")),
+    ));
+    vars.push((
+        "NODE_OPTIONS".to_string(),
+        with_inherited("NODE_OPTIONS", format!("--max-old-space-size={heap}")),
+    ));
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_of_the_limit_with_inherited_options_last() {
+        let vars = for_memory(4 << 30, |_| None);
+        assert_eq!(
+            vars,
+            [
+                ("GOMEMLIMIT".to_string(), "3686MiB".to_string()),
+                ("JAVA_TOOL_OPTIONS".to_string(), "-Xmx3072m".to_string()),
+                (
+                    "NODE_OPTIONS".to_string(),
+                    "--max-old-space-size=3072".to_string()
+                ),
+            ]
+        );
+
+        let vars = for_memory(4 << 30, |key| match key {
+            "GOMEMLIMIT" => Some("1GiB".into()),
+            "NODE_OPTIONS" => Some("--max-old-space-size=512".into()),
+            _ => None,
+        });
+        assert_eq!(vars.len(), 2);
+        assert_eq!(
+            vars[1].1,
+            "--max-old-space-size=3072 --max-old-space-size=512"
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Lightweight containment for `rlm run`'s command, alongside its limits:
+//! namespaces of its own and no new privileges.
+//!
+//! In a cgroup namespace of its own, the command sees its cgroup as the root
+//! of the hierarchy: `/proc/self/cgroup` reads `0::/`, and the cgroup
+//! filesystem, mounted again in a mount namespace of its own, holds its
+//! cgroup's files. Container-aware runtimes (the JVM, Go, .NET) then find
+//! their limits there rather than reading the host's hierarchy. The same
+//! mount namespace gives it a private `/tmp` and read-only directories.
+//!
+//! Unsharing namespaces needs root. Anyone else gets a user namespace as
+//! well, in which they are still themselves: their UID and GID map to
+//! themselves and nothing else, so files of other users show as `nobody`.
+
+use common::{Error, Result};
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What to shut the command off from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sandbox {
+    /// Its cgroup as the root of the hierarchy
+    pub cgroup_ns: bool,
+    /// An empty `/tmp` of its own, gone when it exits
+    pub private_tmp: bool,
+    /// Directories it may read but not change
+    pub read_only: Vec<PathBuf>,
+    /// No gaining privileges through setuid programs or file capabilities
+    pub no_new_privs: bool,
+}
+
+/// A [`Sandbox`] made ready for the hook, which after fork may only make
+/// async-signal-safe calls and so can't allocate.
+struct Prepared {
+    /// Where to mount the cgroup filesystem again, for `cgroup_ns`
+    cgroup_mount: Option<CString>,
+    private_tmp: bool,
+    /// Each with the mount flags it has already, which a user namespace may
+    /// not drop
+    read_only: Vec<(CString, libc::c_ulong)>,
+    no_new_privs: bool,
+    /// uid_map and gid_map contents, when a user namespace is needed
+    maps: Option<(String, String)>,
+}
+
+impl Sandbox {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn mounts(&self) -> bool {
+        self.cgroup_ns || self.private_tmp || !self.read_only.is_empty()
+    }
+
+    /// Have `command`'s process enter it before it execs. Register it after
+    /// the cgroup placement hook, so the namespace is rooted at the command's
+    /// cgroup, and after anything that needs rlm's privileges.
+    pub fn apply_to(&self, command: &mut Command) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let cgroup_mount = self
+            .cgroup_ns
+            .then(|| c_path(crate::hierarchy::root()))
+            .transpose()?;
+        let read_only = self
+            .read_only
+            .iter()
+            .map(|dir| Ok((c_path(dir)?, mount_flags(dir)?)))
+            .collect::<Result<_>>()?;
+        // SAFETY: geteuid and getegid have no preconditions.
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        let maps = (self.mounts() && uid != 0)
+            .then(|| (format!("{uid} {uid} 1"), format!("{gid} {gid} 1")));
+        let prepared = Prepared {
+            cgroup_mount,
+            private_tmp: self.private_tmp,
+            read_only,
+            no_new_privs: self.no_new_privs,
+            maps,
+        };
+        // SAFETY: the hook only makes the prctl, unshare, mount, open, write,
+        // and close system calls, which are async-signal-safe.
+        unsafe {
+            command.pre_exec(move || prepared.enter());
+        }
+        Ok(())
+    }
+}
+
+fn c_path(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::InvalidArgs(format!("{} contains a NUL byte", path.display())))
+}
+
+/// The flags a read-only bind mount of `dir` must keep.
+fn mount_flags(dir: &Path) -> Result<libc::c_ulong> {
+    let path = c_path(dir)?;
+    // SAFETY: `stat` is written by statvfs before it is read.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(Error::InvalidArgs(format!(
+            "can't make {} read-only: {}",
+            dir.display(),
+            io::Error::last_os_error()
+        )));
+    }
+    let kept = [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ];
+    Ok(kept
+        .into_iter()
+        .filter(|(st, _)| stat.f_flag & st != 0)
+        .fold(0, |flags, (_, ms)| flags | ms))
+}
+
+impl Prepared {
+    fn enter(&self) -> io::Result<()> {
+        let check = |rc: libc::c_int| {
+            if rc == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        };
+        if self.no_new_privs {
+            // SAFETY: plain system call.
+            check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+        }
+        if self.cgroup_mount.is_none() && !self.private_tmp && self.read_only.is_empty() {
+            return Ok(());
+        }
+
+        let mut flags = libc::CLONE_NEWNS;
+        if self.cgroup_mount.is_some() {
+            flags |= libc::CLONE_NEWCGROUP;
+        }
+        if self.maps.is_some() {
+            flags |= libc::CLONE_NEWUSER;
+        }
+        // SAFETY: plain system call.
+        check(unsafe { libc::unshare(flags) })?;
+        if let Some((uid_map, gid_map)) = &self.maps {
+            // gid_map can't be written until setgroups is denied
+            write(c"/proc/self/setgroups", b"deny")?;
+            write(c"/proc/self/uid_map", uid_map.as_bytes())?;
+            write(c"/proc/self/gid_map", gid_map.as_bytes())?;
+        }
+
+        let mount =
+            |source: &CStr, target: &CStr, fstype: Option<&CStr>, flags, data: Option<&CStr>| {
+                // SAFETY: the strings are NUL-terminated and outlive the call.
+                check(unsafe {
+                    libc::mount(
+                        source.as_ptr(),
+                        target.as_ptr(),
+                        fstype.map_or(std::ptr::null(), CStr::as_ptr),
+                        flags,
+                        data.map_or(std::ptr::null(), |d| d.as_ptr().cast()),
+                    )
+                })
+            };
+        // Keep the mounts below out of the namespace we came from
+        mount(c"none", c"/", None, libc::MS_REC | libc::MS_PRIVATE, None)?;
+        if let Some(point) = &self.cgroup_mount {
+            let flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+            mount(c"cgroup2", point, Some(c"cgroup2"), flags, None)?;
+        }
+        if self.private_tmp {
+            let flags = libc::MS_NOSUID | libc::MS_NODEV;
+            mount(c"tmpfs", c"/tmp", Some(c"tmpfs"), flags, Some(c"mode=1777"))?;
+        }
+        for (dir, kept) in &self.read_only {
+            mount(dir, dir, None, libc::MS_BIND | libc::MS_REC, None)?;
+            let flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | kept;
+            mount(c"none", dir, None, flags, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write `data` to the file at `path` in one call.
+fn write(path: &CStr, data: &[u8]) -> io::Result<()> {
+    // SAFETY: `path` is NUL-terminated and `data` valid for its length; the
+    // descriptor is closed before returning.
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        let result = if written == data.len() as isize {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        };
+        libc::close(fd);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(sandbox: &Sandbox, script: &str) -> String {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        sandbox.apply_to(&mut command).unwrap();
+        let output = command.output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    #[test]
+    fn no_new_privs_needs_no_namespace() {
+        let sandbox = Sandbox {
+            no_new_privs: true,
+            ..Sandbox::default()
+        };
+        let status = run(&sandbox, "grep NoNewPrivs /proc/self/status");
+        assert_eq!(status.split_whitespace().last(), Some("1"));
+    }
+
+    /// Needs cgroup v2 and permission to unshare namespaces, which CI
+    /// containers usually lack.
+    #[test]
+    #[ignore = "requires cgroup v2 and permission to unshare namespaces; run manually"]
+    fn child_sees_its_cgroup_as_the_root() {
+        let sandbox = Sandbox {
+            cgroup_ns: true,
+            ..Sandbox::default()
+        };
+        let cgroups = run(&sandbox, "cat /proc/self/cgroup");
+        assert!(cgroups.lines().any(|l| l == "0::/"), "{cgroups}");
+    }
+
+    #[test]
+    #[ignore = "requires permission to unshare namespaces; run manually"]
+    fn private_tmp_starts_empty() {
+        let sandbox = Sandbox {
+            private_tmp: true,
+            ..Sandbox::default()
+        };
+        assert_eq!(run(&sandbox, "touch /tmp/x && ls -A /tmp"), "x\n");
+    }
+
+    #[test]
+    #[ignore = "requires permission to unshare namespaces; run manually"]
+    fn read_only_dirs_refuse_writes() {
+        let dir = std::env::temp_dir().join(format!("rlm-sandbox-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sandbox = Sandbox {
+            read_only: vec![dir.clone()],
+            ..Sandbox::default()
+        };
+        let script = format!("touch {}/x 2>/dev/null || echo refused", dir.display());
+        assert_eq!(run(&sandbox, &script), "refused\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+//! Scheduler hints that go with cgroup limits: niceness, I/O priority
+//! class, and the `SCHED_BATCH`/`SCHED_IDLE` policies. Unlike cgroup limits
+//! they are per thread, so on a running process every thread is changed;
+//! threads and processes started later inherit them.
+
+use common::{Error, Result};
+use std::fmt;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::str::FromStr;
+
+/// `ioprio_set` class, as ionice(1) names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    /// First access to the disk, whatever else waits; needs root
+    Realtime,
+    BestEffort,
+    /// Only when no one else uses the disk
+    Idle,
+}
+
+impl IoClass {
+    /// The `ioprio` value: class in the top bits, level 4 (ionice's default)
+    /// for the classes that have levels.
+    fn ioprio(self) -> libc::c_int {
+        const CLASS_SHIFT: libc::c_int = 13;
+        match self {
+            Self::Realtime => (1 << CLASS_SHIFT) | 4,
+            Self::BestEffort => (2 << CLASS_SHIFT) | 4,
+            Self::Idle => 3 << CLASS_SHIFT,
+        }
+    }
+}
+
+impl FromStr for IoClass {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "realtime" => Ok(Self::Realtime),
+            "best-effort" => Ok(Self::BestEffort),
+            "idle" => Ok(Self::Idle),
+            _ => Err(Error::InvalidArgs(format!(
+                "unknown I/O class '{s}' (expected realtime, best-effort, or idle)"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for IoClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Realtime => "realtime",
+            Self::BestEffort => "best-effort",
+            Self::Idle => "idle",
+        })
+    }
+}
+
+/// A non-default CPU scheduling policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// CPU-bound work that can wait: fewer preemptions, longer slices
+    Batch,
+    /// Lower than nice 19: runs only on otherwise idle CPU
+    Idle,
+}
+
+impl Policy {
+    fn raw(self) -> libc::c_int {
+        match self {
+            Self::Batch => libc::SCHED_BATCH,
+            Self::Idle => libc::SCHED_IDLE,
+        }
+    }
+}
+
+impl FromStr for Policy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "batch" => Ok(Self::Batch),
+            "idle" => Ok(Self::Idle),
+            _ => Err(Error::InvalidArgs(format!(
+                "unknown scheduling policy '{s}' (expected batch or idle)"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Batch => "batch",
+            Self::Idle => "idle",
+        })
+    }
+}
+
+/// Scheduler settings to apply; `None` leaves one as it is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hints {
+    /// -20 (most favored) to 19
+    pub nice: Option<i32>,
+    pub io_class: Option<IoClass>,
+    pub policy: Option<Policy>,
+}
+
+impl Hints {
+    pub fn new(
+        nice: Option<i32>,
+        io_class: Option<IoClass>,
+        policy: Option<Policy>,
+    ) -> Result<Self> {
+        if let Some(nice) = nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(Error::InvalidArgs(format!(
+                    "nice {nice} out of range (-20 to 19)"
+                )));
+            }
+        }
+        Ok(Self {
+            nice,
+            io_class,
+            policy,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply to every thread of the running process `pid`.
+    pub fn apply_to_pid(&self, pid: u32) -> Result<()> {
+        let tasks = std::fs::read_dir(format!("/proc/{pid}/task"))
+            .map_err(|_| Error::ProcessNotFound(pid))?;
+        for task in tasks.flatten() {
+            let Some(tid) = task.file_name().to_str().and_then(|t| t.parse().ok()) else {
+                continue;
+            };
+            match self.set(tid) {
+                Ok(()) => {}
+                // The thread exited in the meantime
+                Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {}
+                Err(e) => {
+                    return Err(Error::InvalidArgs(format!(
+                        "failed to set scheduling of pid {pid}: {e}"
+                    )))
+                }
+            }
+        }
+        tracing::info!(pid, hints = ?self, "set scheduler hints");
+        Ok(())
+    }
+
+    /// Have `command`'s process set these on itself before it execs.
+    pub fn apply_to(self, command: &mut Command) {
+        // SAFETY: the hook only makes the sched_setscheduler, setpriority,
+        // and ioprio_set system calls, which are async-signal-safe.
+        unsafe {
+            command.pre_exec(move || self.set(0));
+        }
+    }
+
+    /// Set on thread `tid`, or the calling one for 0.
+    fn set(&self, tid: libc::pid_t) -> io::Result<()> {
+        let check = |rc: libc::c_long| {
+            if rc < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        };
+        if let Some(policy) = self.policy {
+            let param = libc::sched_param { sched_priority: 0 };
+            // SAFETY: `param` is valid for the call's duration.
+            check(unsafe { libc::sched_setscheduler(tid, policy.raw(), &param) }.into())?;
+        }
+        if let Some(nice) = self.nice {
+            // SAFETY: plain system call.
+            check(
+                unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) }.into(),
+            )?;
+        }
+        if let Some(class) = self.io_class {
+            const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+            // SAFETY: plain system call; no pointers.
+            check(unsafe {
+                libc::syscall(
+                    libc::SYS_ioprio_set,
+                    IOPRIO_WHO_PROCESS,
+                    tid,
+                    class.ioprio(),
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_classes_and_policies() {
+        assert_eq!(
+            "best-effort".parse::<IoClass>().unwrap(),
+            IoClass::BestEffort
+        );
+        assert_eq!(IoClass::Idle.ioprio(), 3 << 13);
+        assert_eq!("batch".parse::<Policy>().unwrap(), Policy::Batch);
+        assert!("fifo".parse::<Policy>().is_err());
+        assert!(Hints::new(Some(20), None, None).is_err());
+        assert!(Hints::default().is_empty());
+    }
+
+    #[test]
+    fn child_starts_with_the_hints() {
+        let hints = Hints::new(Some(10), Some(IoClass::Idle), Some(Policy::Batch)).unwrap();
+        let mut command = Command::new("cat");
+        command.arg("/proc/self/stat");
+        hints.apply_to(&mut command);
+        let output = String::from_utf8(command.output().unwrap().stdout).unwrap();
+        // After the comm field: nice is field 19, policy field 41
+        let fields: Vec<&str> = output[output.rfind(')').unwrap() + 2..]
+            .split_whitespace()
+            .collect();
+        assert_eq!(fields[16], "10");
+        assert_eq!(fields[38], libc::SCHED_BATCH.to_string());
+    }
+}
@@ -0,0 +1,124 @@
+//! Time windows for profiles (`schedule: "22:00-07:00"`): `rlm daemon`
+//! applies a scheduled profile's limits only inside its window and lifts them
+//! outside it, so overnight jobs can be held back without slowing them down
+//! during the day.
+
+use common::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A daily window in local time. It may wrap past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    /// Minutes since midnight, inclusive
+    start: u16,
+    /// Minutes since midnight, exclusive
+    end: u16,
+}
+
+impl Schedule {
+    /// Whether `minute` (since local midnight) falls inside the window.
+    pub fn contains(&self, minute: u16) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+
+    /// Whether the window is open now.
+    pub fn is_active(&self) -> bool {
+        local_minute(SystemTime::now()).is_some_and(|m| self.contains(m))
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    /// `HH:MM-HH:MM`, e.g. `22:00-07:00`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::Config(format!("invalid schedule '{s}' (expected HH:MM-HH:MM)"));
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let start = parse_time(start.trim()).ok_or_else(invalid)?;
+        let end = parse_time(end.trim()).ok_or_else(invalid)?;
+        if start == end {
+            return Err(Error::Config(format!(
+                "schedule '{s}' is empty: start and end are the same"
+            )));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+/// `HH:MM` as minutes since midnight.
+fn parse_time(s: &str) -> Option<u16> {
+    let (h, m) = s.split_once(':')?;
+    let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60 && s.len() <= 5).then_some(h * 60 + m)
+}
+
+/// Minutes since local midnight at `time`.
+pub fn local_minute(time: SystemTime) -> Option<u16> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let tm = local_time(i64::try_from(secs).ok()?)?;
+    Some((tm.tm_hour * 60 + tm.tm_min) as u16)
+}
+
+/// The Unix timestamp `t` broken down in local time.
+pub fn local_time(t: i64) -> Option<libc::tm> {
+    let t = t as libc::time_t;
+    // SAFETY: `localtime_r` only writes to the `tm` we pass in.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return None;
+    }
+    Some(tm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_may_wrap_past_midnight() {
+        let night: Schedule = "22:00-07:00".parse().unwrap();
+        assert!(night.contains(23 * 60));
+        assert!(night.contains(0));
+        assert!(night.contains(6 * 60 + 59));
+        assert!(!night.contains(7 * 60));
+        assert!(!night.contains(12 * 60));
+
+        let day: Schedule = "9:30-17:00".parse().unwrap();
+        assert!(day.contains(9 * 60 + 30));
+        assert!(!day.contains(17 * 60));
+        assert!(!day.contains(23 * 60));
+        assert_eq!(day.to_string(), "09:30-17:00");
+    }
+
+    #[test]
+    fn rejects_malformed_schedules() {
+        for s in [
+            "22:00",
+            "24:00-07:00",
+            "22:60-07:00",
+            "10:00-10:00",
+            "ten-eleven",
+        ] {
+            assert!(s.parse::<Schedule>().is_err(), "{s}");
+        }
+    }
+}
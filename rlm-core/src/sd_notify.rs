@@ -0,0 +1,118 @@
+//! systemd's readiness protocol (`sd_notify`), so rlm's daemons can run as
+//! `Type=notify` services with `WatchdogSec=`. Messages are datagrams to the
+//! socket in `$NOTIFY_SOCKET`; outside systemd all of this does nothing.
+
+use std::os::unix::net::UnixDatagram;
+use std::time::{Duration, Instant};
+
+/// Send `state` (`KEY=value` lines) to the service manager. Returns whether
+/// it was sent.
+pub fn notify(state: &str) -> bool {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return false;
+    };
+    let path = path.to_string_lossy();
+    let sent = UnixDatagram::unbound().and_then(|socket| {
+        if let Some(name) = path.strip_prefix('@') {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)
+        } else {
+            socket.send_to(state.as_bytes(), &*path)
+        }
+    });
+    match sent {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::debug!(error = %e, "sd_notify failed");
+            false
+        }
+    }
+}
+
+/// Startup is done.
+pub fn ready() {
+    notify("READY=1");
+}
+
+/// A config reload starts; follow with [`ready`] when it is done.
+pub fn reloading() {
+    notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()));
+}
+
+/// Shutdown starts.
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+fn monotonic_usec() -> u64 {
+    // SAFETY: `clock_gettime` only writes to the timespec we pass in.
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+/// Pings systemd's watchdog at half its timeout, when the service has one.
+#[derive(Debug)]
+pub struct Watchdog {
+    interval: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl Watchdog {
+    /// Read the timeout from `$WATCHDOG_USEC`, as systemd sets it.
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok();
+        Self {
+            interval: ping_interval(
+                var("WATCHDOG_USEC").as_deref(),
+                var("WATCHDOG_PID").as_deref(),
+                std::process::id(),
+            ),
+            last: None,
+        }
+    }
+
+    /// Call from the main loop; pings when one is due.
+    pub fn tick(&mut self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        if self.last.is_none_or(|t| t.elapsed() >= interval) {
+            notify("WATCHDOG=1");
+            self.last = Some(Instant::now());
+        }
+    }
+}
+
+/// Half the watchdog timeout, if one is set for process `pid`.
+fn ping_interval(usec: Option<&str>, watchdog_pid: Option<&str>, pid: u32) -> Option<Duration> {
+    if let Some(p) = watchdog_pid {
+        if p.parse::<u32>().ok() != Some(pid) {
+            return None;
+        }
+    }
+    let usec: u64 = usec?.parse().ok().filter(|&u| u > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pings_at_half_the_timeout_of_our_own_watchdog() {
+        assert_eq!(
+            ping_interval(Some("30000000"), None, 7),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            ping_interval(Some("30000000"), Some("7"), 7),
+            Some(Duration::from_secs(15))
+        );
+        // Meant for another process, off, or not set at all
+        assert_eq!(ping_interval(Some("30000000"), Some("8"), 7), None);
+        assert_eq!(ping_interval(Some("0"), None, 7), None);
+        assert_eq!(ping_interval(None, None, 7), None);
+    }
+}
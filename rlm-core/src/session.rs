@@ -0,0 +1,238 @@
+//! Session headroom, behind `rlm protect-session`: `memory.low` on the
+//! cgroup that holds the compositor and shell, so the kernel reclaims from
+//! them last, and a `memory.high` ceiling on every rlm-managed cgroup so
+//! limited jobs can't take that memory even when they misbehave. Unlike
+//! `rlm protect on` nothing is moved; the ceilings are the ones memory
+//! reservations use, counting the headroom as reserved.
+//!
+//! Protection is bounded by every ancestor's `memory.low`. Ancestors rlm may
+//! write are raised as well, and everything changed is recorded in
+//! `$XDG_RUNTIME_DIR/rlm/session.json` to be put back when turned off.
+
+use crate::CgroupManager;
+use common::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Headroom the GUI toggle reserves: 1 GiB.
+pub const DEFAULT_HEADROOM: u64 = 1 << 30;
+
+/// Compositors and shells the session cgroup is found by, in order of
+/// preference.
+const SHELLS: &[&str] = &[
+    "gnome-shell",
+    "plasmashell",
+    "kwin_wayland",
+    "kwin_x11",
+    "sway",
+    "Hyprland",
+    "niri",
+    "river",
+    "labwc",
+    "wayfire",
+    "weston",
+    "cinnamon",
+    "xfwm4",
+    "marco",
+    "budgie-wm",
+    "Xorg",
+];
+
+/// Session protection in force.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionProtection {
+    /// Cgroup holding the compositor and shell
+    pub cgroup: PathBuf,
+    pub bytes: u64,
+    /// Cgroups whose `memory.low` was raised, with the value to restore
+    raised: Vec<(PathBuf, u64)>,
+    /// Nearest ancestor whose lower `memory.low` could not be raised; it caps
+    /// the protection until raised as root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounded_by: Option<PathBuf>,
+}
+
+/// Reserve `bytes` for the session and cap rlm's cgroups to leave it free.
+/// Enabling again replaces the amount.
+pub fn enable(manager: &CgroupManager, bytes: u64) -> Result<SessionProtection> {
+    let mem_total = crate::cgroup::mem_total_bytes()?;
+    let others = manager.reserved_total();
+    if others.saturating_add(bytes) >= mem_total {
+        return Err(Error::InvalidMemory(format!(
+            "cannot reserve {} for the session ({} already reserved, {} total RAM)",
+            common::format_bytes(bytes),
+            common::format_bytes(others),
+            common::format_bytes(mem_total)
+        )));
+    }
+    if current().is_some() {
+        disable(manager)?;
+    }
+
+    let cgroup = find_session_cgroup()?;
+    let mut protection = SessionProtection {
+        cgroup: cgroup.clone(),
+        bytes,
+        raised: Vec::new(),
+        bounded_by: None,
+    };
+    let old = read_low(&cgroup);
+    fs::write(cgroup.join("memory.low"), bytes.to_string()).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            Error::PermissionDenied {
+                path: cgroup.join("memory.low"),
+            }
+        } else {
+            Error::Cgroup(format!("failed to set memory.low: {e}"))
+        }
+    })?;
+    protection.raised.push((cgroup.clone(), old));
+
+    // Raise the ancestors too, up to the first one we may not write
+    let root = crate::hierarchy::root();
+    for dir in cgroup.ancestors().skip(1) {
+        if dir == root || !dir.starts_with(root) {
+            break;
+        }
+        let low = read_low(dir);
+        if low >= bytes {
+            continue;
+        }
+        if fs::write(dir.join("memory.low"), bytes.to_string()).is_err() {
+            protection.bounded_by = Some(dir.to_path_buf());
+            break;
+        }
+        protection.raised.push((dir.to_path_buf(), low));
+    }
+
+    save(Some(&protection))?;
+    manager.apply_reservation_ceilings()?;
+    tracing::info!(cgroup = %cgroup.display(), bytes, "session protected");
+    Ok(protection)
+}
+
+/// Put back every `memory.low` changed and lift the ceilings. Returns
+/// whether protection was on.
+pub fn disable(manager: &CgroupManager) -> Result<bool> {
+    let Some(protection) = current() else {
+        return Ok(false);
+    };
+    for (dir, low) in protection.raised.iter().rev() {
+        if let Err(e) = fs::write(dir.join("memory.low"), low.to_string()) {
+            tracing::warn!(cgroup = %dir.display(), error = %e, "could not restore memory.low");
+        }
+    }
+    save(None)?;
+    manager.apply_reservation_ceilings()?;
+    Ok(true)
+}
+
+/// The protection in force, if any. A record whose cgroup is gone (the
+/// session ended) counts as none.
+pub fn current() -> Option<SessionProtection> {
+    let json = fs::read_to_string(record_path()?).ok()?;
+    let protection: SessionProtection = serde_json::from_str(&json).ok()?;
+    protection.cgroup.is_dir().then_some(protection)
+}
+
+/// Bytes held for the session, counted as reserved by the ceilings.
+pub(crate) fn headroom() -> u64 {
+    current().map_or(0, |p| p.bytes)
+}
+
+fn record_path() -> Option<PathBuf> {
+    dirs::runtime_dir().map(|d| d.join("rlm").join("session.json"))
+}
+
+fn save(protection: Option<&SessionProtection>) -> Result<()> {
+    let path = record_path().ok_or_else(|| {
+        Error::Config(
+            "no runtime directory (XDG_RUNTIME_DIR) to record session protection in".into(),
+        )
+    })?;
+    let Some(protection) = protection else {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(protection)
+        .map_err(|e| Error::Config(format!("failed to serialize session protection: {e}")))?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+fn read_low(dir: &Path) -> u64 {
+    fs::read_to_string(dir.join("memory.low"))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// The cgroup of the running compositor or shell, preferring one of ours
+/// (any user's when run as root).
+fn find_session_cgroup() -> Result<PathBuf> {
+    let uid = fs::metadata("/proc/self").map(|m| m.uid())?;
+    let mut found: Vec<(usize, String, u32)> = Vec::new();
+    for entry in fs::read_dir("/proc")?.flatten() {
+        let owner = entry.metadata().map(|m| m.uid()).unwrap_or(u32::MAX);
+        if uid != 0 && owner != uid {
+            continue;
+        }
+        let path = entry.path();
+        let Ok(name) = fs::read_to_string(path.join("comm")) else {
+            continue;
+        };
+        let Some(rank) = SHELLS.iter().position(|s| *s == name.trim()) else {
+            continue;
+        };
+        if let Ok(cgroup) = fs::read_to_string(path.join("cgroup")) {
+            found.push((rank, cgroup, owner));
+        }
+    }
+    found.sort_by_key(|(rank, _, _)| *rank);
+    found
+        .iter()
+        .find_map(|(_, cgroup, owner)| {
+            let path = crate::cgroup::unified_cgroup(cgroup)?;
+            let session = session_cgroup_of(path, *owner);
+            crate::hierarchy::dir_of(session)
+        })
+        .ok_or_else(|| Error::Cgroup("no compositor or desktop shell found to protect".into()))
+}
+
+/// The cgroup to protect for a compositor at `path`: `session.slice` of the
+/// user's systemd instance when it runs there (GNOME, KDE), otherwise its
+/// own unit, so the user's other apps aren't protected along with it.
+fn session_cgroup_of(path: &str, uid: u32) -> &str {
+    let user_manager = format!("/user@{uid}.service/session.slice/");
+    match path.find(&user_manager) {
+        Some(at) => &path[..at + user_manager.len() - 1],
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protects_the_session_slice_or_the_compositors_own_unit() {
+        let gnome = "/user.slice/user-1000.slice/user@1000.service/session.slice/org.gnome.Shell@wayland.service";
+        assert_eq!(
+            session_cgroup_of(gnome, 1000),
+            "/user.slice/user-1000.slice/user@1000.service/session.slice"
+        );
+        // Started from a TTY login: its logind scope
+        let sway = "/user.slice/user-1000.slice/session-2.scope";
+        assert_eq!(session_cgroup_of(sway, 1000), sway);
+        // Launched like an app: only itself, not all of app.slice
+        let hypr = "/user.slice/user-1000.slice/user@1000.service/app.slice/hyprland.scope";
+        assert_eq!(session_cgroup_of(hypr, 1000), hypr);
+    }
+}
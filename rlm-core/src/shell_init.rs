@@ -0,0 +1,136 @@
+//! `rlm shell-init`: shell functions that run the commands profiles match
+//! (`match_exe`) through `rlm run --profile`, so typing `cargo build` in a
+//! terminal is limited without the daemon. The functions step aside inside
+//! `rlm shell`, whose budget already covers everything started from it, and
+//! `command cargo` skips them as it skips any function.
+
+use common::Profile;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// The shells `rlm shell-init` writes for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    /// A word the shell reads back as `s` exactly.
+    fn quote(self, s: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("'{}'", s.replace('\'', r"'\''")),
+            Shell::Fish => format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'")),
+        }
+    }
+}
+
+/// The commands to wrap, each with the profile to run it under. A command
+/// more than one profile matches goes to the first by name; entries that
+/// aren't plain command names (paths, patterns) are left out, as no
+/// function could stand for them.
+pub fn wrapped(profiles: &HashMap<String, Profile>) -> BTreeMap<&str, &str> {
+    let mut names: Vec<&String> = profiles.keys().collect();
+    names.sort();
+    let mut commands = BTreeMap::new();
+    for name in names {
+        for exe in &profiles[name].match_exe {
+            if is_command_name(exe) {
+                commands.entry(exe.as_str()).or_insert(name.as_str());
+            }
+        }
+    }
+    commands
+}
+
+fn is_command_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('-')
+        && s != "rlm"
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+'))
+}
+
+/// The script to `eval` (or `source` in fish) from the shell's rc file.
+pub fn script(shell: Shell, profiles: &HashMap<String, Profile>) -> String {
+    let mut out = match shell {
+        Shell::Bash | Shell::Zsh => format!(
+            "# rlm shell integration; in ~/.{}rc: eval \"$(rlm shell-init {})\"\n",
+            shell.name(),
+            shell.name()
+        ),
+        Shell::Fish => "# rlm shell integration; in ~/.config/fish/config.fish: \
+                        rlm shell-init fish | source\n"
+            .to_string(),
+    };
+    for (command, profile) in wrapped(profiles) {
+        let profile = shell.quote(profile);
+        // `writeln!` to a String can't fail
+        let _ = match shell {
+            Shell::Bash | Shell::Zsh => writeln!(
+                out,
+                "{command}() {{\n    \
+                 if [ -n \"${{RLM_SHELL-}}\" ]; then command {command} \"$@\"; \
+                 else command rlm run --profile {profile} -- {command} \"$@\"; fi\n}}"
+            ),
+            Shell::Fish => writeln!(
+                out,
+                "function {command} --wraps {command}\n    \
+                 if set -q RLM_SHELL\n        command {command} $argv\n    \
+                 else\n        command rlm run --profile {profile} -- {command} $argv\n    \
+                 end\nend"
+            ),
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles() -> HashMap<String, Profile> {
+        let profile = |exes: &[&str]| Profile {
+            match_exe: exes.iter().map(|e| e.to_string()).collect(),
+            ..Profile::default()
+        };
+        HashMap::from([
+            ("build".to_string(), profile(&["cargo", "make"])),
+            (
+                "Dev's".to_string(),
+                profile(&["cargo", "/opt/x/bin/y", "go"]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn first_profile_by_name_wins() {
+        let profiles = profiles();
+        let wrapped = wrapped(&profiles);
+        assert_eq!(
+            wrapped.into_iter().collect::<Vec<_>>(),
+            [("cargo", "Dev's"), ("go", "Dev's"), ("make", "build")]
+        );
+    }
+
+    #[test]
+    fn quotes_profile_names_for_each_shell() {
+        let profiles = profiles();
+        let bash = script(Shell::Bash, &profiles);
+        assert!(bash.contains("command rlm run --profile 'Dev'\\''s' -- cargo \"$@\""));
+        assert!(bash.contains("command make \"$@\""));
+        assert!(!bash.contains("/opt"));
+        let fish = script(Shell::Fish, &profiles);
+        assert!(fish.contains("function go --wraps go\n"));
+        assert!(fish.contains("--profile 'Dev\\'s' -- go $argv"));
+    }
+}
@@ -0,0 +1,143 @@
+//! Everything rlm manages as one serializable value, behind
+//! `rlm status --output json`. The field names are a stable interface for
+//! scripts and other frontends: add fields, don't rename them.
+
+use crate::events::EventKind;
+use crate::status::{self, ProcessStatus, Rates, Totals, Usage};
+use crate::CgroupManager;
+use common::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bumped when a field changes meaning or is removed.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// All managed cgroups at one point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagedState {
+    pub version: u32,
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub cgroups: Vec<ManagedCgroup>,
+    pub totals: Totals,
+}
+
+/// One managed cgroup.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagedCgroup {
+    /// Directory name under the rlm base cgroup
+    pub name: String,
+    /// Whether its limits are shared by several processes
+    pub shared: bool,
+    pub members: Vec<Member>,
+    pub limits: Limits,
+    pub usage: Usage,
+    /// Per-second CPU and I/O, when the state was sampled over an interval
+    pub rates: Option<Rates>,
+    /// `memory.events` counters by key ("oom_kill", "max", ...)
+    pub memory_events: BTreeMap<&'static str, u64>,
+}
+
+/// A process in a managed cgroup.
+#[derive(Debug, Clone, Serialize)]
+pub struct Member {
+    pub pid: u32,
+    /// `comm`, if the process is still there
+    pub name: Option<String>,
+}
+
+/// Limits in effect; `None` means unlimited (or the kernel default).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Limits {
+    pub memory_max: Option<u64>,
+    pub memory_low: Option<u64>,
+    pub swap_max: Option<u64>,
+    pub oom_group: bool,
+    /// Percent of one CPU
+    pub cpu_quota: Option<u32>,
+    pub cpu_weight: Option<u32>,
+    pub cpu_idle: bool,
+    pub io_read_bps: Option<u64>,
+    pub io_write_bps: Option<u64>,
+    pub io_weight: Option<u32>,
+    pub pids_max: Option<u64>,
+    pub mems: Option<String>,
+}
+
+impl CgroupManager {
+    /// The current state of every managed cgroup, without rates. For rates,
+    /// build one from `status::get_managed_processes_sampled`.
+    pub fn snapshot(&self) -> Result<ManagedState> {
+        Ok(ManagedState::from_processes(
+            &status::get_managed_processes(self)?,
+        ))
+    }
+}
+
+impl ManagedState {
+    /// Build from status rows, e.g. after filtering them.
+    pub fn from_processes(processes: &[ProcessStatus]) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            cgroups: processes.iter().map(ManagedCgroup::from).collect(),
+            totals: status::totals(processes),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+impl From<&ProcessStatus> for ManagedCgroup {
+    fn from(p: &ProcessStatus) -> Self {
+        let members = if p.members.is_empty() {
+            vec![Member {
+                pid: p.pid,
+                name: Some(p.name.clone()),
+            }]
+        } else {
+            p.members
+                .iter()
+                .map(|&pid| Member {
+                    pid,
+                    name: if pid == p.pid {
+                        Some(p.name.clone())
+                    } else {
+                        status::process_name(pid)
+                    },
+                })
+                .collect()
+        };
+        Self {
+            name: p.cgroup_name.clone(),
+            shared: p.is_shared,
+            members,
+            limits: Limits {
+                memory_max: p.memory_max,
+                memory_low: p.memory_low,
+                swap_max: p.swap_max,
+                oom_group: p.oom_group,
+                cpu_quota: p.cpu_quota,
+                cpu_weight: p.cpu_weight,
+                cpu_idle: p.cpu_idle,
+                io_read_bps: p.io_read_bps,
+                io_write_bps: p.io_write_bps,
+                io_weight: p.io_weight,
+                pids_max: p.pids_max,
+                mems: p.mems.clone(),
+            },
+            usage: p.usage,
+            rates: p.rates,
+            memory_events: EventKind::ALL
+                .iter()
+                .map(|&k| (k.key(), p.memory_events.get(k)))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Typed readers for a cgroup's usage files: `cpu.stat`, the `memory.*`
+//! counters with `memory.stat`, and `io.stat`. Each `from_cgroup` fails only
+//! when the controller's main file is missing (controller not enabled, or
+//! the cgroup is gone); fields the kernel does not have are 0 or `None`.
+
+use common::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// `cpu.stat`. Times are in microseconds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CpuStat {
+    pub usage_usec: u64,
+    pub user_usec: u64,
+    pub system_usec: u64,
+    /// Enforcement periods, throttled ones, and time spent throttled; only
+    /// present with the cpu controller enabled
+    pub nr_periods: u64,
+    pub nr_throttled: u64,
+    pub throttled_usec: u64,
+    /// Every key in file order, including ones without a field
+    pub entries: Vec<(String, u64)>,
+}
+
+impl CpuStat {
+    pub fn from_cgroup(path: &Path) -> Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path.join("cpu.stat"))?))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let entries = parse_flat_keyed(content);
+        let get = |key: &str| lookup(&entries, key).unwrap_or(0);
+        Self {
+            usage_usec: get("usage_usec"),
+            user_usec: get("user_usec"),
+            system_usec: get("system_usec"),
+            nr_periods: get("nr_periods"),
+            nr_throttled: get("nr_throttled"),
+            throttled_usec: get("throttled_usec"),
+            entries,
+        }
+    }
+}
+
+/// Memory use: `memory.current`, `memory.peak`, `memory.swap.current`, and
+/// the breakdown from `memory.stat`. Sizes are in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryStat {
+    pub current: u64,
+    /// Highest use since the cgroup was created (5.19+)
+    pub peak: Option<u64>,
+    /// Swap in use, without swap accounting `None`
+    pub swap_current: Option<u64>,
+    /// Anonymous memory (heap, stack)
+    pub anon: u64,
+    /// Page cache
+    pub file: u64,
+    /// Kernel allocations on the cgroup's behalf
+    pub kernel: u64,
+    pub shmem: u64,
+    pub sock: u64,
+    pub pgfault: u64,
+    pub pgmajfault: u64,
+    /// Every `memory.stat` key in file order
+    pub entries: Vec<(String, u64)>,
+}
+
+impl MemoryStat {
+    pub fn from_cgroup(path: &Path) -> Result<Self> {
+        let current = fs::read_to_string(path.join("memory.current"))?;
+        let number = |file: &str| {
+            fs::read_to_string(path.join(file))
+                .ok()
+                .and_then(|c| c.trim().parse().ok())
+        };
+        let stat = fs::read_to_string(path.join("memory.stat")).unwrap_or_default();
+        Ok(Self {
+            current: current.trim().parse().unwrap_or(0),
+            peak: number("memory.peak"),
+            swap_current: number("memory.swap.current"),
+            ..Self::parse(&stat)
+        })
+    }
+
+    /// Parse `memory.stat`; the fields read from other files are left empty.
+    pub fn parse(content: &str) -> Self {
+        let entries = parse_flat_keyed(content);
+        let get = |key: &str| lookup(&entries, key).unwrap_or(0);
+        Self {
+            anon: get("anon"),
+            file: get("file"),
+            kernel: get("kernel"),
+            shmem: get("shmem"),
+            sock: get("sock"),
+            pgfault: get("pgfault"),
+            pgmajfault: get("pgmajfault"),
+            entries,
+            ..Self::default()
+        }
+    }
+}
+
+/// `io.stat`: cumulative I/O per block device.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IoStat {
+    pub devices: Vec<DeviceIo>,
+}
+
+/// One `io.stat` line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DeviceIo {
+    /// "major:minor"
+    pub device: String,
+    /// Kernel name (e.g. "nvme0n1"); `from_cgroup` leaves it for the
+    /// caller to look up
+    pub name: Option<String>,
+    pub rbytes: u64,
+    pub wbytes: u64,
+    pub rios: u64,
+    pub wios: u64,
+}
+
+impl IoStat {
+    pub fn from_cgroup(path: &Path) -> Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path.join("io.stat"))?))
+    }
+
+    /// Parse lines of "major:minor rbytes=.. wbytes=.. rios=.. wios=.. ..".
+    pub fn parse(content: &str) -> Self {
+        let devices = content
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let mut dev = DeviceIo {
+                    device: parts.next()?.to_string(),
+                    ..DeviceIo::default()
+                };
+                for part in parts {
+                    let Some((key, value)) = part.split_once('=') else {
+                        continue;
+                    };
+                    let value = value.parse().unwrap_or(0);
+                    match key {
+                        "rbytes" => dev.rbytes = value,
+                        "wbytes" => dev.wbytes = value,
+                        "rios" => dev.rios = value,
+                        "wios" => dev.wios = value,
+                        _ => {}
+                    }
+                }
+                Some(dev)
+            })
+            .collect();
+        Self { devices }
+    }
+
+    /// Bytes read, summed over devices.
+    pub fn read_bytes(&self) -> u64 {
+        self.devices
+            .iter()
+            .fold(0, |sum, d| sum.saturating_add(d.rbytes))
+    }
+
+    /// Bytes written, summed over devices.
+    pub fn write_bytes(&self) -> u64 {
+        self.devices
+            .iter()
+            .fold(0, |sum, d| sum.saturating_add(d.wbytes))
+    }
+}
+
+/// Parse a flat-keyed file ("key value" per line).
+fn parse_flat_keyed(content: &str) -> Vec<(String, u64)> {
+    content
+        .lines()
+        .filter_map(|l| {
+            let (key, value) = l.split_once(' ')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn lookup(entries: &[(String, u64)], key: &str) -> Option<u64> {
+    entries.iter().find(|(k, _)| k == key).map(|(_, v)| *v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpu_stat() {
+        let stat = CpuStat::parse(
+            "usage_usec 1500\nuser_usec 1000\nsystem_usec 500\nnr_periods 10\nnr_throttled 3\n",
+        );
+        assert_eq!(stat.usage_usec, 1500);
+        assert_eq!(stat.system_usec, 500);
+        assert_eq!(stat.nr_throttled, 3);
+        assert_eq!(stat.throttled_usec, 0);
+        assert_eq!(stat.entries[4], ("nr_throttled".to_string(), 3));
+    }
+
+    #[test]
+    fn parses_memory_stat() {
+        let stat = MemoryStat::parse("anon 4096\nfile 8192\nkernel 100\npgmajfault 2\n");
+        assert_eq!((stat.anon, stat.file, stat.kernel), (4096, 8192, 100));
+        assert_eq!(stat.pgmajfault, 2);
+        assert_eq!(stat.peak, None);
+        assert_eq!(stat.entries.len(), 4);
+    }
+
+    #[test]
+    fn parses_io_stat_per_device() {
+        let io = IoStat::parse(
+            "259:0 rbytes=4096 wbytes=8192 rios=1 wios=2 dbytes=0 dios=0\n\
+             8:16 rbytes=10 wbytes=0 rios=1 wios=0\n",
+        );
+        assert_eq!(io.devices.len(), 2);
+        assert_eq!(io.devices[0].device, "259:0");
+        assert_eq!((io.devices[0].rbytes, io.devices[0].wbytes), (4096, 8192));
+        assert_eq!((io.devices[0].rios, io.devices[0].wios), (1, 2));
+        assert_eq!(io.devices[1].device, "8:16");
+        assert_eq!((io.read_bytes(), io.write_bytes()), (4106, 8192));
+        assert_eq!(IoStat::parse("").read_bytes(), 0);
+    }
+}
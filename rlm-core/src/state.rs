@@ -0,0 +1,368 @@
+//! Runtime record of the cgroups rlm creates, for crash recovery.
+//!
+//! Every cgroup [`CgroupManager`] creates is recorded in
+//! `$XDG_RUNTIME_DIR/rlm/state.json` with the PID of the process that created
+//! it and its purpose; removing the cgroup drops the record. If an `rlm run`
+//! or GUI session is killed before it cleans up, its cgroups outlive it. [`gc`]
+//! (run at startup and by `rlm gc`) removes the empty ones and re-adopts the
+//! ones whose processes are still running: their limits stay in force and
+//! the cgroup is removed once they exit.
+//!
+//! Tracking is best effort. Without a runtime directory nothing is recorded,
+//! and a failure to update the file never fails the cgroup operation.
+
+use crate::CgroupManager;
+use common::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Cgroups younger than this are never collected: their creator may not
+/// have moved a process in yet.
+const GRACE: Duration = Duration::from_secs(2);
+
+/// One created cgroup.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub cgroup: String,
+    /// What it was created for (`run`, `gtk`, `limit`, ...), from its name.
+    pub purpose: String,
+    pub owner_pid: u32,
+    /// The owner's start time (`/proc/<pid>/stat` field 22), so a reused PID
+    /// isn't mistaken for the owner.
+    pub owner_start: u64,
+    /// Unix time of creation.
+    pub created: u64,
+    /// Set by [`gc`] when the owner died while the cgroup still had processes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub adopted: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    #[serde(default)]
+    cgroups: Vec<Entry>,
+}
+
+/// What [`gc`] did (or, for a dry run, would do).
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Empty cgroups left behind, now removed.
+    pub removed: Vec<String>,
+    /// Orphaned cgroups whose processes are still running, now adopted.
+    pub adopted: Vec<String>,
+    /// Records of cgroups that no longer exist, now dropped.
+    pub forgotten: usize,
+}
+
+impl GcReport {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.adopted.is_empty() && self.forgotten == 0
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Verdict {
+    Keep,
+    Remove,
+    Adopt,
+}
+
+/// Purpose of a cgroup, from its name prefix.
+fn purpose_of(cgroup: &str) -> &'static str {
+    match cgroup.split('-').next().unwrap_or_default() {
+        "pid" | "app" | "multi" => "limit",
+        "run" => "run",
+        "gtk" => "gtk",
+        "guard" => "guard",
+        "reserve" => "reserve",
+        "shell" => "shell",
+        "sup" => "supervise",
+        "protect" => "protect",
+        "unlimit" => "unlimit",
+        _ => "other",
+    }
+}
+
+/// Whether a cgroup lives only as long as the process that created it.
+/// The rest (limits, reservations, supervision) are meant to outlive the
+/// CLI invocation and only go away once empty.
+fn is_ephemeral(purpose: &str) -> bool {
+    matches!(purpose, "run" | "gtk")
+}
+
+/// Decide what to do with one cgroup. `entry` is its record, if any.
+fn verdict(entry: Option<&Entry>, owner_alive: bool, has_procs: bool, recent: bool) -> Verdict {
+    if recent {
+        return Verdict::Keep;
+    }
+    let ephemeral = entry.is_some_and(|e| is_ephemeral(&e.purpose) && !e.adopted);
+    if ephemeral && owner_alive {
+        return Verdict::Keep;
+    }
+    match (has_procs, ephemeral) {
+        (false, _) => Verdict::Remove,
+        (true, true) => Verdict::Adopt,
+        (true, false) => Verdict::Keep,
+    }
+}
+
+fn state_dir() -> Option<PathBuf> {
+    dirs::runtime_dir().map(|d| d.join("rlm"))
+}
+
+/// Apply `f` to the state file under an exclusive lock, so concurrent rlm
+/// processes don't lose each other's records.
+fn update(f: impl FnOnce(&mut State)) -> Result<()> {
+    let Some(dir) = state_dir() else {
+        return Ok(());
+    };
+    fs::create_dir_all(&dir)?;
+    let _lock = Lock::acquire(&dir.join("state.lock"))?;
+
+    let path = dir.join("state.json");
+    let mut state = load_from(&path);
+    f(&mut state);
+
+    let json = serde_json::to_string_pretty(&state)
+        .map_err(|e| common::Error::Config(format!("failed to serialize state: {e}")))?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// A missing or corrupt file is an empty state: the records only help
+/// recovery, and cgroups without one are still collected once empty.
+fn load_from(path: &Path) -> State {
+    fs::read_to_string(path)
+        .map(|s| parse(&s))
+        .unwrap_or_default()
+}
+
+fn parse(json: &str) -> State {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+/// Record a cgroup this process just created.
+pub(crate) fn record(cgroup: &str) {
+    let pid = std::process::id();
+    let entry = Entry {
+        cgroup: cgroup.to_string(),
+        purpose: purpose_of(cgroup).to_string(),
+        owner_pid: pid,
+        owner_start: process_start(pid).unwrap_or(0),
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        adopted: false,
+    };
+    let result = update(|state| {
+        state.cgroups.retain(|e| e.cgroup != entry.cgroup);
+        state.cgroups.push(entry);
+    });
+    if let Err(e) = result {
+        tracing::debug!(cgroup, error = %e, "could not record cgroup in state file");
+    }
+}
+
+/// Drop the record of a removed cgroup.
+pub(crate) fn forget(cgroup: &str) {
+    if let Err(e) = update(|state| state.cgroups.retain(|e| e.cgroup != cgroup)) {
+        tracing::debug!(cgroup, error = %e, "could not update state file");
+    }
+}
+
+/// Tracked cgroups, in creation order.
+pub fn entries() -> Vec<Entry> {
+    state_dir()
+        .map(|d| load_from(&d.join("state.json")).cgroups)
+        .unwrap_or_default()
+}
+
+/// Find cgroups left behind by rlm processes that died, and remove or
+/// re-adopt them. With `dry_run`, only report what would be done.
+pub fn gc(manager: &CgroupManager, dry_run: bool) -> Result<GcReport> {
+    let mut report = GcReport::default();
+    let base = manager.base_path();
+    let tracked = entries();
+
+    report.forgotten = tracked
+        .iter()
+        .filter(|e| !base.join(&e.cgroup).is_dir())
+        .count();
+
+    let Ok(dirs) = fs::read_dir(base) else {
+        if !dry_run && report.forgotten > 0 {
+            update(|state| state.cgroups.clear())?;
+        }
+        return Ok(report);
+    };
+
+    for dir in dirs.flatten() {
+        let path = dir.path();
+        let Some(name) = dir.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        // The protect toggle is on as long as its cgroup exists, empty or not
+        if !path.is_dir() || purpose_of(&name) == "protect" {
+            continue;
+        }
+        let entry = tracked.iter().find(|e| e.cgroup == name);
+        let owner_alive = entry.is_some_and(|e| process_start(e.owner_pid) == Some(e.owner_start));
+        let has_procs = fs::read_to_string(path.join("cgroup.procs"))
+            .map(|c| c.lines().any(|l| !l.trim().is_empty()))
+            .unwrap_or(false);
+        let recent = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age < GRACE);
+
+        match verdict(entry, owner_alive, has_procs, recent) {
+            Verdict::Keep => {}
+            Verdict::Remove => {
+                if !dry_run {
+                    if let Err(e) = manager.cleanup_cgroup(&name) {
+                        tracing::warn!(cgroup = %name, error = %e, "could not remove leftover cgroup");
+                        continue;
+                    }
+                }
+                report.removed.push(name);
+            }
+            Verdict::Adopt => report.adopted.push(name),
+        }
+    }
+
+    if !dry_run && (report.forgotten > 0 || !report.adopted.is_empty()) {
+        update(|state| {
+            state.cgroups.retain(|e| base.join(&e.cgroup).is_dir());
+            for e in &mut state.cgroups {
+                if report.adopted.contains(&e.cgroup) {
+                    e.adopted = true;
+                }
+            }
+        })?;
+    }
+    for name in &report.adopted {
+        tracing::info!(cgroup = %name, "adopted cgroup left by an exited rlm session");
+    }
+    Ok(report)
+}
+
+/// Start time of a process in clock ticks since boot, or `None` if it is gone.
+fn process_start(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    parse_start_time(&stat)
+}
+
+/// Field 22 of `/proc/<pid>/stat`. The comm field (2) may contain spaces and
+/// parentheses, so count from the last `)`.
+fn parse_start_time(stat: &str) -> Option<u64> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Exclusive `flock` on the lock file, released on drop.
+struct Lock(File);
+
+impl Lock {
+    fn acquire(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        // SAFETY: the descriptor is valid for the lifetime of `file`.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self(file))
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // SAFETY: as above; closing the file would also release the lock.
+        unsafe {
+            libc::flock(self.0.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(cgroup: &str) -> Entry {
+        Entry {
+            cgroup: cgroup.into(),
+            purpose: purpose_of(cgroup).into(),
+            owner_pid: 4242,
+            owner_start: 100,
+            created: 0,
+            adopted: false,
+        }
+    }
+
+    #[test]
+    fn purposes_follow_name_prefix() {
+        assert_eq!(purpose_of("run-4242-17"), "run");
+        assert_eq!(purpose_of("gtk-4242-1"), "gtk");
+        assert_eq!(purpose_of("pid-4242"), "limit");
+        assert_eq!(purpose_of("reserve-db"), "reserve");
+        assert_eq!(purpose_of("protect"), "protect");
+    }
+
+    #[test]
+    fn crashed_run_is_removed_or_adopted() {
+        let run = entry("run-4242-17");
+        assert_eq!(verdict(Some(&run), true, true, false), Verdict::Keep);
+        assert_eq!(verdict(Some(&run), false, false, false), Verdict::Remove);
+        assert_eq!(verdict(Some(&run), false, true, false), Verdict::Adopt);
+
+        let adopted = Entry {
+            adopted: true,
+            ..run
+        };
+        assert_eq!(verdict(Some(&adopted), false, true, false), Verdict::Keep);
+        assert_eq!(
+            verdict(Some(&adopted), false, false, false),
+            Verdict::Remove
+        );
+    }
+
+    #[test]
+    fn persistent_limits_outlive_their_creator() {
+        let limit = entry("pid-4242");
+        assert_eq!(verdict(Some(&limit), false, true, false), Verdict::Keep);
+        assert_eq!(verdict(Some(&limit), false, false, false), Verdict::Remove);
+        // Untracked (created before tracking existed, or the record was lost)
+        assert_eq!(verdict(None, false, true, false), Verdict::Keep);
+        assert_eq!(verdict(None, false, false, false), Verdict::Remove);
+        // Freshly created: its process may not be in yet
+        assert_eq!(verdict(None, false, false, true), Verdict::Keep);
+    }
+
+    #[test]
+    fn parses_start_time_after_comm() {
+        let stat = "4242 (my (odd) app) S 1 4242 4242 0 -1 4194560 100 0 0 0 \
+                    5 2 0 0 20 0 1 0 987654 1000000 100";
+        assert_eq!(parse_start_time(stat), Some(987654));
+        assert_eq!(parse_start_time("garbage"), None);
+    }
+
+    #[test]
+    fn state_round_trips_and_tolerates_corruption() {
+        let state = State {
+            cgroups: vec![entry("run-1-2")],
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(!json.contains("adopted"));
+        assert_eq!(parse(&json).cgroups, state.cgroups);
+        assert!(parse("{ not json").cgroups.is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+//! One cgroup in depth, behind `rlm stats`: its limits (and the tightest
+//! memory limit inherited from ancestors), usage counters, `cpu.stat`,
+//! `io.stat` per device, and PSI. Also the [`RunSummary`] `rlm run
+//! --summary` prints once its command exits.
+
+use crate::pressure::{self, Pressure};
+use crate::stat::{CpuStat, IoStat, MemoryStat};
+use crate::CgroupManager;
+use common::{Error, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Limit files shown, in display order. Missing ones (controller not
+/// enabled, older kernel) are skipped.
+const LIMIT_FILES: &[&str] = &[
+    "memory.max",
+    "memory.high",
+    "memory.low",
+    "memory.min",
+    "memory.swap.max",
+    "memory.oom.group",
+    "cpu.max",
+    "cpu.weight",
+    "cpu.idle",
+    "io.max",
+    "io.weight",
+    "pids.max",
+    "cpuset.cpus",
+    "cpuset.mems",
+];
+
+/// Everything `rlm stats` shows about one cgroup.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub path: PathBuf,
+    pub procs: Vec<u32>,
+    /// (file, contents) of each limit file present; multi-line files
+    /// (`io.max`) keep their lines
+    pub limits: Vec<(&'static str, String)>,
+    /// Tightest `memory.max` of this cgroup and its ancestors, and where it
+    /// is set
+    pub effective_memory_max: Option<(u64, PathBuf)>,
+    pub memory: Option<MemoryStat>,
+    pub pids_current: Option<u64>,
+    pub cpu: Option<CpuStat>,
+    /// Per device, with kernel names looked up
+    pub io: IoStat,
+    pub pressure: Pressure,
+}
+
+/// Resolve a `rlm stats` target: a PID (its rlm cgroup if managed, else
+/// its own) or the name of a managed cgroup.
+pub fn resolve(manager: &CgroupManager, target: &str) -> Result<PathBuf> {
+    match target.parse::<u32>() {
+        Ok(pid) => manager.cgroup_path_for_pid(pid),
+        Err(_) => manager.managed_cgroup_path(target),
+    }
+}
+
+/// Read everything about the cgroup at `path`.
+pub fn read(path: &Path) -> Result<Stats> {
+    if !path.join("cgroup.procs").exists() {
+        return Err(Error::Cgroup(format!("{} is not a cgroup", path.display())));
+    }
+    let read = |file: &str| fs::read_to_string(path.join(file)).ok();
+    let number = |file: &str| read(file).and_then(|c| c.trim().parse().ok());
+
+    let procs = read("cgroup.procs")
+        .map(|c| c.lines().filter_map(|l| l.trim().parse().ok()).collect())
+        .unwrap_or_default();
+    let limits = LIMIT_FILES
+        .iter()
+        .filter_map(|&f| {
+            let value = read(f)?.trim_end().to_string();
+            Some((f, if value.is_empty() { "-".into() } else { value }))
+        })
+        .collect();
+    let mut io = IoStat::from_cgroup(path).unwrap_or_default();
+    for dev in &mut io.devices {
+        dev.name = device_name(&dev.device);
+    }
+    let target = path.file_name().map(|n| n.to_string_lossy().into_owned());
+
+    Ok(Stats {
+        path: path.to_path_buf(),
+        procs,
+        limits,
+        effective_memory_max: effective_memory_max(path),
+        memory: MemoryStat::from_cgroup(path).ok(),
+        pids_current: number("pids.current"),
+        cpu: CpuStat::from_cgroup(path).ok(),
+        io,
+        pressure: pressure::of_cgroup(path, target.as_deref().unwrap_or("/")),
+    })
+}
+
+/// The lowest `memory.max` from `path` up to the cgroup root: a child can
+/// never use more than any ancestor allows.
+fn effective_memory_max(path: &Path) -> Option<(u64, PathBuf)> {
+    path.ancestors()
+        .take_while(|p| p.join("cgroup.procs").exists())
+        .filter_map(|p| {
+            let max = fs::read_to_string(p.join("memory.max")).ok()?;
+            Some((max.trim().parse().ok()?, p.to_path_buf()))
+        })
+        .min_by_key(|(max, _)| *max)
+}
+
+/// What a command used over its run, from its cgroup's counters. Times are
+/// in seconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub wall_secs: f64,
+    pub user_secs: f64,
+    pub system_secs: f64,
+    /// Time the cpu limit held it back
+    pub throttled_secs: f64,
+    /// `memory.peak` (Linux 5.19+)
+    pub peak_memory: Option<u64>,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    /// Times it went over `memory.high`
+    pub memory_high_events: u64,
+    pub oom_kills: u64,
+}
+
+impl RunSummary {
+    /// Read the cgroup at `path`, which must still exist, for a command that
+    /// ran for `wall`.
+    pub fn read(path: &Path, wall: Duration) -> Self {
+        let secs = |usec: u64| usec as f64 / 1e6;
+        let cpu = CpuStat::from_cgroup(path).unwrap_or_default();
+        let io = IoStat::from_cgroup(path).unwrap_or_default();
+        let events = fs::read_to_string(path.join("memory.events"))
+            .map(|c| crate::supervise::parse_memory_events(&c))
+            .unwrap_or_default();
+        Self {
+            wall_secs: wall.as_secs_f64(),
+            user_secs: secs(cpu.user_usec),
+            system_secs: secs(cpu.system_usec),
+            throttled_secs: secs(cpu.throttled_usec),
+            peak_memory: MemoryStat::from_cgroup(path).ok().and_then(|m| m.peak),
+            read_bytes: io.read_bytes(),
+            write_bytes: io.write_bytes(),
+            memory_high_events: events.high,
+            oom_kills: events.oom_kill,
+        }
+    }
+}
+
+/// Kernel name of a block device from its "major:minor".
+fn device_name(device: &str) -> Option<String> {
+    let link = fs::read_link(format!("/sys/dev/block/{device}")).ok()?;
+    Some(link.file_name()?.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_summary_reads_counters() {
+        let dir = std::env::temp_dir().join(format!("rlm-summary-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |file: &str, content: &str| fs::write(dir.join(file), content).unwrap();
+        write(
+            "cpu.stat",
+            "usage_usec 3500000\nuser_usec 3000000\nsystem_usec 500000\nthrottled_usec 250000\n",
+        );
+        write("memory.current", "4096\n");
+        write("memory.peak", "1048576\n");
+        write("io.stat", "8:0 rbytes=100 wbytes=200 rios=1 wios=2\n");
+        write("memory.events", "low 0\nhigh 3\nmax 0\noom 1\noom_kill 1\n");
+
+        let summary = RunSummary::read(&dir, Duration::from_secs(5));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                wall_secs: 5.0,
+                user_secs: 3.0,
+                system_secs: 0.5,
+                throttled_secs: 0.25,
+                peak_memory: Some(1 << 20),
+                read_bytes: 100,
+                write_bytes: 200,
+                memory_high_events: 3,
+                oom_kills: 1,
+            }
+        );
+    }
+}
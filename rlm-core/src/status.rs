@@ -1,7 +1,13 @@
+use crate::backend::{CgroupBackend, FsBackend};
+use crate::events::{self, Counters};
+use crate::stat::{CpuStat, IoStat};
 use crate::CgroupManager;
 use common::Result;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct ProcessStatus {
@@ -9,31 +15,185 @@ pub struct ProcessStatus {
     pub name: String,
     pub cgroup_name: String,
     pub memory_max: Option<u64>,
+    /// Highest memory use since the cgroup was created (`memory.peak`, 5.19+)
+    pub memory_peak: Option<u64>,
+    /// Reserved (protected) memory, for `reserve-*` cgroups
+    pub memory_low: Option<u64>,
+    /// `memory.swap.max`, if not unlimited (0 = swap disabled)
+    pub swap_max: Option<u64>,
+    /// `memory.oom.group`: an OOM kill takes the whole cgroup
+    pub oom_group: bool,
+    /// `memory.events` counters (OOM kills, `memory.high`/`max` breaches)
+    pub memory_events: Counters,
     pub cpu_quota: Option<u32>,
+    /// `cpu.weight`, if changed from the default of 100
+    pub cpu_weight: Option<u32>,
+    /// `cpu.idle` scheduling (runs only when the CPU is otherwise idle)
+    pub cpu_idle: bool,
     pub io_read_bps: Option<u64>,
     pub io_write_bps: Option<u64>,
+    /// `io.weight`, if changed from the default of 100
+    pub io_weight: Option<u32>,
+    /// Maximum number of processes and threads (`pids.max`)
+    pub pids_max: Option<u64>,
+    /// NUMA memory nodes (`cpuset.mems`), if restricted
+    pub mems: Option<String>,
     pub is_shared: bool,
     pub process_count: Option<usize>,
+    /// Every process in the cgroup (`cgroup.procs`), `pid` first
+    pub members: Vec<u32>,
+    /// Usage counters read alongside the limits
+    pub usage: Usage,
+    /// Usage rates, once a `UsageSampler` has seen this cgroup twice
+    pub rates: Option<Rates>,
+}
+
+/// Current usage of a cgroup. CPU and I/O are cumulative counters; see
+/// `Rates` for per-second values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    /// `memory.current`
+    pub memory_current: Option<u64>,
+    /// `usage_usec` from `cpu.stat`
+    pub cpu_usage_usec: Option<u64>,
+    /// `rbytes` from `io.stat`, summed over devices
+    pub io_read_bytes: u64,
+    /// `wbytes` from `io.stat`, summed over devices
+    pub io_write_bytes: u64,
+}
+
+impl Usage {
+    /// Read the usage of the cgroup at `cgroup_path`.
+    pub fn read(cgroup_path: &Path) -> Self {
+        Self::read_from(&FsBackend, cgroup_path)
+    }
+
+    /// [`read`](Self::read) from `backend`.
+    pub fn read_from(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Self {
+        let read = |file: &str| backend.read(&cgroup_path.join(file));
+        let io = read("io.stat")
+            .map(|c| IoStat::parse(&c))
+            .unwrap_or_default();
+        Self {
+            memory_current: read("memory.current")
+                .ok()
+                .and_then(|c| c.trim().parse().ok()),
+            cpu_usage_usec: read("cpu.stat").ok().map(|c| CpuStat::parse(&c).usage_usec),
+            io_read_bytes: io.read_bytes(),
+            io_write_bytes: io.write_bytes(),
+        }
+    }
+
+    /// Rates between an `earlier` reading and this one, `elapsed` apart.
+    pub fn rates_since(&self, earlier: &Usage, elapsed: Duration) -> Rates {
+        let secs = elapsed.as_secs_f64().max(1e-3);
+        let per_sec = |new: u64, old: u64| (new.saturating_sub(old) as f64 / secs) as u64;
+        Rates {
+            cpu_percent: match (self.cpu_usage_usec, earlier.cpu_usage_usec) {
+                (Some(new), Some(old)) => Some(new.saturating_sub(old) as f64 / 1e6 / secs * 100.0),
+                _ => None,
+            },
+            io_read_bps: per_sec(self.io_read_bytes, earlier.io_read_bytes),
+            io_write_bps: per_sec(self.io_write_bytes, earlier.io_write_bytes),
+        }
+    }
+}
+
+/// Per-second usage between two readings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Rates {
+    /// Share of one CPU, like `cpu_quota` (200 = two full CPUs)
+    pub cpu_percent: Option<f64>,
+    pub io_read_bps: u64,
+    pub io_write_bps: u64,
+}
+
+/// Turns successive status reads into rates by remembering the previous
+/// reading of each cgroup. Keep one around across refreshes.
+#[derive(Debug, Default)]
+pub struct UsageSampler {
+    last: HashMap<String, (Instant, Usage)>,
+}
+
+impl UsageSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fill in `rates` for cgroups seen before, and remember these readings.
+    /// Cgroups that are gone are forgotten.
+    pub fn update(&mut self, processes: &mut [ProcessStatus]) {
+        let now = Instant::now();
+        let mut last = HashMap::with_capacity(processes.len());
+        for p in processes.iter_mut() {
+            if let Some((then, before)) = self.last.get(&p.cgroup_name) {
+                p.rates = Some(p.usage.rates_since(before, now - *then));
+            }
+            last.insert(p.cgroup_name.clone(), (now, p.usage));
+        }
+        self.last = last;
+    }
+}
+
+/// Like `get_managed_processes`, with rates measured over `interval`
+/// (which this blocks for).
+pub fn get_managed_processes_sampled(
+    manager: &CgroupManager,
+    interval: Duration,
+) -> Result<Vec<ProcessStatus>> {
+    let mut sampler = UsageSampler::new();
+    sampler.update(&mut get_managed_processes(manager)?);
+    std::thread::sleep(interval);
+    let mut processes = get_managed_processes(manager)?;
+    sampler.update(&mut processes);
+    Ok(processes)
+}
+
+/// Sums over managed cgroups, next to what the machine has: how much of
+/// it is fenced off.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Totals {
+    pub cgroups: usize,
+    pub processes: usize,
+    /// `memory.current` of all managed cgroups
+    pub memory_used: u64,
+    /// Sum of memory limits, over cgroups that have one
+    pub memory_limit: u64,
+    /// Sum of CPU quotas, in percent of one CPU
+    pub cpu_quota: u32,
+    /// RAM in the machine, if it could be read
+    pub machine_memory: Option<u64>,
+    pub machine_cpus: usize,
+}
+
+/// Totals over `processes` (as returned by `get_managed_processes`).
+pub fn totals(processes: &[ProcessStatus]) -> Totals {
+    Totals {
+        cgroups: processes.len(),
+        processes: processes.iter().map(|p| p.members.len().max(1)).sum(),
+        memory_used: processes
+            .iter()
+            .filter_map(|p| p.usage.memory_current)
+            .sum(),
+        memory_limit: processes.iter().filter_map(|p| p.memory_max).sum(),
+        cpu_quota: processes.iter().filter_map(|p| p.cpu_quota).sum(),
+        machine_memory: crate::cgroup::mem_total_bytes().ok(),
+        machine_cpus: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    }
 }
 
 /// Get status of all processes managed by rlm
 pub fn get_managed_processes(manager: &CgroupManager) -> Result<Vec<ProcessStatus>> {
     let base = manager.base_path();
-    if !base.exists() {
+    let backend = manager.backend();
+    if !backend.is_dir(base) {
         return Ok(Vec::new());
     }
 
     let mut results = Vec::new();
     let mut dead_cgroups = Vec::new();
 
-    for entry in fs::read_dir(base)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if !path.is_dir() {
-            continue;
-        }
-
+    for path in backend.children(base)? {
         let Some(cgroup_name) = path.file_name().and_then(|n| n.to_str()) else {
             continue;
         };
@@ -49,14 +209,26 @@ pub fn get_managed_processes(manager: &CgroupManager) -> Result<Vec<ProcessStatu
         // - "multi-XXXX" (CLI limit --all-pids - shared)
         // - "run-XXXX-XXXX" (CLI run command: pid + timestamp)
         // - "gtk-XXXX-N" (GUI run command)
+        // - "reserve-NAME" (CLI reserve command - shared)
+        // - "shell-XXXX" (CLI shell --attach: the shell's pid)
+        // - "sup-NAME" (CLI supervise, kept by rlm-guard - shared)
+        // - "profile-NAME" (rlm daemon, profiles with match_exe - shared)
         let pid = if let Some(pid_str) = cgroup_name.strip_prefix("pid-") {
             pid_str.parse::<u32>().ok()
-        } else if cgroup_name.starts_with("app-") || cgroup_name.starts_with("multi-") {
+        } else if cgroup_name.starts_with("app-")
+            || cgroup_name.starts_with("multi-")
+            || cgroup_name.starts_with("shell-")
+            || cgroup_name.starts_with("sup-")
+            || cgroup_name.starts_with("profile-")
+        {
             // For shared cgroups, read first PID from cgroup.procs
-            read_first_pid(&path)
-        } else if cgroup_name.starts_with("run-") || cgroup_name.starts_with("gtk-") {
+            read_first_pid(backend, &path)
+        } else if cgroup_name.starts_with("run-")
+            || cgroup_name.starts_with("gtk-")
+            || cgroup_name.starts_with("reserve-")
+        {
             // For run-* and gtk-* cgroups, read PID from cgroup.procs
-            read_first_pid(&path)
+            read_first_pid(backend, &path)
         } else {
             continue;
         };
@@ -68,7 +240,7 @@ pub fn get_managed_processes(manager: &CgroupManager) -> Result<Vec<ProcessStatu
             // mid-setup would race-delete a cgroup that's about to be used.
             // Reaping is merely DEFERRED here, not skipped: a genuinely-dead
             // fresh cgroup is collected on the next status pass once 2s elapse.
-            if !recently_modified(&path, 2) {
+            if !recently_modified(backend, &path, 2) {
                 dead_cgroups.push(cgroup_name.to_string());
             }
             continue;
@@ -85,15 +257,37 @@ pub fn get_managed_processes(manager: &CgroupManager) -> Result<Vec<ProcessStatu
             }
         };
 
-        let memory_max = parse_memory_max(&path);
-        let cpu_quota = parse_cpu_quota(&path);
-        let (io_read_bps, io_write_bps) = parse_io_limits(&path);
+        let memory_max = parse_memory_max(backend, &path);
+        let memory_low = parse_memory_low(backend, &path);
+        let memory_peak = backend
+            .read(&path.join("memory.peak"))
+            .ok()
+            .and_then(|c| c.trim().parse().ok());
+        let swap_max = parse_swap_max(backend, &path);
+        let oom_group = backend
+            .read(&path.join("memory.oom.group"))
+            .is_ok_and(|c| c.trim() == "1");
+        let cpu_quota = parse_cpu_quota(backend, &path);
+        let cpu_weight = parse_cpu_weight(backend, &path);
+        let cpu_idle = parse_cpu_idle(backend, &path);
+        let (io_read_bps, io_write_bps) = parse_io_limits(backend, &path);
+        let io_weight = parse_io_weight(backend, &path);
+        let pids_max = parse_pids_max(backend, &path);
+        let mems = parse_mems(backend, &path);
 
         // Skip processes with no active limits (all set to max/unlimited)
         if memory_max.is_none()
+            && memory_low.is_none()
+            && swap_max.is_none()
+            && !oom_group
             && cpu_quota.is_none()
+            && cpu_weight.is_none()
+            && !cpu_idle
             && io_read_bps.is_none()
             && io_write_bps.is_none()
+            && io_weight.is_none()
+            && pids_max.is_none()
+            && mems.is_none()
         {
             dead_cgroups.push(cgroup_name.to_string());
             continue;
@@ -103,29 +297,43 @@ pub fn get_managed_processes(manager: &CgroupManager) -> Result<Vec<ProcessStatu
         let is_shared = cgroup_name.starts_with("app-")
             || cgroup_name.starts_with("multi-")
             || cgroup_name.starts_with("run-")
-            || cgroup_name.starts_with("gtk-");
+            || cgroup_name.starts_with("gtk-")
+            || cgroup_name.starts_with("reserve-")
+            || cgroup_name.starts_with("shell-")
+            || cgroup_name.starts_with("sup-")
+            || cgroup_name.starts_with("profile-");
 
+        let members = read_pids(backend, &path);
         // Count processes in shared cgroups
-        let process_count = if is_shared {
-            if let Ok(content) = fs::read_to_string(path.join("cgroup.procs")) {
-                Some(content.lines().filter(|l| !l.trim().is_empty()).count())
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        let process_count = (is_shared && !members.is_empty()).then_some(members.len());
 
         results.push(ProcessStatus {
             pid,
             name: proc_name,
             cgroup_name: cgroup_name.to_string(),
             memory_max,
+            memory_peak,
+            memory_low,
+            swap_max,
+            oom_group,
+            memory_events: ["memory.events.local", "memory.events"]
+                .iter()
+                .find_map(|f| backend.read(&path.join(f)).ok())
+                .map(|c| events::parse_counters(&c))
+                .unwrap_or_default(),
             cpu_quota,
+            cpu_weight,
+            cpu_idle,
             io_read_bps,
             io_write_bps,
+            io_weight,
+            pids_max,
+            mems,
             is_shared,
             process_count,
+            members,
+            usage: Usage::read_from(backend, &path),
+            rates: None,
         });
     }
 
@@ -140,22 +348,35 @@ pub fn get_managed_processes(manager: &CgroupManager) -> Result<Vec<ProcessStatu
 }
 
 /// Whether `path` was modified within the last `secs` seconds.
-fn recently_modified(path: &Path, secs: u64) -> bool {
-    fs::metadata(path)
-        .and_then(|m| m.modified())
-        .ok()
+fn recently_modified(backend: &dyn CgroupBackend, path: &Path, secs: u64) -> bool {
+    backend
+        .modified(path)
         .and_then(|t| t.elapsed().ok())
         .map(|age| age.as_secs() < secs)
         .unwrap_or(false)
 }
 
-fn read_first_pid(cgroup_path: &Path) -> Option<u32> {
-    let content = fs::read_to_string(cgroup_path.join("cgroup.procs")).ok()?;
+/// Name (`comm`) of a process, if it still exists.
+pub fn process_name(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn read_pids(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Vec<u32> {
+    backend
+        .read(&cgroup_path.join("cgroup.procs"))
+        .map(|c| c.lines().filter_map(|l| l.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+fn read_first_pid(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u32> {
+    let content = backend.read(&cgroup_path.join("cgroup.procs")).ok()?;
     content.lines().next()?.trim().parse().ok()
 }
 
-fn parse_memory_max(cgroup_path: &Path) -> Option<u64> {
-    let content = fs::read_to_string(cgroup_path.join("memory.max")).ok()?;
+fn parse_memory_max(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u64> {
+    let content = backend.read(&cgroup_path.join("memory.max")).ok()?;
     let content = content.trim();
     if content == "max" {
         return None;
@@ -163,8 +384,28 @@ fn parse_memory_max(cgroup_path: &Path) -> Option<u64> {
     content.parse().ok()
 }
 
-fn parse_cpu_quota(cgroup_path: &Path) -> Option<u32> {
-    let content = fs::read_to_string(cgroup_path.join("cpu.max")).ok()?;
+fn parse_memory_low(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u64> {
+    let content = backend.read(&cgroup_path.join("memory.low")).ok()?;
+    content.trim().parse().ok().filter(|&b| b > 0)
+}
+
+fn parse_swap_max(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u64> {
+    let content = backend.read(&cgroup_path.join("memory.swap.max")).ok()?;
+    content.trim().parse().ok()
+}
+
+fn parse_pids_max(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u64> {
+    let content = backend.read(&cgroup_path.join("pids.max")).ok()?;
+    content.trim().parse().ok()
+}
+
+fn parse_mems(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<String> {
+    let content = backend.read(&cgroup_path.join("cpuset.mems")).ok()?;
+    Some(content.trim().to_string()).filter(|m| !m.is_empty())
+}
+
+fn parse_cpu_quota(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u32> {
+    let content = backend.read(&cgroup_path.join("cpu.max")).ok()?;
     let content = content.trim();
     if content == "max" || content.starts_with("max ") {
         return None;
@@ -183,8 +424,32 @@ fn parse_cpu_quota(cgroup_path: &Path) -> Option<u32> {
     Some(quota.saturating_mul(100).saturating_div(period) as u32)
 }
 
-fn parse_io_limits(cgroup_path: &Path) -> (Option<u64>, Option<u64>) {
-    let content = match fs::read_to_string(cgroup_path.join("io.max")) {
+fn parse_cpu_weight(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u32> {
+    let content = backend.read(&cgroup_path.join("cpu.weight")).ok()?;
+    content.trim().parse().ok().filter(|&w| w != 100)
+}
+
+/// The `default` entry of `io.weight` ("default 100"; per-device
+/// overrides follow on later lines).
+fn parse_io_weight(backend: &dyn CgroupBackend, cgroup_path: &Path) -> Option<u32> {
+    let content = backend.read(&cgroup_path.join("io.weight")).ok()?;
+    content
+        .lines()
+        .find_map(|l| l.strip_prefix("default "))?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&w| w != 100)
+}
+
+fn parse_cpu_idle(backend: &dyn CgroupBackend, cgroup_path: &Path) -> bool {
+    backend
+        .read(&cgroup_path.join("cpu.idle"))
+        .is_ok_and(|c| c.trim() == "1")
+}
+
+fn parse_io_limits(backend: &dyn CgroupBackend, cgroup_path: &Path) -> (Option<u64>, Option<u64>) {
+    let content = match backend.read(&cgroup_path.join("io.max")) {
         Ok(c) => c,
         Err(_) => return (None, None),
     };
@@ -209,3 +474,27 @@ fn parse_io_limits(cgroup_path: &Path) -> (Option<u64>, Option<u64>) {
 
     (read_bps, write_bps)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_from_two_readings() {
+        let before = Usage {
+            memory_current: Some(1),
+            cpu_usage_usec: Some(CpuStat::parse("usage_usec 1000000\nuser_usec 1\n").usage_usec),
+            io_read_bytes: 0,
+            io_write_bytes: 4096,
+        };
+        let after = Usage {
+            cpu_usage_usec: Some(1_500_000),
+            io_read_bytes: 2048,
+            ..before
+        };
+        let rates = after.rates_since(&before, Duration::from_secs(2));
+        assert_eq!(rates.cpu_percent, Some(25.0));
+        assert_eq!(rates.io_read_bps, 1024);
+        assert_eq!(rates.io_write_bps, 0);
+    }
+}
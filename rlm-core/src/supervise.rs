@@ -0,0 +1,511 @@
+//! Supervised applications: keep an app in its `sup-<name>` cgroup and
+//! intervene when it is OOM-killed or keeps exceeding its soft limit.
+//!
+//! The decision step ([`Watch::observe`]) is pure and takes the cgroup's
+//! `memory.events` counters, so it is unit-testable without root.
+//! [`Supervisor::tick`] wires it to real cgroups, `/proc`, and restarts, and
+//! reuses the rules planner for placing the app's processes.
+
+use crate::process::{self, ProcessInfo};
+use crate::rules::{self, CompiledRule};
+use crate::CgroupManager;
+use common::{Config, CpuLimit, Limit, MemoryLimit, OomPolicy};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+
+/// Consecutive ticks with new `memory.high` breaches that count as "repeatedly".
+pub const BREACH_TICKS: u32 = 3;
+/// Restarts per supervised app per daemon run, so a crash loop gives up.
+pub const MAX_RESTARTS: u32 = 5;
+/// Each tightening keeps this percentage of the previous limit.
+const TIGHTEN_PERCENT: u64 = 75;
+/// Floors so tightening never limits an app into uselessness.
+const MIN_MEMORY: u64 = 64 * 1024 * 1024;
+const MIN_CPU_PERCENT: u32 = 5;
+
+/// Cgroup name for a supervised app (`sup-<name>`).
+pub fn cgroup_name_for(name: &str) -> String {
+    format!("sup-{}", name.replace(['/', ' '], "_"))
+}
+
+/// The `memory.events` counters supervision looks at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryEvents {
+    /// Times the cgroup went over `memory.high` (throttled).
+    pub high: u64,
+    /// Processes killed by the OOM killer.
+    pub oom_kill: u64,
+}
+
+/// Parse `memory.events` (`key value` per line). Missing keys count as 0.
+pub fn parse_memory_events(content: &str) -> MemoryEvents {
+    let mut events = MemoryEvents::default();
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Ok(value) = value.parse() else {
+            continue;
+        };
+        match key {
+            "high" => events.high = value,
+            "oom_kill" => events.oom_kill = value,
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Why supervision intervened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    OomKilled,
+    RepeatedBreach,
+}
+
+/// What to do about a trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub trigger: Trigger,
+    /// Lower the limits before anything else.
+    pub tighten: bool,
+    /// Relaunch the app (it was OOM-killed and nothing of it is left).
+    pub restart: bool,
+}
+
+/// Per-app supervision state across ticks.
+#[derive(Debug, Default)]
+pub struct Watch {
+    last: Option<MemoryEvents>,
+    breach_streak: u32,
+    restarts: u32,
+}
+
+impl Watch {
+    /// Compare `events` with the previous tick and decide whether to step in.
+    /// `events` is `None` when the cgroup doesn't exist (counters restart from
+    /// zero when it is recreated); `alive` is whether any matching process runs.
+    pub fn observe(
+        &mut self,
+        events: Option<MemoryEvents>,
+        alive: bool,
+        policy: OomPolicy,
+    ) -> Option<Step> {
+        let Some(events) = events else {
+            self.last = None;
+            self.breach_streak = 0;
+            return None;
+        };
+        // The first sample after (re)start is only a baseline.
+        let last = self.last.replace(events)?;
+
+        let trigger = if events.oom_kill > last.oom_kill {
+            self.breach_streak = 0;
+            Trigger::OomKilled
+        } else if events.high > last.high {
+            self.breach_streak += 1;
+            if self.breach_streak < BREACH_TICKS {
+                return None;
+            }
+            self.breach_streak = 0;
+            Trigger::RepeatedBreach
+        } else {
+            self.breach_streak = 0;
+            return None;
+        };
+
+        let restart = trigger == Trigger::OomKilled
+            && !alive
+            && policy != OomPolicy::Log
+            && self.restarts < MAX_RESTARTS;
+        if restart {
+            self.restarts += 1;
+        }
+        Some(Step {
+            trigger,
+            tighten: policy == OomPolicy::Tighten,
+            restart,
+        })
+    }
+
+    /// Restarts so far in this daemon run.
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+}
+
+/// `limit` with memory and CPU lowered by one tightening step (I/O is kept).
+pub fn tighten(limit: &Limit) -> Limit {
+    let memory = limit.memory.and_then(|m| {
+        let bytes = (m.bytes() / 100 * TIGHTEN_PERCENT).max(MIN_MEMORY.min(m.bytes()));
+        MemoryLimit::parse(&bytes.to_string()).ok()
+    });
+    let cpu = limit.cpu.and_then(|c| {
+        let percent = (u64::from(c.percent()) * TIGHTEN_PERCENT / 100) as u32;
+        let percent = percent.max(MIN_CPU_PERCENT.min(c.percent()));
+        CpuLimit::parse(&percent.to_string()).ok()
+    });
+    Limit {
+        memory,
+        swap: limit.swap,
+        oom_group: limit.oom_group,
+        cpu,
+        cpu_weight: limit.cpu_weight,
+        idle: limit.idle,
+        io: limit.io,
+        io_path: limit.io_path.clone(),
+        io_weight: limit.io_weight,
+        pids: limit.pids,
+        mems: limit.mems.clone(),
+    }
+}
+
+/// How to relaunch an app, captured from its main process while it runs.
+#[derive(Debug, Clone)]
+struct LaunchSpec {
+    argv: Vec<String>,
+    cwd: Option<PathBuf>,
+}
+
+impl LaunchSpec {
+    fn capture(pid: u32) -> Option<Self> {
+        let raw = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+        let argv: Vec<String> = raw
+            .split(|&b| b == 0)
+            .filter(|a| !a.is_empty())
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect();
+        if argv.is_empty() {
+            return None;
+        }
+        let cwd = fs::read_link(format!("/proc/{pid}/cwd")).ok();
+        Some(Self { argv, cwd })
+    }
+}
+
+struct Supervised {
+    rule: CompiledRule,
+    policy: OomPolicy,
+    watch: Watch,
+    launch: Option<LaunchSpec>,
+}
+
+/// Runs supervision for every configured app; owned by rlm-guard.
+pub struct Supervisor {
+    apps: Vec<Supervised>,
+    /// Relaunched processes, kept so they are reaped when they exit.
+    children: Vec<Child>,
+}
+
+impl Supervisor {
+    /// Compile the supervised apps from config. Apps with unparseable limits
+    /// are skipped (logged once).
+    pub fn new(cfg: &Config) -> Self {
+        let apps = cfg
+            .supervise
+            .iter()
+            .filter_map(|(name, s)| {
+                let mut rule = CompiledRule::compile(name, &s.rule)?;
+                rule.cgroup = cgroup_name_for(name);
+                Some(Supervised {
+                    rule,
+                    policy: s.on_oom,
+                    watch: Watch::default(),
+                    launch: None,
+                })
+            })
+            .collect();
+        Self {
+            apps,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn app_count(&self) -> usize {
+        self.apps.len()
+    }
+
+    /// Switch to the apps of a reloaded config. Apps still configured keep
+    /// their restart count and launch command; relaunched processes are
+    /// still reaped.
+    pub fn reload(&mut self, cfg: &Config) {
+        let mut old = std::mem::replace(&mut self.apps, Self::new(cfg).apps);
+        for app in &mut self.apps {
+            if let Some(i) = old.iter().position(|o| o.rule.name == app.rule.name) {
+                let before = old.swap_remove(i);
+                app.watch = before.watch;
+                app.launch = before.launch;
+            }
+        }
+    }
+
+    /// One supervision pass: intervene where needed, then keep each app's
+    /// processes placed. Best-effort; failures are logged.
+    pub fn tick(&mut self, mgr: &CgroupManager) {
+        self.children
+            .retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+
+        let procs = match process::list_all() {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::warn!(error = %e, "supervise: failed to list processes; skipping tick");
+                return;
+            }
+        };
+
+        for app in &mut self.apps {
+            let matching: Vec<&ProcessInfo> =
+                procs.iter().filter(|p| app.rule.matches(p)).collect();
+            if let Some(main) = matching.iter().map(|p| p.pid).min() {
+                if app.launch.is_none() {
+                    app.launch = LaunchSpec::capture(main);
+                }
+            }
+
+            let cgroup_path = mgr.base_path().join(&app.rule.cgroup);
+            let events = read_memory_events(&cgroup_path);
+            if let Some(step) = app.watch.observe(events, !matching.is_empty(), app.policy) {
+                if intervene(mgr, app, step, &mut self.children) {
+                    // The relaunched process shows up in next tick's scan.
+                    continue;
+                }
+            }
+
+            let placed = mgr.pids_in_cgroup(&app.rule.cgroup);
+            let exists = !placed.is_empty() || mgr.cgroup_exists(&app.rule.cgroup);
+            for action in rules::plan(&app.rule, &procs, &placed, exists) {
+                if let Err(e) = rules::apply(mgr, &app.rule, &action) {
+                    tracing::warn!(app = app.rule.name, ?action, error = %e, "supervise: action failed");
+                }
+            }
+        }
+    }
+}
+
+fn read_memory_events(cgroup_path: &Path) -> Option<MemoryEvents> {
+    fs::read_to_string(cgroup_path.join("memory.events"))
+        .ok()
+        .map(|c| parse_memory_events(&c))
+}
+
+/// Apply one step, logging it. Returns whether the app was relaunched.
+fn intervene(
+    mgr: &CgroupManager,
+    app: &mut Supervised,
+    step: Step,
+    children: &mut Vec<Child>,
+) -> bool {
+    let name = &app.rule.name;
+    if step.tighten {
+        app.rule.limit = tighten(&app.rule.limit);
+        match mgr.prepare_cgroup(&app.rule.cgroup, &app.rule.limit) {
+            Ok(_) => tracing::warn!(
+                app = name,
+                trigger = ?step.trigger,
+                memory = ?app.rule.limit.memory.map(|m| m.bytes()),
+                cpu = ?app.rule.limit.cpu.map(|c| c.percent()),
+                "supervise: tightened limits"
+            ),
+            Err(e) => tracing::warn!(app = name, error = %e, "supervise: failed to tighten limits"),
+        }
+    }
+
+    if !step.restart {
+        if !step.tighten {
+            tracing::warn!(app = name, trigger = ?step.trigger, policy = %app.policy, "supervise: limit trouble");
+        }
+        if step.trigger == Trigger::OomKilled && app.watch.restarts() >= MAX_RESTARTS {
+            tracing::warn!(
+                app = name,
+                "supervise: restart limit reached; not restarting"
+            );
+        }
+        return false;
+    }
+
+    let Some(launch) = app.launch.clone() else {
+        tracing::warn!(
+            app = name,
+            "supervise: OOM-killed but no command line recorded; cannot restart"
+        );
+        return false;
+    };
+    match relaunch(mgr, &app.rule, &launch) {
+        Ok(child) => {
+            tracing::warn!(
+                app = name,
+                pid = child.id(),
+                restart = app.watch.restarts(),
+                "supervise: restarted after OOM kill"
+            );
+            children.push(child);
+            true
+        }
+        Err(e) => {
+            tracing::warn!(app = name, error = %e, "supervise: restart failed");
+            false
+        }
+    }
+}
+
+fn relaunch(
+    mgr: &CgroupManager,
+    rule: &CompiledRule,
+    launch: &LaunchSpec,
+) -> common::Result<Child> {
+    let path = mgr.prepare_cgroup(&rule.cgroup, &rule.limit)?;
+    let (program, args) = launch
+        .argv
+        .split_first()
+        .ok_or_else(|| common::Error::InvalidArgs("empty command line".into()))?;
+    let mut cmd = mgr.placement_command(&path, program)?;
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(cwd) = &launch.cwd {
+        cmd.current_dir(cwd);
+    }
+    Ok(cmd.spawn()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ev(high: u64, oom_kill: u64) -> Option<MemoryEvents> {
+        Some(MemoryEvents { high, oom_kill })
+    }
+
+    #[test]
+    fn parses_memory_events() {
+        let c = "low 0\nhigh 12\nmax 3\noom 1\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(
+            parse_memory_events(c),
+            MemoryEvents {
+                high: 12,
+                oom_kill: 2
+            }
+        );
+        assert_eq!(parse_memory_events(""), MemoryEvents::default());
+    }
+
+    #[test]
+    fn first_sample_is_baseline() {
+        let mut w = Watch::default();
+        assert_eq!(w.observe(ev(5, 3), true, OomPolicy::Restart), None);
+        assert_eq!(w.observe(ev(5, 3), true, OomPolicy::Restart), None);
+    }
+
+    #[test]
+    fn oom_kill_restarts_only_when_app_is_gone() {
+        let mut w = Watch::default();
+        w.observe(ev(0, 0), true, OomPolicy::Restart);
+        let step = w.observe(ev(0, 1), true, OomPolicy::Restart).unwrap();
+        assert_eq!(step.trigger, Trigger::OomKilled);
+        assert!(!step.restart, "a surviving process means no restart");
+
+        let step = w.observe(ev(0, 2), false, OomPolicy::Restart).unwrap();
+        assert!(step.restart);
+        assert!(!step.tighten);
+        assert_eq!(w.restarts(), 1);
+    }
+
+    #[test]
+    fn log_policy_never_restarts_or_tightens() {
+        let mut w = Watch::default();
+        w.observe(ev(0, 0), true, OomPolicy::Log);
+        let step = w.observe(ev(0, 1), false, OomPolicy::Log).unwrap();
+        assert!(!step.restart && !step.tighten);
+    }
+
+    #[test]
+    fn breach_must_repeat_before_triggering() {
+        let mut w = Watch::default();
+        w.observe(ev(0, 0), true, OomPolicy::Tighten);
+        assert_eq!(w.observe(ev(1, 0), true, OomPolicy::Tighten), None);
+        assert_eq!(w.observe(ev(2, 0), true, OomPolicy::Tighten), None);
+        let step = w.observe(ev(3, 0), true, OomPolicy::Tighten).unwrap();
+        assert_eq!(step.trigger, Trigger::RepeatedBreach);
+        assert!(step.tighten && !step.restart);
+
+        // A quiet tick resets the streak
+        w.observe(ev(4, 0), true, OomPolicy::Tighten);
+        w.observe(ev(4, 0), true, OomPolicy::Tighten);
+        assert_eq!(w.observe(ev(5, 0), true, OomPolicy::Tighten), None);
+    }
+
+    #[test]
+    fn restarts_are_capped() {
+        let mut w = Watch::default();
+        w.observe(ev(0, 0), false, OomPolicy::Restart);
+        for kills in 1..=u64::from(MAX_RESTARTS) {
+            assert!(
+                w.observe(ev(0, kills), false, OomPolicy::Restart)
+                    .unwrap()
+                    .restart
+            );
+        }
+        let step = w
+            .observe(
+                ev(0, u64::from(MAX_RESTARTS) + 1),
+                false,
+                OomPolicy::Restart,
+            )
+            .unwrap();
+        assert!(!step.restart);
+    }
+
+    #[test]
+    fn missing_cgroup_resets_baseline() {
+        let mut w = Watch::default();
+        w.observe(ev(0, 4), true, OomPolicy::Restart);
+        w.observe(None, false, OomPolicy::Restart);
+        // Recreated cgroup starts from zero: not an OOM kill
+        assert_eq!(w.observe(ev(0, 0), true, OomPolicy::Restart), None);
+    }
+
+    #[test]
+    fn tighten_lowers_memory_and_cpu_with_floors() {
+        let limit = Limit {
+            memory: Some(MemoryLimit::parse("1G").unwrap()),
+            swap: None,
+            oom_group: false,
+            cpu: Some(CpuLimit::parse("100%").unwrap()),
+            cpu_weight: None,
+            idle: false,
+            io: None,
+            io_path: None,
+            io_weight: None,
+            pids: None,
+            mems: None,
+        };
+        let t = tighten(&limit);
+        assert_eq!(t.memory.unwrap().bytes(), 1024 * 1024 * 1024 / 100 * 75);
+        assert_eq!(t.cpu.unwrap().percent(), 75);
+
+        let small = Limit {
+            memory: Some(MemoryLimit::parse("65M").unwrap()),
+            swap: None,
+            oom_group: false,
+            cpu: Some(CpuLimit::parse("6%").unwrap()),
+            cpu_weight: None,
+            idle: false,
+            io: None,
+            io_path: None,
+            io_weight: None,
+            pids: None,
+            mems: None,
+        };
+        let t = tighten(&small);
+        assert_eq!(t.memory.unwrap().bytes(), MIN_MEMORY);
+        assert_eq!(t.cpu.unwrap().percent(), MIN_CPU_PERCENT);
+    }
+
+    #[test]
+    fn cgroup_name_is_sanitized() {
+        assert_eq!(cgroup_name_for("my app/x"), "sup-my_app_x");
+    }
+}
@@ -0,0 +1,446 @@
+//! Limits on systemd units, behind `rlm limit --unit`. Rather than moving a
+//! service's processes into an rlm cgroup, where systemd would fight over
+//! them, the limits become the unit's own resource properties, set through
+//! `systemctl set-property` (systemd's `SetUnitProperties`). They apply at
+//! once and, unless `runtime`, survive restarts of the unit and reboots.
+//!
+//! Units limited this way are recorded in `~/.local/share/rlm/units.json`
+//! so `rlm status` can list them and `rlm unlimit --unit` knows which
+//! properties to reset. Runtime limits are recorded with the boot they were
+//! set in and forgotten after a reboot, along with the limits themselves.
+
+use common::{Error, Limit, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The service manager a unit belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Manager {
+    System,
+    User,
+}
+
+impl Manager {
+    fn flag(self) -> &'static str {
+        match self {
+            Manager::System => "--system",
+            Manager::User => "--user",
+        }
+    }
+}
+
+/// A unit rlm has set limits on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub unit: String,
+    pub manager: Manager,
+    /// Properties set, as `Name=value`.
+    pub properties: Vec<String>,
+    /// Boot ID the limits were set in, for runtime-only limits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Record {
+    #[serde(default)]
+    units: Vec<Entry>,
+}
+
+/// A limited unit's current use and limits, as systemd reports them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct UnitStatus {
+    pub unit: String,
+    pub active: String,
+    pub memory_current: Option<u64>,
+    pub memory_max: Option<u64>,
+    /// CPU quota in percent of one core
+    pub cpu_quota: Option<u32>,
+    pub tasks_current: Option<u64>,
+    pub tasks_max: Option<u64>,
+    pub runtime: bool,
+}
+
+/// Set `limit` on `unit`, in whichever manager has it loaded (the system
+/// one first). With `runtime` the limits last until the next reboot.
+pub fn apply(unit: &str, limit: &Limit, runtime: bool) -> Result<Entry> {
+    let properties = properties(limit)?;
+    let manager = locate(unit)?;
+    let mut args = vec![manager.flag(), "set-property"];
+    if runtime {
+        args.push("--runtime");
+    }
+    args.push(unit);
+    args.extend(properties.iter().map(String::as_str));
+    systemctl(&args)?;
+
+    let entry = Entry {
+        unit: unit.to_string(),
+        manager,
+        properties,
+        boot: if runtime { boot_id() } else { None },
+    };
+    let mut record = load();
+    record
+        .units
+        .retain(|e| e.unit != entry.unit || e.manager != entry.manager);
+    record.units.push(entry.clone());
+    save(&record)?;
+    Ok(entry)
+}
+
+/// Reset the properties rlm set on `unit`. Returns false if rlm has no
+/// limits recorded for it.
+pub fn remove(unit: &str) -> Result<bool> {
+    let mut record = load();
+    let Some(index) = record.units.iter().position(|e| e.unit == unit) else {
+        return Ok(false);
+    };
+    let entry = record.units.remove(index);
+    let resets = resets(&entry.properties);
+    let mut args = vec![entry.manager.flag(), "set-property"];
+    if entry.boot.is_some() {
+        args.push("--runtime");
+    }
+    args.push(unit);
+    args.extend(resets.iter().map(String::as_str));
+    systemctl(&args)?;
+    save(&record)?;
+    Ok(true)
+}
+
+/// Units with limits from rlm.
+pub fn list() -> Vec<Entry> {
+    load().units
+}
+
+/// Current use and limits of every unit with limits from rlm.
+pub fn status() -> Vec<UnitStatus> {
+    list()
+        .into_iter()
+        .map(|entry| {
+            let output = systemctl(&[
+                entry.manager.flag(),
+                "show",
+                "-p",
+                "ActiveState,MemoryCurrent,MemoryMax,CPUQuotaPerSecUSec,TasksCurrent,TasksMax",
+                &entry.unit,
+            ])
+            .unwrap_or_default();
+            let mut status = parse_show(&output);
+            status.unit = entry.unit;
+            status.runtime = entry.boot.is_some();
+            status
+        })
+        .collect()
+}
+
+/// `Name=value` unit properties for `limit`.
+pub fn properties(limit: &Limit) -> Result<Vec<String>> {
+    if limit.oom_group {
+        return Err(Error::InvalidArgs(
+            "--oom-group is not a unit property; set OOMPolicy=kill in the unit file instead"
+                .into(),
+        ));
+    }
+    let mut props = Vec::new();
+    if let Some(memory) = limit.memory {
+        props.push(format!("MemoryMax={}", memory.bytes()));
+    }
+    if let Some(swap) = limit.swap {
+        props.push(format!("MemorySwapMax={}", swap.bytes()));
+    } else if limit.memory.is_some() {
+        // As for processes: --memory alone keeps the unit out of swap
+        props.push("MemorySwapMax=0".into());
+    }
+    if let Some(cpu) = limit.cpu {
+        props.push(format!("CPUQuota={}%", cpu.percent()));
+    }
+    if limit.idle {
+        props.push("CPUWeight=idle".into());
+    } else if let Some(weight) = limit.cpu_weight {
+        props.push(format!("CPUWeight={}", weight.weight()));
+    }
+    if let Some(io) = limit.io {
+        // systemd resolves a path to the disk holding it
+        let device = limit
+            .io_path
+            .as_ref()
+            .map_or_else(|| "/".to_string(), |p| p.display().to_string());
+        if let Some(bps) = io.read_bps {
+            props.push(format!("IOReadBandwidthMax={device} {bps}"));
+        }
+        if let Some(bps) = io.write_bps {
+            props.push(format!("IOWriteBandwidthMax={device} {bps}"));
+        }
+    }
+    if let Some(weight) = limit.io_weight {
+        props.push(format!("IOWeight={}", weight.weight()));
+    }
+    if let Some(pids) = limit.pids {
+        props.push(format!("TasksMax={}", pids.count()));
+    }
+    if let Some(ref mems) = limit.mems {
+        props.push(format!("AllowedMemoryNodes={}", mems.as_str()));
+    }
+    Ok(props)
+}
+
+/// A drop-in with `limit` as resource directives, for
+/// `/etc/systemd/system/<unit>.d/`: a `[Service]` section, or with `slice`
+/// a slice unit that services join with `Slice=`. `profile` names the
+/// source in its header.
+pub fn unit_file(profile: &str, limit: &Limit, slice: bool) -> Result<String> {
+    let directives = properties(&Limit {
+        oom_group: false,
+        ..limit.clone()
+    })?;
+    let mut out = format!("# Generated by rlm from profile '{profile}'\n");
+    if slice {
+        out.push_str(&format!(
+            "# Install as /etc/systemd/system/<name>.slice and add Slice=<name>.slice\n\
+             # to each service that should share these limits.\n\
+             [Unit]\nDescription=rlm profile {profile}\n\n[Slice]\n"
+        ));
+    } else {
+        out.push_str(
+            "# Install as /etc/systemd/system/<unit>.d/rlm.conf, then run\n\
+             # systemctl daemon-reload and restart the unit.\n\
+             [Service]\n",
+        );
+    }
+    for directive in directives {
+        out.push_str(&directive);
+        out.push('\n');
+    }
+    if limit.oom_group {
+        if slice {
+            out.push_str("# oom_group: set OOMPolicy=kill on the services instead\n");
+        } else {
+            out.push_str("OOMPolicy=kill\n");
+        }
+    }
+    Ok(out)
+}
+
+/// Assignments that put `properties` back to systemd's defaults: an empty
+/// value resets a property.
+fn resets(properties: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = properties
+        .iter()
+        .filter_map(|p| p.split_once('='))
+        .map(|(name, _)| format!("{name}="))
+        .collect();
+    names.dedup();
+    names
+}
+
+/// Parse `systemctl show` output for [`status`].
+fn parse_show(output: &str) -> UnitStatus {
+    // systemd prints unset limits as "infinity" and unknown use as [not set]
+    let number = |value: &str| value.parse::<u64>().ok().filter(|&n| n != u64::MAX);
+    let mut status = UnitStatus::default();
+    for (key, value) in output.lines().filter_map(|l| l.split_once('=')) {
+        match key {
+            "ActiveState" => status.active = value.to_string(),
+            "MemoryCurrent" => status.memory_current = number(value),
+            "MemoryMax" => status.memory_max = number(value),
+            "CPUQuotaPerSecUSec" => status.cpu_quota = cpu_percent(value),
+            "TasksCurrent" => status.tasks_current = number(value),
+            "TasksMax" => status.tasks_max = number(value),
+            _ => {}
+        }
+    }
+    status
+}
+
+/// Percent of one core from `CPUQuotaPerSecUSec`, e.g. `500ms` is 50%.
+fn cpu_percent(value: &str) -> Option<u32> {
+    let (number, unit) = value.split_at(value.find(|c: char| c.is_alphabetic())?);
+    let number: f64 = number.parse().ok()?;
+    let usec = match unit {
+        "us" => number,
+        "ms" => number * 1_000.0,
+        "s" => number * 1_000_000.0,
+        _ => return None,
+    };
+    Some((usec / 10_000.0).round() as u32)
+}
+
+/// The scope unit of logind session `id`, or of the caller's own session.
+/// Session scopes are gone when the session ends, so limits on them are
+/// always runtime ones.
+pub fn session_scope(id: Option<&str>) -> Result<String> {
+    let own = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".into());
+    let id = id.unwrap_or(&own);
+    let output = Command::new("loginctl")
+        .args(["show-session", id, "-p", "Scope", "--value"])
+        .output()
+        .map_err(|e| Error::InvalidArgs(format!("failed to run loginctl: {e}")))?;
+    let scope = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || scope.is_empty() {
+        return Err(Error::InvalidArgs(format!(
+            "no login session '{id}' (see `loginctl list-sessions`)"
+        )));
+    }
+    Ok(scope)
+}
+
+/// The slice systemd keeps all of user `uid`'s sessions and services in.
+pub fn user_slice(uid: u32) -> String {
+    format!("user-{uid}.slice")
+}
+
+/// Whether a service manager has `unit` loaded: a user slice only is while
+/// they are logged in or lingering.
+pub fn is_loaded(unit: &str) -> bool {
+    locate(unit).is_ok()
+}
+
+/// The manager that has `unit` loaded, system first.
+fn locate(unit: &str) -> Result<Manager> {
+    for manager in [Manager::System, Manager::User] {
+        let state = systemctl(&[manager.flag(), "show", "-p", "LoadState", "--value", unit]);
+        if state.is_ok_and(|s| s.trim() == "loaded") {
+            return Ok(manager);
+        }
+    }
+    Err(Error::InvalidArgs(format!(
+        "no systemd unit '{unit}' is loaded (see `systemctl list-units`)"
+    )))
+}
+
+fn systemctl(args: &[&str]) -> Result<String> {
+    let output = Command::new("systemctl")
+        .args(args)
+        .output()
+        .map_err(|e| Error::InvalidArgs(format!("failed to run systemctl: {e}")))?;
+    if !output.status.success() {
+        return Err(Error::Cgroup(format!(
+            "systemctl {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn boot_id() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn record_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("rlm").join("units.json"))
+}
+
+/// The record, without runtime limits from earlier boots.
+fn load() -> Record {
+    let mut record: Record = record_path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let boot = boot_id();
+    record.units.retain(|e| e.boot.is_none() || e.boot == boot);
+    record
+}
+
+fn save(record: &Record) -> Result<()> {
+    let path = record_path()
+        .ok_or_else(|| Error::Config("no data directory to record unit limits in".into()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(record)
+        .map_err(|e| Error::Config(format!("failed to serialize unit limits: {e}")))?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{CpuLimit, MemoryLimit};
+
+    #[test]
+    fn limits_become_unit_properties() {
+        let limit = Limit {
+            memory: Some(MemoryLimit::parse("2G").unwrap()),
+            cpu: Some(CpuLimit::parse("150%").unwrap()),
+            io: Some(common::IoLimit {
+                read_bps: Some(1 << 20),
+                write_bps: None,
+            }),
+            io_path: Some("/home".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            properties(&limit).unwrap(),
+            [
+                "MemoryMax=2147483648",
+                "MemorySwapMax=0",
+                "CPUQuota=150%",
+                "IOReadBandwidthMax=/home 1048576",
+            ]
+        );
+        let oom = Limit {
+            oom_group: true,
+            ..Default::default()
+        };
+        assert!(properties(&oom).is_err());
+        assert_eq!(
+            resets(&properties(&limit).unwrap()),
+            [
+                "MemoryMax=",
+                "MemorySwapMax=",
+                "CPUQuota=",
+                "IOReadBandwidthMax="
+            ]
+        );
+    }
+
+    #[test]
+    fn profiles_export_as_unit_files() {
+        let limit = Limit {
+            memory: Some(MemoryLimit::parse("512M").unwrap()),
+            oom_group: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            unit_file("Light", &limit, false)
+                .unwrap()
+                .lines()
+                .filter(|l| !l.starts_with('#'))
+                .collect::<Vec<_>>(),
+            [
+                "[Service]",
+                "MemoryMax=536870912",
+                "MemorySwapMax=0",
+                "OOMPolicy=kill"
+            ]
+        );
+        let slice = unit_file("Light", &limit, true).unwrap();
+        assert!(slice.contains("[Unit]\nDescription=rlm profile Light\n\n[Slice]\nMemoryMax="));
+        assert!(!slice.contains("OOMPolicy=kill\n"));
+    }
+
+    #[test]
+    fn reads_systemctl_show() {
+        let status = parse_show(
+            "ActiveState=active\nMemoryCurrent=104857600\nMemoryMax=infinity\n\
+             CPUQuotaPerSecUSec=1.500000s\nTasksCurrent=[not set]\nTasksMax=512\n",
+        );
+        assert_eq!(status.active, "active");
+        assert_eq!(status.memory_current, Some(100 << 20));
+        assert_eq!(status.memory_max, None);
+        assert_eq!(status.cpu_quota, Some(150));
+        assert_eq!(status.tasks_current, None);
+        assert_eq!(status.tasks_max, Some(512));
+        assert_eq!(cpu_percent("500ms"), Some(50));
+        assert_eq!(cpu_percent("infinity"), None);
+    }
+}
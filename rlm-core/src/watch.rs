@@ -0,0 +1,438 @@
+//! Changes to managed cgroups as they happen, from inotify instead of
+//! polling: cgroups created and removed, processes joining and leaving, and
+//! memory events.
+//!
+//! The kernel signals a modify on `cgroup.procs` when a process is moved in
+//! by a write (which is how rlm and systemd move them), on `cgroup.events`
+//! when a cgroup becomes empty or populated, and on `memory.events` when a
+//! counter moves. Forks and exits that leave a cgroup populated raise
+//! nothing, so membership is also re-read every [`RESCAN`].
+
+use crate::events::{self, Counters, MemoryEvent};
+use crate::CgroupManager;
+use common::{Error, Result};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often membership is re-read to catch forks and exits.
+pub const RESCAN: Duration = Duration::from_secs(5);
+
+/// Something that changed in the managed cgroups.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchEvent {
+    CgroupCreated {
+        cgroup: String,
+    },
+    CgroupRemoved {
+        cgroup: String,
+    },
+    /// Processes joined or left a cgroup.
+    ProcessesChanged {
+        cgroup: String,
+        added: Vec<u32>,
+        removed: Vec<u32>,
+    },
+    /// A `memory.events` counter moved.
+    Memory(MemoryEvent),
+}
+
+impl WatchEvent {
+    /// The cgroup it happened in.
+    pub fn cgroup(&self) -> &str {
+        match self {
+            WatchEvent::CgroupCreated { cgroup }
+            | WatchEvent::CgroupRemoved { cgroup }
+            | WatchEvent::ProcessesChanged { cgroup, .. } => cgroup,
+            WatchEvent::Memory(e) => &e.cgroup,
+        }
+    }
+}
+
+/// An inotify descriptor.
+pub(crate) struct Inotify(File);
+
+impl Inotify {
+    pub(crate) fn new() -> Result<Self> {
+        // SAFETY: plain syscall; the returned descriptor is owned by the File.
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // SAFETY: `fd` is a fresh descriptor nothing else owns.
+        Ok(Self(unsafe { File::from_raw_fd(fd) }))
+    }
+
+    pub(crate) fn add_watch(&self, path: &Path, mask: u32) -> Result<i32> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| Error::InvalidArgs(format!("invalid path {}", path.display())))?;
+        // SAFETY: valid descriptor and NUL-terminated path for the call's duration.
+        let wd = unsafe { libc::inotify_add_watch(self.0.as_raw_fd(), c_path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(wd)
+    }
+
+    /// Wait up to `timeout` (forever for `None`) and return the (wd, mask,
+    /// name) records available then; empty on timeout.
+    pub(crate) fn read(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<(i32, u32, Option<String>)>> {
+        let mut pfd = libc::pollfd {
+            fd: self.0.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        // SAFETY: one valid pollfd for the call's duration.
+        if unsafe { libc::poll(&mut pfd, 1, ms) } < 0 {
+            let err = std::io::Error::last_os_error();
+            return match err.kind() {
+                std::io::ErrorKind::Interrupted => Ok(Vec::new()),
+                _ => Err(err.into()),
+            };
+        }
+        let mut buf = [0u8; 4096];
+        match self.0.read(&mut buf) {
+            Ok(n) => Ok(parse_inotify(&buf[..n])),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl AsRawFd for Inotify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// What a watch descriptor is watching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Watched {
+    /// `cgroup.procs` or `cgroup.events`
+    Members,
+    Memory,
+}
+
+#[derive(Debug, Default)]
+struct CgroupState {
+    pids: Vec<u32>,
+    counters: Counters,
+    memory_file: Option<PathBuf>,
+}
+
+/// Watches every managed cgroup, including ones created after it started.
+/// Get one from [`CgroupManager::watch`].
+///
+/// Iterating blocks until the next event. To plug it into an event loop
+/// instead, wait for its descriptor to be readable and call
+/// [`Watcher::wait`] with a zero timeout.
+pub struct Watcher {
+    inotify: Inotify,
+    base: PathBuf,
+    base_wd: i32,
+    watches: HashMap<i32, (String, Watched)>,
+    cgroups: HashMap<String, CgroupState>,
+    last_rescan: Instant,
+    pending: VecDeque<WatchEvent>,
+}
+
+impl CgroupManager {
+    /// Watch managed cgroups for changes. See [`Watcher`].
+    pub fn watch(&self) -> Result<Watcher> {
+        Watcher::new(self.base_path())
+    }
+}
+
+impl Watcher {
+    fn new(base: &Path) -> Result<Self> {
+        if !base.is_dir() {
+            return Err(Error::Cgroup(format!(
+                "{} does not exist yet: nothing is managed",
+                base.display()
+            )));
+        }
+        let inotify = Inotify::new()?;
+        let base_wd = inotify.add_watch(
+            base,
+            libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_DELETE | libc::IN_MOVED_FROM,
+        )?;
+        let mut watcher = Self {
+            inotify,
+            base: base.to_path_buf(),
+            base_wd,
+            watches: HashMap::new(),
+            cgroups: HashMap::new(),
+            last_rescan: Instant::now(),
+            pending: VecDeque::new(),
+        };
+        for name in events::cgroup_names(base) {
+            watcher.add_cgroup(&name);
+        }
+        Ok(watcher)
+    }
+
+    /// Names of the cgroups being watched.
+    pub fn cgroups(&self) -> impl Iterator<Item = &str> {
+        self.cgroups.keys().map(String::as_str)
+    }
+
+    /// Wait up to `timeout` (forever for `None`) for changes and return
+    /// them; empty if nothing changed in time.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<Vec<WatchEvent>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let mut events = Vec::new();
+            let until_rescan = RESCAN.saturating_sub(self.last_rescan.elapsed());
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            let records = self
+                .inotify
+                .read(Some(left.map_or(until_rescan, |l| l.min(until_rescan))))?;
+            self.handle(records, &mut events);
+            if self.last_rescan.elapsed() >= RESCAN {
+                self.last_rescan = Instant::now();
+                let names: Vec<String> = self.cgroups.keys().cloned().collect();
+                for name in names {
+                    self.reread_members(&name, &mut events);
+                }
+            }
+            let timed_out = deadline.is_some_and(|d| Instant::now() >= d);
+            if !events.is_empty() || timed_out {
+                return Ok(events);
+            }
+        }
+    }
+
+    fn handle(&mut self, records: Vec<(i32, u32, Option<String>)>, events: &mut Vec<WatchEvent>) {
+        let mut members = Vec::new();
+        let mut memory = Vec::new();
+        for (wd, mask, name) in records {
+            if wd == self.base_wd {
+                let Some(name) = name.filter(|n| n != "unlimit") else {
+                    continue;
+                };
+                if mask & libc::IN_ISDIR == 0 {
+                    continue;
+                }
+                if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                    if self.add_cgroup(&name) {
+                        events.push(WatchEvent::CgroupCreated {
+                            cgroup: name.clone(),
+                        });
+                        // Processes moved in before the watches were added
+                        self.reread_members(&name, events);
+                    }
+                } else if self.cgroups.remove(&name).is_some() {
+                    self.watches.retain(|_, (n, _)| *n != name);
+                    events.push(WatchEvent::CgroupRemoved { cgroup: name });
+                }
+            } else if mask & libc::IN_IGNORED != 0 {
+                self.watches.remove(&wd);
+            } else if let Some((name, what)) = self.watches.get(&wd) {
+                let list = match what {
+                    Watched::Members => &mut members,
+                    Watched::Memory => &mut memory,
+                };
+                if !list.contains(name) {
+                    list.push(name.clone());
+                }
+            }
+        }
+        for name in members {
+            self.reread_members(&name, events);
+        }
+        for name in memory {
+            self.reread_memory(&name, events);
+        }
+    }
+
+    /// Start watching one cgroup; its current state is the baseline. False
+    /// if it was already watched or is gone.
+    fn add_cgroup(&mut self, name: &str) -> bool {
+        if self.cgroups.contains_key(name) {
+            return false;
+        }
+        let path = self.base.join(name);
+        if !path.is_dir() {
+            return false;
+        }
+        let mut state = CgroupState {
+            memory_file: events::events_file(&path),
+            ..CgroupState::default()
+        };
+        for file in ["cgroup.procs", "cgroup.events"] {
+            match self.inotify.add_watch(&path.join(file), libc::IN_MODIFY) {
+                Ok(wd) => {
+                    self.watches
+                        .insert(wd, (name.to_string(), Watched::Members));
+                }
+                Err(e) => tracing::debug!(cgroup = name, file, error = %e, "cannot watch"),
+            }
+        }
+        if let Some(file) = &state.memory_file {
+            match self.inotify.add_watch(file, libc::IN_MODIFY) {
+                Ok(wd) => {
+                    self.watches.insert(wd, (name.to_string(), Watched::Memory));
+                    state.counters = read_counters(file);
+                }
+                Err(e) => tracing::debug!(cgroup = name, error = %e, "cannot watch memory events"),
+            }
+        }
+        self.cgroups.insert(name.to_string(), state);
+        true
+    }
+
+    fn reread_members(&mut self, name: &str, events: &mut Vec<WatchEvent>) {
+        let path = self.base.join(name);
+        let Some(state) = self.cgroups.get_mut(name) else {
+            return;
+        };
+        let Ok(content) = fs::read_to_string(path.join("cgroup.procs")) else {
+            return;
+        };
+        let mut pids: Vec<u32> = content
+            .lines()
+            .filter_map(|l| l.trim().parse().ok())
+            .collect();
+        pids.sort_unstable();
+        let (added, removed) = diff_pids(&state.pids, &pids);
+        state.pids = pids;
+        if !added.is_empty() || !removed.is_empty() {
+            events.push(WatchEvent::ProcessesChanged {
+                cgroup: name.to_string(),
+                added,
+                removed,
+            });
+        }
+    }
+
+    fn reread_memory(&mut self, name: &str, events: &mut Vec<WatchEvent>) {
+        let Some(state) = self.cgroups.get_mut(name) else {
+            return;
+        };
+        let Some(file) = &state.memory_file else {
+            return;
+        };
+        let now = read_counters(file);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        for (kind, count) in state.counters.changes(&now) {
+            events.push(WatchEvent::Memory(MemoryEvent {
+                time,
+                cgroup: name.to_string(),
+                kind,
+                count,
+                total: now.get(kind),
+            }));
+        }
+        state.counters = now;
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = Result<WatchEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(Ok(event));
+        }
+        match self.wait(None) {
+            Ok(events) => {
+                self.pending.extend(events);
+                self.pending.pop_front().map(Ok)
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl AsRawFd for Watcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
+
+fn read_counters(file: &Path) -> Counters {
+    fs::read_to_string(file)
+        .map(|c| events::parse_counters(&c))
+        .unwrap_or_default()
+}
+
+/// PIDs in `new` but not `old`, and in `old` but not `new`. Both sorted.
+fn diff_pids(old: &[u32], new: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let added = new
+        .iter()
+        .filter(|p| old.binary_search(p).is_err())
+        .copied()
+        .collect();
+    let removed = old
+        .iter()
+        .filter(|p| new.binary_search(p).is_err())
+        .copied()
+        .collect();
+    (added, removed)
+}
+
+/// Split a buffer read from an inotify descriptor into (wd, mask, name).
+fn parse_inotify(buf: &[u8]) -> Vec<(i32, u32, Option<String>)> {
+    const HEADER: usize = 16; // wd, mask, cookie, len
+    let mut out = Vec::new();
+    let mut offset = 0;
+    while offset + HEADER <= buf.len() {
+        let field = |i: usize| {
+            let at = offset + i * 4;
+            [buf[at], buf[at + 1], buf[at + 2], buf[at + 3]]
+        };
+        let wd = i32::from_ne_bytes(field(0));
+        let mask = u32::from_ne_bytes(field(1));
+        let len = u32::from_ne_bytes(field(3)) as usize;
+        let name_end = (offset + HEADER + len).min(buf.len());
+        let name = buf[offset + HEADER..name_end]
+            .split(|&b| b == 0)
+            .next()
+            .filter(|n| !n.is_empty())
+            .map(|n| String::from_utf8_lossy(n).into_owned());
+        out.push((wd, mask, name));
+        offset += HEADER + len;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_inotify_records() {
+        let mut buf = Vec::new();
+        for (wd, mask, name) in [(1i32, 2u32, &b""[..]), (7, 0x100, &b"pid-42\0\0"[..])] {
+            buf.extend_from_slice(&wd.to_ne_bytes());
+            buf.extend_from_slice(&mask.to_ne_bytes());
+            buf.extend_from_slice(&0u32.to_ne_bytes());
+            buf.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+            buf.extend_from_slice(name);
+        }
+        assert_eq!(
+            parse_inotify(&buf),
+            vec![(1, 2, None), (7, 0x100, Some("pid-42".to_string()))]
+        );
+    }
+
+    #[test]
+    fn diffs_pids() {
+        assert_eq!(diff_pids(&[1, 2, 3], &[2, 3, 4, 5]), (vec![4, 5], vec![1]));
+        assert_eq!(diff_pids(&[7], &[7]), (vec![], vec![]));
+    }
+}
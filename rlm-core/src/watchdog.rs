@@ -0,0 +1,388 @@
+//! Soft limits, behind `rlm watchdog`: watch an application's usage from
+//! `/proc` without putting it in a limited cgroup, and notify, freeze, or
+//! kill when it crosses a threshold.
+//!
+//! The decision step ([`Watchdog::check`]) is pure, so it is unit-testable;
+//! [`sample`] and [`act`] touch `/proc`, cgroups, and signals.
+
+use crate::process;
+use crate::CgroupManager;
+use common::{Error, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Usage has to drop below this percentage of a threshold before crossing
+/// it again triggers again, so hovering at the line is one breach.
+const REARM_PERCENT: f64 = 90.0;
+
+/// What to do when a threshold is crossed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Action {
+    /// Desktop notification and a line on stderr.
+    #[default]
+    Notify,
+    /// Freeze the processes (cgroup freezer) until the watchdog stops.
+    Freeze,
+    /// SIGTERM the processes.
+    Kill,
+}
+
+impl std::str::FromStr for Action {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "notify" => Ok(Self::Notify),
+            "freeze" => Ok(Self::Freeze),
+            "kill" => Ok(Self::Kill),
+            _ => Err(Error::InvalidArgs(format!(
+                "unknown watchdog action '{s}' (expected notify, freeze, or kill)"
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Notify => "notify",
+            Self::Freeze => "freeze",
+            Self::Kill => "kill",
+        })
+    }
+}
+
+/// Usage of all processes of one application.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Observed {
+    pub pids: Vec<u32>,
+    /// Summed resident memory, in bytes
+    pub memory: u64,
+    /// Summed CPU since the previous sample, in percent of one CPU; `None`
+    /// on the first sample
+    pub cpu_percent: Option<f64>,
+}
+
+/// A threshold that was crossed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breach {
+    Memory { used: u64, threshold: u64 },
+    Cpu { percent: f64, threshold: u32 },
+}
+
+impl std::fmt::Display for Breach {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use common::format_bytes;
+        match self {
+            Breach::Memory { used, threshold } => write!(
+                f,
+                "memory {} over {}",
+                format_bytes(*used),
+                format_bytes(*threshold)
+            ),
+            Breach::Cpu { percent, threshold } => {
+                write!(f, "CPU {percent:.0}% over {threshold}%")
+            }
+        }
+    }
+}
+
+/// Thresholds and the state needed to trigger once per crossing.
+#[derive(Debug, Clone, Default)]
+pub struct Watchdog {
+    pub memory: Option<u64>,
+    /// Percent of one CPU
+    pub cpu: Option<u32>,
+    /// How long usage must stay over a threshold before it counts
+    pub sustain: Duration,
+    memory_state: Crossing,
+    cpu_state: Crossing,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Crossing {
+    #[default]
+    Under,
+    Over(Instant),
+    /// Triggered; waiting to drop below the re-arm level
+    Fired,
+}
+
+impl Watchdog {
+    pub fn new(memory: Option<u64>, cpu: Option<u32>, sustain: Duration) -> Self {
+        Self {
+            memory,
+            cpu,
+            sustain,
+            ..Self::default()
+        }
+    }
+
+    /// Feed one observation taken at `now`; returns the thresholds that were
+    /// just crossed (and held for `sustain`).
+    pub fn check(&mut self, observed: &Observed, now: Instant) -> Vec<Breach> {
+        let mut breaches = Vec::new();
+        if let Some(threshold) = self.memory {
+            let used = observed.memory;
+            if step(
+                &mut self.memory_state,
+                used as f64,
+                threshold as f64,
+                self.sustain,
+                now,
+            ) {
+                breaches.push(Breach::Memory { used, threshold });
+            }
+        }
+        if let (Some(threshold), Some(percent)) = (self.cpu, observed.cpu_percent) {
+            if step(
+                &mut self.cpu_state,
+                percent,
+                threshold as f64,
+                self.sustain,
+                now,
+            ) {
+                breaches.push(Breach::Cpu { percent, threshold });
+            }
+        }
+        breaches
+    }
+}
+
+/// Advance one threshold's state; true when it fires.
+fn step(state: &mut Crossing, value: f64, threshold: f64, sustain: Duration, now: Instant) -> bool {
+    let over = value > threshold;
+    match *state {
+        Crossing::Under if over => {
+            *state = Crossing::Over(now);
+        }
+        Crossing::Over(_) if !over => {
+            *state = Crossing::Under;
+            return false;
+        }
+        Crossing::Fired if value < threshold * REARM_PERCENT / 100.0 => {
+            *state = Crossing::Under;
+            return false;
+        }
+        _ => {}
+    }
+    if let Crossing::Over(since) = *state {
+        if now.duration_since(since) >= sustain {
+            *state = Crossing::Fired;
+            return true;
+        }
+    }
+    false
+}
+
+/// Reads an application's usage from `/proc`, remembering CPU times
+/// between calls for the CPU rate.
+#[derive(Debug, Default)]
+pub struct Sampler {
+    last: Option<(Instant, HashMap<u32, u64>)>,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Usage of every running process of `name`. No processes is not an
+    /// error: the application may start later.
+    pub fn sample(&mut self, name: &str) -> Observed {
+        let pids: Vec<u32> = process::find_all_by_executable(name)
+            .map(|ps| ps.iter().map(|p| p.pid).collect())
+            .unwrap_or_default();
+        let now = Instant::now();
+        let memory = pids.iter().filter_map(|&pid| rss_bytes(pid)).sum();
+        let ticks: HashMap<u32, u64> = pids
+            .iter()
+            .filter_map(|&pid| Some((pid, cpu_ticks(pid)?)))
+            .collect();
+
+        let cpu_percent = self.last.as_ref().map(|(then, before)| {
+            let used: u64 = ticks
+                .iter()
+                .map(|(pid, &t)| t.saturating_sub(before.get(pid).copied().unwrap_or(t)))
+                .sum();
+            // SAFETY: sysconf has no preconditions.
+            let hz = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+            let secs = now.duration_since(*then).as_secs_f64().max(1e-3);
+            used as f64 / hz / secs * 100.0
+        });
+        self.last = Some((now, ticks));
+        Observed {
+            pids,
+            memory,
+            cpu_percent,
+        }
+    }
+}
+
+/// Resident memory of `pid` (`VmRSS`).
+pub(crate) fn rss_bytes(pid: u32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let kb: u64 = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// User plus system CPU time of `pid`, in clock ticks.
+pub(crate) fn cpu_ticks(pid: u32) -> Option<u64> {
+    parse_cpu_ticks(&fs::read_to_string(format!("/proc/{pid}/stat")).ok()?)
+}
+
+/// `utime + stime` from a `/proc/<pid>/stat` line. The command name can
+/// contain spaces and parentheses, so fields are counted after its last ')'.
+fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Carry out `action` on `pids`. Freezing moves each process into a guard
+/// cgroup; [`release`] thaws them again.
+pub fn act(
+    manager: &CgroupManager,
+    action: Action,
+    name: &str,
+    pids: &[u32],
+    breach: &Breach,
+) -> Result<()> {
+    match action {
+        Action::Notify => {
+            notify(&format!("{name}: {breach}"));
+            Ok(())
+        }
+        Action::Freeze => {
+            notify(&format!(
+                "{name}: {breach}, freezing {} process(es)",
+                pids.len()
+            ));
+            for &pid in pids {
+                manager.freeze_pid(pid)?;
+            }
+            Ok(())
+        }
+        Action::Kill => {
+            notify(&format!(
+                "{name}: {breach}, terminating {} process(es)",
+                pids.len()
+            ));
+            for &pid in pids {
+                // SAFETY: plain syscall on a PID we just looked up.
+                if unsafe { libc::kill(pid as i32, libc::SIGTERM) } != 0 {
+                    let err = std::io::Error::last_os_error();
+                    if err.raw_os_error() != Some(libc::ESRCH) {
+                        return Err(err.into());
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Thaw processes frozen by [`act`] and remove their guard cgroups.
+pub fn release(manager: &CgroupManager, pids: &[u32]) {
+    for &pid in pids {
+        if let Err(e) = manager.thaw_pid(pid) {
+            tracing::warn!(pid, error = %e, "watchdog: thaw failed");
+        }
+        if let Err(e) = manager.cleanup_guard(pid) {
+            tracing::warn!(pid, error = %e, "watchdog: cleanup failed");
+        }
+    }
+}
+
+/// Best-effort desktop notification via `notify-send`.
+fn notify(message: &str) {
+    match std::process::Command::new("notify-send")
+        .args(["rlm watchdog", message])
+        .spawn()
+    {
+        Ok(mut child) => {
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+        }
+        Err(e) => tracing::debug!(error = %e, "notify-send unavailable"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(bytes: u64) -> Observed {
+        Observed {
+            memory: bytes,
+            ..Observed::default()
+        }
+    }
+
+    #[test]
+    fn fires_once_per_crossing() {
+        let mut dog = Watchdog::new(Some(1000), None, Duration::ZERO);
+        let t = Instant::now();
+        assert!(dog.check(&memory(500), t).is_empty());
+        assert_eq!(
+            dog.check(&memory(1200), t),
+            vec![Breach::Memory {
+                used: 1200,
+                threshold: 1000
+            }]
+        );
+        // Still over, or just under but above the re-arm level: no repeat
+        assert!(dog.check(&memory(1300), t).is_empty());
+        assert!(dog.check(&memory(950), t).is_empty());
+        assert!(dog.check(&memory(1100), t).is_empty());
+        // Well under re-arms it
+        assert!(dog.check(&memory(800), t).is_empty());
+        assert_eq!(dog.check(&memory(1100), t).len(), 1);
+    }
+
+    #[test]
+    fn waits_for_sustained_breach() {
+        let mut dog = Watchdog::new(None, Some(100), Duration::from_secs(10));
+        let t = Instant::now();
+        let cpu = |p: f64| Observed {
+            cpu_percent: Some(p),
+            ..Observed::default()
+        };
+        assert!(dog.check(&cpu(150.0), t).is_empty());
+        assert!(dog
+            .check(&cpu(150.0), t + Duration::from_secs(5))
+            .is_empty());
+        // A dip resets the clock
+        assert!(dog.check(&cpu(50.0), t + Duration::from_secs(6)).is_empty());
+        assert!(dog
+            .check(&cpu(150.0), t + Duration::from_secs(7))
+            .is_empty());
+        assert!(dog
+            .check(&cpu(150.0), t + Duration::from_secs(15))
+            .is_empty());
+        assert_eq!(
+            dog.check(&cpu(150.0), t + Duration::from_secs(17)),
+            vec![Breach::Cpu {
+                percent: 150.0,
+                threshold: 100
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_cpu_ticks_after_command_name() {
+        let stat = "42 (Web Content (x)) S 1 42 42 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 5";
+        assert_eq!(parse_cpu_ticks(stat), Some(300));
+        assert_eq!(parse_cpu_ticks("garbage"), None);
+    }
+}